@@ -256,6 +256,9 @@ class ManagementService(
                             _mutableCurrentAccessMethod.update { event.newAccessMethod.toDomain() }
                         }
                         ManagementInterface.DaemonEvent.EventCase.REMOVE_DEVICE -> {}
+                        ManagementInterface.DaemonEvent.EventCase.RELAY_LIST_CHANGES -> {}
+                        ManagementInterface.DaemonEvent.EventCase.LEAK_DETECTED -> {}
+                        ManagementInterface.DaemonEvent.EventCase.ACCOUNT_EXPIRY_WARNING -> {}
                         ManagementInterface.DaemonEvent.EventCase.EVENT_NOT_SET -> {}
                     }
                 }
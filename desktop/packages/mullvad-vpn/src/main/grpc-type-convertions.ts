@@ -27,15 +27,18 @@ import {
   FeatureIndicator,
   FirewallPolicyError,
   FirewallPolicyErrorType,
+  IAccountExpiryWarning,
   IAppVersionInfo,
   IBridgeConstraints,
   ICustomList,
   IDevice,
+  ILeakTestReport,
   IObfuscationEndpoint,
   IOpenVpnConstraints,
   IProxyEndpoint,
   IRelayListCity,
   IRelayListCountry,
+  IRelayListDiff,
   IRelayListHostname,
   IRelayListWithEndpointData,
   IRelaySettingsNormal,
@@ -830,6 +833,21 @@ export function convertFromDaemonEvent(data: grpcTypes.DaemonEvent): DaemonEvent
     return { accessMethodSetting: convertFromApiAccessMethodSetting(newAccessMethod) };
   }
 
+  const relayListChanges = data.getRelayListChanges();
+  if (relayListChanges !== undefined) {
+    return { relayListChanges: convertFromRelayListDiff(relayListChanges) };
+  }
+
+  const leakDetected = data.getLeakDetected();
+  if (leakDetected !== undefined) {
+    return { leakDetected: convertFromLeakTestReport(leakDetected) };
+  }
+
+  const accountExpiryWarning = data.getAccountExpiryWarning();
+  if (accountExpiryWarning !== undefined) {
+    return { accountExpiryWarning: convertFromAccountExpiryWarning(accountExpiryWarning) };
+  }
+
   // Handle unknown daemon events
   const keys = Object.entries(data.toObject())
     .filter(([, value]) => value !== undefined)
@@ -837,6 +855,54 @@ export function convertFromDaemonEvent(data: grpcTypes.DaemonEvent): DaemonEvent
   throw new Error(`Unknown daemon event received containing ${keys}`);
 }
 
+function convertFromRelayListDiff(diff: grpcTypes.RelayListDiff): IRelayListDiff {
+  return {
+    added: diff.getAddedList(),
+    removed: diff.getRemovedList(),
+    activated: diff.getActivatedList(),
+    deactivated: diff.getDeactivatedList(),
+    addressChanges: diff.getAddressChangesList().map((change) => ({
+      hostname: change.getHostname(),
+      oldAddress: change.hasOldAddress() ? change.getOldAddress() : undefined,
+      newAddress: change.hasNewAddress() ? change.getNewAddress() : undefined,
+    })),
+    featureChanges: diff.getFeatureChangesList().map((change) => ({
+      hostname: change.getHostname(),
+      feature:
+        change.getFeature() === grpcTypes.RelayFeatureChange.Feature.QUIC ? 'quic' : 'daita',
+      enabled: change.getEnabled(),
+    })),
+  };
+}
+
+function convertFromLeakTestReport(report: grpcTypes.LeakTestReport): ILeakTestReport {
+  const amIMullvad = report.getAmIMullvad();
+  return {
+    interface: report.getInterface(),
+    destination: report.getDestination(),
+    reachableNodes: report.getReachableNodesList().map((node) => ({
+      address: node.getAddress(),
+      ttl: node.hasTtl() ? node.getTtl() : undefined,
+    })),
+    reachableDnsServers: report.getReachableDnsServersList(),
+    amIMullvad: amIMullvad && {
+      notMullvadIp: amIMullvad.hasNotMullvadIp() ? amIMullvad.getNotMullvadIp() : undefined,
+    },
+    leaking: report.getLeaking(),
+  };
+}
+
+function convertFromAccountExpiryWarning(
+  warning: grpcTypes.AccountExpiryWarning,
+): IAccountExpiryWarning {
+  return {
+    expiry: ensureExists(warning.getExpiry(), "no 'expiry' field for account expiry warning")
+      .toDate()
+      .toISOString(),
+    remaining: warning.getRemaining()?.getSeconds() ?? 0,
+  };
+}
+
 function convertFromOwnership(ownership: grpcTypes.Ownership): Ownership {
   switch (ownership) {
     case grpcTypes.Ownership.ANY:
@@ -763,6 +763,14 @@ class ApplicationMain
           IpcMainEventChannel.settings.notifyApiAccessMethodSettingChange?.(
             daemonEvent.accessMethodSetting,
           );
+        } else if ('leakDetected' in daemonEvent) {
+          log.warn(
+            `Leak detected on ${daemonEvent.leakDetected.interface}:`,
+            daemonEvent.leakDetected,
+          );
+        } else if ('accountExpiryWarning' in daemonEvent) {
+          // The renderer shows its own notifications based on the account expiry
+          log.info(`Account expires at ${daemonEvent.accountExpiryWarning.expiry}`);
         }
       },
       (error: Error) => {
@@ -175,7 +175,10 @@ export type DaemonEvent =
   | { appVersionInfo: IAppVersionInfo }
   | { device: DeviceEvent }
   | { deviceRemoval: Array<IDevice> }
-  | { accessMethodSetting: AccessMethodSetting };
+  | { accessMethodSetting: AccessMethodSetting }
+  | { relayListChanges: IRelayListDiff }
+  | { leakDetected: ILeakTestReport }
+  | { accountExpiryWarning: IAccountExpiryWarning };
 
 export type DaemonAppUpgradeEventStatusDownloadStarted = {
   type: 'APP_UPGRADE_STATUS_DOWNLOAD_STARTED';
@@ -366,6 +369,30 @@ export interface IRelayList {
   countries: IRelayListCountry[];
 }
 
+export interface IRelayListDiff {
+  added: string[];
+  removed: string[];
+  activated: string[];
+  deactivated: string[];
+  addressChanges: Array<{ hostname: string; oldAddress?: string; newAddress?: string }>;
+  featureChanges: Array<{ hostname: string; feature: 'daita' | 'quic'; enabled: boolean }>;
+}
+
+export interface ILeakTestReport {
+  interface: string;
+  destination: string;
+  reachableNodes: Array<{ address: string; ttl?: number }>;
+  reachableDnsServers: string[];
+  amIMullvad?: { notMullvadIp?: string };
+  leaking: boolean;
+}
+
+export interface IAccountExpiryWarning {
+  expiry: string;
+  // Seconds left on the account. Zero if it has expired.
+  remaining: number;
+}
+
 export interface IWireguardEndpointData {
   portRanges: [number, number][];
   udp2tcpPorts: number[];
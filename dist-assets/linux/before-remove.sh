@@ -15,6 +15,15 @@ if [[ "$1" =~ $is_number_re ]] && [ "$1" -gt 0 ]; then
 fi
 
 if [[ "$1" == "upgrade" ]]; then
+    # Older versions cannot read an encrypted device key, so store it in plain text when
+    # downgrading. The daemon is still running at this point and must be stopped first, since it
+    # would otherwise keep the key encrypted.
+    if /opt/Mullvad\ VPN/resources/mullvad-setup is-older-version "${2:-}"; then
+        /opt/Mullvad\ VPN/resources/mullvad-setup prepare-restart || true
+        systemctl stop mullvad-daemon.service || true
+        /opt/Mullvad\ VPN/resources/mullvad-setup restore-plain-device-key \
+            || echo "Failed to restore plain device key"
+    fi
     exit 0;
 fi
 
@@ -15,7 +15,6 @@ use mullvad_update::{
     version::{Version, VersionInfo, VersionParameters},
     version_provider::VersionInfoProvider,
 };
-use rand::seq::SliceRandom;
 use std::{cmp::Ordering, path::PathBuf};
 use tokio::{
     sync::{mpsc, oneshot},
@@ -499,9 +498,10 @@ impl<D: AppDelegate + 'static, A: From<UiAppDownloaderParameters<D>> + AppDownlo
                 TargetVersion::Beta => version_info.beta.as_ref().expect("selected version exists"),
             };
 
-            let Some(app_url) = select_cdn_url(&selected_version.urls) else {
+            if selected_version.urls.is_empty() {
                 return;
-            };
+            }
+            let app_urls = selected_version.urls.clone();
             let app_version = selected_version.version.clone();
             let app_sha256 = selected_version.sha256;
             let app_size = selected_version.size;
@@ -516,7 +516,7 @@ impl<D: AppDelegate + 'static, A: From<UiAppDownloaderParameters<D>> + AppDownlo
 
             let downloader = A::from(UiAppDownloaderParameters {
                 app_version,
-                app_url: app_url.to_owned(),
+                app_urls,
                 app_size,
                 app_progress: UiProgressUpdater::new(self_.queue()),
                 app_sha256,
@@ -578,12 +578,6 @@ impl<D: AppDelegate + 'static, A: From<UiAppDownloaderParameters<D>> + AppDownlo
     }
 }
 
-/// Select a mirror to download from
-/// Currently, the selection is random
-fn select_cdn_url(urls: &[String]) -> Option<&str> {
-    urls.choose(&mut rand::thread_rng()).map(String::as_str)
-}
-
 fn format_latest_version(version: &Version) -> String {
     format!("{}: {}", resource::LATEST_VERSION_PREFIX, version.version)
 }
@@ -48,6 +48,16 @@ impl<Delegate: AppDelegate, Downloader: AppDownloader + Send + 'static> AppDownl
                 })
             }
             Err(err) => {
+                if let app::DownloadError::FetchApp { failures, .. } = &err {
+                    for failure in failures {
+                        log::error!(
+                            "Failed to download from {}: {:#}",
+                            failure.url,
+                            failure.error
+                        );
+                    }
+                }
+
                 self.queue.queue_main(move |self_| {
                     self_.clear_status_text();
                     self_.clear_download_text();
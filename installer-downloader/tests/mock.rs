@@ -121,15 +121,16 @@ impl<const EXE_SUCCEED: bool, const VERIFY_SUCCEED: bool, const LAUNCH_SUCCEED:
     for FakeAppDownloader<EXE_SUCCEED, VERIFY_SUCCEED, LAUNCH_SUCCEED>
 {
     async fn download_executable(mut self) -> Result<impl DownloadedInstaller, DownloadError> {
-        self.params.app_progress.set_url(&self.params.app_url);
+        self.params.app_progress.set_url(&self.params.app_urls[0]);
         self.params.app_progress.clear_progress();
         if EXE_SUCCEED {
             self.params.app_progress.set_progress(1.);
             Ok(FakeInstaller::<EXE_SUCCEED, VERIFY_SUCCEED, LAUNCH_SUCCEED>)
         } else {
-            Err(DownloadError::FetchApp(anyhow::anyhow!(
-                "fetching app failed"
-            )))
+            Err(DownloadError::FetchApp {
+                error: anyhow::anyhow!("fetching app failed"),
+                failures: vec![],
+            })
         }
     }
 }
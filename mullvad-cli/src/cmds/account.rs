@@ -1,9 +1,19 @@
 use anyhow::{Result, anyhow};
 use clap::Subcommand;
+use futures::StreamExt;
 use itertools::Itertools;
-use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::{account::AccountNumber, device::DeviceState};
-use std::io::{self, Write};
+use mullvad_management_interface::{MullvadProxyClient, client::DaemonEvent};
+use mullvad_types::{
+    account::{AccountExpiryWarning, AccountNumber, ExpiryReminders},
+    device::DeviceState,
+};
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use super::parse_duration;
+use crate::output;
 
 const NOT_LOGGED_IN_MESSAGE: &str = "Not logged in on any account";
 const REVOKED_MESSAGE: &str = "The current device has been revoked";
@@ -27,6 +37,10 @@ pub enum Account {
         /// Enable verbose output
         #[arg(long, short = 'v')]
         verbose: bool,
+
+        /// Keep running and print a warning whenever the account is about to run out of time
+        #[arg(long, short = 'w')]
+        watch: bool,
     },
 
     /// List devices associated with an account
@@ -55,6 +69,27 @@ pub enum Account {
         /// Voucher code to submit
         voucher: String,
     },
+
+    /// Configure when to warn that the account is about to run out of time
+    #[clap(subcommand)]
+    Reminders(Reminders),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Reminders {
+    /// Display the current expiry reminders
+    Get,
+
+    /// Warn when the time left on the account drops below each of the given thresholds
+    Set {
+        /// Amounts of time such as "7d" or "12h", or "expired" to warn when the account expires.
+        /// Pass no thresholds to disable the reminders.
+        #[arg(value_parser = parse_threshold)]
+        thresholds: Vec<Duration>,
+    },
+
+    /// Restore the default expiry reminders
+    Reset,
 }
 
 impl Account {
@@ -70,7 +105,13 @@ impl Account {
                 .await
             }
             Account::Logout => Self::logout(&mut rpc).await,
-            Account::Get { verbose } => Self::get(&mut rpc, verbose).await,
+            Account::Get { verbose, watch } => {
+                Self::get(&mut rpc, verbose).await?;
+                if watch {
+                    Self::watch(&mut rpc).await?;
+                }
+                Ok(())
+            }
             Account::ListDevices { account, verbose } => {
                 Self::list_devices(&mut rpc, account, verbose).await
             }
@@ -78,12 +119,15 @@ impl Account {
                 Self::revoke_device(&mut rpc, device, account).await
             }
             Account::Redeem { voucher } => Self::redeem_voucher(&mut rpc, voucher).await,
+            Account::Reminders(cmd) => cmd.handle(&mut rpc).await,
         }
     }
 
     async fn create(rpc: &mut MullvadProxyClient) -> Result<()> {
         rpc.create_new_account().await?;
-        println!("New account created!");
+        if !output::is_json() {
+            println!("New account created!");
+        }
         Self::get(rpc, false).await
     }
 
@@ -104,6 +148,19 @@ impl Account {
 
         let state = rpc.get_device().await?;
 
+        if output::is_json() {
+            let account_data = match &state {
+                DeviceState::LoggedIn(device) => {
+                    Some(rpc.get_account_data(device.account_number.clone()).await?)
+                }
+                DeviceState::LoggedOut | DeviceState::Revoked => None,
+            };
+            return output::print_json(&serde_json::json!({
+                "device_state": state,
+                "account_data": account_data,
+            }));
+        }
+
         match state {
             DeviceState::LoggedIn(device) => {
                 println!("{:<20}{}", "Mullvad account:", device.account_number);
@@ -139,6 +196,21 @@ impl Account {
         Ok(())
     }
 
+    /// Print account expiry warnings as they are emitted by the daemon, until interrupted.
+    async fn watch(rpc: &mut MullvadProxyClient) -> Result<()> {
+        let mut events = rpc.events_listen().await?;
+        while let Some(event) = events.next().await {
+            if let DaemonEvent::AccountExpiryWarning(warning) = event? {
+                if output::is_json() {
+                    output::print_json(&warning)?;
+                } else {
+                    print_expiry_warning(&warning);
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn list_devices(
         rpc: &mut MullvadProxyClient,
         account: Option<String>,
@@ -146,9 +218,13 @@ impl Account {
     ) -> Result<()> {
         let account_number = account_else_current(rpc, account).await?;
         let mut device_list = rpc.list_devices(account_number).await?;
+        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
+
+        if output::is_json() {
+            return output::print_json(&device_list);
+        }
 
         println!("Devices on the account:");
-        device_list.sort_unstable_by_key(|dev| dev.created.timestamp());
         for device in device_list {
             if verbose {
                 println!();
@@ -192,6 +268,9 @@ impl Account {
         voucher.retain(|c| c.is_alphanumeric());
 
         let submission = rpc.submit_voucher(voucher).await?;
+        if output::is_json() {
+            return output::print_json(&submission);
+        }
         println!(
             "Added {} to the account",
             format_duration(submission.time_added)
@@ -204,6 +283,68 @@ impl Account {
     }
 }
 
+impl Reminders {
+    async fn handle(self, rpc: &mut MullvadProxyClient) -> Result<()> {
+        match self {
+            Reminders::Get => {
+                let reminders = rpc.get_settings().await?.expiry_reminders;
+                if output::is_json() {
+                    return output::print_json(&reminders);
+                }
+                if reminders.thresholds().is_empty() {
+                    println!("Expiry reminders are disabled");
+                }
+                for &threshold in reminders.thresholds() {
+                    println!("{}", format_threshold(threshold));
+                }
+            }
+            Reminders::Set { thresholds } => {
+                rpc.set_expiry_reminders(ExpiryReminders::new(thresholds)?)
+                    .await?;
+                println!("Updated expiry reminders");
+            }
+            Reminders::Reset => {
+                rpc.set_expiry_reminders(ExpiryReminders::default()).await?;
+                println!("Restored the default expiry reminders");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn print_expiry_warning(warning: &AccountExpiryWarning) {
+    let expiry = warning.expiry.with_timezone(&chrono::Local);
+    if warning.is_expired() {
+        println!("The account ran out of time at {expiry}");
+    } else {
+        println!(
+            "The account runs out of time in {}, at {expiry}",
+            format_duration(warning.remaining.as_secs())
+        );
+    }
+}
+
+fn format_threshold(threshold: Duration) -> String {
+    if threshold.is_zero() {
+        "When the account expires".to_owned()
+    } else {
+        format!(
+            "{} before the account expires",
+            format_duration(threshold.as_secs())
+        )
+    }
+}
+
+/// Parse an expiry reminder threshold, such as "3d", or "expired".
+fn parse_threshold(s: &str) -> Result<Duration, String> {
+    if s == "expired" {
+        return Ok(Duration::ZERO);
+    }
+    parse_duration(s)
+        .and_then(|duration| duration.to_std().ok())
+        .ok_or_else(|| format!("Invalid threshold: {s}"))
+}
+
 async fn account_else_current(
     rpc: &mut MullvadProxyClient,
     account_number: Option<String>,
@@ -6,6 +6,7 @@ use talpid_types::net::proxy::CustomProxy;
 use clap::{Args, Subcommand};
 
 use super::proxies::{ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd};
+use crate::output;
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum ApiAccess {
@@ -71,7 +72,11 @@ impl ApiAccess {
     /// Show all API access methods.
     async fn list() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        for (index, api_access_method) in rpc.get_api_access_methods().await?.iter().enumerate() {
+        let access_methods = rpc.get_api_access_methods().await?;
+        if output::is_json() {
+            return output::print_json(&access_methods);
+        }
+        for (index, api_access_method) in access_methods.iter().enumerate() {
             println!(
                 "{}. {}",
                 index + 1,
@@ -232,6 +237,9 @@ impl ApiAccess {
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let current = rpc.get_current_api_access_method().await?;
+        if output::is_json() {
+            return output::print_json(&current);
+        }
         let mut access_method_formatter = pp::ApiAccessMethodFormatter::new(&current);
         access_method_formatter.settings.write_enabled = false;
         println!("{access_method_formatter}");
@@ -1,8 +1,11 @@
 use anyhow::Result;
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+#[cfg(target_os = "macos")]
+use mullvad_types::network_rule::{MacAddress, NetworkAction, NetworkMatch, NetworkRule};
 
 use super::BooleanOption;
+use crate::output;
 
 #[derive(Subcommand, Debug)]
 pub enum AutoConnect {
@@ -10,6 +13,43 @@ pub enum AutoConnect {
     Get,
     /// Change auto-connect setting
     Set { policy: BooleanOption },
+    /// Manage rules that connect or disconnect the tunnel depending on the current network.
+    /// The first rule matching the network takes precedence over the auto-connect setting
+    #[cfg(target_os = "macos")]
+    #[clap(subcommand)]
+    Rule(Rule),
+}
+
+#[cfg(target_os = "macos")]
+#[derive(Subcommand, Debug)]
+pub enum Rule {
+    /// Add a rule to the end of the list
+    Add {
+        /// What to do when the rule matches
+        action: NetworkAction,
+        #[clap(subcommand)]
+        network: RuleNetwork,
+    },
+    /// Remove a rule
+    Remove {
+        /// Position of the rule, as shown by `list`
+        index: usize,
+    },
+    /// List the rules in the order that they are evaluated
+    List,
+    /// Remove all rules
+    Clear,
+}
+
+#[cfg(target_os = "macos")]
+#[derive(Subcommand, Debug)]
+pub enum RuleNetwork {
+    /// Match networks where the default route uses this interface, e.g. en0
+    Interface { name: String },
+    /// Match networks whose default gateway has this MAC address
+    Gateway { mac: MacAddress },
+    /// Match any network
+    Any,
 }
 
 impl AutoConnect {
@@ -17,6 +57,8 @@ impl AutoConnect {
         match self {
             AutoConnect::Get => Self::get().await,
             AutoConnect::Set { policy } => Self::set(policy).await,
+            #[cfg(target_os = "macos")]
+            AutoConnect::Rule(cmd) => cmd.handle().await,
         }
     }
 
@@ -29,8 +71,55 @@ impl AutoConnect {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let auto_connect = BooleanOption::from(rpc.get_settings().await?.auto_connect);
+        let auto_connect = rpc.get_settings().await?.auto_connect;
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({ "auto_connect": auto_connect }));
+        }
+        let auto_connect = BooleanOption::from(auto_connect);
         println!("Autoconnect: {auto_connect}");
         Ok(())
     }
 }
+
+#[cfg(target_os = "macos")]
+impl Rule {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut rules = rpc.get_settings().await?.network_rules;
+        let message = match self {
+            Rule::Add { action, network } => {
+                let network = match network {
+                    RuleNetwork::Interface { name } => NetworkMatch::Interface(name),
+                    RuleNetwork::Gateway { mac } => NetworkMatch::GatewayMac(mac),
+                    RuleNetwork::Any => NetworkMatch::Any,
+                };
+                let rule = NetworkRule { network, action };
+                let message = format!("Added rule: {rule}");
+                rules.push(rule);
+                message
+            }
+            Rule::Remove { index } => {
+                if index >= rules.len() {
+                    anyhow::bail!("There is no rule at position {index}");
+                }
+                format!("Removed rule: {}", rules.remove(index))
+            }
+            Rule::List => {
+                if output::is_json() {
+                    return output::print_json(&rules);
+                }
+                for (index, rule) in rules.iter().enumerate() {
+                    println!("{index}: {rule}");
+                }
+                return Ok(());
+            }
+            Rule::Clear => {
+                rules.clear();
+                "Removed all rules".to_owned()
+            }
+        };
+        rpc.set_network_rules(rules).await?;
+        println!("{message}");
+        Ok(())
+    }
+}
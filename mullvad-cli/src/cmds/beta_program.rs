@@ -3,6 +3,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::BooleanOption;
+use crate::output;
 
 #[derive(Subcommand, Debug)]
 pub enum BetaProgram {
@@ -36,7 +37,13 @@ impl BetaProgram {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let opt = BooleanOption::from(rpc.get_settings().await?.show_beta_releases);
+        let show_beta_releases = rpc.get_settings().await?.show_beta_releases;
+        if output::is_json() {
+            return output::print_json(
+                &serde_json::json!({ "show_beta_releases": show_beta_releases }),
+            );
+        }
+        let opt = BooleanOption::from(show_beta_releases);
         println!("Beta program: {opt}");
         Ok(())
     }
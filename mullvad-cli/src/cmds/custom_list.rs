@@ -1,4 +1,5 @@
 use super::{relay::resolve_location_constraint, relay_constraints::LocationArgs};
+use crate::output;
 use anyhow::{Result, anyhow, bail};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
@@ -86,8 +87,13 @@ impl CustomList {
     /// Print all custom lists.
     async fn list() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
+        let custom_lists = rpc.get_settings().await?.custom_lists;
+        if output::is_json() {
+            let custom_lists: Vec<_> = custom_lists.into_iter().collect();
+            return output::print_json(&custom_lists);
+        }
         let cache = rpc.get_relay_locations().await?;
-        for custom_list in rpc.get_settings().await?.custom_lists {
+        for custom_list in custom_lists {
             Self::print_custom_list(&custom_list, &cache)
         }
         Ok(())
@@ -98,6 +104,9 @@ impl CustomList {
     async fn get(name: String) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let custom_list = find_list_by_name(&mut rpc, &name).await?;
+        if output::is_json() {
+            return output::print_json(&custom_list);
+        }
         let cache = rpc.get_relay_locations().await?;
         Self::print_custom_list_content(&custom_list, &cache);
         Ok(())
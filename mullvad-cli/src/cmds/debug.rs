@@ -2,9 +2,12 @@ use anyhow::Result;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
+    leak_test::{AmIMullvad, LeakTestReport},
     relay_constraints::{RelayConstraints, RelaySettings},
 };
 
+use crate::output;
+
 #[derive(clap::Subcommand, Debug)]
 pub enum DebugCommands {
     /// Block all internet connection by setting an invalid relay constraint.
@@ -12,6 +15,13 @@ pub enum DebugCommands {
     /// Relay
     #[clap(subcommand)]
     Relay(RelayDebugCommands),
+    /// Try to send traffic outside the tunnel on the default route interface, and report whether
+    /// it succeeded. Requires being connected.
+    LeakTest {
+        /// Format output as JSON. This is the same as `--output json`
+        #[arg(long, short = 'j')]
+        json: bool,
+    },
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -66,6 +76,56 @@ impl DebugCommands {
                 println!("{relay} is now marked as active");
                 Ok(())
             }
+            DebugCommands::LeakTest { json } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                let report = rpc.run_leak_test().await?;
+                if json || output::is_json() {
+                    output::print_json(&report)?;
+                } else {
+                    print_leak_test_report(&report);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn print_leak_test_report(report: &LeakTestReport) {
+    println!("Interface: {}", report.interface);
+    println!("Destination: {}", report.destination);
+
+    if report.reachable_nodes.is_empty() {
+        println!("Reachable nodes: none");
+    } else {
+        println!("Reachable nodes:");
+        for node in &report.reachable_nodes {
+            match node.ttl {
+                Some(ttl) => println!("  {} (TTL {ttl})", node.address),
+                None => println!("  {}", node.address),
+            }
         }
     }
+
+    if report.reachable_dns_servers.is_empty() {
+        println!("Reachable DNS servers: none");
+    } else {
+        println!("Reachable DNS servers:");
+        for server in &report.reachable_dns_servers {
+            println!("  {server}");
+        }
+    }
+
+    match report.am_i_mullvad {
+        Some(AmIMullvad::Mullvad) => println!("am.i.mullvad.net: connected to Mullvad"),
+        Some(AmIMullvad::NotMullvad { ip }) => {
+            println!("am.i.mullvad.net: not connected to Mullvad, IP address is {ip}")
+        }
+        None => (),
+    }
+
+    if report.is_leaking() {
+        println!("Verdict: traffic is leaking outside the tunnel");
+    } else {
+        println!("Verdict: no leak detected");
+    }
 }
@@ -3,6 +3,11 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
 use std::net::IpAddr;
+use talpid_types::net::dns::EncryptedDnsServer;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use talpid_types::net::dns::SplitDnsRule;
+
+use crate::output;
 
 #[derive(Subcommand, Debug)]
 pub enum Dns {
@@ -14,6 +19,32 @@ pub enum Dns {
         #[clap(subcommand)]
         cmd: DnsSet,
     },
+
+    /// Manage rules that send queries for names within a domain to other DNS servers than the
+    /// ones set above
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[clap(subcommand)]
+    Split(SplitDns),
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[derive(Subcommand, Debug)]
+pub enum SplitDns {
+    /// Add a rule, replacing any existing rule for the same domain
+    Add {
+        /// Domain that the rule applies to, including all of its subdomains, e.g. corp.example
+        domain: String,
+        /// One or more IP addresses of DNS resolvers. Private addresses are reached outside the
+        /// tunnel
+        #[arg(required(true), num_args = 1..)]
+        servers: Vec<IpAddr>,
+    },
+    /// Remove the rule for a domain
+    Remove { domain: String },
+    /// List all rules
+    List,
+    /// Remove all rules
+    Clear,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -49,8 +80,25 @@ pub enum DnsSet {
     /// Set a list of custom DNS servers
     Custom {
         /// One or more IP addresses pointing to DNS resolvers
-        #[arg(required(true), num_args = 1..)]
+        #[cfg_attr(
+            target_os = "macos",
+            arg(required_unless_present_any = ["doh", "dot"], num_args = 0..)
+        )]
+        #[cfg_attr(not(target_os = "macos"), arg(required(true), num_args = 1..))]
         servers: Vec<IpAddr>,
+
+        /// Use a DNS over HTTPS server, e.g. `--doh https://dns.quad9.net/dns-query 9.9.9.9`.
+        /// The IP address is used to reach the server. Can be given multiple times
+        #[cfg(target_os = "macos")]
+        #[arg(long, num_args = 2, value_names = ["URL", "BOOTSTRAP_IP"])]
+        doh: Vec<String>,
+
+        /// Use a DNS over TLS server, e.g. `--dot dns.quad9.net 9.9.9.9`. The IP address is
+        /// used to reach the server. A port other than 853 may be given as `HOSTNAME:PORT`. Can
+        /// be given multiple times
+        #[cfg(target_os = "macos")]
+        #[arg(long, num_args = 2, value_names = ["HOSTNAME", "BOOTSTRAP_IP"])]
+        dot: Vec<String>,
     },
 }
 
@@ -80,14 +128,36 @@ impl Dns {
                 .await
             }
             Dns::Set {
-                cmd: DnsSet::Custom { servers },
-            } => Self::set_custom(servers).await,
+                cmd:
+                    DnsSet::Custom {
+                        servers,
+                        #[cfg(target_os = "macos")]
+                        doh,
+                        #[cfg(target_os = "macos")]
+                        dot,
+                    },
+            } => {
+                #[cfg(target_os = "macos")]
+                let encrypted_servers = parse_encrypted_servers(&doh, EncryptedDnsServer::https)?
+                    .into_iter()
+                    .chain(parse_encrypted_servers(&dot, EncryptedDnsServer::tls)?)
+                    .collect();
+                #[cfg(not(target_os = "macos"))]
+                let encrypted_servers = vec![];
+
+                Self::set_custom(servers, encrypted_servers).await
+            }
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            Dns::Split(cmd) => cmd.handle().await,
         }
     }
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let options = rpc.get_settings().await?.tunnel_options.dns_options;
+        if output::is_json() {
+            return output::print_json(&options);
+        }
 
         match options.state {
             DnsState::Default => {
@@ -110,6 +180,16 @@ impl Dns {
                 for server in &options.custom_options.addresses {
                     println!("{server}");
                 }
+                for server in &options.custom_options.encrypted_servers {
+                    println!("{server}");
+                }
+            }
+        }
+
+        if !options.split_dns_rules.is_empty() {
+            println!("Split DNS:");
+            for rule in &options.split_dns_rules {
+                println!("{rule}");
             }
         }
 
@@ -143,12 +223,18 @@ impl Dns {
         Ok(())
     }
 
-    async fn set_custom(servers: Vec<IpAddr>) -> Result<()> {
+    async fn set_custom(
+        servers: Vec<IpAddr>,
+        encrypted_servers: Vec<EncryptedDnsServer>,
+    ) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let settings = rpc.get_settings().await?;
         rpc.set_dns_options(DnsOptions {
             state: DnsState::Custom,
-            custom_options: CustomDnsOptions { addresses: servers },
+            custom_options: CustomDnsOptions {
+                addresses: servers,
+                encrypted_servers,
+            },
             ..settings.tunnel_options.dns_options
         })
         .await?;
@@ -156,3 +242,63 @@ impl Dns {
         Ok(())
     }
 }
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+impl SplitDns {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        let rules = &mut options.split_dns_rules;
+        let message = match self {
+            SplitDns::Add { domain, servers } => {
+                let rule = SplitDnsRule::new(&domain, servers)?;
+                let message = format!("Added rule: {rule}");
+                rules.retain(|existing| existing.domain != rule.domain);
+                rules.push(rule);
+                message
+            }
+            SplitDns::Remove { domain } => {
+                let domain = domain.strip_suffix('.').unwrap_or(&domain);
+                let Some(index) = rules
+                    .iter()
+                    .position(|rule| rule.domain.eq_ignore_ascii_case(domain))
+                else {
+                    anyhow::bail!("There is no rule for {domain}");
+                };
+                format!("Removed rule: {}", rules.remove(index))
+            }
+            SplitDns::List => {
+                if output::is_json() {
+                    return output::print_json(rules);
+                }
+                for rule in rules.iter() {
+                    println!("{rule}");
+                }
+                return Ok(());
+            }
+            SplitDns::Clear => {
+                rules.clear();
+                "Removed all rules".to_owned()
+            }
+        };
+        rpc.set_dns_options(options).await?;
+        println!("{message}");
+        Ok(())
+    }
+}
+
+/// Parse pairs of server and bootstrap IP address, as given to `--doh` or `--dot`.
+#[cfg(target_os = "macos")]
+fn parse_encrypted_servers<E: std::error::Error + Send + Sync + 'static>(
+    args: &[String],
+    parse: impl Fn(&str, IpAddr) -> Result<EncryptedDnsServer, E>,
+) -> Result<Vec<EncryptedDnsServer>> {
+    args.chunks_exact(2)
+        .map(|pair| {
+            let bootstrap_address = pair[1]
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid bootstrap IP address: {}", pair[1]))?;
+            Ok(parse(&pair[0], bootstrap_address)?)
+        })
+        .collect()
+}
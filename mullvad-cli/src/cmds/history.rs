@@ -0,0 +1,111 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use clap::Args;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{
+    connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter},
+    states::TunnelState,
+};
+use talpid_types::tunnel::ActionAfterDisconnect;
+
+use super::parse_duration;
+use crate::output;
+
+#[derive(Args, Debug)]
+pub struct History {
+    /// Only show changes at or after this time. Either a time such as "2025-01-31 14:00" or
+    /// "2025-01-31", in local time, or a duration such as "30m", "2h", or "7d" before now
+    #[arg(long, value_parser = parse_time)]
+    since: Option<DateTime<Utc>>,
+
+    /// Only show changes before this time, given in the same format as for --since
+    #[arg(long, value_parser = parse_time)]
+    until: Option<DateTime<Utc>>,
+
+    /// Format output as JSON. This is the same as `--output json`
+    #[arg(long, short = 'j')]
+    json: bool,
+}
+
+impl History {
+    pub async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let entries = rpc
+            .get_connection_history(ConnectionHistoryFilter {
+                since: self.since,
+                until: self.until,
+            })
+            .await?;
+
+        if self.json || output::is_json() {
+            return output::print_json(&entries);
+        }
+
+        if entries.is_empty() {
+            println!("No tunnel state changes have been recorded");
+        }
+        for entry in &entries {
+            print_entry(entry);
+        }
+        Ok(())
+    }
+}
+
+fn print_entry(entry: &ConnectionHistoryEntry) {
+    let time = entry.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+    let state = match &entry.state {
+        TunnelState::Disconnected {
+            locked_down: true, ..
+        } => "Disconnected (blocked by lockdown mode)".to_owned(),
+        TunnelState::Disconnected { .. } => "Disconnected".to_owned(),
+        TunnelState::Connecting {
+            endpoint, location, ..
+        } => match location
+            .as_ref()
+            .and_then(|location| location.hostname.as_ref())
+        {
+            Some(hostname) => format!("Connecting to {hostname}: {endpoint}"),
+            None => format!("Connecting: {endpoint}"),
+        },
+        TunnelState::Connected {
+            endpoint, location, ..
+        } => match location
+            .as_ref()
+            .and_then(|location| location.hostname.as_ref())
+        {
+            Some(hostname) => format!("Connected to {hostname}: {endpoint}"),
+            None => format!("Connected: {endpoint}"),
+        },
+        TunnelState::Disconnecting(ActionAfterDisconnect::Nothing) => "Disconnecting".to_owned(),
+        TunnelState::Disconnecting(ActionAfterDisconnect::Block) => {
+            "Disconnecting to block".to_owned()
+        }
+        TunnelState::Disconnecting(ActionAfterDisconnect::Reconnect) => {
+            "Disconnecting to reconnect".to_owned()
+        }
+        TunnelState::Error(error_state) if error_state.block_failure().is_some() => {
+            format!("Error, failed to block: {}", error_state.cause())
+        }
+        TunnelState::Error(error_state) => format!("Blocked: {}", error_state.cause()),
+    };
+    println!("{time}  {state}");
+}
+
+/// Parse a local time, or a duration before now.
+fn parse_time(s: &str) -> Result<DateTime<Utc>> {
+    if let Some(duration) = parse_duration(s) {
+        return Ok(Utc::now() - duration);
+    }
+
+    let time = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|date| date.and_time(Default::default()))
+        })
+        .map_err(|_| anyhow!("Invalid time: {s}"))?;
+    let time = time
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| anyhow!("Invalid local time: {s}"))?;
+    Ok(time.with_timezone(&Utc))
+}
@@ -1,8 +1,11 @@
 use anyhow::Result;
 use clap::Subcommand;
+#[cfg(target_os = "linux")]
+use ipnetwork::IpNetwork;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::BooleanOption;
+use crate::output;
 
 #[derive(Subcommand, Debug)]
 pub enum Lan {
@@ -14,6 +17,24 @@ pub enum Lan {
         #[arg(value_parser = BooleanOption::custom_parser("allow", "block"))]
         policy: BooleanOption,
     },
+
+    /// Manage which local networks are reachable when local network sharing is allowed
+    #[cfg(target_os = "linux")]
+    #[clap(subcommand)]
+    Allow(AllowedNetworks),
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Subcommand, Debug)]
+pub enum AllowedNetworks {
+    /// Allow traffic to a local network, e.g. 10.20.0.0/16 or 192.168.1.10/32
+    Add { network: IpNetwork },
+
+    /// Stop allowing traffic to a local network
+    Remove { network: IpNetwork },
+
+    /// List the local networks that are reachable when local network sharing is allowed
+    List,
 }
 
 impl Lan {
@@ -21,6 +42,8 @@ impl Lan {
         match self {
             Lan::Get => Self::get().await,
             Lan::Set { policy } => Self::set(policy).await,
+            #[cfg(target_os = "linux")]
+            Lan::Allow(cmd) => cmd.handle().await,
         }
     }
 
@@ -33,9 +56,39 @@ impl Lan {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let allow_lan =
-            BooleanOption::with_labels(rpc.get_settings().await?.allow_lan, "allow", "block");
+        let allow_lan = rpc.get_settings().await?.allow_lan;
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({ "allow_lan": allow_lan }));
+        }
+        let allow_lan = BooleanOption::with_labels(allow_lan, "allow", "block");
         println!("Local network sharing setting: {allow_lan}");
         Ok(())
     }
 }
+
+#[cfg(target_os = "linux")]
+impl AllowedNetworks {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match self {
+            AllowedNetworks::Add { network } => {
+                rpc.add_allowed_lan_network(network).await?;
+                println!("Added {network} to allowed local networks");
+            }
+            AllowedNetworks::Remove { network } => {
+                rpc.remove_allowed_lan_network(network).await?;
+                println!("Removed {network} from allowed local networks");
+            }
+            AllowedNetworks::List => {
+                let settings = rpc.get_settings().await?;
+                if output::is_json() {
+                    return output::print_json(&settings.allowed_lan_networks);
+                }
+                for network in &settings.allowed_lan_networks {
+                    println!("{network}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::BooleanOption;
+use crate::output;
 
 #[derive(Subcommand, Debug)]
 pub enum LockdownMode {
@@ -29,7 +30,13 @@ impl LockdownMode {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let state = BooleanOption::from(rpc.get_settings().await?.block_when_disconnected);
+        let block_when_disconnected = rpc.get_settings().await?.block_when_disconnected;
+        if output::is_json() {
+            return output::print_json(
+                &serde_json::json!({ "block_when_disconnected": block_when_disconnected }),
+            );
+        }
+        let state = BooleanOption::from(block_when_disconnected);
         println!("Block traffic when the VPN is disconnected: {state}");
         Ok(())
     }
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+
+use super::BooleanOption;
+use crate::output;
+
+#[derive(Subcommand, Debug)]
+pub enum Metrics {
+    /// Display the current metrics endpoint settings
+    Get,
+    /// Enable or disable the metrics endpoint
+    ///
+    /// The endpoint does not require authentication, so any local user can read the metrics while
+    /// it is enabled.
+    Set {
+        policy: BooleanOption,
+        /// TCP port on the loopback interface to serve metrics on
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+        port: Option<u16>,
+    },
+}
+
+impl Metrics {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Metrics::Get => Self::get().await,
+            Metrics::Set { policy, port } => Self::set(policy, port).await,
+        }
+    }
+
+    async fn set(policy: BooleanOption, port: Option<u16>) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut metrics = rpc.get_settings().await?.metrics;
+        metrics.enabled = *policy;
+        if let Some(port) = port {
+            metrics.port = port;
+        }
+        rpc.set_metrics_settings(metrics).await?;
+        println!("Changed metrics endpoint setting");
+        Ok(())
+    }
+
+    async fn get() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let metrics = rpc.get_settings().await?.metrics;
+        if output::is_json() {
+            return output::print_json(&metrics);
+        }
+        println!("Metrics endpoint: {}", BooleanOption::from(metrics.enabled));
+        println!("Address: http://127.0.0.1:{}/metrics", metrics.port);
+        Ok(())
+    }
+}
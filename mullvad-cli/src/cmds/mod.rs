@@ -1,3 +1,4 @@
+use chrono::TimeDelta;
 use clap::builder::{PossibleValuesParser, TypedValueParser, ValueParser};
 use std::{io::stdin, ops::Deref};
 
@@ -9,10 +10,13 @@ pub mod bridge;
 pub mod custom_list;
 pub mod debug;
 pub mod dns;
+pub mod history;
 pub mod lan;
 pub mod lockdown;
+pub mod metrics;
 pub mod obfuscation;
 pub mod patch;
+pub mod profile;
 pub mod proxies;
 pub mod relay;
 pub mod relay_constraints;
@@ -110,3 +114,17 @@ async fn receive_confirmation(msg: &'static str, default: bool) -> bool {
     .await
     .unwrap()
 }
+
+/// Parse a duration such as "30m", which is a number followed by one of the units s, m, h, or d.
+fn parse_duration(s: &str) -> Option<TimeDelta> {
+    let unit_start = s.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = s.split_at(unit_start);
+    let number: i64 = number.parse().ok()?;
+    match unit {
+        "s" => TimeDelta::try_seconds(number),
+        "m" => TimeDelta::try_minutes(number),
+        "h" => TimeDelta::try_hours(number),
+        "d" => TimeDelta::try_days(number),
+        _ => None,
+    }
+}
@@ -8,6 +8,8 @@ use mullvad_types::{
     },
 };
 
+use crate::output;
+
 #[derive(Subcommand, Debug)]
 pub enum Obfuscation {
     /// Get current obfuscation settings
@@ -44,6 +46,9 @@ impl Obfuscation {
             Obfuscation::Get => {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let obfuscation_settings = rpc.get_settings().await?.obfuscation_settings;
+                if output::is_json() {
+                    return output::print_json(&obfuscation_settings);
+                }
                 println!(
                     "Obfuscation mode: {}",
                     obfuscation_settings.selected_obfuscation
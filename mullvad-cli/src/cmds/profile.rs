@@ -0,0 +1,71 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+
+use crate::output;
+
+#[derive(Subcommand, Debug)]
+pub enum Profile {
+    /// Save the current connection settings as a profile, replacing any profile with the same name
+    Save { name: String },
+    /// Apply all settings saved in a profile at once
+    Apply { name: String },
+    /// List the saved profiles
+    List {
+        /// Also print the settings saved in each profile
+        #[arg(long, short = 'v')]
+        verbose: bool,
+    },
+    /// Delete a profile
+    Delete { name: String },
+    /// Show the settings that would change if a profile was applied
+    Diff { name: String },
+}
+
+impl Profile {
+    pub async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match self {
+            Profile::Save { name } => {
+                rpc.save_settings_profile(name.clone()).await?;
+                println!("Saved the current settings as profile \"{name}\"");
+            }
+            Profile::Apply { name } => {
+                rpc.apply_settings_profile(name.clone()).await?;
+                println!("Applied profile \"{name}\"");
+            }
+            Profile::List { verbose } => {
+                let profiles = rpc.list_settings_profiles().await?;
+                if output::is_json() {
+                    return output::print_json(&profiles);
+                }
+                if profiles.is_empty() {
+                    println!("No profiles have been saved");
+                }
+                for profile in profiles {
+                    println!("{}", profile.name);
+                    if verbose {
+                        println!("{}", profile.patch);
+                    }
+                }
+            }
+            Profile::Delete { name } => {
+                rpc.delete_settings_profile(name.clone()).await?;
+                println!("Deleted profile \"{name}\"");
+            }
+            Profile::Diff { name } => {
+                let diffs = rpc.diff_settings_profile(name.clone()).await?;
+                if output::is_json() {
+                    return output::print_json(&diffs);
+                }
+                if diffs.is_empty() {
+                    println!("The current settings match profile \"{name}\"");
+                }
+                for diff in diffs {
+                    println!("{}: {} -> {}", diff.key, diff.current, diff.profile);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::{Context, Result, anyhow, bail};
-use clap::Subcommand;
+use clap::{Args, Subcommand, ValueEnum};
 use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
@@ -9,19 +9,26 @@ use mullvad_types::{
     relay_constraints::{
         GeographicLocationConstraint, LocationConstraint, LocationConstraintFormatter,
         OpenVpnConstraints, Ownership, Provider, Providers, RelayConstraints, RelayOverride,
-        RelaySettings, TransportPort, WireguardConstraints, allowed_ip::AllowedIps,
+        RelaySelectionMode, RelaySettings, TransportPort, WireguardConstraints,
+        allowed_ip::AllowedIps,
     },
     relay_list::{RelayEndpointData, RelayListCountry},
+    settings::{CustomDnsOptions, DnsOptions, DnsState, Settings},
+    wg_quick::WgQuickConfig,
 };
 use std::{
     collections::HashMap,
     io::BufRead,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+};
+use talpid_types::net::{
+    Endpoint, IpVersion, TransportProtocol, TunnelType, obfuscation::ObfuscatorConfig, openvpn,
+    wireguard,
 };
-use talpid_types::net::{Endpoint, IpVersion, TransportProtocol, TunnelType, openvpn, wireguard};
 
 use super::{BooleanOption, relay_constraints::LocationArgs};
-use crate::{cmds::receive_confirmation, print_option};
+use crate::{cmds::receive_confirmation, output, print_option};
 
 #[derive(Subcommand, Debug)]
 pub enum Relay {
@@ -33,7 +40,12 @@ pub enum Relay {
     Set(SetCommands),
 
     /// List available relays
-    List,
+    List {
+        /// Show the relays that were added, removed, or changed by the most recent update of the
+        /// relay list instead
+        #[arg(long)]
+        changes: bool,
+    },
 
     /// Update the relay list
     Update,
@@ -41,6 +53,9 @@ pub enum Relay {
     /// Override options for individual relays/servers
     #[clap(subcommand)]
     Override(OverrideCommands),
+
+    /// Print the configuration of the connected WireGuard relay in the wg-quick format
+    Export,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -90,6 +105,13 @@ pub enum SetCommands {
         ownership: Constraint<Ownership>,
     },
 
+    /// Set how to choose among the relays matching all other constraints.
+    SelectionMode {
+        /// 'random' picks a relay at random, 'lowest-latency' prefers the
+        /// relays with the lowest measured latency.
+        mode: RelaySelectionMode,
+    },
+
     /// Set tunnel protocol specific constraints
     #[clap(subcommand)]
     Tunnel(SetTunnelCommands),
@@ -209,9 +231,92 @@ pub enum SetCustomCommands {
         /// IPv6 gateway address
         #[arg(long)]
         v6_gateway: Option<Ipv6Addr>,
+        #[clap(flatten)]
+        obfuscation: CustomObfuscationArgs,
+    },
+
+    /// Use a custom WireGuard relay from a wg-quick configuration file. The MTU and DNS
+    /// servers in the file are applied to the tunnel settings.
+    #[clap(arg_required_else_help = true)]
+    WireguardConfig {
+        /// Path to the configuration file
+        path: PathBuf,
+        /// IPv4 gateway address. Defaults to the first host in the network of the interface
+        /// address
+        #[arg(long)]
+        v4_gateway: Option<Ipv4Addr>,
+        /// IPv6 gateway address
+        #[arg(long)]
+        v6_gateway: Option<Ipv6Addr>,
     },
 }
 
+/// Obfuscation server that a custom WireGuard relay is reached through. The Shadowsocks and QUIC
+/// servers must forward traffic to the WireGuard server on the loopback interface of their host,
+/// at the port of the configured peer endpoint.
+#[derive(Args, Debug, Clone)]
+pub struct CustomObfuscationArgs {
+    /// Reach the relay through an obfuscation server
+    #[arg(long, requires = "obfuscation_server")]
+    obfuscation: Option<CustomObfuscation>,
+    /// IP address and port of the obfuscation server
+    #[arg(long, requires = "obfuscation")]
+    obfuscation_server: Option<SocketAddr>,
+    /// Password of the Shadowsocks server. The cipher must be AES-256-GCM
+    #[arg(long)]
+    shadowsocks_password: Option<String>,
+    /// Host name that the QUIC server presents a certificate for
+    #[arg(long)]
+    quic_hostname: Option<String>,
+    /// Authentication token of the QUIC server
+    #[arg(long)]
+    quic_token: Option<String>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum CustomObfuscation {
+    Udp2tcp,
+    Shadowsocks,
+    Quic,
+}
+
+impl CustomObfuscationArgs {
+    fn into_config(self) -> Result<Option<ObfuscatorConfig>> {
+        let (Some(obfuscation), Some(endpoint)) = (self.obfuscation, self.obfuscation_server)
+        else {
+            return Ok(None);
+        };
+        let config = match obfuscation {
+            CustomObfuscation::Udp2tcp => ObfuscatorConfig::Udp2Tcp { endpoint },
+            CustomObfuscation::Shadowsocks => ObfuscatorConfig::Shadowsocks {
+                endpoint,
+                password: Some(
+                    self.shadowsocks_password
+                        .context("--shadowsocks-password is required for Shadowsocks")?,
+                ),
+                wireguard_port: None,
+            },
+            CustomObfuscation::Quic => {
+                let auth_token = self
+                    .quic_token
+                    .context("--quic-token is required for QUIC")?;
+                if auth_token.starts_with("Bearer") {
+                    bail!("The QUIC token must not include the \"Bearer\" prefix");
+                }
+                ObfuscatorConfig::Quic {
+                    hostname: self
+                        .quic_hostname
+                        .context("--quic-hostname is required for QUIC")?,
+                    endpoint,
+                    auth_token,
+                    wireguard_port: None,
+                }
+            }
+        };
+        Ok(Some(config))
+    }
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum OverrideCommands {
     /// Show current custom fields for servers
@@ -260,10 +365,12 @@ impl Relay {
     pub async fn handle(self) -> Result<()> {
         match self {
             Relay::Get => Self::get().await,
-            Relay::List => Self::list().await,
+            Relay::List { changes: false } => Self::list().await,
+            Relay::List { changes: true } => Self::list_changes().await,
             Relay::Update => Self::update().await,
             Relay::Set(subcmd) => Self::set(subcmd).await,
             Relay::Override(subcmd) => Self::r#override(subcmd).await,
+            Relay::Export => Self::export().await,
         }
     }
 
@@ -271,6 +378,9 @@ impl Relay {
         let mut rpc = MullvadProxyClient::new().await?;
         let settings = rpc.get_settings().await?;
         let relay_settings = settings.relay_settings;
+        if output::is_json() {
+            return output::print_json(&relay_settings);
+        }
 
         match relay_settings {
             RelaySettings::CustomTunnelEndpoint(endpoint) => {
@@ -295,6 +405,7 @@ impl Relay {
 
                 print_option!("Provider(s)", constraints.providers,);
                 print_option!("Ownership", constraints.ownership,);
+                print_option!("Selection mode", constraints.selection_mode,);
 
                 println!("OpenVPN constraints");
 
@@ -343,14 +454,23 @@ impl Relay {
     async fn list() -> Result<()> {
         let mut countries = get_active_relays().await?;
         countries.sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-        for mut country in countries {
+        for country in &mut countries {
             country
                 .cities
                 .sort_by(|c1, c2| natord::compare_ignore_case(&c1.name, &c2.name));
-            println!("{} ({})", country.name, country.code);
-            for mut city in country.cities {
+            for city in &mut country.cities {
                 city.relays
                     .sort_by(|r1, r2| natord::compare_ignore_case(&r1.hostname, &r2.hostname));
+            }
+        }
+
+        if output::is_json() {
+            return output::print_json(&countries);
+        }
+
+        for country in countries {
+            println!("{} ({})", country.name, country.code);
+            for city in country.cities {
                 println!(
                     "\t{} ({}) @ {:.5}°N, {:.5}°W",
                     city.name, city.code, city.latitude, city.longitude
@@ -384,6 +504,69 @@ impl Relay {
         Ok(())
     }
 
+    async fn list_changes() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let diff = rpc.get_relay_list_changes().await?;
+        if output::is_json() {
+            return output::print_json(&diff);
+        }
+        if diff.is_empty() {
+            println!("The relay list has not changed since the daemon was started");
+            return Ok(());
+        }
+        let settings = rpc.get_settings().await?;
+        let used_relays = used_relays(&settings);
+        let usage = |hostname: &str| match used_relays.get(hostname) {
+            Some(usage) => format!(" ({usage})"),
+            None => String::new(),
+        };
+
+        for hostname in &diff.added {
+            println!("Added: {hostname}");
+        }
+        for hostname in &diff.removed {
+            println!("Removed: {hostname}{}", usage(hostname));
+        }
+        for hostname in &diff.activated {
+            println!("Activated: {hostname}");
+        }
+        for hostname in &diff.deactivated {
+            println!("Deactivated: {hostname}{}", usage(hostname));
+        }
+        for change in &diff.address_changes {
+            let format_address = |address: Option<IpAddr>| match address {
+                Some(address) => address.to_string(),
+                None => "none".to_owned(),
+            };
+            println!(
+                "Address changed: {} ({} -> {})",
+                change.hostname,
+                format_address(change.old),
+                format_address(change.new)
+            );
+        }
+        for change in &diff.feature_changes {
+            let action = if change.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            println!("{} {action}: {}", change.feature, change.hostname);
+        }
+
+        if diff
+            .unavailable()
+            .any(|hostname| used_relays.contains_key(hostname))
+        {
+            println!();
+            println!(
+                "Some relays that are no longer available are used by the settings. Use \
+                 'mullvad relay set location' or 'mullvad custom-list' to select other relays."
+            );
+        }
+        Ok(())
+    }
+
     async fn update() -> Result<()> {
         MullvadProxyClient::new()
             .await?
@@ -422,6 +605,7 @@ impl Relay {
             }
             SetCommands::Provider { providers } => Self::set_providers(providers).await,
             SetCommands::Ownership { ownership } => Self::set_ownership(ownership).await,
+            SetCommands::SelectionMode { mode } => Self::set_selection_mode(mode).await,
             SetCommands::Tunnel(subcmd) => Self::set_tunnel(subcmd).await,
             SetCommands::TunnelProtocol { protocol } => Self::set_tunnel_protocol(protocol).await,
         }
@@ -463,7 +647,9 @@ impl Relay {
                 tunnel_ip,
                 v4_gateway,
                 v6_gateway,
+                obfuscation,
             } => {
+                let obfuscation = obfuscation.into_config()?;
                 Self::read_custom_wireguard_relay(
                     host,
                     port,
@@ -471,9 +657,15 @@ impl Relay {
                     tunnel_ip,
                     v4_gateway,
                     v6_gateway,
+                    obfuscation,
                 )
                 .await?
             }
+            SetCustomCommands::WireguardConfig {
+                path,
+                v4_gateway,
+                v6_gateway,
+            } => return Self::import_wireguard_config(path, v4_gateway, v6_gateway).await,
         };
         let mut rpc = MullvadProxyClient::new().await?;
         rpc.set_relay_settings(RelaySettings::CustomTunnelEndpoint(custom_endpoint))
@@ -482,6 +674,51 @@ impl Relay {
         Ok(())
     }
 
+    async fn import_wireguard_config(
+        path: PathBuf,
+        ipv4_gateway: Option<Ipv4Addr>,
+        ipv6_gateway: Option<Ipv6Addr>,
+    ) -> Result<()> {
+        let config: WgQuickConfig = tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .parse()
+            .context("Invalid WireGuard configuration")?;
+        let custom_endpoint = config
+            .to_custom_tunnel_endpoint(ipv4_gateway, ipv6_gateway)
+            .context("Unsupported WireGuard configuration")?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.set_relay_settings(RelaySettings::CustomTunnelEndpoint(custom_endpoint))
+            .await?;
+        println!("Relay constraints updated");
+
+        if let Some(mtu) = config.interface.mtu {
+            rpc.set_wireguard_mtu(Some(mtu)).await?;
+            println!("WireGuard MTU set to {mtu}");
+        }
+        if !config.interface.dns.is_empty() {
+            let settings = rpc.get_settings().await?;
+            rpc.set_dns_options(DnsOptions {
+                state: DnsState::Custom,
+                custom_options: CustomDnsOptions {
+                    addresses: config.interface.dns,
+                    encrypted_servers: vec![],
+                },
+                ..settings.tunnel_options.dns_options
+            })
+            .await?;
+            println!("Updated DNS settings");
+        }
+        Ok(())
+    }
+
+    async fn export() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        print!("{}", rpc.export_wireguard_config().await?);
+        Ok(())
+    }
+
     fn read_custom_openvpn_relay(
         host: String,
         port: u16,
@@ -499,6 +736,8 @@ impl Relay {
                 username,
                 password,
             }),
+            obfuscation: None,
+            preshared_key: None,
         }
     }
 
@@ -509,6 +748,7 @@ impl Relay {
         tunnel_ip: Vec<IpAddr>,
         ipv4_gateway: Ipv4Addr,
         ipv6_gateway: Option<Ipv6Addr>,
+        obfuscation: Option<ObfuscatorConfig>,
     ) -> Result<CustomTunnelEndpoint> {
         println!("Reading private key from standard input");
 
@@ -548,6 +788,8 @@ impl Relay {
                 #[cfg(target_os = "linux")]
                 fwmark: None,
             }),
+            obfuscation,
+            preshared_key: None,
         })
     }
 
@@ -613,6 +855,13 @@ impl Relay {
         .await
     }
 
+    async fn set_selection_mode(mode: RelaySelectionMode) -> Result<()> {
+        Self::update_constraints(|constraints| {
+            constraints.selection_mode = mode;
+        })
+        .await
+    }
+
     async fn set_openvpn_constraints(
         port: Option<Constraint<u16>>,
         protocol: Option<Constraint<TransportProtocol>>,
@@ -754,6 +1003,9 @@ impl Relay {
             OverrideCommands::Get => {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?;
+                if output::is_json() {
+                    return output::print_json(&settings.relay_overrides);
+                }
 
                 let mut overrides = HashMap::new();
                 for relay_override in settings.relay_overrides {
@@ -965,6 +1217,48 @@ pub async fn resolve_location_constraint(
     }
 }
 
+/// Return the hostnames of relays that are selected by the relay or bridge constraints, or that are
+/// part of a custom list, along with a description of where they are used.
+fn used_relays(settings: &Settings) -> HashMap<String, String> {
+    let selected_hostname = |location: &Constraint<LocationConstraint>| {
+        location
+            .as_ref()
+            .option()
+            .and_then(LocationConstraint::get_hostname)
+            .cloned()
+    };
+
+    let mut used_relays = HashMap::new();
+    for custom_list in settings.custom_lists.iter() {
+        let hostnames = custom_list
+            .locations
+            .iter()
+            .filter_map(GeographicLocationConstraint::get_hostname);
+        for hostname in hostnames {
+            used_relays.insert(
+                hostname.clone(),
+                format!("in custom list {}", custom_list.name),
+            );
+        }
+    }
+    if let Some(hostname) = selected_hostname(&settings.bridge_settings.normal.location) {
+        used_relays.insert(hostname, "selected bridge".to_owned());
+    }
+    if let RelaySettings::Normal(constraints) = &settings.relay_settings {
+        let wireguard_constraints = &constraints.wireguard_constraints;
+        let entry_location = wireguard_constraints
+            .multihop()
+            .then_some(&wireguard_constraints.entry_location);
+        if let Some(hostname) = entry_location.and_then(selected_hostname) {
+            used_relays.insert(hostname, "selected entry relay".to_owned());
+        }
+        if let Some(hostname) = selected_hostname(&constraints.location) {
+            used_relays.insert(hostname, "selected relay".to_owned());
+        }
+    }
+    used_relays
+}
+
 /// Return a list of all relays that are active and not bridges
 pub async fn get_active_relays() -> Result<Vec<RelayListCountry>> {
     let mut rpc = MullvadProxyClient::new().await?;
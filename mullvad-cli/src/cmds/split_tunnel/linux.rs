@@ -1,11 +1,27 @@
-use anyhow::Result;
-use clap::Subcommand;
+use anyhow::{Context, Result};
+use clap::{Subcommand, ValueEnum};
 use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::settings::SplitTunnelMode;
+use std::path::PathBuf;
 
-/// Manage split tunneling. To launch applications outside the tunnel, use the program
-/// 'mullvad-exclude' instead of this command
+use super::super::BooleanOption;
+use crate::output;
+
+/// Manage split tunneling. Applications can be excluded from the tunnel by path, and individual
+/// processes by PID. To launch a single instance of an application outside the tunnel, use the
+/// program 'mullvad-exclude' instead of this command. In include mode, only these applications
+/// and processes use the tunnel instead
 #[derive(Subcommand, Debug)]
 pub enum SplitTunnel {
+    /// Display the split tunnel status and apps
+    Get,
+    /// Enable or disable split tunneling of apps
+    Set { policy: BooleanOption },
+    /// Set whether split apps and processes are excluded from or included in the tunnel
+    Mode { mode: Mode },
+    /// Manage applications to exclude from the tunnel
+    #[clap(subcommand)]
+    App(App),
     /// List all processes that are excluded from the tunnel
     List,
     /// Add a PID to exclude from the tunnel
@@ -16,14 +32,77 @@ pub enum SplitTunnel {
     Clear,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Mode {
+    /// Only split apps and processes bypass the tunnel
+    Exclude,
+    /// Only split apps and processes use the tunnel
+    Include,
+}
+
+impl From<Mode> for SplitTunnelMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Exclude => SplitTunnelMode::Exclude,
+            Mode::Include => SplitTunnelMode::Include,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum App {
+    Add { path: PathBuf },
+    Remove { path: PathBuf },
+    Clear,
+}
+
 impl SplitTunnel {
     pub async fn handle(self) -> Result<()> {
         match self {
+            SplitTunnel::Get => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                let settings = rpc.get_settings().await?.split_tunnel;
+                if output::is_json() {
+                    return output::print_json(&settings);
+                }
+
+                let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
+
+                println!("Split tunneling state: {enable_exclusions}");
+                println!("Split tunneling mode: {}", settings.mode);
+
+                match settings.mode {
+                    SplitTunnelMode::Exclude => println!("Excluded applications:"),
+                    SplitTunnelMode::Include => println!("Included applications:"),
+                }
+                for path in &settings.apps {
+                    println!("{}", path.display());
+                }
+
+                Ok(())
+            }
+            SplitTunnel::Set { policy } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.set_split_tunnel_state(*policy).await?;
+                println!("Split tunnel policy: {policy}");
+                Ok(())
+            }
+            SplitTunnel::Mode { mode } => {
+                let mode = SplitTunnelMode::from(mode);
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.set_split_tunnel_mode(mode).await?;
+                println!("Split tunnel mode: {mode}");
+                Ok(())
+            }
+            SplitTunnel::App(subcmd) => Self::app(subcmd).await,
             SplitTunnel::List => {
                 let pids = MullvadProxyClient::new()
                     .await?
                     .get_split_tunnel_processes()
                     .await?;
+                if output::is_json() {
+                    return output::print_json(&pids);
+                }
 
                 println!("Excluded PIDs:");
                 for pid in &pids {
@@ -58,4 +137,38 @@ impl SplitTunnel {
             }
         }
     }
+
+    async fn app(subcmd: App) -> Result<()> {
+        match subcmd {
+            App::Add { path } => {
+                // The daemon matches against the resolved path of running executables
+                let path = std::fs::canonicalize(&path)
+                    .with_context(|| format!("Failed to resolve path {}", path.display()))?;
+                MullvadProxyClient::new()
+                    .await?
+                    .add_split_tunnel_app(path)
+                    .await?;
+                println!("Added path to excluded apps list");
+                Ok(())
+            }
+            App::Remove { path } => {
+                // The app may have been uninstalled, in which case the path cannot be resolved
+                let path = std::fs::canonicalize(&path).unwrap_or(path);
+                MullvadProxyClient::new()
+                    .await?
+                    .remove_split_tunnel_app(path)
+                    .await?;
+                println!("Stopped excluding app from tunnel");
+                Ok(())
+            }
+            App::Clear => {
+                MullvadProxyClient::new()
+                    .await?
+                    .clear_split_tunnel_apps()
+                    .await?;
+                println!("Stopped excluding all apps");
+                Ok(())
+            }
+        }
+    }
 }
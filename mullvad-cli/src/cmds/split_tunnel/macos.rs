@@ -5,6 +5,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::super::BooleanOption;
+use crate::output;
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -33,6 +34,9 @@ impl SplitTunnel {
             SplitTunnel::Get => {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?.split_tunnel;
+                if output::is_json() {
+                    return output::print_json(&settings);
+                }
 
                 let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
 
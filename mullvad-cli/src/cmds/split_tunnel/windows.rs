@@ -8,6 +8,7 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
 use super::super::BooleanOption;
+use crate::output;
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -42,6 +43,16 @@ impl SplitTunnel {
             SplitTunnel::Get { list_processes } => {
                 let mut rpc = MullvadProxyClient::new().await?;
                 let settings = rpc.get_settings().await?.split_tunnel;
+                if output::is_json() && list_processes {
+                    let processes = rpc.get_excluded_processes().await?;
+                    return output::print_json(&serde_json::json!({
+                        "split_tunnel": settings,
+                        "excluded_processes": processes,
+                    }));
+                }
+                if output::is_json() {
+                    return output::print_json(&settings);
+                }
 
                 let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
 
@@ -4,9 +4,13 @@ use futures::StreamExt;
 use mullvad_management_interface::{MullvadProxyClient, client::DaemonEvent};
 use mullvad_types::{device::DeviceState, states::TunnelState};
 use serde::Serialize;
-use std::fmt::Debug;
+use std::{fmt::Debug, time::Duration};
+use talpid_types::net::wireguard::TunnelStats;
 
-use crate::format;
+use crate::{format, output};
+
+/// How often to sample tunnel statistics when listening.
+const TUNNEL_STATS_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Subcommand, Debug, PartialEq)]
 pub enum Status {
@@ -24,9 +28,13 @@ pub struct StatusArgs {
     #[arg(long, short = 'd', conflicts_with_all = ["verbose", "json"])]
     debug: bool,
 
-    /// Format output as JSON
+    /// Format output as JSON. This is the same as `--output json`
     #[arg(long, short = 'j', conflicts_with_all = ["verbose", "debug"])]
     json: bool,
+
+    /// Show traffic statistics for the tunnel
+    #[arg(long, short = 's')]
+    stats: bool,
 }
 
 impl Status {
@@ -35,8 +43,25 @@ impl Status {
         args: StatusArgs,
         mut previous_tunnel_state: TunnelState,
     ) -> Result<()> {
+        let mut stats_rpc = rpc.clone();
+        let mut stats_stream = if args.stats {
+            Some(stats_rpc.tunnel_stats_listen(TUNNEL_STATS_INTERVAL).await?)
+        } else {
+            None
+        };
+
         let mut event_stream = rpc.events_listen().await?;
-        while let Some(event) = event_stream.next().await {
+        loop {
+            let event = tokio::select! {
+                event = event_stream.next() => event,
+                Some(stats) = async { stats_stream.as_mut()?.next().await } => {
+                    print_tunnel_stats(&args, &stats?)?;
+                    continue;
+                }
+            };
+            let Some(event) = event else {
+                break;
+            };
             match event? {
                 DaemonEvent::TunnelState(new_state) => {
                     if args.debug {
@@ -68,6 +93,15 @@ impl Status {
                 DaemonEvent::NewAccessMethod(access_method) => {
                     print_debug_or_json(&args, "New access method", &access_method)?;
                 }
+                DaemonEvent::RelayListChanges(diff) => {
+                    print_debug_or_json(&args, "Relay list changes", &diff)?;
+                }
+                DaemonEvent::LeakDetected(report) => {
+                    print_debug_or_json(&args, "Leak detected", &report)?;
+                }
+                DaemonEvent::AccountExpiryWarning(warning) => {
+                    print_debug_or_json(&args, "Account expiry warning", &warning)?;
+                }
             }
         }
         Ok(())
@@ -75,11 +109,17 @@ impl Status {
 }
 
 pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
+    let args = StatusArgs {
+        json: args.json || (output::is_json() && !args.debug),
+        ..args
+    };
     let mut rpc = MullvadProxyClient::new().await?;
     let state = rpc.get_tunnel_state().await?;
     let device = rpc.get_device().await?;
 
-    print_account_logged_out(&state, &device);
+    if !args.json {
+        print_account_logged_out(&state, &device);
+    }
 
     if args.debug {
         println!("Tunnel state: {state:#?}");
@@ -90,6 +130,14 @@ pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
         format::print_state(&state, None, args.verbose);
     }
 
+    if args.stats {
+        match rpc.get_tunnel_stats().await? {
+            Some(stats) => print_tunnel_stats(&args, &stats)?,
+            None if !args.json && !args.debug => println!("No tunnel statistics available"),
+            None => (),
+        }
+    }
+
     if cmd == Some(Status::Listen) {
         Status::listen(rpc, args, state).await?;
     }
@@ -111,6 +159,15 @@ fn print_account_logged_out(state: &TunnelState, device: &DeviceState) {
     }
 }
 
+fn print_tunnel_stats(args: &StatusArgs, stats: &TunnelStats) -> Result<()> {
+    if args.debug || args.json {
+        print_debug_or_json(args, "Tunnel stats", stats)
+    } else {
+        format::print_tunnel_stats(stats);
+        Ok(())
+    }
+}
+
 fn print_debug_or_json<T: Debug + Serialize>(
     args: &StatusArgs,
     debug_message: &str,
@@ -3,12 +3,14 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
-    relay_constraints::{AllowedIps, RelaySettings, WireguardConstraints},
+    relay_constraints::{AllowedIps, RelaySettings, RetryStep, WireguardConstraints},
     wireguard::{DEFAULT_ROTATION_INTERVAL, QuantumResistantState, RotationInterval},
 };
+use std::time::Duration;
+use talpid_types::net::wireguard::{ConnectivityCheckOptions, ConnectivityProbe};
 
 use super::BooleanOption;
-use crate::print_option;
+use crate::{output, print_option};
 
 #[derive(Subcommand, Debug)]
 pub enum Tunnel {
@@ -18,6 +20,65 @@ pub enum Tunnel {
     /// Set tunnel options
     #[clap(subcommand)]
     Set(TunnelOptions),
+
+    /// Manage the order in which connection methods are tried when connecting to a WireGuard
+    /// relay fails
+    #[clap(subcommand)]
+    RetryOrder(RetryOrder),
+
+    /// Manage how the app detects that a WireGuard tunnel has stopped working
+    #[clap(subcommand)]
+    ConnectivityCheck(ConnectivityCheck),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConnectivityCheck {
+    /// Show the connectivity check options
+    Get,
+
+    /// Change the connectivity check options. Options that are not given are left unchanged.
+    #[clap(arg_required_else_help = true)]
+    Set {
+        /// Seconds to wait for a response to outgoing traffic before probing the tunnel
+        #[arg(long)]
+        rx_timeout: Option<u64>,
+        /// Seconds without any traffic before probing the tunnel
+        #[arg(long)]
+        traffic_timeout: Option<u64>,
+        /// Seconds to wait for traffic after probing before the tunnel is considered broken
+        #[arg(long)]
+        probe_timeout: Option<u64>,
+        /// Host to probe instead of pinging the tunnel gateway. Can be given multiple times.
+        /// Either 'icmp:<IPv4 address>', 'tcp:<IP address>:<port>', or 'http:<IP address>:<port>'.
+        ///
+        /// Example: "tcp:10.64.0.1:1337"
+        #[arg(long = "probe", conflicts_with = "gateway")]
+        probes: Vec<ConnectivityProbe>,
+        /// Ping the tunnel gateway, which is the default
+        #[arg(long)]
+        gateway: bool,
+    },
+
+    /// Restore the default connectivity check options
+    Reset,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum RetryOrder {
+    /// Show the retry order
+    Get,
+
+    /// Set the retry order. Each step is either 'any' or a combination of an obfuscation method
+    /// (off, udp2tcp, shadowsocks, quic), an IP version (ipv4, ipv6), and a port, joined by '+'.
+    ///
+    /// Example: "any udp2tcp+ipv6 shadowsocks+443 quic"
+    Set {
+        #[arg(required = true)]
+        steps: Vec<RetryStep>,
+    },
+
+    /// Restore the default retry order
+    Reset,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -78,12 +139,31 @@ impl Tunnel {
         match self {
             Tunnel::Get => Self::get().await,
             Tunnel::Set(options) => Self::set(options).await,
+            Tunnel::RetryOrder(cmd) => cmd.handle().await,
+            Tunnel::ConnectivityCheck(cmd) => cmd.handle().await,
         }
     }
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let tunnel_options = rpc.get_settings().await?.tunnel_options;
+        let settings = rpc.get_settings().await?;
+        let tunnel_options = settings.tunnel_options;
+
+        // Get the WireGuard allowed IPs
+        let wireguard_constraints = match settings.relay_settings {
+            RelaySettings::Normal(settings) => settings.wireguard_constraints,
+            RelaySettings::CustomTunnelEndpoint(_) => WireguardConstraints::default(),
+        };
+
+        let key = rpc.get_wireguard_key().await?;
+
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "tunnel_options": tunnel_options,
+                "wireguard_key": key,
+                "allowed_ips": wireguard_constraints.allowed_ips,
+            }));
+        }
 
         println!("OpenVPN options");
 
@@ -113,7 +193,6 @@ impl Tunnel {
 
         print_option!("DAITA", tunnel_options.wireguard.daita.enabled);
 
-        let key = rpc.get_wireguard_key().await?;
         print_option!("Public key", key.key,);
         print_option!(format_args!(
             "Created {}",
@@ -127,12 +206,6 @@ impl Tunnel {
             },
         );
 
-        // Get the WireGuard allowed IPs
-        let wireguard_constraints = match rpc.get_settings().await?.relay_settings {
-            RelaySettings::Normal(settings) => settings.wireguard_constraints,
-            RelaySettings::CustomTunnelEndpoint(_) => WireguardConstraints::default(),
-        };
-
         print_option!(
             "Allowed IPs",
             match wireguard_constraints.allowed_ips {
@@ -265,3 +338,105 @@ impl Tunnel {
         Ok(())
     }
 }
+
+impl RetryOrder {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match self {
+            RetryOrder::Get => {
+                let (steps, is_default) = match rpc.get_settings().await?.retry_order {
+                    Some(steps) => (steps, false),
+                    None => (RetryStep::default_order(), true),
+                };
+                if output::is_json() {
+                    return output::print_json(
+                        &serde_json::json!({ "steps": steps, "default": is_default }),
+                    );
+                }
+                println!("Retry order{}:", if is_default { " (default)" } else { "" });
+                for (i, step) in steps.iter().enumerate() {
+                    println!("{:>4}. {step}", i + 1);
+                }
+            }
+            RetryOrder::Set { steps } => {
+                rpc.set_retry_order(Some(steps)).await?;
+                println!("Updated retry order");
+            }
+            RetryOrder::Reset => {
+                rpc.set_retry_order(None).await?;
+                println!("Restored the default retry order");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ConnectivityCheck {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match self {
+            ConnectivityCheck::Get => {
+                let options = rpc
+                    .get_settings()
+                    .await?
+                    .tunnel_options
+                    .wireguard
+                    .connectivity_check;
+                if output::is_json() {
+                    return output::print_json(&options);
+                }
+                println!("Connectivity check");
+                print_option!("RX timeout", format!("{} s", options.rx_timeout.as_secs()));
+                print_option!(
+                    "Traffic timeout",
+                    format!("{} s", options.traffic_timeout.as_secs())
+                );
+                print_option!(
+                    "Probe timeout",
+                    format!("{} s", options.probe_timeout.as_secs())
+                );
+                if options.probes.is_empty() {
+                    print_option!("Probes", "ICMP to the tunnel gateway");
+                } else {
+                    let probes: Vec<_> = options.probes.iter().map(ToString::to_string).collect();
+                    print_option!("Probes", probes.join(", "));
+                }
+            }
+            ConnectivityCheck::Set {
+                rx_timeout,
+                traffic_timeout,
+                probe_timeout,
+                probes,
+                gateway,
+            } => {
+                let mut options = rpc
+                    .get_settings()
+                    .await?
+                    .tunnel_options
+                    .wireguard
+                    .connectivity_check;
+                if let Some(rx_timeout) = rx_timeout {
+                    options.rx_timeout = Duration::from_secs(rx_timeout);
+                }
+                if let Some(traffic_timeout) = traffic_timeout {
+                    options.traffic_timeout = Duration::from_secs(traffic_timeout);
+                }
+                if let Some(probe_timeout) = probe_timeout {
+                    options.probe_timeout = Duration::from_secs(probe_timeout);
+                }
+                if gateway || !probes.is_empty() {
+                    options.probes = probes;
+                }
+                options.validate()?;
+                rpc.set_wireguard_connectivity_check(options).await?;
+                println!("Updated the connectivity check options");
+            }
+            ConnectivityCheck::Reset => {
+                rpc.set_wireguard_connectivity_check(ConnectivityCheckOptions::default())
+                    .await?;
+                println!("Restored the default connectivity check options");
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
 use anyhow::{Context, Result};
 use mullvad_management_interface::MullvadProxyClient;
 
+use crate::output;
+
 pub async fn print() -> Result<()> {
-    println!("{:22}: {}", "Current version", mullvad_version::VERSION);
+    if !output::is_json() {
+        println!("{:22}: {}", "Current version", mullvad_version::VERSION);
+    }
 
     let mut rpc = MullvadProxyClient::new()
         .await
@@ -13,7 +17,7 @@ pub async fn print() -> Result<()> {
         .await
         .context("Failed to get current mullvad-daemon version")?;
 
-    if daemon_version != mullvad_version::VERSION {
+    if daemon_version != mullvad_version::VERSION && !output::is_json() {
         println!("{:22}: {}", "mullvad-daemon version", daemon_version);
     };
 
@@ -21,6 +25,15 @@ pub async fn print() -> Result<()> {
         .get_version_info()
         .await
         .context("Failed to get version info")?;
+
+    if output::is_json() {
+        return output::print_json(&serde_json::json!({
+            "current_version": mullvad_version::VERSION,
+            "daemon_version": daemon_version,
+            "version_info": version_info,
+        }));
+    }
+
     println!(
         "{:22}: {}",
         "Is supported", version_info.current_version_supported
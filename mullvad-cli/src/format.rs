@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::SystemTime};
 
 use itertools::Itertools;
 use mullvad_types::{
@@ -6,7 +6,10 @@ use mullvad_types::{
     states::TunnelState,
 };
 use talpid_types::{
-    net::{Endpoint, TunnelEndpoint},
+    net::{
+        Endpoint, TunnelEndpoint,
+        wireguard::{PeerStats, TunnelStats},
+    },
     tunnel::{ActionAfterDisconnect, ErrorState},
 };
 
@@ -273,6 +276,74 @@ fn format_endpoint(hostname: Option<&str>, endpoint: &Endpoint, verbose: bool) -
     }
 }
 
+pub fn print_tunnel_stats(stats: &TunnelStats) {
+    match &stats.entry {
+        Some(entry) => {
+            print_peer_stats("Entry", entry, stats.sampled_at);
+            print_peer_stats("Exit", &stats.exit, stats.sampled_at);
+        }
+        None => print_peer_stats("Tunnel", &stats.exit, stats.sampled_at),
+    }
+    if let Some(connectivity) = &stats.connectivity {
+        let age = |time: SystemTime| {
+            let age = stats.sampled_at.duration_since(time).unwrap_or_default();
+            format!("{} s ago", age.as_secs())
+        };
+        print_option!(
+            "Last received",
+            connectivity.last_rx.map(age).unwrap_or("never".to_owned())
+        );
+        if let Some(probing_since) = connectivity.probing_since {
+            print_option!("Probing since", age(probing_since));
+        }
+    }
+}
+
+fn print_peer_stats(hop: &str, stats: &PeerStats, sampled_at: SystemTime) {
+    print_option!(
+        format!("{hop} sent"),
+        format_traffic(stats.tx_bytes, stats.tx_rate)
+    );
+    print_option!(
+        format!("{hop} received"),
+        format_traffic(stats.rx_bytes, stats.rx_rate)
+    );
+    let handshake = match stats.last_handshake {
+        Some(last_handshake) => {
+            let age = sampled_at
+                .duration_since(last_handshake)
+                .unwrap_or_default()
+                .as_secs();
+            format!("{age} s ago")
+        }
+        None => "never".to_owned(),
+    };
+    print_option!(format!("{hop} last handshake"), handshake);
+}
+
+fn format_traffic(bytes: u64, rate: Option<f64>) -> String {
+    match rate {
+        Some(rate) => format!("{} ({}/s)", format_bytes(bytes as f64), format_bytes(rate)),
+        None => format_bytes(bytes as f64),
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
 fn print_error_state(error_state: &ErrorState) {
     if error_state.block_failure().is_some() {
         eprintln!("Mullvad daemon failed to setup firewall rules!");
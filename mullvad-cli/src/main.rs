@@ -1,16 +1,29 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 mod cmds;
 mod format;
+mod output;
 use cmds::*;
+use output::OutputFormat;
 
 pub const BIN_NAME: &str = env!("CARGO_BIN_NAME");
 
 #[derive(Debug, Parser)]
 #[command(author, version = mullvad_version::VERSION, about, long_about = None)]
 #[command(propagate_version = true)]
-enum Cli {
+struct Cli {
+    /// Output format. When this is "json", commands that display information print it as JSON,
+    /// and errors are printed to standard error as JSON objects
+    #[arg(long, short = 'o', global = true, value_enum, default_value_t)]
+    output: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
     /// Control and display information about your Mullvad account
     #[clap(subcommand)]
     Account(account::Account),
@@ -38,10 +51,22 @@ enum Cli {
     #[clap(subcommand)]
     Dns(dns::Dns),
 
+    /// Show the recorded history of tunnel state changes
+    History(history::History),
+
     /// Control the allow local network sharing setting
     #[clap(subcommand)]
     Lan(lan::Lan),
 
+    /// Control the local endpoint that exports daemon metrics in the OpenMetrics format
+    #[clap(subcommand)]
+    Metrics(metrics::Metrics),
+
+    /// Save and apply named sets of connection settings, such as one for the office and one for
+    /// traveling
+    #[clap(subcommand)]
+    Profile(profile::Profile),
+
     /// Connect to a VPN relay
     Connect {
         /// Wait until connected before exiting
@@ -158,32 +183,48 @@ async fn main() -> Result<()> {
     #[cfg(unix)]
     handle_sigpipe().unwrap();
 
-    match Cli::parse() {
-        Cli::Account(cmd) => cmd.handle().await,
-        Cli::Bridge(cmd) => cmd.handle().await,
-        Cli::Connect { wait } => tunnel_state::connect(wait).await,
-        Cli::Reconnect { wait } => tunnel_state::reconnect(wait).await,
-        Cli::Debug(cmd) => cmd.handle().await,
-        Cli::Disconnect { wait } => tunnel_state::disconnect(wait).await,
-        Cli::AutoConnect(cmd) => cmd.handle().await,
-        Cli::BetaProgram(cmd) => cmd.handle().await,
-        Cli::LockdownMode(cmd) => cmd.handle().await,
-        Cli::Dns(cmd) => cmd.handle().await,
-        Cli::Lan(cmd) => cmd.handle().await,
-        Cli::Obfuscation(cmd) => cmd.handle().await,
-        Cli::ApiAccess(cmd) => cmd.handle().await,
-        Cli::Version => version::print().await,
-        Cli::FactoryReset => reset::handle().await,
-        Cli::Relay(cmd) => cmd.handle().await,
-        Cli::Tunnel(cmd) => cmd.handle().await,
-        Cli::SplitTunnel(cmd) => cmd.handle().await,
-        Cli::Status { cmd, args } => status::handle(cmd, args).await,
-        Cli::CustomList(cmd) => cmd.handle().await,
-        Cli::ImportSettings { file } => patch::import(file).await,
-        Cli::ExportSettings { file } => patch::export(file).await,
+    let Cli { output, command } = Cli::parse();
+    output::set_format(output);
+
+    match run(command).await {
+        Err(error) if output::is_json() => {
+            output::print_json_error(&error);
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Account(cmd) => cmd.handle().await,
+        Command::Bridge(cmd) => cmd.handle().await,
+        Command::Connect { wait } => tunnel_state::connect(wait).await,
+        Command::Reconnect { wait } => tunnel_state::reconnect(wait).await,
+        Command::Debug(cmd) => cmd.handle().await,
+        Command::Disconnect { wait } => tunnel_state::disconnect(wait).await,
+        Command::AutoConnect(cmd) => cmd.handle().await,
+        Command::BetaProgram(cmd) => cmd.handle().await,
+        Command::LockdownMode(cmd) => cmd.handle().await,
+        Command::Dns(cmd) => cmd.handle().await,
+        Command::History(cmd) => cmd.handle().await,
+        Command::Lan(cmd) => cmd.handle().await,
+        Command::Metrics(cmd) => cmd.handle().await,
+        Command::Profile(cmd) => cmd.handle().await,
+        Command::Obfuscation(cmd) => cmd.handle().await,
+        Command::ApiAccess(cmd) => cmd.handle().await,
+        Command::Version => version::print().await,
+        Command::FactoryReset => reset::handle().await,
+        Command::Relay(cmd) => cmd.handle().await,
+        Command::Tunnel(cmd) => cmd.handle().await,
+        Command::SplitTunnel(cmd) => cmd.handle().await,
+        Command::Status { cmd, args } => status::handle(cmd, args).await,
+        Command::CustomList(cmd) => cmd.handle().await,
+        Command::ImportSettings { file } => patch::import(file).await,
+        Command::ExportSettings { file } => patch::export(file).await,
 
         #[cfg(all(unix, not(target_os = "android")))]
-        Cli::ShellCompletions { shell, dir } => {
+        Command::ShellCompletions { shell, dir } => {
             use anyhow::Context;
             use clap::CommandFactory;
 
@@ -0,0 +1,63 @@
+//! Machine-readable output. When `--output json` is passed, query commands print their result as
+//! a single JSON value instead of text, and errors are printed as JSON objects. See
+//! `docs/cli-json-output.md` for the schema.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Format used for printing the results of commands
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON, with one value per result
+    Json,
+}
+
+/// Set the output format used by all commands. This should be called once, before running any
+/// command.
+pub fn set_format(format: OutputFormat) {
+    OUTPUT_FORMAT
+        .set(format)
+        .expect("output format must only be set once");
+}
+
+/// Returns whether results should be printed as JSON.
+pub fn is_json() -> bool {
+    OUTPUT_FORMAT.get().copied().unwrap_or_default() == OutputFormat::Json
+}
+
+/// Print `value` as a single line of JSON to stdout.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let json = serde_json::to_string(value).context("Failed to format output as JSON")?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Print `error`, including its causes, as a JSON object to stderr.
+pub fn print_json_error(error: &anyhow::Error) {
+    #[derive(Serialize)]
+    struct ErrorObject {
+        message: String,
+        causes: Vec<String>,
+    }
+    #[derive(Serialize)]
+    struct ErrorOutput {
+        error: ErrorObject,
+    }
+
+    let output = ErrorOutput {
+        error: ErrorObject {
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(ToString::to_string).collect(),
+        },
+    };
+    match serde_json::to_string(&output) {
+        Ok(json) => eprintln!("{json}"),
+        Err(_) => eprintln!("Error: {error:?}"),
+    }
+}
@@ -0,0 +1,415 @@
+//! Emits an [AccountExpiryWarning] whenever the time left on the account drops below one of the
+//! thresholds in [ExpiryReminders]. The expiry is fetched again when a reminder is due, so that no
+//! warning is emitted if time has been added to the account in the meantime.
+
+use crate::device;
+use chrono::{DateTime, Utc};
+use futures::{
+    FutureExt, StreamExt,
+    channel::mpsc,
+    future::{self, BoxFuture},
+};
+use mullvad_types::account::{AccountExpiryWarning, ExpiryReminders};
+use std::time::Duration;
+use talpid_future::retry::{ExponentialBackoff, Jittered};
+use talpid_types::ErrorExt;
+
+/// Delay before the first retry, if the expiry cannot be fetched.
+const RETRY_INITIAL_DELAY: Duration = Duration::from_secs(60);
+/// Maximum delay between retries, if the expiry cannot be fetched.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+const fn retry_strategy() -> Jittered<ExponentialBackoff> {
+    Jittered::jitter(
+        ExponentialBackoff::new(RETRY_INITIAL_DELAY, 2).max_delay(Some(RETRY_MAX_DELAY)),
+    )
+}
+
+enum Command {
+    /// The expiry was fetched by someone else.
+    SetExpiry(DateTime<Utc>),
+    /// Fetch the expiry right away, e.g. after logging in.
+    Refresh,
+    /// Stop emitting warnings until the expiry is known again.
+    LoggedOut,
+    SetReminders(ExpiryReminders),
+}
+
+#[derive(Clone)]
+pub(crate) struct ExpiryMonitorHandle {
+    tx: mpsc::UnboundedSender<Command>,
+}
+
+impl ExpiryMonitorHandle {
+    pub fn set_expiry(&self, expiry: DateTime<Utc>) {
+        self.send(Command::SetExpiry(expiry));
+    }
+
+    pub fn refresh(&self) {
+        self.send(Command::Refresh);
+    }
+
+    pub fn logged_out(&self) {
+        self.send(Command::LoggedOut);
+    }
+
+    pub fn set_reminders(&self, reminders: ExpiryReminders) {
+        self.send(Command::SetReminders(reminders));
+    }
+
+    fn send(&self, command: Command) {
+        // The monitor only stops when all handles are dropped
+        let _ = self.tx.unbounded_send(command);
+    }
+}
+
+/// Spawn the expiry monitor. The expiry is fetched using `fetch_expiry` right away, and then
+/// whenever the next reminder is due. `on_warning` is called for every warning.
+pub(crate) fn spawn(
+    fetch_expiry: impl FnMut() -> BoxFuture<'static, Result<DateTime<Utc>, device::Error>>
+    + Send
+    + 'static,
+    reminders: ExpiryReminders,
+    on_warning: impl Fn(AccountExpiryWarning) + Send + 'static,
+) -> ExpiryMonitorHandle {
+    let (tx, rx) = mpsc::unbounded();
+    let monitor = ExpiryMonitor {
+        fetch_expiry,
+        on_warning,
+        schedule: Schedule::new(reminders),
+        expiry: None,
+        refresh_delay: Some(Duration::ZERO),
+        retry_strategy: retry_strategy(),
+    };
+    tokio::spawn(monitor.run(rx));
+    ExpiryMonitorHandle { tx }
+}
+
+struct ExpiryMonitor<F, W> {
+    fetch_expiry: F,
+    on_warning: W,
+    schedule: Schedule,
+    /// The last known expiry, or `None` if logged out.
+    expiry: Option<DateTime<Utc>>,
+    /// Time until the expiry should be fetched, if that is sooner than the next reminder.
+    refresh_delay: Option<Duration>,
+    retry_strategy: Jittered<ExponentialBackoff>,
+}
+
+impl<F, W> ExpiryMonitor<F, W>
+where
+    F: FnMut() -> BoxFuture<'static, Result<DateTime<Utc>, device::Error>>,
+    W: Fn(AccountExpiryWarning),
+{
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>) {
+        loop {
+            let delay = self.next_wakeup(Utc::now());
+            let mut wakeup = Box::pin(
+                async move {
+                    match delay {
+                        Some(delay) => talpid_time::sleep(delay).await,
+                        None => future::pending().await,
+                    }
+                }
+                .fuse(),
+            );
+
+            futures::select! {
+                command = commands.next() => match command {
+                    Some(command) => self.handle_command(command),
+                    None => return,
+                },
+                () = wakeup => self.refresh().await,
+            }
+        }
+    }
+
+    fn next_wakeup(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.refresh_delay
+            .or_else(|| self.schedule.time_until_next(self.expiry?, now))
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::SetExpiry(expiry) => self.update_expiry(expiry),
+            Command::Refresh => self.refresh_delay = Some(Duration::ZERO),
+            Command::LoggedOut => self.logged_out(),
+            Command::SetReminders(reminders) => {
+                self.schedule
+                    .set_reminders(reminders, self.expiry, Utc::now());
+            }
+        }
+    }
+
+    async fn refresh(&mut self) {
+        match (self.fetch_expiry)().await {
+            Ok(expiry) => self.update_expiry(expiry),
+            Err(
+                device::Error::NoDevice
+                | device::Error::InvalidDevice
+                | device::Error::InvalidAccount,
+            ) => self.logged_out(),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to fetch account expiry")
+                );
+                self.refresh_delay = self.retry_strategy.next();
+            }
+        }
+    }
+
+    fn update_expiry(&mut self, expiry: DateTime<Utc>) {
+        self.expiry = Some(expiry);
+        self.refresh_delay = None;
+        self.retry_strategy = retry_strategy();
+        if let Some(warning) = self.schedule.update(expiry, Utc::now()) {
+            (self.on_warning)(warning);
+        }
+    }
+
+    fn logged_out(&mut self) {
+        self.expiry = None;
+        self.refresh_delay = None;
+        self.retry_strategy = retry_strategy();
+        self.schedule.reset();
+    }
+}
+
+/// Keeps track of which reminders have been emitted for the current expiry.
+struct Schedule {
+    reminders: ExpiryReminders,
+    /// The smallest threshold that the remaining time has dropped below.
+    passed: Option<Duration>,
+}
+
+impl Schedule {
+    fn new(reminders: ExpiryReminders) -> Self {
+        Schedule {
+            reminders,
+            passed: None,
+        }
+    }
+
+    /// Update the expiry, returning a warning if the remaining time has dropped below a threshold
+    /// that has not been warned about yet.
+    fn update(
+        &mut self,
+        expiry: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Option<AccountExpiryWarning> {
+        let remaining = remaining(expiry, now);
+        let threshold = self.passed_threshold(remaining);
+        let warn = match (threshold, self.passed) {
+            (Some(threshold), Some(passed)) => threshold < passed,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        self.passed = threshold;
+        warn.then_some(AccountExpiryWarning { expiry, remaining })
+    }
+
+    /// Returns the time until the next threshold is reached, or `None` if there are no thresholds
+    /// left to reach.
+    fn time_until_next(&self, expiry: DateTime<Utc>, now: DateTime<Utc>) -> Option<Duration> {
+        let remaining = remaining(expiry, now);
+        self.reminders
+            .thresholds()
+            .iter()
+            .find(|&&threshold| threshold < remaining)
+            .map(|&threshold| remaining - threshold)
+    }
+
+    /// Replace the thresholds. Thresholds that the remaining time is already below are not warned
+    /// about.
+    fn set_reminders(
+        &mut self,
+        reminders: ExpiryReminders,
+        expiry: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) {
+        if self.reminders == reminders {
+            return;
+        }
+        self.reminders = reminders;
+        self.passed = expiry.and_then(|expiry| self.passed_threshold(remaining(expiry, now)));
+    }
+
+    fn reset(&mut self) {
+        self.passed = None;
+    }
+
+    /// Returns the smallest threshold that is greater than or equal to `remaining`.
+    fn passed_threshold(&self, remaining: Duration) -> Option<Duration> {
+        self.reminders
+            .thresholds()
+            .iter()
+            .rev()
+            .find(|&&threshold| remaining <= threshold)
+            .copied()
+    }
+}
+
+fn remaining(expiry: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (expiry - now).to_std().unwrap_or(Duration::ZERO)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeDelta;
+    use std::sync::{Arc, Mutex};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn days(days: u32) -> TimeDelta {
+        TimeDelta::days(i64::from(days))
+    }
+
+    #[test]
+    fn test_schedule() {
+        let now = Utc::now();
+        let expiry = now + days(5);
+        let mut schedule = Schedule::new(ExpiryReminders::default());
+
+        let warning = schedule
+            .update(expiry, now)
+            .expect("expected 7 day warning");
+        assert_eq!(warning.remaining, 5 * DAY);
+        assert_eq!(schedule.time_until_next(expiry, now), Some(2 * DAY));
+
+        // Nothing new has been crossed
+        assert!(schedule.update(expiry, now + days(1)).is_none());
+
+        let warning = schedule
+            .update(expiry, now + days(2))
+            .expect("expected 3 day warning");
+        assert_eq!(warning.remaining, 3 * DAY);
+        assert_eq!(
+            schedule.time_until_next(expiry, now + days(2)),
+            Some(2 * DAY)
+        );
+
+        let warning = schedule
+            .update(expiry, now + days(6))
+            .expect("expected expiry warning");
+        assert!(warning.is_expired());
+        assert_eq!(schedule.time_until_next(expiry, now + days(6)), None);
+        assert!(schedule.update(expiry, now + days(7)).is_none());
+    }
+
+    #[test]
+    fn test_schedule_time_added() {
+        let now = Utc::now();
+        let mut schedule = Schedule::new(ExpiryReminders::default());
+
+        assert!(schedule.update(now + days(2), now).is_some());
+        // Adding a little time should not warn about the 3 day threshold again
+        assert!(schedule.update(now + days(3), now).is_none());
+        // Adding enough time resets the reminders
+        assert!(schedule.update(now + days(30), now).is_none());
+        assert!(schedule.update(now + days(30), now + days(24)).is_some());
+    }
+
+    #[test]
+    fn test_schedule_set_reminders() {
+        let now = Utc::now();
+        let expiry = now + days(2);
+        let mut schedule = Schedule::new(ExpiryReminders::new(vec![DAY]).unwrap());
+
+        assert!(schedule.update(expiry, now).is_none());
+        // Thresholds that have already been passed are not warned about
+        schedule.set_reminders(ExpiryReminders::default(), Some(expiry), now);
+        assert!(schedule.update(expiry, now).is_none());
+        assert!(schedule.update(expiry, now + days(1)).is_some());
+    }
+
+    /// Spawn a monitor for an account that expires `expires_in` from now, and that fails to fetch
+    /// the expiry `failures` times. Returns the warnings emitted and the number of fetches.
+    fn spawn_monitor(
+        expires_in: Duration,
+        failures: usize,
+    ) -> (
+        ExpiryMonitorHandle,
+        Arc<Mutex<Vec<AccountExpiryWarning>>>,
+        Arc<Mutex<usize>>,
+    ) {
+        // The wall clock does not advance with the mocked time, so the expiry is moved closer
+        // instead
+        let expiry = Utc::now() + TimeDelta::from_std(expires_in).unwrap();
+        let started = tokio::time::Instant::now();
+        let fetches = Arc::new(Mutex::new(0));
+        let warnings = Arc::new(Mutex::new(vec![]));
+
+        let fetch_count = fetches.clone();
+        let fetch_expiry = move || {
+            let mut fetches = fetch_count.lock().unwrap();
+            *fetches += 1;
+            let result = if *fetches <= failures {
+                Err(device::Error::Cancelled)
+            } else {
+                Ok(expiry - TimeDelta::from_std(started.elapsed()).unwrap())
+            };
+            Box::pin(async move { result }) as BoxFuture<'static, _>
+        };
+        let emitted = warnings.clone();
+        let on_warning = move |warning| emitted.lock().unwrap().push(warning);
+
+        let handle = spawn(fetch_expiry, ExpiryReminders::default(), on_warning);
+        (handle, warnings, fetches)
+    }
+
+    /// Test that a warning is emitted when each threshold is reached
+    #[tokio::test(start_paused = true)]
+    async fn test_monitor_warnings() {
+        let (_handle, warnings, fetches) = spawn_monitor(4 * DAY, 0);
+
+        talpid_time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(warnings.lock().unwrap().len(), 1, "expected 7 day warning");
+        assert_eq!(*fetches.lock().unwrap(), 1, "expected immediate fetch");
+
+        talpid_time::sleep(DAY).await;
+        assert_eq!(warnings.lock().unwrap().len(), 2, "expected 3 day warning");
+
+        talpid_time::sleep(2 * DAY).await;
+        assert_eq!(warnings.lock().unwrap().len(), 3, "expected 1 day warning");
+
+        talpid_time::sleep(DAY).await;
+        let warnings = warnings.lock().unwrap();
+        assert_eq!(warnings.len(), 4, "expected expiry warning");
+        assert!(warnings[3].is_expired());
+        assert_eq!(
+            *fetches.lock().unwrap(),
+            4,
+            "expected one fetch per threshold"
+        );
+    }
+
+    /// Test that fetching the expiry is retried with a backoff
+    #[tokio::test(start_paused = true)]
+    async fn test_monitor_retry() {
+        let (_handle, warnings, fetches) = spawn_monitor(2 * DAY, 3);
+
+        // The delays are at most 1, 2, and 4 minutes
+        talpid_time::sleep(RETRY_INITIAL_DELAY * 7 + Duration::from_secs(1)).await;
+        assert_eq!(*fetches.lock().unwrap(), 4);
+        assert_eq!(warnings.lock().unwrap().len(), 1, "expected 3 day warning");
+    }
+
+    /// Test that no warnings are emitted while logged out
+    #[tokio::test(start_paused = true)]
+    async fn test_monitor_logged_out() {
+        let (handle, warnings, fetches) = spawn_monitor(8 * DAY, 0);
+
+        talpid_time::sleep(Duration::from_secs(1)).await;
+        handle.logged_out();
+        talpid_time::sleep(5 * DAY).await;
+        assert!(warnings.lock().unwrap().is_empty());
+        assert_eq!(*fetches.lock().unwrap(), 1);
+
+        handle.refresh();
+        talpid_time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(*fetches.lock().unwrap(), 2);
+        assert_eq!(warnings.lock().unwrap().len(), 1, "expected 3 day warning");
+    }
+}
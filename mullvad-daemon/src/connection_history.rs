@@ -0,0 +1,213 @@
+//! Bounded journal of tunnel state transitions. Each transition is appended to a file in the cache
+//! directory, so that connection problems can be looked into after the fact, also across restarts
+//! of the daemon.
+
+use mullvad_types::connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+const CONNECTION_HISTORY_FILE: &str = "connection-history.jsonl";
+
+/// Maximum number of entries to keep. The oldest entries are discarded first.
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to serialize connection history entry")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Unable to write connection history file")]
+    Write(#[source] io::Error),
+}
+
+pub struct ConnectionHistory {
+    path: PathBuf,
+    entries: VecDeque<ConnectionHistoryEntry>,
+    /// Number of entries in the file. Entries are appended to the file, which is only rewritten
+    /// with the retained entries once it holds twice as many as [`MAX_ENTRIES`].
+    entries_in_file: usize,
+}
+
+impl ConnectionHistory {
+    /// Load the history from `cache_dir`. Entries that cannot be parsed are skipped.
+    pub async fn new(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(CONNECTION_HISTORY_FILE);
+        let content = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read connection history")
+                );
+                String::new()
+            }
+        };
+
+        let (entries, entries_in_file) = parse_entries(&content);
+        log::debug!(
+            "Loaded {} connection history entries from {}",
+            entries.len(),
+            path.display()
+        );
+        ConnectionHistory {
+            path,
+            entries,
+            entries_in_file,
+        }
+    }
+
+    /// Return the entries matching `filter`, oldest first.
+    pub fn get(&self, filter: &ConnectionHistoryFilter) -> Vec<ConnectionHistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| filter.matches(entry))
+            .cloned()
+            .collect()
+    }
+
+    /// Add an entry to the history and save it to disk.
+    pub async fn push(&mut self, entry: ConnectionHistoryEntry) -> Result<(), Error> {
+        let line = serde_json::to_string(&entry).map_err(Error::Serialize)?;
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+
+        if self.entries_in_file >= 2 * MAX_ENTRIES {
+            self.rewrite().await
+        } else {
+            self.append(&line).await
+        }
+    }
+
+    async fn append(&mut self, line: &str) -> Result<(), Error> {
+        let mut options = fs::OpenOptions::new();
+        #[cfg(unix)]
+        {
+            options.mode(0o600);
+        }
+        let mut file = options
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .await
+            .map_err(Error::Write)?;
+        file.write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(Error::Write)?;
+        file.flush().await.map_err(Error::Write)?;
+        self.entries_in_file += 1;
+        Ok(())
+    }
+
+    /// Replace the file with one containing only the retained entries.
+    async fn rewrite(&mut self) -> Result<(), Error> {
+        let mut content = String::new();
+        for entry in &self.entries {
+            content += &serde_json::to_string(entry).map_err(Error::Serialize)?;
+            content.push('\n');
+        }
+
+        let mut file = mullvad_fs::AtomicFile::new(&self.path)
+            .await
+            .map_err(Error::Write)?;
+        file.write_all(content.as_bytes())
+            .await
+            .map_err(Error::Write)?;
+        file.finalize().await.map_err(Error::Write)?;
+        self.entries_in_file = self.entries.len();
+        Ok(())
+    }
+}
+
+/// Parse one entry per line, and return the last [`MAX_ENTRIES`] entries along with the number of
+/// lines.
+fn parse_entries(content: &str) -> (VecDeque<ConnectionHistoryEntry>, usize) {
+    let mut entries = VecDeque::new();
+    let mut num_lines = 0;
+    let mut num_invalid = 0;
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        num_lines += 1;
+        match serde_json::from_str(line) {
+            Ok(entry) => {
+                entries.push_back(entry);
+                if entries.len() > MAX_ENTRIES {
+                    entries.pop_front();
+                }
+            }
+            Err(_) => num_invalid += 1,
+        }
+    }
+
+    if num_invalid > 0 {
+        log::warn!("Skipped {num_invalid} invalid connection history entries");
+    }
+    (entries, num_lines)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use mullvad_types::states::TunnelState;
+
+    fn entry(seconds: i64) -> ConnectionHistoryEntry {
+        ConnectionHistoryEntry {
+            time: DateTime::<Utc>::from_timestamp(seconds, 0).unwrap(),
+            state: TunnelState::Disconnected {
+                location: None,
+                #[cfg(not(target_os = "android"))]
+                locked_down: false,
+            },
+        }
+    }
+
+    /// Invalid lines are skipped, and only the most recent entries are kept.
+    #[test]
+    fn test_parse_entries() {
+        let mut content = String::from("not an entry\n\n");
+        for seconds in 0..(MAX_ENTRIES as i64 + 10) {
+            content += &serde_json::to_string(&entry(seconds)).unwrap();
+            content.push('\n');
+        }
+
+        let (entries, num_lines) = parse_entries(&content);
+        assert_eq!(num_lines, MAX_ENTRIES + 11);
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries.front().unwrap().time, entry(10).time);
+        assert_eq!(
+            entries.back().unwrap().time,
+            entry(MAX_ENTRIES as i64 + 9).time
+        );
+    }
+
+    #[test]
+    fn test_filter_entries() {
+        let history = ConnectionHistory {
+            path: PathBuf::new(),
+            entries: (0..10).map(entry).collect(),
+            entries_in_file: 10,
+        };
+
+        let filter = ConnectionHistoryFilter {
+            since: Some(entry(3).time),
+            until: Some(entry(6).time),
+        };
+        let times: Vec<_> = history
+            .get(&filter)
+            .into_iter()
+            .map(|entry| entry.time)
+            .collect();
+        assert_eq!(times, [entry(3).time, entry(4).time, entry(5).time]);
+        assert_eq!(history.get(&ConnectionHistoryFilter::default()).len(), 10);
+    }
+}
@@ -0,0 +1,361 @@
+//! Protects the WireGuard private key in the device cache.
+//!
+//! The device cache has the same format as before, except that the private key may be replaced
+//! with an object that says where the key is stored. By default, the key is encrypted using a key
+//! derived from the machine ID, so that copies of the settings directory, e.g. in backups, are
+//! useless on other machines.
+//!
+//! The storage is selected using [STORAGE_ENV_VAR]. Device caches that use another storage are
+//! migrated when they are loaded, so selecting [KeyStorage::Plain] restores the format that older
+//! versions expect.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, Nonce,
+    aead::{Aead, KeyInit},
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::{fmt, io, str::FromStr};
+use talpid_types::net::wireguard::PrivateKey;
+use zeroize::Zeroizing;
+
+use super::PrivateDeviceState;
+
+/// Environment variable used to select how the private key is stored. See [KeyStorage].
+pub const STORAGE_ENV_VAR: &str = "MULLVAD_DEVICE_KEY_STORAGE";
+
+/// Location of the private key in a serialized [PrivateDeviceState].
+const PRIVATE_KEY_POINTER: &str = "/logged_in/device/wg_data/private_key";
+
+/// Used to derive the encryption key from the machine ID.
+const KDF_INFO: &[u8] = b"mullvad-vpn device key";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to parse device cache")]
+    Deserialize(#[source] serde_json::Error),
+
+    #[error("Failed to serialize device cache")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("The stored private key is malformed")]
+    InvalidKey,
+
+    /// The machine ID is needed to encrypt or decrypt the key, but could not be read. Unlike the
+    /// other errors, this does not mean that the device cache is invalid.
+    #[error("Failed to read the machine ID")]
+    MachineId(#[source] io::Error),
+
+    #[error("Failed to encrypt the private key")]
+    Encrypt,
+
+    #[error("Failed to decrypt the private key. The device cache may belong to another machine")]
+    Decrypt,
+}
+
+impl Error {
+    /// Returns whether the key storage could not be accessed, as opposed to the device cache being
+    /// invalid. The device cache may be readable later, so it should not be discarded.
+    pub fn is_storage_unavailable(&self) -> bool {
+        matches!(self, Error::MachineId(_))
+    }
+}
+
+/// Where the private key is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStorage {
+    /// In the device cache, unencrypted. This is the format used by older versions.
+    Plain,
+    /// In the device cache, encrypted using a key derived from the machine ID.
+    Encrypted,
+}
+
+impl KeyStorage {
+    /// Returns the storage selected using [STORAGE_ENV_VAR], or the default one.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(STORAGE_ENV_VAR) else {
+            return Self::default();
+        };
+        value.parse().unwrap_or_else(|_| {
+            log::error!("Invalid value for {STORAGE_ENV_VAR}: {value}");
+            Self::default()
+        })
+    }
+}
+
+impl Default for KeyStorage {
+    fn default() -> Self {
+        // There is no machine ID on Android, but the settings directory is only accessible to
+        // the app.
+        if cfg!(target_os = "android") {
+            KeyStorage::Plain
+        } else {
+            KeyStorage::Encrypted
+        }
+    }
+}
+
+impl FromStr for KeyStorage {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "plain" => Ok(KeyStorage::Plain),
+            "encrypted" => Ok(KeyStorage::Encrypted),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for KeyStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyStorage::Plain => f.write_str("plain"),
+            KeyStorage::Encrypted => f.write_str("encrypted"),
+        }
+    }
+}
+
+/// A private key that is not stored in plain text, as it appears in the device cache.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "storage", rename_all = "snake_case")]
+enum StoredKey {
+    Encrypted(EncryptedKey),
+}
+
+/// A private key encrypted using a key derived from the machine ID and `salt`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct EncryptedKey {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Parse a device cache, returning the device state and how its private key was stored.
+pub fn deserialize(data: &str) -> Result<(PrivateDeviceState, KeyStorage), Error> {
+    let mut value: serde_json::Value = serde_json::from_str(data).map_err(Error::Deserialize)?;
+    let mut storage = KeyStorage::Plain;
+
+    let stored_key = value
+        .pointer_mut(PRIVATE_KEY_POINTER)
+        .filter(|key| key.is_object());
+    if let Some(stored_key) = stored_key {
+        let key = match serde_json::from_value(stored_key.take()).map_err(Error::Deserialize)? {
+            StoredKey::Encrypted(encrypted) => {
+                storage = KeyStorage::Encrypted;
+                decrypt(&encrypted, &machine_id()?)?
+            }
+        };
+        *stored_key = serde_json::Value::String(key.to_base64());
+    }
+
+    let device = serde_json::from_value(value).map_err(Error::Deserialize)?;
+    Ok((device, storage))
+}
+
+/// Serialize a device state, storing its private key using `storage`.
+pub fn serialize(device: &PrivateDeviceState, storage: KeyStorage) -> Result<Vec<u8>, Error> {
+    let mut value = serde_json::to_value(device).map_err(Error::Serialize)?;
+
+    let stored_key = match device.device() {
+        Some(device) => store(&device.device.wg_data.private_key, storage)?,
+        None => None,
+    };
+    if let (Some(stored_key), Some(key)) = (stored_key, value.pointer_mut(PRIVATE_KEY_POINTER)) {
+        *key = serde_json::to_value(stored_key).map_err(Error::Serialize)?;
+    }
+
+    serde_json::to_vec_pretty(&value).map_err(Error::Serialize)
+}
+
+fn store(key: &PrivateKey, storage: KeyStorage) -> Result<Option<StoredKey>, Error> {
+    match storage {
+        KeyStorage::Plain => Ok(None),
+        KeyStorage::Encrypted => Ok(Some(StoredKey::Encrypted(encrypt(key, &machine_id()?)?))),
+    }
+}
+
+fn encrypt(key: &PrivateKey, machine_id: &[u8]) -> Result<EncryptedKey, Error> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let plaintext = Zeroizing::new(key.to_bytes());
+    let ciphertext = cipher(&salt, machine_id)
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| Error::Encrypt)?;
+    Ok(EncryptedKey {
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+}
+
+fn decrypt(key: &EncryptedKey, machine_id: &[u8]) -> Result<PrivateKey, Error> {
+    let decode = |data: &str| STANDARD.decode(data).map_err(|_| Error::InvalidKey);
+    let salt = decode(&key.salt)?;
+    let nonce = decode(&key.nonce)?;
+    let ciphertext = decode(&key.ciphertext)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(Error::InvalidKey);
+    }
+
+    let plaintext = cipher(&salt, machine_id)
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map(Zeroizing::new)
+        .map_err(|_| Error::Decrypt)?;
+    let key: [u8; 32] = plaintext
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::InvalidKey)?;
+    Ok(PrivateKey::from(key))
+}
+
+fn cipher(salt: &[u8], machine_id: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(salt), machine_id)
+        .expand(KDF_INFO, key.as_mut_slice())
+        .expect("32 bytes is a valid output length for HKDF-SHA256");
+    ChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+}
+
+/// Returns an identifier that is unique to this installation of the OS.
+fn machine_id() -> Result<Vec<u8>, Error> {
+    let id = read_machine_id().map_err(Error::MachineId)?;
+    if id.is_empty() {
+        return Err(Error::MachineId(io::Error::new(
+            io::ErrorKind::NotFound,
+            "The machine ID is empty",
+        )));
+    }
+    Ok(id)
+}
+
+#[cfg(target_os = "linux")]
+fn read_machine_id() -> io::Result<Vec<u8>> {
+    std::fs::read_to_string("/etc/machine-id")
+        .or_else(|_| std::fs::read_to_string("/var/lib/dbus/machine-id"))
+        .map(|id| id.trim().as_bytes().to_vec())
+}
+
+#[cfg(target_os = "macos")]
+fn read_machine_id() -> io::Result<Vec<u8>> {
+    let mut buffer = [0u8; 64];
+    let mut len = buffer.len();
+    // SAFETY: The name is null-terminated, and `len` is the size of `buffer`.
+    let result = unsafe {
+        libc::sysctlbyname(
+            c"kern.uuid".as_ptr(),
+            buffer.as_mut_ptr().cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let id = &buffer[..len];
+    Ok(id.strip_suffix(&[0]).unwrap_or(id).to_vec())
+}
+
+#[cfg(windows)]
+fn read_machine_id() -> io::Result<Vec<u8>> {
+    use winreg::{RegKey, enums::HKEY_LOCAL_MACHINE};
+
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey(r"SOFTWARE\Microsoft\Cryptography")?
+        .get_value::<String, _>("MachineGuid")
+        .map(String::into_bytes)
+}
+
+#[cfg(target_os = "android")]
+fn read_machine_id() -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "There is no machine ID on Android",
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DEVICE_CACHE: &str = r#"{
+        "logged_in": {
+            "account_number": "1234123412341234",
+            "device": {
+                "id": "aa4b6d1d-7c23-4f4e-9a5c-7e1bba5fc0ba",
+                "name": "happy seal",
+                "wg_data": {
+                    "private_key": "mPKs2p4UfrOEZuKfLLUL4N6d/WJ4zDvQVAGUHJN5BXw=",
+                    "addresses": {
+                        "ipv4_address": "10.0.0.1/32",
+                        "ipv6_address": "fc00::1/128"
+                    },
+                    "created": "2025-01-01T00:00:00Z"
+                },
+                "hijack_dns": false,
+                "created": "2025-01-01T00:00:00Z"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = PrivateKey::new_from_random();
+        let encrypted = encrypt(&key, b"machine").unwrap();
+
+        let decrypted = decrypt(&encrypted, b"machine").unwrap();
+        assert_eq!(decrypted.to_bytes(), key.to_bytes());
+
+        // The key cannot be decrypted on another machine
+        assert!(matches!(
+            decrypt(&encrypted, b"other machine"),
+            Err(Error::Decrypt)
+        ));
+    }
+
+    /// Test that a missing machine ID is not mistaken for a corrupt device cache
+    #[test]
+    fn test_storage_unavailable() {
+        let error = Error::MachineId(io::Error::from(io::ErrorKind::NotFound));
+        assert!(error.is_storage_unavailable());
+        assert!(!Error::Decrypt.is_storage_unavailable());
+        assert!(!Error::InvalidKey.is_storage_unavailable());
+    }
+
+    /// Test that device caches in the old format are still read, and that the key is hidden when
+    /// they are written again.
+    #[test]
+    fn test_migrate_plain() {
+        let (device, storage) = deserialize(DEVICE_CACHE).unwrap();
+        assert_eq!(storage, KeyStorage::Plain);
+        let key = device
+            .device()
+            .unwrap()
+            .device
+            .wg_data
+            .private_key
+            .to_base64();
+
+        // Only the key is changed, so the cache can be converted back to the old format
+        let plain = serialize(&device, KeyStorage::Plain).unwrap();
+        let plain: serde_json::Value = serde_json::from_slice(&plain).unwrap();
+        let original: serde_json::Value = serde_json::from_str(DEVICE_CACHE).unwrap();
+        assert_eq!(plain, original);
+
+        // This relies on a machine ID, so skip the rest if there is none
+        if machine_id().is_err() {
+            return;
+        }
+        let encrypted = serialize(&device, KeyStorage::Encrypted).unwrap();
+        let encrypted = String::from_utf8(encrypted).unwrap();
+        assert!(!encrypted.contains(&key));
+
+        let (decrypted, storage) = deserialize(&encrypted).unwrap();
+        assert_eq!(storage, KeyStorage::Encrypted);
+        assert_eq!(decrypted, device);
+    }
+}
@@ -36,7 +36,9 @@ use tokio::{
 };
 
 mod api;
+pub mod key_storage;
 mod service;
+use key_storage::KeyStorage;
 pub(crate) use service::{AccountService, DeviceService};
 
 /// File that used to store account and device data.
@@ -70,6 +72,8 @@ pub enum Error {
     DeviceIoError(#[from] Arc<io::Error>),
     #[error("Failed parse device cache")]
     ParseDeviceCache(#[from] Arc<serde_json::Error>),
+    #[error("Failed to load or store the device key")]
+    KeyStorage(#[from] Arc<key_storage::Error>),
     #[error("Unexpected HTTP request error")]
     OtherRestError(#[from] rest::Error),
     #[error("The device update task is not running")]
@@ -92,6 +96,7 @@ macro_rules! impl_into_arc_err {
 
 impl_into_arc_err!(io::Error);
 impl_into_arc_err!(serde_json::Error);
+impl_into_arc_err!(key_storage::Error);
 
 /// Contains the current device state.
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
@@ -1161,10 +1166,22 @@ impl AccountManager {
 pub struct DeviceCacher {
     file: io::BufWriter<fs::File>,
     path: std::path::PathBuf,
+    key_storage: KeyStorage,
 }
 
 impl DeviceCacher {
+    /// Open the device cache, storing the private key as selected by
+    /// [key_storage::STORAGE_ENV_VAR].
     pub async fn new(settings_dir: &Path) -> Result<(DeviceCacher, PrivateDeviceState), Error> {
+        Self::with_key_storage(settings_dir, KeyStorage::from_env()).await
+    }
+
+    /// Open the device cache, storing the private key using `key_storage`. If the key is
+    /// currently stored elsewhere, it is migrated.
+    pub async fn with_key_storage(
+        settings_dir: &Path,
+        key_storage: KeyStorage,
+    ) -> Result<(DeviceCacher, PrivateDeviceState), Error> {
         let path = settings_dir.join(DEVICE_CACHE_FILENAME);
         let cache_exists = path.is_file();
         let mut should_save = false;
@@ -1176,19 +1193,41 @@ impl DeviceCacher {
             .open(&path)
             .await?;
 
+        let mut stored_key_storage = key_storage;
         let device: PrivateDeviceState = if cache_exists {
             let mut reader = io::BufReader::new(&mut file);
             let mut buffer = String::new();
             reader.read_to_string(&mut buffer).await?;
             if !buffer.is_empty() {
-                serde_json::from_str(&buffer).unwrap_or_else(|error| {
-                    should_save = true;
-                    log::error!(
-                        "{}",
-                        error.display_chain_with_msg("Wiping device config due to an error")
-                    );
-                    PrivateDeviceState::LoggedOut
-                })
+                let result = tokio::task::spawn_blocking(move || key_storage::deserialize(&buffer))
+                    .await
+                    .expect("Device cache task panicked");
+                match result {
+                    Ok((device, storage)) => {
+                        stored_key_storage = storage;
+                        device
+                    }
+                    // The device cache is fine, so keep it until the key can be read
+                    Err(error) if error.is_storage_unavailable() => {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg(&format!(
+                                "Failed to read the device key. Set {}=plain to store it without \
+                                 encryption",
+                                key_storage::STORAGE_ENV_VAR
+                            ))
+                        );
+                        return Err(Error::from(error));
+                    }
+                    Err(error) => {
+                        should_save = true;
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Wiping device config due to an error")
+                        );
+                        PrivateDeviceState::LoggedOut
+                    }
+                }
             } else {
                 should_save = true;
                 PrivateDeviceState::LoggedOut
@@ -1198,9 +1237,15 @@ impl DeviceCacher {
             PrivateDeviceState::LoggedOut
         };
 
+        if stored_key_storage != key_storage && device.logged_in() {
+            log::info!("Moving device key from {stored_key_storage} to {key_storage} storage");
+            should_save = true;
+        }
+
         let mut store = DeviceCacher {
             file: io::BufWriter::new(file),
             path,
+            key_storage,
         };
 
         if should_save {
@@ -1227,7 +1272,20 @@ impl DeviceCacher {
     }
 
     pub async fn write(&mut self, device: &PrivateDeviceState) -> Result<(), Error> {
-        let data = serde_json::to_vec_pretty(&device).unwrap();
+        let storage = self.key_storage;
+        let device_clone = device.clone();
+        let data =
+            tokio::task::spawn_blocking(move || key_storage::serialize(&device_clone, storage))
+                .await
+                .expect("Device cache task panicked")
+                .inspect_err(|error| {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg(&format!(
+                            "Failed to store the device key using {storage} storage"
+                        ))
+                    );
+                })?;
 
         self.file.get_mut().set_len(0).await?;
         self.file.seek(io::SeekFrom::Start(0)).await?;
@@ -1240,7 +1298,7 @@ impl DeviceCacher {
 
     pub async fn remove(self) -> Result<(), Error> {
         let path = {
-            let DeviceCacher { path, file } = self;
+            let DeviceCacher { path, file, .. } = self;
             let std_file = file.into_inner().into_std().await;
             let _ = tokio::task::spawn_blocking(move || drop(std_file)).await;
             path
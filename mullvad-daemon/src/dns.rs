@@ -15,6 +15,21 @@ const DNS_SOCIAL_MEDIA_BLOCKING_IP_BIT: u8 = 1 << 5; // 0b00100000
 
 /// Return the DNS resolvers to use
 pub fn addresses_from_options(options: &DnsOptions) -> DnsConfig {
+    let config = servers_from_options(options);
+
+    // Split DNS rules are enforced by the local DNS resolver, which only exists on Linux and macOS
+    if cfg!(any(target_os = "linux", target_os = "macos")) {
+        config.with_split_rules(&options.split_dns_rules)
+    } else {
+        if !options.split_dns_rules.is_empty() {
+            log::warn!("Split DNS is not supported on this platform");
+        }
+        config
+    }
+}
+
+/// Return the DNS resolvers to use for names that no split DNS rule applies to
+fn servers_from_options(options: &DnsOptions) -> DnsConfig {
     match options.state {
         DnsState::Default => {
             // Check if we should use a custom blocking DNS resolver.
@@ -48,7 +63,6 @@ pub fn addresses_from_options(options: &DnsOptions) -> DnsConfig {
                 DnsConfig::default()
             }
         }
-        DnsState::Custom if options.custom_options.addresses.is_empty() => DnsConfig::default(),
         DnsState::Custom => {
             let (non_tunnel_config, tunnel_config): (Vec<_>, Vec<_>) = options
                 .custom_options
@@ -56,7 +70,25 @@ pub fn addresses_from_options(options: &DnsOptions) -> DnsConfig {
                 .iter()
                 // Private IP ranges should not be tunneled
                 .partition(|&addr| is_local_address(addr));
-            DnsConfig::from_addresses(&tunnel_config, &non_tunnel_config)
+
+            // Encrypted DNS servers are queried by the local DNS resolver, which only supports
+            // them on macOS
+            let encrypted_config = if cfg!(target_os = "macos") {
+                &options.custom_options.encrypted_servers[..]
+            } else {
+                if !options.custom_options.encrypted_servers.is_empty() {
+                    log::warn!("Encrypted DNS servers are not supported on this platform");
+                }
+                &[]
+            };
+
+            if tunnel_config.is_empty()
+                && non_tunnel_config.is_empty()
+                && encrypted_config.is_empty()
+            {
+                return DnsConfig::default();
+            }
+            DnsConfig::from_servers(&tunnel_config, &non_tunnel_config, encrypted_config)
         }
     }
 }
@@ -66,6 +98,7 @@ mod test {
     use crate::dns::addresses_from_options;
     use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
     use talpid_core::dns::DnsConfig;
+    use talpid_types::net::dns::{EncryptedDnsServer, SplitDnsRule};
 
     #[test]
     fn test_default_dns() {
@@ -73,6 +106,7 @@ mod test {
             state: DnsState::Default,
             custom_options: CustomDnsOptions::default(),
             default_options: DefaultDnsOptions::default(),
+            split_dns_rules: vec![],
         };
 
         assert_eq!(addresses_from_options(&public_cfg), DnsConfig::default());
@@ -87,6 +121,7 @@ mod test {
                 block_ads: true,
                 ..DefaultDnsOptions::default()
             },
+            split_dns_rules: vec![],
         };
 
         assert_eq!(
@@ -104,8 +139,10 @@ mod test {
             state: DnsState::Custom,
             custom_options: CustomDnsOptions {
                 addresses: vec![public_ip, private_ip],
+                encrypted_servers: vec![],
             },
             default_options: DefaultDnsOptions::default(),
+            split_dns_rules: vec![],
         };
 
         assert_eq!(
@@ -113,4 +150,48 @@ mod test {
             DnsConfig::from_addresses(&[public_ip], &[private_ip],)
         );
     }
+
+    // Encrypted servers are only used where there is a local DNS resolver to query them
+    #[test]
+    fn test_encrypted_custom_dns() {
+        let server = EncryptedDnsServer::https(
+            "https://dns.quad9.net/dns-query",
+            "9.9.9.9".parse().unwrap(),
+        )
+        .unwrap();
+        let encrypted_cfg = DnsOptions {
+            state: DnsState::Custom,
+            custom_options: CustomDnsOptions {
+                addresses: vec![],
+                encrypted_servers: vec![server.clone()],
+            },
+            default_options: DefaultDnsOptions::default(),
+            split_dns_rules: vec![],
+        };
+
+        let expected = if cfg!(target_os = "macos") {
+            DnsConfig::from_servers(&[], &[], &[server])
+        } else {
+            DnsConfig::default()
+        };
+        assert_eq!(addresses_from_options(&encrypted_cfg), expected);
+    }
+    // Split DNS rules apply regardless of which servers are used for other names
+    #[test]
+    fn test_split_dns() {
+        let rule = SplitDnsRule::new("corp.example", vec!["192.168.1.1".parse().unwrap()]).unwrap();
+        let split_cfg = DnsOptions {
+            state: DnsState::Default,
+            custom_options: CustomDnsOptions::default(),
+            default_options: DefaultDnsOptions::default(),
+            split_dns_rules: vec![rule.clone()],
+        };
+
+        let expected = if cfg!(any(target_os = "linux", target_os = "macos")) {
+            DnsConfig::default().with_split_rules(&[rule])
+        } else {
+            DnsConfig::default()
+        };
+        assert_eq!(addresses_from_options(&split_cfg), expected);
+    }
 }
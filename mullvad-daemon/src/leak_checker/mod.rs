@@ -1,6 +1,6 @@
 use futures::{FutureExt, select};
-pub use mullvad_leak_checker::LeakInfo;
-use std::time::Duration;
+use mullvad_types::leak_test::LeakTestReport;
+use std::{net::IpAddr, time::Duration};
 use talpid_routing::RouteManagerHandle;
 use talpid_types::{net::Endpoint, tunnel::TunnelStateTransition};
 use tokio::sync::mpsc;
@@ -8,6 +8,7 @@ use tokio::sync::mpsc;
 /// An actor that tries to leak traffic outside the tunnel while we are connected.
 pub struct LeakChecker {
     task_event_tx: mpsc::UnboundedSender<TaskEvent>,
+    route_manager: RouteManagerHandle,
 }
 
 /// [LeakChecker] internal task state.
@@ -18,7 +19,8 @@ struct Task {
 }
 
 enum TaskEvent {
-    NewTunnelState(TunnelStateTransition),
+    /// The tunnel state changed. The DNS servers are those that are used in the tunnel.
+    NewTunnelState(TunnelStateTransition, Vec<IpAddr>),
     AddCallback(Box<dyn LeakCheckerCallback>),
 }
 
@@ -32,7 +34,7 @@ pub enum CallbackResult {
 }
 
 pub trait LeakCheckerCallback: Send + 'static {
-    fn on_leak(&mut self, info: LeakInfo) -> CallbackResult;
+    fn on_leak(&mut self, report: LeakTestReport) -> CallbackResult;
 }
 
 impl LeakChecker {
@@ -41,18 +43,48 @@ impl LeakChecker {
 
         let task = Task {
             events_rx,
-            route_manager,
+            route_manager: route_manager.clone(),
             callbacks: vec![],
         };
 
         tokio::task::spawn(task.run());
 
-        LeakChecker { task_event_tx }
+        LeakChecker {
+            task_event_tx,
+            route_manager,
+        }
     }
 
-    /// Call when we transition to a new tunnel state.
-    pub fn on_tunnel_state_transition(&mut self, tunnel_state: TunnelStateTransition) {
-        self.send(TaskEvent::NewTunnelState(tunnel_state))
+    /// Run a leak test right away, sending probes to `destination` and `dns_servers` outside the
+    /// tunnel. Unlike the test that runs when we connect, this also queries am.i.mullvad.net if the
+    /// daemon was built with the `am-i-mullvad` feature.
+    pub fn run_leak_test(
+        &self,
+        destination: Endpoint,
+        dns_servers: Vec<IpAddr>,
+    ) -> impl Future<Output = anyhow::Result<LeakTestReport>> + use<> {
+        let route_manager = self.route_manager.clone();
+        async move {
+            #[cfg_attr(not(feature = "am-i-mullvad"), allow(unused_mut))]
+            let mut report = check_for_leaks(&route_manager, destination, dns_servers).await?;
+
+            #[cfg(feature = "am-i-mullvad")]
+            {
+                report.am_i_mullvad = Some(check_am_i_mullvad().await?);
+            }
+
+            Ok(report)
+        }
+    }
+
+    /// Call when we transition to a new tunnel state. `dns_servers` are the DNS servers that are
+    /// used in the tunnel, which must not be reachable outside of it.
+    pub fn on_tunnel_state_transition(
+        &mut self,
+        tunnel_state: TunnelStateTransition,
+        dns_servers: Vec<IpAddr>,
+    ) {
+        self.send(TaskEvent::NewTunnelState(tunnel_state, dns_servers))
     }
 
     /// Call `callback` if a leak is detected.
@@ -76,7 +108,9 @@ impl Task {
             };
 
             match event {
-                TaskEvent::NewTunnelState(s) => self.on_new_tunnel_state(s).await,
+                TaskEvent::NewTunnelState(s, dns_servers) => {
+                    self.on_new_tunnel_state(s, dns_servers).await
+                }
                 TaskEvent::AddCallback(c) => self.on_add_callback(c),
             }
         }
@@ -86,7 +120,11 @@ impl Task {
         self.callbacks.push(c);
     }
 
-    async fn on_new_tunnel_state(&mut self, mut tunnel_state: TunnelStateTransition) {
+    async fn on_new_tunnel_state(
+        &mut self,
+        mut tunnel_state: TunnelStateTransition,
+        mut dns_servers: Vec<IpAddr>,
+    ) {
         'leak_test: loop {
             let TunnelStateTransition::Connected(tunnel) = &tunnel_state else {
                 break 'leak_test;
@@ -94,19 +132,22 @@ impl Task {
 
             let ping_destination = tunnel.endpoint;
             let route_manager = self.route_manager.clone();
+            let servers = dns_servers.clone();
             let leak_test = async {
                 // Give the connection a little time to settle before starting the test.
                 tokio::time::sleep(Duration::from_millis(5000)).await;
 
-                check_for_leaks(&route_manager, ping_destination).await
+                check_for_leaks(&route_manager, ping_destination, servers).await
             };
 
             // Make sure the tunnel state doesn't change while we're doing the leak test.
             // If that happens, then our results might be invalid.
             let another_tunnel_state = async {
                 'listen_for_events: while let Some(event) = self.events_rx.recv().await {
-                    let new_state = match event {
-                        TaskEvent::NewTunnelState(tunnel_state) => tunnel_state,
+                    let (new_state, new_dns_servers) = match event {
+                        TaskEvent::NewTunnelState(tunnel_state, dns_servers) => {
+                            (tunnel_state, dns_servers)
+                        }
                         TaskEvent::AddCallback(c) => {
                             self.on_add_callback(c);
                             continue 'listen_for_events;
@@ -118,6 +159,7 @@ impl Task {
                     } else {
                         // Tunnel state changed! We have to discard the leak test and try again.
                         tunnel_state = new_state;
+                        dns_servers = new_dns_servers;
                         break 'listen_for_events;
                     }
                 }
@@ -130,9 +172,9 @@ impl Task {
                 leak_result = leak_test.fuse() => leak_result,
             };
 
-            let leak_info = match leak_result {
-                Ok(Some(leak_info)) => leak_info,
-                Ok(None) => {
+            let report = match leak_result {
+                Ok(report) if report.is_leaking() => report,
+                Ok(_) => {
                     log::debug!("No leak detected");
                     break 'leak_test;
                 }
@@ -142,10 +184,10 @@ impl Task {
                 }
             };
 
-            log::debug!("Leak detected: {leak_info:?}");
+            log::debug!("Leak detected: {report:?}");
 
             self.callbacks
-                .retain_mut(|callback| callback.on_leak(leak_info.clone()) == CallbackResult::Ok);
+                .retain_mut(|callback| callback.on_leak(report.clone()) == CallbackResult::Ok);
 
             break 'leak_test;
         }
@@ -157,18 +199,25 @@ impl Task {
 async fn check_for_leaks(
     _route_manager: &RouteManagerHandle,
     _destination: Endpoint,
-) -> anyhow::Result<Option<LeakInfo>> {
+    _dns_servers: Vec<IpAddr>,
+) -> anyhow::Result<LeakTestReport> {
     // TODO: We currently don't have a way to get the non-tunnel interface on Android.
-    Ok(None)
+    anyhow::bail!("Leak checks are not supported on Android")
 }
 
 #[cfg(not(target_os = "android"))]
 async fn check_for_leaks(
     route_manager: &RouteManagerHandle,
     destination: Endpoint,
-) -> anyhow::Result<Option<LeakInfo>> {
+    dns_servers: Vec<IpAddr>,
+) -> anyhow::Result<LeakTestReport> {
     use anyhow::{Context, anyhow};
-    use mullvad_leak_checker::{LeakStatus, traceroute::TracerouteOpt};
+    use mullvad_leak_checker::{
+        LeakInfo, LeakStatus,
+        dns::{DNS_PORT, DnsLeakOpt},
+        traceroute::TracerouteOpt,
+    };
+    use mullvad_types::leak_test::ReachableNode;
 
     #[cfg(target_os = "linux")]
     let interface = {
@@ -225,10 +274,20 @@ async fn check_for_leaks(
         mullvad_leak_checker::Interface::Luid(route.iface)
     };
 
+    let mut report = LeakTestReport {
+        interface: interface
+            .name()
+            .unwrap_or_else(|_| format!("{interface:?}")),
+        destination: destination.address.ip(),
+        reachable_nodes: vec![],
+        reachable_dns_servers: vec![],
+        am_i_mullvad: None,
+    };
+
     log::debug!("Attempting to leak traffic on interface {interface:?} to {destination}");
 
-    mullvad_leak_checker::traceroute::try_run_leak_test(&TracerouteOpt {
-        interface,
+    let traceroute_status = mullvad_leak_checker::traceroute::try_run_leak_test(&TracerouteOpt {
+        interface: interface.clone(),
         destination: destination.address.ip(),
 
         #[cfg(unix)]
@@ -239,19 +298,74 @@ async fn check_for_leaks(
         icmp: true,
     })
     .await
-    .map_err(|e| anyhow!("{e:#}"))
-    .map(|status| match status {
-        LeakStatus::NoLeak => None,
-        LeakStatus::LeakDetected(info) => Some(info),
+    .map_err(|e| anyhow!("{e:#}"))?;
+    if let LeakStatus::LeakDetected(LeakInfo::NodeReachableOnInterface {
+        reachable_nodes, ..
+    }) = traceroute_status
+    {
+        report.reachable_nodes = reachable_nodes
+            .into_iter()
+            .map(|node| ReachableNode {
+                address: node.address,
+                ttl: node.ttl,
+            })
+            .collect();
+    }
+
+    log::debug!("Attempting to send DNS queries on interface {interface:?}");
+
+    // Query the relay and the DNS servers used in the tunnel. None of them should answer queries
+    // sent outside the tunnel.
+    let mut servers = vec![destination.address.ip()];
+    servers.extend(dns_servers);
+    servers.sort();
+    servers.dedup();
+
+    // There is no domain that echoes the resolver address that we could rely on, so queries are
+    // not sent through the system resolver.
+    let dns_status = mullvad_leak_checker::dns::try_run_leak_test(&DnsLeakOpt {
+        interface,
+        servers,
+        port: DNS_PORT,
+        resolver_echo_domain: None,
+        allowed_resolvers: vec![],
     })
+    .await
+    .map_err(|e| anyhow!("{e:#}"))?;
+    if let LeakStatus::LeakDetected(LeakInfo::DnsLeak {
+        reachable_servers, ..
+    }) = dns_status
+    {
+        report.reachable_dns_servers = reachable_servers;
+    }
+
+    Ok(report)
+}
+
+/// Ask am.i.mullvad.net whether our traffic exits through a Mullvad relay.
+#[cfg(feature = "am-i-mullvad")]
+async fn check_am_i_mullvad() -> anyhow::Result<mullvad_types::leak_test::AmIMullvad> {
+    use mullvad_leak_checker::{
+        LeakInfo, LeakStatus,
+        am_i_mullvad::{self, AmIMullvadOpt},
+    };
+    use mullvad_types::leak_test::AmIMullvad;
+
+    log::debug!("Querying am.i.mullvad.net");
+
+    match am_i_mullvad::try_run_leak_test(&AmIMullvadOpt::default()).await? {
+        LeakStatus::LeakDetected(LeakInfo::AmIMullvad { ip }) => Ok(AmIMullvad::NotMullvad { ip }),
+        LeakStatus::LeakDetected(info) => anyhow::bail!("Unexpected leak info: {info:?}"),
+        LeakStatus::NoLeak => Ok(AmIMullvad::Mullvad),
+    }
 }
 
 impl<T> LeakCheckerCallback for T
 where
-    T: FnMut(LeakInfo) -> bool + Send + 'static,
+    T: FnMut(LeakTestReport) -> bool + Send + 'static,
 {
-    fn on_leak(&mut self, info: LeakInfo) -> CallbackResult {
-        if self(info) {
+    fn on_leak(&mut self, report: LeakTestReport) -> CallbackResult {
+        if self(report) {
             CallbackResult::Ok
         } else {
             CallbackResult::Drop
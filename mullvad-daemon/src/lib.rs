@@ -2,12 +2,14 @@
 #![allow(rustdoc::private_intra_doc_links)]
 
 mod access_method;
+mod account_expiry;
 pub mod account_history;
 mod android_dns;
 mod api;
 mod api_address_updater;
 #[cfg(not(target_os = "android"))]
 mod cleanup;
+mod connection_history;
 mod custom_list;
 pub mod device;
 mod dns;
@@ -18,7 +20,10 @@ pub mod logging;
 #[cfg(target_os = "macos")]
 mod macos;
 pub mod management_interface;
+mod metrics;
 mod migrations;
+mod network_rules;
+mod relay_latency;
 mod relay_list;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
@@ -31,6 +36,7 @@ pub mod version;
 
 use crate::target_state::PersistentTargetState;
 use api::DaemonAccessMethodResolver;
+use connection_history::ConnectionHistory;
 use device::{AccountEvent, PrivateAccountAndDevice, PrivateDeviceEvent};
 use futures::{
     StreamExt,
@@ -38,50 +44,60 @@ use futures::{
     future::{AbortHandle, Future, abortable},
 };
 use geoip::GeoIpHandler;
-use leak_checker::{LeakChecker, LeakInfo};
+use ipnetwork::IpNetwork;
+use leak_checker::LeakChecker;
 use management_interface::ManagementInterfaceServer;
+use metrics::{Metrics, MetricsServer, TunnelStateTracker};
 use mullvad_api::{ApiEndpoint, access_mode::AccessMethodEvent, proxy::ApiConnectionMode};
 use mullvad_encrypted_dns_proxy::state::EncryptedDnsProxyState;
 use mullvad_relay_selector::{RelaySelector, SelectorConfig};
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
 use mullvad_types::relay_constraints::GeographicLocationConstraint;
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use mullvad_types::settings::SplitApp;
 #[cfg(daita)]
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
     access_method::{AccessMethod, AccessMethodSetting},
-    account::{AccountData, AccountNumber, VoucherSubmission},
+    account::{AccountData, AccountNumber, ExpiryReminders, VoucherSubmission},
     auth_failed::AuthFailed,
+    connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter},
     constraints::Constraint,
     custom_list::CustomList,
     device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
     features::{FeatureIndicator, FeatureIndicators, compute_feature_indicators},
+    leak_test::LeakTestReport,
     location::{GeoIpLocation, LocationEventData},
+    network_rule::{CurrentNetwork, NetworkAction, NetworkRule},
     relay_constraints::{
         BridgeSettings, BridgeState, BridgeType, ObfuscationSettings, RelayOverride, RelaySettings,
-        allowed_ip::AllowedIps,
+        RetryStep, allowed_ip::AllowedIps,
+    },
+    relay_list::{RelayList, RelayListDiff},
+    settings::{
+        DnsOptions, MetricsSettings, Settings, SplitTunnelMode,
+        profile::{SettingDiff, SettingsProfile},
     },
-    relay_list::RelayList,
-    settings::{DnsOptions, Settings},
     states::{Secured, TargetState, TargetStateStrict, TunnelState},
     version::AppVersionInfo,
+    wg_quick::WgQuickConfig,
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
 };
+use network_rules::NetworkRuleEngine;
+use relay_latency::{LatencyMonitor, LatencyMonitorHandle};
 use relay_list::{RELAYS_FILENAME, RelayListUpdater, RelayListUpdaterHandle};
 use settings::SettingsPersister;
 use std::collections::BTreeSet;
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use std::collections::HashSet;
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
 use std::{
     marker::PhantomData,
+    net::IpAddr,
     path::PathBuf,
     pin::Pin,
     sync::{Arc, Weak},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 #[cfg(target_os = "android")]
 use talpid_core::connectivity_listener::ConnectivityListener;
@@ -99,7 +115,10 @@ use talpid_types::android::AndroidContext;
 use talpid_types::split_tunnel::ExcludedProcess;
 use talpid_types::{
     ErrorExt,
-    net::{IpVersion, TunnelType},
+    net::{
+        IpVersion, TunnelType,
+        wireguard::{ConnectivityCheckOptions, TunnelStats},
+    },
     tunnel::{ErrorStateCause, TunnelStateTransition},
 };
 use tokio::io;
@@ -169,7 +188,6 @@ pub enum Error {
     #[error("Unable to initialize split tunneling")]
     InitSplitTunneling(#[source] split_tunnel::Error),
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     #[error("Split tunneling error")]
     SplitTunnelError(#[source] split_tunnel::Error),
 
@@ -211,6 +229,24 @@ pub enum Error {
     #[error("No custom bridge has been specified")]
     NoCustomProxySaved,
 
+    #[error("Not connected to a WireGuard relay")]
+    NotConnectedToWireguardRelay,
+
+    #[error("Failed to export WireGuard config")]
+    ExportWireguardConfig(#[source] mullvad_types::wg_quick::Error),
+
+    #[error("No step in the retry order is compatible with the relay constraints")]
+    IncompatibleRetryOrder,
+
+    #[error("Not connected to a relay")]
+    NotConnected,
+
+    #[error("Leak test failed")]
+    LeakTest(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Failed to start metrics endpoint")]
+    MetricsServer(#[source] metrics::Error),
+
     #[cfg(target_os = "macos")]
     #[error("Failed to set exclusion group")]
     GroupIdError(#[source] io::Error),
@@ -232,6 +268,19 @@ pub enum DaemonCommand {
     Reconnect(oneshot::Sender<bool>),
     /// Request the current state.
     GetState(oneshot::Sender<TunnelState>),
+    /// Request traffic statistics for the current WireGuard tunnel, if any.
+    GetTunnelStats(oneshot::Sender<Option<TunnelStats>>),
+    /// Request the recorded tunnel state transitions that match the filter, oldest first.
+    GetConnectionHistory(
+        oneshot::Sender<Vec<ConnectionHistoryEntry>>,
+        ConnectionHistoryFilter,
+    ),
+    /// Request the values exported by the metrics endpoint.
+    GetMetrics(oneshot::Sender<Metrics>),
+    /// Export the config of the currently connected WireGuard relay in the wg-quick format.
+    ExportWireguardConfig(ResponseTx<String, Error>),
+    /// Try to leak traffic outside the tunnel right away, and report the result.
+    RunLeakTest(ResponseTx<LeakTestReport, Error>),
     CreateNewAccount(ResponseTx<String, Error>),
     /// Request the metadata for an account.
     GetAccountData(
@@ -248,6 +297,8 @@ pub enum DaemonCommand {
     ClearAccountHistory(ResponseTx<(), Error>),
     /// Get the list of countries and cities where there are relays.
     GetRelayLocations(oneshot::Sender<RelayList>),
+    /// Get the changes made to the relay list by the most recent update that changed it.
+    GetRelayListChanges(oneshot::Sender<RelayListDiff>),
     /// Trigger an asynchronous relay list update. This returns before the relay list is actually
     /// updated.
     UpdateRelayLocations,
@@ -267,6 +318,11 @@ pub enum DaemonCommand {
     SetRelaySettings(ResponseTx<(), settings::Error>, RelaySettings),
     /// Set the allow LAN setting.
     SetAllowLan(ResponseTx<(), settings::Error>, bool),
+    /// Add a network to the list of LAN networks that are reachable when LAN access is allowed.
+    AddAllowedLanNetwork(ResponseTx<(), settings::Error>, IpNetwork),
+    /// Remove a network from the list of LAN networks that are reachable when LAN access is
+    /// allowed.
+    RemoveAllowedLanNetwork(ResponseTx<(), settings::Error>, IpNetwork),
     /// Set the beta program setting.
     SetShowBetaReleases(ResponseTx<(), settings::Error>, bool),
     /// Set the block_when_disconnected setting.
@@ -274,6 +330,12 @@ pub enum DaemonCommand {
     SetBlockWhenDisconnected(ResponseTx<(), settings::Error>, bool),
     /// Set the auto-connect setting.
     SetAutoConnect(ResponseTx<(), settings::Error>, bool),
+    /// Set the rules that connect or disconnect the tunnel depending on the current network.
+    SetNetworkRules(ResponseTx<(), settings::Error>, Vec<NetworkRule>),
+    /// Configure the local metrics endpoint.
+    SetMetricsSettings(ResponseTx<(), Error>, MetricsSettings),
+    /// Set when to warn that the account is about to run out of time.
+    SetExpiryReminders(ResponseTx<(), settings::Error>, ExpiryReminders),
     /// Set the mssfix argument for OpenVPN
     SetOpenVpnMssfix(ResponseTx<(), settings::Error>, Option<u16>),
     /// Set proxy details for OpenVPN
@@ -304,6 +366,8 @@ pub enum DaemonCommand {
     SetWireguardMtu(ResponseTx<(), settings::Error>, Option<u16>),
     /// Set allowed IPs for wireguard tunnels
     SetWireguardAllowedIps(ResponseTx<(), settings::Error>, Constraint<AllowedIps>),
+    /// Set the timeouts and probes used to detect broken wireguard tunnels
+    SetWireguardConnectivityCheck(ResponseTx<(), settings::Error>, ConnectivityCheckOptions),
     /// Set automatic key rotation interval for wireguard tunnels
     SetWireguardRotationInterval(ResponseTx<(), settings::Error>, Option<RotationInterval>),
     /// Get the daemon settings
@@ -375,17 +439,15 @@ pub enum DaemonCommand {
     #[cfg(target_os = "linux")]
     ClearSplitTunnelProcesses(ResponseTx<(), split_tunnel::Error>),
     /// Exclude traffic of an application from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     AddSplitTunnelApp(ResponseTx<(), Error>, SplitApp),
     /// Remove application from list of apps to exclude from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     RemoveSplitTunnelApp(ResponseTx<(), Error>, SplitApp),
     /// Clear list of apps to exclude from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ClearSplitTunnelApps(ResponseTx<(), Error>),
     /// Enable or disable split tunneling
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     SetSplitTunnelState(ResponseTx<(), Error>, bool),
+    /// Set whether split apps are excluded from or included in the tunnel
+    SetSplitTunnelMode(ResponseTx<(), settings::Error>, SplitTunnelMode),
     /// Returns all processes currently being excluded from the tunnel
     #[cfg(windows)]
     GetSplitTunnelProcesses(ResponseTx<Vec<ExcludedProcess>, split_tunnel::Error>),
@@ -394,6 +456,9 @@ pub enum DaemonCommand {
     CheckVolumes(ResponseTx<(), Error>),
     /// Register settings for WireGuard obfuscator
     SetObfuscationSettings(ResponseTx<(), settings::Error>, ObfuscationSettings),
+    /// Set the order in which WireGuard connection methods are tried. `None` restores the
+    /// default order
+    SetRetryOrder(ResponseTx<(), Error>, Option<Vec<RetryStep>>),
     /// Saves the target tunnel state and enters a blocking state. The state is restored
     /// upon restart.
     PrepareRestart(bool),
@@ -411,6 +476,19 @@ pub enum DaemonCommand {
     ApplyJsonSettings(ResponseTx<(), settings::patch::Error>, String),
     /// Return a JSON blob containing all overridable settings, if there are any
     ExportJsonSettings(ResponseTx<String, settings::patch::Error>),
+    /// Save the current settings as a named profile
+    SaveSettingsProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Apply the settings of a named profile
+    ApplySettingsProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Request all settings profiles
+    ListSettingsProfiles(oneshot::Sender<Vec<SettingsProfile>>),
+    /// Delete a named settings profile
+    DeleteSettingsProfile(ResponseTx<(), settings::profiles::Error>, String),
+    /// Request the settings in a profile that differ from the current settings
+    DiffSettingsProfile(
+        ResponseTx<Vec<SettingDiff>, settings::profiles::Error>,
+        String,
+    ),
     /// Request the current feature indicators.
     GetFeatureIndicators(oneshot::Sender<FeatureIndicators>),
 
@@ -459,13 +537,18 @@ pub(crate) enum InternalDaemonEvent {
     /// A generic event for when any settings change.
     SettingsChanged,
     /// The split tunnel paths or state were updated.
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ExcludedPathsEvent(ExcludedPathsUpdate, oneshot::Sender<Result<(), Error>>),
     /// A network leak was detected.
-    LeakDetected(LeakInfo),
+    LeakDetected(LeakTestReport),
+    /// The time left on the account dropped below one of the expiry reminder thresholds.
+    AccountExpiryWarning(mullvad_types::account::AccountExpiryWarning),
+    /// The network of the non-tunnel default route changed. `None` if there is no default route.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    NetworkChanged(Option<CurrentNetwork>),
+    /// A new relay list was downloaded, in which relays were added, removed, or changed.
+    RelayListChanged(RelayListDiff),
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 pub(crate) enum ExcludedPathsUpdate {
     SetState(bool),
     SetPaths(HashSet<SplitApp>),
@@ -638,22 +721,27 @@ pub struct Daemon {
     tunnel_state: TunnelState,
     target_state: PersistentTargetState,
     #[cfg(target_os = "linux")]
-    exclude_pids: split_tunnel::PidManager,
+    exclude_pids: Arc<split_tunnel::PidManager>,
+    #[cfg(target_os = "linux")]
+    exclude_apps: split_tunnel::AppMonitor,
     rx: mpsc::UnboundedReceiver<InternalDaemonEvent>,
     tx: DaemonEventSender,
     reconnection_job: Option<AbortHandle>,
     management_interface: ManagementInterfaceServer,
     migration_complete: migrations::MigrationComplete,
     settings: SettingsPersister,
+    settings_profiles: settings::profiles::Profiles,
     account_history: account_history::AccountHistory,
     device_checker: device::TunnelStateChangeHandler,
     account_manager: device::AccountManagerHandle,
+    expiry_monitor: account_expiry::ExpiryMonitorHandle,
     access_mode_handler: mullvad_api::access_mode::AccessModeSelectorHandle,
     api_runtime: mullvad_api::Runtime,
     api_handle: mullvad_api::rest::MullvadRestHandle,
     version_handle: version::router::VersionRouterHandle,
     relay_selector: RelaySelector,
     relay_list_updater: RelayListUpdaterHandle,
+    latency_monitor: LatencyMonitorHandle,
     parameters_generator: tunnel::ParametersGenerator,
     shutdown_tasks: Vec<Pin<Box<dyn Future<Output = ()> + Send + Sync>>>,
     tunnel_state_machine_handle: TunnelStateMachineHandle,
@@ -661,6 +749,12 @@ pub struct Daemon {
     volume_update_tx: mpsc::UnboundedSender<()>,
     location_handler: GeoIpHandler,
     leak_checker: LeakChecker,
+    network_rules: NetworkRuleEngine,
+    tunnel_state_tracker: TunnelStateTracker,
+    metrics_server: Option<MetricsServer>,
+    connection_history: ConnectionHistory,
+    /// Changes made to the relay list by the most recent update that changed it.
+    relay_list_changes: RelayListDiff,
     cache_dir: PathBuf,
 }
 pub struct DaemonConfig {
@@ -729,6 +823,10 @@ impl Daemon {
 
         let settings_event_listener = management_interface.notifier().clone();
         let mut settings = SettingsPersister::load(&config.settings_dir).await;
+        #[cfg(target_os = "linux")]
+        management_interface
+            .notifier()
+            .set_access_settings(settings.management_access.clone());
         settings.register_change_listener(move |settings| {
             // Notify management interface server of changes to the settings
             settings_event_listener.notify_settings(settings.to_owned());
@@ -809,6 +907,26 @@ impl Daemon {
         .await
         .map_err(Error::LoadAccountManager)?;
 
+        let expiry_monitor = {
+            let account_manager = account_manager.clone();
+            let internal_event_tx = internal_event_tx.clone();
+            account_expiry::spawn(
+                move || {
+                    let account_manager = account_manager.clone();
+                    Box::pin(async move { account_manager.check_expiry().await })
+                },
+                settings.expiry_reminders.clone(),
+                move |warning| {
+                    let _ =
+                        internal_event_tx.send(InternalDaemonEvent::AccountExpiryWarning(warning));
+                },
+            )
+        };
+        let settings_expiry_monitor = expiry_monitor.clone();
+        settings.register_change_listener(move |settings| {
+            settings_expiry_monitor.set_reminders(settings.expiry_reminders.clone());
+        });
+
         let account_history = account_history::AccountHistory::new(
             &config.settings_dir,
             data.device().map(|device| device.account_number.clone()),
@@ -816,6 +934,9 @@ impl Daemon {
         .await
         .map_err(Error::LoadAccountHistory)?;
 
+        let connection_history = ConnectionHistory::new(&config.cache_dir).await;
+        let settings_profiles = settings::profiles::Profiles::load(&config.settings_dir).await;
+
         let target_state = if settings.auto_connect {
             log::info!("Automatically connecting since auto-connect is turned on");
             PersistentTargetState::new_secured(&config.cache_dir).await
@@ -871,6 +992,10 @@ impl Daemon {
         .await
         .map_err(Error::RouteManager)?;
 
+        // Set up the split tunnel cgroup before the firewall looks for it
+        #[cfg(target_os = "linux")]
+        let exclude_pids = Arc::new(split_tunnel::PidManager::default());
+
         let (offline_state_tx, offline_state_rx) = mpsc::unbounded();
         #[cfg(target_os = "windows")]
         let (volume_update_tx, volume_update_rx) = mpsc::unbounded();
@@ -890,6 +1015,10 @@ impl Daemon {
                 reset_firewall: *target_state != TargetState::Secured,
                 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
                 exclude_paths,
+                #[cfg(target_os = "linux")]
+                split_tunnel_mode: settings.split_tunnel.effective_mode(),
+                #[cfg(target_os = "linux")]
+                allowed_lan_nets: settings.allowed_lan_networks.clone(),
             },
             parameters_generator.clone(),
             config.log_dir,
@@ -915,8 +1044,12 @@ impl Daemon {
         api::forward_offline_state(api_availability.clone(), offline_state_rx);
 
         let relay_list_listener = management_interface.notifier().clone();
-        let on_relay_list_update = move |relay_list: &RelayList| {
+        let relay_list_event_tx = internal_event_tx.clone();
+        let on_relay_list_update = move |relay_list: &RelayList, diff: RelayListDiff| {
             relay_list_listener.notify_relay_list(relay_list.clone());
+            if !diff.is_empty() {
+                let _ = relay_list_event_tx.send(InternalDaemonEvent::RelayListChanged(diff));
+            }
         };
 
         let mut relay_list_updater = RelayListUpdater::spawn(
@@ -938,6 +1071,17 @@ impl Daemon {
         // Attempt to download a fresh relay list
         relay_list_updater.update().await;
 
+        let tunnel_state = TunnelState::Disconnected {
+            location: None,
+            #[cfg(not(target_os = "android"))]
+            locked_down: settings.block_when_disconnected,
+        };
+
+        let latency_monitor = LatencyMonitor::spawn(relay_selector.clone());
+        if *target_state == TargetState::Unsecured {
+            latency_monitor.measure(&tunnel_state);
+        }
+
         let location_handler = GeoIpHandler::new(
             api_runtime.rest_handle(
                 #[cfg(not(target_os = "android"))]
@@ -948,26 +1092,47 @@ impl Daemon {
             internal_event_tx.clone().to_specialized_sender(),
         );
 
+        #[cfg(target_os = "macos")]
+        network_rules::spawn_monitor(route_manager.clone(), internal_event_tx.clone());
+
         let leak_checker = {
             let mut leak_checker = LeakChecker::new(route_manager);
             let internal_event_tx = internal_event_tx.clone();
-            leak_checker.add_leak_callback(move |info| {
+            leak_checker.add_leak_callback(move |report| {
                 internal_event_tx
-                    .send(InternalDaemonEvent::LeakDetected(info))
+                    .send(InternalDaemonEvent::LeakDetected(report))
                     .is_ok()
             });
             leak_checker
         };
 
-        let daemon = Daemon {
-            tunnel_state: TunnelState::Disconnected {
-                location: None,
-                #[cfg(not(target_os = "android"))]
-                locked_down: settings.block_when_disconnected,
-            },
+        #[cfg(target_os = "linux")]
+        let exclude_apps = {
+            let mut exclude_apps = split_tunnel::AppMonitor::new(exclude_pids.clone());
+            if settings.split_tunnel.enable_exclusions {
+                let apps = settings
+                    .split_tunnel
+                    .apps
+                    .iter()
+                    .cloned()
+                    .map(SplitApp::into_path);
+                if let Err(error) = exclude_apps.set_apps(apps) {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to exclude split tunnel apps")
+                    );
+                }
+            }
+            exclude_apps
+        };
+
+        let mut daemon = Daemon {
+            tunnel_state,
             target_state,
             #[cfg(target_os = "linux")]
-            exclude_pids: split_tunnel::PidManager::default(),
+            exclude_pids,
+            #[cfg(target_os = "linux")]
+            exclude_apps,
             rx: internal_event_rx,
             tx: internal_event_tx,
             reconnection_job: None,
@@ -977,12 +1142,14 @@ impl Daemon {
             account_history,
             device_checker: device::TunnelStateChangeHandler::new(account_manager.clone()),
             account_manager,
+            expiry_monitor,
             access_mode_handler,
             api_runtime,
             api_handle,
             version_handle,
             relay_selector,
             relay_list_updater,
+            latency_monitor,
             parameters_generator,
             shutdown_tasks: vec![],
             tunnel_state_machine_handle,
@@ -990,11 +1157,19 @@ impl Daemon {
             volume_update_tx,
             location_handler,
             leak_checker,
+            network_rules: NetworkRuleEngine::new(settings.network_rules.clone()),
+            tunnel_state_tracker: TunnelStateTracker::default(),
+            metrics_server: None,
+            connection_history,
+            settings_profiles,
+            relay_list_changes: RelayListDiff::default(),
             cache_dir: config.cache_dir,
         };
 
         api_availability.unsuspend();
 
+        daemon.start_metrics_server_or_log().await;
+
         #[cfg(target_os = "macos")]
         {
             let account_manager = daemon.account_manager.clone();
@@ -1121,13 +1296,31 @@ impl Daemon {
             LocationEvent(location_data) => self.handle_location_event(location_data),
             SettingsChanged => {
                 self.update_feature_indicators_on_settings_changed();
+                self.latency_monitor.measure(&self.tunnel_state);
             }
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ExcludedPathsEvent(update, tx) => self.handle_new_excluded_paths(update, tx).await,
-            LeakDetected(leak_info) => {
+            LeakDetected(report) => {
                 log::warn!("Network leak detected! Please contact Mullvad support.");
-                log::warn!("{leak_info:?}")
+                log::warn!("{report:?}");
+                self.management_interface
+                    .notifier()
+                    .notify_leak_detected(report);
+            }
+            AccountExpiryWarning(warning) => {
+                log::info!(
+                    "Account expiry reminder: the account expires at {}",
+                    warning.expiry
+                );
+                self.management_interface
+                    .notifier()
+                    .notify_account_expiry_warning(warning);
             }
+            NetworkChanged(network) => {
+                if self.network_rules.on_network_change(network) {
+                    self.apply_network_action().await;
+                }
+            }
+            RelayListChanged(diff) => self.handle_relay_list_changes(diff),
         }
         should_stop
     }
@@ -1136,8 +1329,9 @@ impl Daemon {
         &mut self,
         tunnel_state_transition: TunnelStateTransition,
     ) {
+        let tunnel_dns_servers = self.tunnel_dns_servers().await;
         self.leak_checker
-            .on_tunnel_state_transition(tunnel_state_transition.clone());
+            .on_tunnel_state_transition(tunnel_state_transition.clone(), tunnel_dns_servers);
 
         self.reset_rpc_sockets_on_tunnel_state_transition(&tunnel_state_transition);
         self.device_checker
@@ -1198,6 +1392,7 @@ impl Daemon {
         match tunnel_state {
             TunnelState::Disconnected { .. } => {
                 self.api_handle.availability.reset_inactivity_timer();
+                self.latency_monitor.measure(&tunnel_state);
             }
             _ => {
                 self.api_handle.availability.stop_inactivity_timer();
@@ -1230,6 +1425,18 @@ impl Daemon {
             _ => {}
         }
 
+        self.tunnel_state_tracker
+            .on_transition(&self.tunnel_state, &tunnel_state);
+        let entry = ConnectionHistoryEntry {
+            time: chrono::Utc::now(),
+            state: tunnel_state.clone(),
+        };
+        if let Err(error) = self.connection_history.push(entry).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to save connection history")
+            );
+        }
         self.tunnel_state = tunnel_state.clone();
         self.management_interface
             .notifier()
@@ -1272,6 +1479,27 @@ impl Daemon {
         self.location_handler.send_geo_location_request(use_ipv6);
     }
 
+    /// Logs and broadcasts the changes made by a relay list update, i.e. the
+    /// [`InternalDaemonEvent::RelayListChanged`] event.
+    fn handle_relay_list_changes(&mut self, diff: RelayListDiff) {
+        log::info!(
+            "Relay list changed: {} added, {} removed, {} activated, {} deactivated, {} address \
+             changes, {} feature changes",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.activated.len(),
+            diff.deactivated.len(),
+            diff.address_changes.len(),
+            diff.feature_changes.len(),
+        );
+        relay_list::warn_about_unavailable_relays(&diff, &self.settings);
+
+        self.management_interface
+            .notifier()
+            .notify_relay_list_changes(diff.clone());
+        self.relay_list_changes = diff;
+    }
+
     /// Receives and handles the geographical exit location received from am.i.mullvad.net, i.e. the
     /// [`InternalDaemonEvent::LocationEvent`] event.
     fn handle_location_event(&mut self, location_data: LocationEventData) {
@@ -1394,11 +1622,17 @@ impl Daemon {
             SetTargetState(tx, state) => self.on_set_target_state(tx, state).await,
             Reconnect(tx) => self.on_reconnect(tx),
             GetState(tx) => self.on_get_state(tx),
+            GetTunnelStats(tx) => self.on_get_tunnel_stats(tx),
+            GetConnectionHistory(tx, filter) => self.on_get_connection_history(tx, filter),
+            GetMetrics(tx) => self.on_get_metrics(tx),
+            ExportWireguardConfig(tx) => self.on_export_wireguard_config(tx).await,
+            RunLeakTest(tx) => self.on_run_leak_test(tx).await,
             CreateNewAccount(tx) => self.on_create_new_account(tx),
             GetAccountData(tx, account_number) => self.on_get_account_data(tx, account_number),
             GetWwwAuthToken(tx) => self.on_get_www_auth_token(tx).await,
             SubmitVoucher(tx, voucher) => self.on_submit_voucher(tx, voucher),
             GetRelayLocations(tx) => self.on_get_relay_locations(tx),
+            GetRelayListChanges(tx) => self.on_get_relay_list_changes(tx),
             UpdateRelayLocations => self.on_update_relay_locations().await,
             LoginAccount(tx, account_number) => self.on_login_account(tx, account_number),
             LogoutAccount(tx) => self.on_logout_account(tx),
@@ -1412,6 +1646,10 @@ impl Daemon {
             ClearAccountHistory(tx) => self.on_clear_account_history(tx).await,
             SetRelaySettings(tx, update) => self.on_set_relay_settings(tx, update).await,
             SetAllowLan(tx, allow_lan) => self.on_set_allow_lan(tx, allow_lan).await,
+            AddAllowedLanNetwork(tx, network) => self.on_add_allowed_lan_network(tx, network).await,
+            RemoveAllowedLanNetwork(tx, network) => {
+                self.on_remove_allowed_lan_network(tx, network).await
+            }
             SetShowBetaReleases(tx, enabled) => self.on_set_show_beta_releases(tx, enabled).await,
             #[cfg(not(target_os = "android"))]
             SetBlockWhenDisconnected(tx, block_when_disconnected) => {
@@ -1419,6 +1657,11 @@ impl Daemon {
                     .await
             }
             SetAutoConnect(tx, auto_connect) => self.on_set_auto_connect(tx, auto_connect).await,
+            SetNetworkRules(tx, rules) => self.on_set_network_rules(tx, rules).await,
+            SetMetricsSettings(tx, metrics_settings) => {
+                self.on_set_metrics_settings(tx, metrics_settings).await
+            }
+            SetExpiryReminders(tx, reminders) => self.on_set_expiry_reminders(tx, reminders).await,
             SetOpenVpnMssfix(tx, mssfix_arg) => self.on_set_openvpn_mssfix(tx, mssfix_arg).await,
             SetBridgeSettings(tx, bridge_settings) => {
                 self.on_set_bridge_settings(tx, bridge_settings).await
@@ -1448,6 +1691,9 @@ impl Daemon {
             }
             ClearAllRelayOverrides(tx) => self.on_clear_all_relay_overrides(tx).await,
             SetWireguardMtu(tx, mtu) => self.on_set_wireguard_mtu(tx, mtu).await,
+            SetWireguardConnectivityCheck(tx, options) => {
+                self.on_set_wireguard_connectivity_check(tx, options).await
+            }
             SetWireguardAllowedIps(tx, allowed_ips) => {
                 self.on_set_wireguard_allowed_ips(tx, allowed_ips).await
             }
@@ -1490,14 +1736,11 @@ impl Daemon {
             RemoveSplitTunnelProcess(tx, pid) => self.on_remove_split_tunnel_process(tx, pid),
             #[cfg(target_os = "linux")]
             ClearSplitTunnelProcesses(tx) => self.on_clear_split_tunnel_processes(tx),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             AddSplitTunnelApp(tx, app) => self.on_add_split_tunnel_app(tx, app),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             RemoveSplitTunnelApp(tx, path) => self.on_remove_split_tunnel_app(tx, path),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ClearSplitTunnelApps(tx) => self.on_clear_split_tunnel_apps(tx),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             SetSplitTunnelState(tx, enabled) => self.on_set_split_tunnel_state(tx, enabled),
+            SetSplitTunnelMode(tx, mode) => self.on_set_split_tunnel_mode(tx, mode).await,
             #[cfg(windows)]
             GetSplitTunnelProcesses(tx) => self.on_get_split_tunnel_processes(tx),
             #[cfg(target_os = "windows")]
@@ -1505,6 +1748,7 @@ impl Daemon {
             SetObfuscationSettings(tx, settings) => {
                 self.on_set_obfuscation_settings(tx, settings).await
             }
+            SetRetryOrder(tx, retry_order) => self.on_set_retry_order(tx, retry_order).await,
             PrepareRestart(shutdown) => self.on_prepare_restart(shutdown),
             #[cfg(target_os = "android")]
             BypassSocket(fd, tx) => self.on_bypass_socket(fd, tx),
@@ -1516,6 +1760,11 @@ impl Daemon {
             }
             ApplyJsonSettings(tx, blob) => self.on_apply_json_settings(tx, blob).await,
             ExportJsonSettings(tx) => self.on_export_json_settings(tx),
+            SaveSettingsProfile(tx, name) => self.on_save_settings_profile(tx, name).await,
+            ApplySettingsProfile(tx, name) => self.on_apply_settings_profile(tx, name).await,
+            ListSettingsProfiles(tx) => self.on_list_settings_profiles(tx),
+            DeleteSettingsProfile(tx, name) => self.on_delete_settings_profile(tx, name).await,
+            DiffSettingsProfile(tx, name) => self.on_diff_settings_profile(tx, name),
             GetFeatureIndicators(tx) => self.on_get_feature_indicators(tx),
             DisableRelay { relay, tx } => self.on_toggle_relay(relay, false, tx),
             EnableRelay { relay, tx } => self.on_toggle_relay(relay, true, tx),
@@ -1532,6 +1781,14 @@ impl Daemon {
     }
 
     async fn handle_device_event(&mut self, event: AccountEvent) {
+        match &event {
+            AccountEvent::Device(PrivateDeviceEvent::Login(_)) => self.expiry_monitor.refresh(),
+            AccountEvent::Device(PrivateDeviceEvent::Logout | PrivateDeviceEvent::Revoked) => {
+                self.expiry_monitor.logged_out()
+            }
+            AccountEvent::Expiry(expiry) => self.expiry_monitor.set_expiry(*expiry),
+            _ => (),
+        }
         match &event {
             AccountEvent::Device(PrivateDeviceEvent::Login(device)) => {
                 if let Err(error) = self
@@ -1700,7 +1957,6 @@ impl Daemon {
         });
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn handle_new_excluded_paths(
         &mut self,
         update: ExcludedPathsUpdate,
@@ -1735,6 +1991,15 @@ impl Daemon {
                         self.reconnect_tunnel();
                     }
                 }
+                // The include mode only takes effect while split tunneling is enabled
+                #[cfg(target_os = "linux")]
+                {
+                    let (tx, _rx) = oneshot::channel();
+                    self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                        self.settings.split_tunnel.effective_mode(),
+                        tx,
+                    ));
+                }
                 save_result
             }
             ExcludedPathsUpdate::SetPaths(paths) => self
@@ -1743,6 +2008,11 @@ impl Daemon {
                 .await
                 .map_err(Error::SettingsError),
         };
+        #[cfg(target_os = "linux")]
+        let save_result = save_result.and_then(|changed| {
+            self.update_excluded_apps()?;
+            Ok(changed)
+        });
         let _ = tx.send(save_result.map(|_| ()));
     }
 
@@ -1769,6 +2039,133 @@ impl Daemon {
         Self::oneshot_send(tx, self.tunnel_state.clone(), "current state");
     }
 
+    fn on_get_tunnel_stats(&self, tx: oneshot::Sender<Option<TunnelStats>>) {
+        let tunnel_stats = self.tunnel_state_machine_handle.tunnel_stats().clone();
+        tokio::spawn(async move {
+            Self::oneshot_send(tx, tunnel_stats.get().await, "tunnel stats");
+        });
+    }
+
+    fn on_get_connection_history(
+        &self,
+        tx: oneshot::Sender<Vec<ConnectionHistoryEntry>>,
+        filter: ConnectionHistoryFilter,
+    ) {
+        let entries = self.connection_history.get(&filter);
+        Self::oneshot_send(tx, entries, "connection history");
+    }
+
+    fn on_get_metrics(&self, tx: oneshot::Sender<Metrics>) {
+        let relay = match &self.tunnel_state {
+            TunnelState::Connecting { location, .. } | TunnelState::Connected { location, .. } => {
+                location
+                    .as_ref()
+                    .and_then(|location| location.hostname.clone())
+            }
+            _ => None,
+        };
+        let mut metrics = Metrics {
+            tunnel_state: metrics::tunnel_state_name(&self.tunnel_state),
+            time_in_state: self.tunnel_state_tracker.time_in_state(),
+            reconnects: self.tunnel_state_tracker.reconnects(),
+            relay,
+            access_method: None,
+            relay_list_updated: self.relay_selector.last_updated(),
+            key_created: None,
+            tunnel_stats: None,
+        };
+
+        let tunnel_stats = self.tunnel_state_machine_handle.tunnel_stats().clone();
+        let access_mode_handler = self.access_mode_handler.clone();
+        let account_manager = self.account_manager.clone();
+        tokio::spawn(async move {
+            metrics.tunnel_stats = tunnel_stats.get().await;
+            metrics.access_method = access_mode_handler
+                .get_current()
+                .await
+                .ok()
+                .map(|mode| mode.setting.name);
+            metrics.key_created = account_manager
+                .data()
+                .await
+                .ok()
+                .and_then(|data| data.into_device())
+                .map(|config| SystemTime::from(config.device.wg_data.created));
+            Self::oneshot_send(tx, metrics, "metrics");
+        });
+    }
+
+    async fn on_export_wireguard_config(&self, tx: ResponseTx<String, Error>) {
+        let result = self.export_wireguard_config().await;
+        Self::oneshot_send(tx, result, "export_wireguard_config response");
+    }
+
+    async fn export_wireguard_config(&self) -> Result<String, Error> {
+        if !self.tunnel_state.is_connected() {
+            return Err(Error::NotConnectedToWireguardRelay);
+        }
+        let config = self
+            .parameters_generator
+            .get_last_wireguard_config()
+            .await
+            .ok_or(Error::NotConnectedToWireguardRelay)?;
+
+        let mut gateways = vec![IpAddr::V4(config.ipv4_gateway)];
+        gateways.extend(config.ipv6_gateway.map(IpAddr::V6));
+        let dns = dns::addresses_from_options(&self.settings.tunnel_options.dns_options)
+            .resolve(
+                &gateways,
+                #[cfg(target_os = "macos")]
+                53,
+            )
+            .addresses()
+            .collect();
+
+        WgQuickConfig::from_connection_config(
+            &config,
+            dns,
+            self.settings.tunnel_options.wireguard.mtu,
+        )
+        .map(|config| config.to_string())
+        .map_err(Error::ExportWireguardConfig)
+    }
+
+    async fn on_run_leak_test(&self, tx: ResponseTx<LeakTestReport, Error>) {
+        let TunnelState::Connected { endpoint, .. } = &self.tunnel_state else {
+            Self::oneshot_send(tx, Err(Error::NotConnected), "run_leak_test response");
+            return;
+        };
+
+        let dns_servers = self.tunnel_dns_servers().await;
+        let leak_test = self
+            .leak_checker
+            .run_leak_test(endpoint.endpoint, dns_servers);
+        tokio::spawn(async move {
+            let result = leak_test
+                .await
+                .map_err(|error| Error::LeakTest(error.into()));
+            Self::oneshot_send(tx, result, "run_leak_test response");
+        });
+    }
+
+    /// Return the DNS servers that are used in the tunnel. The gateway of the relay is included
+    /// if it is used and the last tunnel was a WireGuard tunnel.
+    async fn tunnel_dns_servers(&self) -> Vec<IpAddr> {
+        let mut gateways = vec![];
+        if let Some(config) = self.parameters_generator.get_last_wireguard_config().await {
+            gateways.push(IpAddr::V4(config.ipv4_gateway));
+            gateways.extend(config.ipv6_gateway.map(IpAddr::V6));
+        }
+        dns::addresses_from_options(&self.settings.tunnel_options.dns_options)
+            .resolve(
+                &gateways,
+                #[cfg(target_os = "macos")]
+                53,
+            )
+            .tunnel_config()
+            .to_vec()
+    }
+
     fn on_is_performing_post_upgrade(&self, tx: oneshot::Sender<bool>) {
         let performing_post_upgrade = !self.migration_complete.is_complete();
         Self::oneshot_send(tx, performing_post_upgrade, "performing post upgrade");
@@ -1859,6 +2256,10 @@ impl Daemon {
         Self::oneshot_send(tx, self.relay_selector.get_relays(), "relay locations");
     }
 
+    fn on_get_relay_list_changes(&self, tx: oneshot::Sender<RelayListDiff>) {
+        Self::oneshot_send(tx, self.relay_list_changes.clone(), "relay list changes");
+    }
+
     async fn on_update_relay_locations(&mut self) {
         self.relay_list_updater.update().await;
     }
@@ -2041,6 +2442,14 @@ impl Daemon {
             last_error = Some("Failed to reset settings");
         }
 
+        if let Err(error) = self.settings_profiles.clear().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to remove settings profiles")
+            );
+            last_error = Some("Failed to remove settings profiles");
+        }
+
         // Shut the daemon down.
         let _ = self.tx.send(InternalDaemonEvent::TriggerShutdown(false));
 
@@ -2078,6 +2487,10 @@ impl Daemon {
         let result = self.exclude_pids.add(pid).inspect_err(|error| {
             log::error!("{}", error.display_chain_with_msg("Unable to add PID"));
         });
+        if result.is_ok() {
+            // Keep the process excluded even if its app is removed from the excluded apps
+            self.exclude_apps.forget_process(pid);
+        }
         Self::oneshot_send(tx, result, "add_split_tunnel_process response");
     }
 
@@ -2090,6 +2503,7 @@ impl Daemon {
         let result = self.exclude_pids.remove(pid).inspect_err(|error| {
             log::error!("{}", error.display_chain_with_msg("Unable to remove PID"));
         });
+        self.exclude_apps.forget_process(pid);
         Self::oneshot_send(tx, result, "remove_split_tunnel_process response");
     }
 
@@ -2098,6 +2512,7 @@ impl Daemon {
         let result = self.exclude_pids.clear().inspect_err(|error| {
             log::error!("{}", error.display_chain_with_msg("Unable to clear PIDs"));
         });
+        self.exclude_apps.forget_processes();
         Self::oneshot_send(tx, result, "clear_split_tunnel_processes response");
     }
 
@@ -2222,7 +2637,41 @@ impl Daemon {
         });
     }
 
-    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "android"))]
+    /// Update the split app paths in both the settings and the app monitor. The app monitor is
+    /// updated once the settings have been saved.
+    #[cfg(target_os = "linux")]
+    fn set_split_tunnel_paths(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        _response_msg: &'static str,
+        _settings: Settings,
+        update: ExcludedPathsUpdate,
+    ) {
+        let _ = self
+            .tx
+            .send(InternalDaemonEvent::ExcludedPathsEvent(update, tx));
+    }
+
+    /// Exclude the split tunnel apps in the current settings, if split tunneling is enabled
+    #[cfg(target_os = "linux")]
+    fn update_excluded_apps(&mut self) -> Result<(), Error> {
+        let split_tunnel = &self.settings.split_tunnel;
+        let excluded_apps = if split_tunnel.enable_exclusions {
+            split_tunnel.apps.clone()
+        } else {
+            HashSet::new()
+        };
+        self.exclude_apps
+            .set_apps(excluded_apps.into_iter().map(SplitApp::into_path))
+            .map_err(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to set excluded apps list")
+                );
+                Error::SplitTunnelError(error)
+            })
+    }
+
     fn on_add_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, app: SplitApp) {
         let settings = self.settings.to_settings();
 
@@ -2240,7 +2689,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_remove_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, app: impl Into<SplitApp>) {
         let settings = self.settings.to_settings();
 
@@ -2258,7 +2706,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_clear_split_tunnel_apps(&mut self, tx: ResponseTx<(), Error>) {
         let settings = self.settings.to_settings();
         let new_list = HashSet::new();
@@ -2270,7 +2717,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_set_split_tunnel_state(&mut self, tx: ResponseTx<(), Error>, state: bool) {
         let settings = self.settings.to_settings();
         self.set_split_tunnel_paths(
@@ -2281,6 +2727,35 @@ impl Daemon {
         );
     }
 
+    async fn on_set_split_tunnel_mode(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        mode: SplitTunnelMode,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.split_tunnel.mode = mode)
+            .await
+        {
+            #[cfg(target_os = "linux")]
+            Ok(true) => {
+                self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                    self.settings.split_tunnel.effective_mode(),
+                    oneshot_map(tx, |tx, ()| {
+                        Self::oneshot_send(tx, Ok(()), "set_split_tunnel_mode response");
+                    }),
+                ));
+            }
+            Ok(_) => {
+                Self::oneshot_send(tx, Ok(()), "set_split_tunnel_mode response");
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_split_tunnel_mode response");
+            }
+        }
+    }
+
     #[cfg(windows)]
     fn on_get_split_tunnel_processes(
         &self,
@@ -2351,6 +2826,62 @@ impl Daemon {
         }
     }
 
+    async fn on_add_allowed_lan_network(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        network: IpNetwork,
+    ) {
+        self.update_allowed_lan_networks(tx, "add_allowed_lan_network response", move |networks| {
+            if !networks.contains(&network) {
+                networks.push(network);
+            }
+        })
+        .await
+    }
+
+    async fn on_remove_allowed_lan_network(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        network: IpNetwork,
+    ) {
+        self.update_allowed_lan_networks(
+            tx,
+            "remove_allowed_lan_network response",
+            move |networks| networks.retain(|allowed| *allowed != network),
+        )
+        .await
+    }
+
+    async fn update_allowed_lan_networks(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        response_msg: &'static str,
+        update_fn: impl FnOnce(&mut Vec<IpNetwork>),
+    ) {
+        match self
+            .settings
+            .update(move |settings| update_fn(&mut settings.allowed_lan_networks))
+            .await
+        {
+            #[cfg(target_os = "linux")]
+            Ok(true) => {
+                self.send_tunnel_command(TunnelCommand::AllowedLanNets(
+                    self.settings.allowed_lan_networks.clone(),
+                    oneshot_map(tx, move |tx, ()| {
+                        Self::oneshot_send(tx, Ok(()), response_msg);
+                    }),
+                ));
+            }
+            Ok(_) => {
+                Self::oneshot_send(tx, Ok(()), response_msg);
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), response_msg);
+            }
+        }
+    }
+
     async fn on_set_show_beta_releases(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2393,7 +2924,7 @@ impl Daemon {
             Ok(settings_changed) => {
                 if settings_changed {
                     self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
-                        BlockWhenDisconnected::from(block_when_disconnected),
+                        BlockWhenDisconnected::from(self.block_when_disconnected()),
                         oneshot_map(tx, |tx, ()| {
                             Self::oneshot_send(tx, Ok(()), "set_block_when_disconnected response");
                         }),
@@ -2429,6 +2960,152 @@ impl Daemon {
         }
     }
 
+    async fn on_set_network_rules(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        rules: Vec<NetworkRule>,
+    ) {
+        let new_rules = rules.clone();
+        match self
+            .settings
+            .update(move |settings| settings.network_rules = new_rules)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed && self.network_rules.set_rules(rules) {
+                    self.apply_network_action().await;
+                }
+                Self::oneshot_send(tx, Ok(()), "set_network_rules response");
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_network_rules response");
+            }
+        }
+    }
+
+    /// Connect or disconnect the tunnel according to the network rule matching the current
+    /// network.
+    async fn apply_network_action(&mut self) {
+        let action = self.network_rules.action();
+        if let Some(action) = action {
+            log::info!("Network rule matched the current network: {action}");
+        }
+        match action {
+            Some(NetworkAction::Connect) => {
+                self.set_target_state(TargetState::Secured).await;
+            }
+            Some(NetworkAction::Disconnect | NetworkAction::Block) => {
+                self.set_target_state(TargetState::Unsecured).await;
+            }
+            None => (),
+        }
+
+        #[cfg(not(target_os = "android"))]
+        {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
+                BlockWhenDisconnected::from(self.block_when_disconnected()),
+                tx,
+            ));
+        }
+    }
+
+    /// Whether to block traffic in the disconnected state, either because of the setting or
+    /// because a network rule says so.
+    #[cfg(not(target_os = "android"))]
+    fn block_when_disconnected(&self) -> bool {
+        self.settings.block_when_disconnected
+            || self.network_rules.action() == Some(NetworkAction::Block)
+    }
+
+    async fn on_set_metrics_settings(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        metrics_settings: MetricsSettings,
+    ) {
+        let result = self.set_metrics_settings(metrics_settings).await;
+        Self::oneshot_send(tx, result, "set_metrics_settings response");
+    }
+
+    /// Start the metrics endpoint using `metrics_settings`, and save them if that succeeds.
+    /// Otherwise, the endpoint of the current settings is restored.
+    async fn set_metrics_settings(
+        &mut self,
+        metrics_settings: MetricsSettings,
+    ) -> Result<(), Error> {
+        // Retry starting the server if it failed to start with the same settings
+        let server_running = self.metrics_server.is_some() || !metrics_settings.enabled;
+        if metrics_settings == self.settings.metrics && server_running {
+            return Ok(());
+        }
+        if let Err(error) = self.restart_metrics_server(metrics_settings).await {
+            self.start_metrics_server_or_log().await;
+            return Err(Error::MetricsServer(error));
+        }
+
+        if let Err(error) = self
+            .settings
+            .update(move |settings| settings.metrics = metrics_settings)
+            .await
+        {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to save settings")
+            );
+            self.start_metrics_server_or_log().await;
+            return Err(Error::SettingsError(error));
+        }
+        Ok(())
+    }
+
+    async fn on_set_expiry_reminders(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        reminders: ExpiryReminders,
+    ) {
+        // The expiry monitor is notified by the settings change listener
+        let result = self
+            .settings
+            .update(move |settings| settings.expiry_reminders = reminders)
+            .await
+            .map(|_| ());
+        if let Err(error) = &result {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to save settings")
+            );
+        }
+        Self::oneshot_send(tx, result, "set_expiry_reminders response");
+    }
+
+    /// Start the metrics endpoint if it is enabled in the settings, replacing any running one.
+    async fn start_metrics_server_or_log(&mut self) {
+        if let Err(error) = self.restart_metrics_server(self.settings.metrics).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to start metrics endpoint")
+            );
+        }
+    }
+
+    /// Stop the metrics endpoint, and start it again if it is enabled in `metrics_settings`.
+    async fn restart_metrics_server(
+        &mut self,
+        metrics_settings: MetricsSettings,
+    ) -> Result<(), metrics::Error> {
+        // Stop the old server first so that the port can be reused
+        self.metrics_server = None;
+
+        if metrics_settings.enabled {
+            let server =
+                MetricsServer::start(metrics_settings.port, self.tx.to_specialized_sender())
+                    .await?;
+            self.metrics_server = Some(server);
+        }
+        Ok(())
+    }
+
     async fn on_set_openvpn_mssfix(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2524,6 +3201,40 @@ impl Daemon {
         }
     }
 
+    async fn on_set_retry_order(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        retry_order: Option<Vec<RetryStep>>,
+    ) {
+        let incompatible = retry_order
+            .as_ref()
+            .is_some_and(|order| !self.relay_selector.is_compatible_retry_order(order));
+        if incompatible {
+            Self::oneshot_send(tx, Err(Error::IncompatibleRetryOrder), "set_retry_order");
+            return;
+        }
+
+        match self
+            .settings
+            .update(move |settings| settings.retry_order = retry_order)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.reconnect_tunnel();
+                }
+                Self::oneshot_send(tx, Ok(()), "set_retry_order");
+            }
+            Err(err) => {
+                log::error!(
+                    "{}",
+                    err.display_chain_with_msg("Failed to set retry order")
+                );
+                Self::oneshot_send(tx, Err(Error::SettingsError(err)), "set_retry_order");
+            }
+        }
+    }
+
     async fn on_set_bridge_state(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2824,6 +3535,34 @@ impl Daemon {
         }
     }
 
+    async fn on_set_wireguard_connectivity_check(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        options: ConnectivityCheckOptions,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.tunnel_options.wireguard.connectivity_check = options)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_connectivity_check response");
+                if settings_changed {
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the connectivity check changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_connectivity_check response");
+            }
+        }
+    }
+
     async fn on_set_wireguard_rotation_interval(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -3109,6 +3848,10 @@ impl Daemon {
         let result = self.settings.reset().await;
         Self::oneshot_send(tx, result, "reset_settings response");
 
+        self.network_rules
+            .set_rules(self.settings.network_rules.clone());
+        self.start_metrics_server_or_log().await;
+
         // TODO: All of the functions below should probably be handled by settings observers
         //       whenever settings are updated. For instance, changing "allow_lan" should probably
         //       cause a tunnel command to be sent.
@@ -3119,11 +3862,21 @@ impl Daemon {
             self.send_tunnel_command(TunnelCommand::SetExcludedApps(tx, vec![]));
         }
 
+        #[cfg(target_os = "linux")]
+        {
+            if let Err(error) = self.exclude_apps.set_apps([]) {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to clear excluded apps list")
+                );
+            }
+        }
+
         #[cfg(not(target_os = "android"))]
         {
             let (tx, _rx) = oneshot::channel();
             self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
-                BlockWhenDisconnected::from(self.settings.block_when_disconnected),
+                BlockWhenDisconnected::from(self.block_when_disconnected()),
                 tx,
             ));
         }
@@ -3131,6 +3884,24 @@ impl Daemon {
         let (tx, _rx) = oneshot::channel();
         self.send_tunnel_command(TunnelCommand::AllowLan(self.settings.allow_lan, tx));
 
+        #[cfg(target_os = "linux")]
+        {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::AllowedLanNets(
+                self.settings.allowed_lan_networks.clone(),
+                tx,
+            ));
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                self.settings.split_tunnel.effective_mode(),
+                tx,
+            ));
+        }
+
         let (tx, _rx) = oneshot::channel();
         let dns = dns::addresses_from_options(&self.settings.tunnel_options.dns_options);
         self.send_tunnel_command(TunnelCommand::Dns(dns, tx));
@@ -3287,6 +4058,111 @@ impl Daemon {
         Self::oneshot_send(tx, result, "export_json_settings response");
     }
 
+    async fn on_save_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let result = match settings::patch::export_profile(&self.settings) {
+            Ok(profile) => self.settings_profiles.save(name, profile).await,
+            Err(error) => Err(settings::profiles::Error::Patch(error)),
+        };
+        Self::oneshot_send(tx, result, "save_settings_profile response");
+    }
+
+    async fn on_apply_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let result = self.apply_settings_profile(&name).await;
+        Self::oneshot_send(tx, result, "apply_settings_profile response");
+    }
+
+    /// Replace the settings in a single update, so that either the entire profile is applied or
+    /// none of it is.
+    async fn apply_settings_profile(
+        &mut self,
+        name: &str,
+    ) -> Result<(), settings::profiles::Error> {
+        let profile = self.settings_profiles.get(name)?;
+        let new_settings = settings::patch::merge_validate_profile(&self.settings, profile)
+            .map_err(settings::profiles::Error::Patch)?;
+
+        let old_settings = self.settings.to_settings();
+        let settings_changed = self
+            .settings
+            .update(move |settings| *settings = new_settings)
+            .await
+            .map_err(settings::profiles::Error::Settings)?;
+
+        log::info!("Applied settings profile \"{name}\"");
+        if settings_changed {
+            self.apply_replaced_settings(&old_settings);
+        }
+        Ok(())
+    }
+
+    /// Send the settings that the tunnel state machine keeps track of to it if they differ from
+    /// `old_settings`, and reconnect to apply the rest.
+    fn apply_replaced_settings(&mut self, old_settings: &Settings) {
+        if self.settings.allow_lan != old_settings.allow_lan {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::AllowLan(self.settings.allow_lan, tx));
+        }
+        #[cfg(target_os = "linux")]
+        if self.settings.allowed_lan_networks != old_settings.allowed_lan_networks {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::AllowedLanNets(
+                self.settings.allowed_lan_networks.clone(),
+                tx,
+            ));
+        }
+        #[cfg(not(target_os = "android"))]
+        if self.settings.block_when_disconnected != old_settings.block_when_disconnected {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
+                BlockWhenDisconnected::from(self.block_when_disconnected()),
+                tx,
+            ));
+        }
+        if self.settings.tunnel_options.dns_options != old_settings.tunnel_options.dns_options {
+            let resolvers = dns::addresses_from_options(&self.settings.tunnel_options.dns_options);
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::Dns(resolvers, tx));
+        }
+        self.reconnect_tunnel();
+    }
+
+    fn on_list_settings_profiles(&self, tx: oneshot::Sender<Vec<SettingsProfile>>) {
+        Self::oneshot_send(
+            tx,
+            self.settings_profiles.list(),
+            "list_settings_profiles response",
+        );
+    }
+
+    async fn on_delete_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::profiles::Error>,
+        name: String,
+    ) {
+        let result = self.settings_profiles.delete(&name).await;
+        Self::oneshot_send(tx, result, "delete_settings_profile response");
+    }
+
+    fn on_diff_settings_profile(
+        &self,
+        tx: ResponseTx<Vec<SettingDiff>, settings::profiles::Error>,
+        name: String,
+    ) {
+        let result = self.settings_profiles.get(&name).and_then(|profile| {
+            settings::patch::diff_profile(&self.settings, profile)
+                .map_err(settings::profiles::Error::Patch)
+        });
+        Self::oneshot_send(tx, result, "diff_settings_profile response");
+    }
+
     fn on_get_feature_indicators(&self, tx: oneshot::Sender<FeatureIndicators>) {
         let feature_indicators = match &self.tunnel_state {
             TunnelState::Connecting {
@@ -3,21 +3,27 @@ use futures::{
     StreamExt,
     channel::{mpsc, oneshot},
 };
+use ipnetwork::IpNetwork;
 use mullvad_api::{StatusCode, rest::Error as RestError};
 use mullvad_management_interface::types::FromProtobufTypeError;
 use mullvad_management_interface::{
     Code, Request, Response, ServerJoinHandle, Status,
     types::{self, daemon_event, management_service_server::ManagementService},
 };
+#[cfg(target_os = "linux")]
+use mullvad_types::management_access::{ManagementAccessSettings, Role};
 use mullvad_types::relay_constraints::GeographicLocationConstraint;
 use mullvad_types::{
-    account::AccountNumber,
+    account::{AccountExpiryWarning, AccountNumber, ExpiryReminders},
+    connection_history::ConnectionHistoryFilter,
+    leak_test::LeakTestReport,
+    network_rule::NetworkRule,
     relay_constraints::{
-        BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings, RetryStep,
         allowed_ip::AllowedIps,
     },
-    relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    relay_list::{RelayList, RelayListDiff},
+    settings::{DnsOptions, MetricsSettings, Settings},
     states::{TargetState, TunnelState},
     version,
     wireguard::{RotationInterval, RotationIntervalError},
@@ -29,11 +35,16 @@ use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
-use talpid_types::ErrorExt;
+use talpid_types::{
+    ErrorExt,
+    net::wireguard::{ConnectivityCheckOptions, TunnelStats},
+};
 use tokio::time::timeout;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
 const RPC_SERVER_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+/// Lower bound for the sampling interval of `TunnelStatsListen`.
+const MIN_TUNNEL_STATS_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -46,7 +57,7 @@ pub type AppUpgradeBroadcast = tokio::sync::broadcast::Sender<version::AppUpgrad
 
 struct ManagementServiceImpl {
     daemon_tx: DaemonCommandSender,
-    subscriptions: Arc<Mutex<Vec<EventsListenerSender>>>,
+    subscriptions: Arc<Mutex<Vec<EventsListener>>>,
     pub app_upgrade_broadcast: AppUpgradeBroadcast,
 }
 
@@ -54,6 +65,32 @@ pub type ServiceResult<T> = std::result::Result<Response<T>, Status>;
 type EventsListenerReceiver = UnboundedReceiverStream<Result<types::DaemonEvent, Status>>;
 type EventsListenerSender = tokio::sync::mpsc::UnboundedSender<Result<types::DaemonEvent, Status>>;
 
+/// A subscriber of `EventsListen`.
+struct EventsListener {
+    tx: EventsListenerSender,
+    /// Whether the account number is removed from events sent to this subscriber
+    hide_account_number: bool,
+}
+
+impl EventsListener {
+    fn send(&self, mut event: types::DaemonEvent) -> bool {
+        if self.hide_account_number {
+            match &mut event.event {
+                Some(daemon_event::Event::Device(device_event)) => {
+                    if let Some(state) = &mut device_event.new_state {
+                        strip_account_number(state);
+                    }
+                }
+                Some(daemon_event::Event::RemoveDevice(remove_event)) => {
+                    remove_event.account_number.clear();
+                }
+                _ => (),
+            }
+        }
+        self.tx.send(Ok(event)).is_ok()
+    }
+}
+
 type AppUpgradeEventListenerReceiver =
     Box<dyn futures::Stream<Item = Result<types::AppUpgradeEvent, Status>> + Send + Unpin>;
 
@@ -64,6 +101,7 @@ const USED_VOUCHER_MESSAGE: &str = "This voucher code has already been used";
 impl ManagementService for ManagementServiceImpl {
     type GetSplitTunnelProcessesStream = UnboundedReceiverStream<Result<i32, Status>>;
     type EventsListenStream = EventsListenerReceiver;
+    type TunnelStatsListenStream = UnboundedReceiverStream<Result<types::TunnelStats, Status>>;
     type AppUpgradeEventsListenStream = AppUpgradeEventListenerReceiver;
 
     // Control and get the tunnel state
@@ -103,14 +141,107 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(types::TunnelState::from(state)))
     }
 
+    async fn get_tunnel_stats(&self, _: Request<()>) -> ServiceResult<types::TunnelStats> {
+        log::debug!("get_tunnel_stats");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetTunnelStats(tx))?;
+        let stats = self
+            .wait_for_result(rx)
+            .await?
+            .ok_or_else(|| Status::not_found("there is no WireGuard tunnel"))?;
+        Ok(Response::new(types::TunnelStats::from(stats)))
+    }
+
+    async fn export_wireguard_config(&self, _: Request<()>) -> ServiceResult<String> {
+        log::debug!("export_wireguard_config");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ExportWireguardConfig(tx))?;
+        let config = self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(config))
+    }
+
+    async fn run_leak_test(&self, _: Request<()>) -> ServiceResult<types::LeakTestReport> {
+        log::debug!("run_leak_test");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RunLeakTest(tx))?;
+        let report = self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(types::LeakTestReport::from(report)))
+    }
+
+    async fn get_connection_history(
+        &self,
+        request: Request<types::ConnectionHistoryFilter>,
+    ) -> ServiceResult<types::ConnectionHistory> {
+        let filter = ConnectionHistoryFilter::try_from(request.into_inner())?;
+        log::debug!("get_connection_history({filter:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetConnectionHistory(tx, filter))?;
+        let entries = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::ConnectionHistory {
+            entries: entries
+                .into_iter()
+                .map(types::ConnectionHistoryEntry::from)
+                .collect(),
+        }))
+    }
+
+    async fn tunnel_stats_listen(
+        &self,
+        request: Request<types::Duration>,
+    ) -> ServiceResult<Self::TunnelStatsListenStream> {
+        let interval = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("unexpected negative interval"))?
+            .max(MIN_TUNNEL_STATS_INTERVAL);
+        log::debug!("tunnel_stats_listen({:?})", interval);
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let daemon_tx = self.daemon_tx.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut previous: Option<TunnelStats> = None;
+            while !tx.is_closed() {
+                ticker.tick().await;
+
+                let (stats_tx, stats_rx) = oneshot::channel();
+                if daemon_tx
+                    .send(DaemonCommand::GetTunnelStats(stats_tx))
+                    .is_err()
+                {
+                    break;
+                }
+                let Ok(stats) = stats_rx.await else {
+                    break;
+                };
+                let Some(mut stats) = stats else {
+                    // Not connected. Don't compute rates across tunnels.
+                    previous = None;
+                    continue;
+                };
+                if let Some(previous) = &previous {
+                    stats.set_rates_since(previous);
+                }
+                previous = Some(stats.clone());
+
+                if tx.send(Ok(types::TunnelStats::from(stats))).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    }
+
     // Control the daemon and receive events
     //
 
-    async fn events_listen(&self, _: Request<()>) -> ServiceResult<Self::EventsListenStream> {
+    async fn events_listen(&self, request: Request<()>) -> ServiceResult<Self::EventsListenStream> {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
 
         let mut subscriptions = self.subscriptions.lock().unwrap();
-        subscriptions.push(tx);
+        subscriptions.push(EventsListener {
+            tx,
+            hide_account_number: !may_see_account_number(&request),
+        });
 
         Ok(Response::new(UnboundedReceiverStream::new(rx)))
     }
@@ -207,6 +338,16 @@ impl ManagementService for ManagementServiceImpl {
             .map(|relays| Response::new(types::RelayList::from(relays)))
     }
 
+    async fn get_relay_list_changes(&self, _: Request<()>) -> ServiceResult<types::RelayListDiff> {
+        log::debug!("get_relay_list_changes");
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetRelayListChanges(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|diff| Response::new(types::RelayListDiff::from(diff)))
+    }
+
     async fn set_bridge_settings(
         &self,
         request: Request<types::BridgeSettings>,
@@ -235,6 +376,22 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_retry_order(&self, request: Request<types::RetryOrder>) -> ServiceResult<()> {
+        let steps = request
+            .into_inner()
+            .steps
+            .into_iter()
+            .map(RetryStep::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(map_protobuf_type_err)?;
+        let retry_order = if steps.is_empty() { None } else { Some(steps) };
+        log::debug!("set_retry_order({:?})", retry_order);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRetryOrder(tx, retry_order))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
     async fn set_bridge_state(&self, request: Request<types::BridgeState>) -> ServiceResult<()> {
         let bridge_state =
             BridgeState::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
@@ -275,6 +432,34 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn add_allowed_lan_network(&self, request: Request<String>) -> ServiceResult<()> {
+        if cfg!(not(target_os = "linux")) {
+            return Err(Status::unimplemented(
+                "Configuring allowed LAN networks is only supported on Linux",
+            ));
+        }
+        let network = parse_lan_network(&request.into_inner())?;
+        log::debug!("add_allowed_lan_network({network})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddAllowedLanNetwork(tx, network))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn remove_allowed_lan_network(&self, request: Request<String>) -> ServiceResult<()> {
+        if cfg!(not(target_os = "linux")) {
+            return Err(Status::unimplemented(
+                "Configuring allowed LAN networks is only supported on Linux",
+            ));
+        }
+        let network = parse_lan_network(&request.into_inner())?;
+        log::debug!("remove_allowed_lan_network({network})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveAllowedLanNetwork(tx, network))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     async fn set_show_beta_releases(&self, request: Request<bool>) -> ServiceResult<()> {
         let enabled = request.into_inner();
         log::debug!("set_show_beta_releases({})", enabled);
@@ -315,6 +500,32 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_network_rules(&self, request: Request<types::NetworkRules>) -> ServiceResult<()> {
+        if cfg!(not(target_os = "macos")) {
+            return Err(Status::unimplemented(
+                "Network rules are only supported on macOS",
+            ));
+        }
+        let rules = Vec::<NetworkRule>::try_from(request.into_inner())?;
+        log::debug!("set_network_rules({rules:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetNetworkRules(tx, rules))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn set_metrics_settings(
+        &self,
+        request: Request<types::MetricsSettings>,
+    ) -> ServiceResult<()> {
+        let metrics_settings = MetricsSettings::try_from(request.into_inner())?;
+        log::debug!("set_metrics_settings({metrics_settings:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetMetricsSettings(tx, metrics_settings))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
     async fn set_openvpn_mssfix(&self, request: Request<u32>) -> ServiceResult<()> {
         let mssfix = request.into_inner();
         let mssfix = if mssfix != 0 {
@@ -339,6 +550,23 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_wireguard_connectivity_check(
+        &self,
+        request: Request<types::ConnectivityCheckOptions>,
+    ) -> ServiceResult<()> {
+        let options = ConnectivityCheckOptions::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+        options
+            .validate()
+            .map_err(|error| Status::invalid_argument(error.to_string()))?;
+
+        log::debug!("set_wireguard_connectivity_check({:?})", options);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardConnectivityCheck(tx, options))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     async fn set_enable_ipv6(&self, request: Request<bool>) -> ServiceResult<()> {
         let enable_ipv6 = request.into_inner();
         log::debug!("set_enable_ipv6({})", enable_ipv6);
@@ -418,6 +646,19 @@ impl ManagementService for ManagementServiceImpl {
         let options = DnsOptions::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
         log::debug!("set_dns_options({:?})", options);
 
+        if cfg!(not(target_os = "macos")) && !options.custom_options.encrypted_servers.is_empty() {
+            return Err(Status::unimplemented(
+                "Encrypted DNS servers are only supported on macOS",
+            ));
+        }
+        if cfg!(not(any(target_os = "linux", target_os = "macos")))
+            && !options.split_dns_rules.is_empty()
+        {
+            return Err(Status::unimplemented(
+                "Split DNS is only supported on Linux and macOS",
+            ));
+        }
+
         let (tx, rx) = oneshot::channel();
         self.send_command_to_daemon(DaemonCommand::SetDnsOptions(tx, options))?;
         self.wait_for_result(rx).await??;
@@ -546,13 +787,29 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn set_expiry_reminders(
+        &self,
+        request: Request<types::ExpiryReminders>,
+    ) -> ServiceResult<()> {
+        let reminders = ExpiryReminders::try_from(request.into_inner())?;
+        log::debug!("set_expiry_reminders({reminders:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetExpiryReminders(tx, reminders))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     // Device management
-    async fn get_device(&self, _: Request<()>) -> ServiceResult<types::DeviceState> {
+    async fn get_device(&self, request: Request<()>) -> ServiceResult<types::DeviceState> {
         log::debug!("get_device");
         let (tx, rx) = oneshot::channel();
         self.send_command_to_daemon(DaemonCommand::GetDevice(tx))?;
         let device = self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
-        Ok(Response::new(types::DeviceState::from(device)))
+        let mut device = types::DeviceState::from(device);
+        if !may_see_account_number(&request) {
+            strip_account_number(&mut device);
+        }
+        Ok(Response::new(device))
     }
 
     async fn update_device(&self, _: Request<()>) -> ServiceResult<()> {
@@ -939,7 +1196,6 @@ impl ManagementService for ManagementServiceImpl {
         }
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn add_split_tunnel_app(&self, request: Request<String>) -> ServiceResult<()> {
         use mullvad_types::settings::SplitApp;
         log::debug!("add_split_tunnel_app");
@@ -952,12 +1208,6 @@ impl ManagementService for ManagementServiceImpl {
             .map(Response::new)
     }
 
-    #[cfg(target_os = "linux")]
-    async fn add_split_tunnel_app(&self, _: Request<String>) -> ServiceResult<()> {
-        Ok(Response::new(()))
-    }
-
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn remove_split_tunnel_app(&self, request: Request<String>) -> ServiceResult<()> {
         use mullvad_types::settings::SplitApp;
         log::debug!("remove_split_tunnel_app");
@@ -969,12 +1219,7 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
             .map(Response::new)
     }
-    #[cfg(target_os = "linux")]
-    async fn remove_split_tunnel_app(&self, _: Request<String>) -> ServiceResult<()> {
-        Ok(Response::new(()))
-    }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn clear_split_tunnel_apps(&self, _: Request<()>) -> ServiceResult<()> {
         log::debug!("clear_split_tunnel_apps");
         let (tx, rx) = oneshot::channel();
@@ -984,12 +1229,7 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
             .map(Response::new)
     }
-    #[cfg(target_os = "linux")]
-    async fn clear_split_tunnel_apps(&self, _: Request<()>) -> ServiceResult<()> {
-        Ok(Response::new(()))
-    }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn set_split_tunnel_state(&self, request: Request<bool>) -> ServiceResult<()> {
         log::debug!("set_split_tunnel_state");
         let enabled = request.into_inner();
@@ -1000,8 +1240,24 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
             .map(Response::new)
     }
-    #[cfg(target_os = "linux")]
-    async fn set_split_tunnel_state(&self, _: Request<bool>) -> ServiceResult<()> {
+
+    async fn set_split_tunnel_mode(
+        &self,
+        request: Request<types::SplitTunnelMode>,
+    ) -> ServiceResult<()> {
+        use mullvad_types::settings::SplitTunnelMode;
+        let mode =
+            SplitTunnelMode::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_split_tunnel_mode({mode})");
+        #[cfg(not(target_os = "linux"))]
+        if mode == SplitTunnelMode::Include {
+            return Err(Status::unimplemented(
+                "Including apps in the tunnel is only supported on Linux",
+            ));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetSplitTunnelMode(tx, mode))?;
+        self.wait_for_result(rx).await??;
         Ok(Response::new(()))
     }
 
@@ -1080,6 +1336,63 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(blob))
     }
 
+    async fn save_settings_profile(&self, name: Request<String>) -> ServiceResult<()> {
+        let name = name.into_inner();
+        log::debug!("save_settings_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SaveSettingsProfile(tx, name))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn apply_settings_profile(&self, name: Request<String>) -> ServiceResult<()> {
+        let name = name.into_inner();
+        log::debug!("apply_settings_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ApplySettingsProfile(tx, name))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn list_settings_profiles(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::SettingsProfiles> {
+        log::debug!("list_settings_profiles");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ListSettingsProfiles(tx))?;
+        let profiles = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::SettingsProfiles {
+            profiles: profiles
+                .into_iter()
+                .map(types::SettingsProfile::from)
+                .collect(),
+        }))
+    }
+
+    async fn delete_settings_profile(&self, name: Request<String>) -> ServiceResult<()> {
+        let name = name.into_inner();
+        log::debug!("delete_settings_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DeleteSettingsProfile(tx, name))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn diff_settings_profile(
+        &self,
+        name: Request<String>,
+    ) -> ServiceResult<types::SettingDiffs> {
+        let name = name.into_inner();
+        log::debug!("diff_settings_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DiffSettingsProfile(tx, name))?;
+        let diffs = self.wait_for_result(rx).await??;
+        Ok(Response::new(types::SettingDiffs {
+            diffs: diffs.into_iter().map(types::SettingDiff::from).collect(),
+        }))
+    }
+
     #[cfg(target_os = "android")]
     async fn init_play_purchase(
         &self,
@@ -1278,12 +1591,15 @@ impl ManagementInterfaceServer {
         rpc_socket_path: impl AsRef<Path>,
         app_upgrade_broadcast: tokio::sync::broadcast::Sender<version::AppUpgradeEvent>,
     ) -> Result<ManagementInterfaceServer, Error> {
-        let subscriptions = Arc::<Mutex<Vec<EventsListenerSender>>>::default();
+        let subscriptions = Arc::<Mutex<Vec<EventsListener>>>::default();
 
         // NOTE: It is important that the channel buffer size is kept at 0. When sending a signal
         // to abort the gRPC server, the sender can be awaited to know when the gRPC server has
         // received and started processing the shutdown signal.
         let (server_abort_tx, server_abort_rx) = mpsc::channel(0);
+        // Calls are held by the server until the settings have been loaded
+        #[cfg(target_os = "linux")]
+        let (access_settings_tx, access_settings_rx) = tokio::sync::watch::channel(None);
 
         let server = ManagementServiceImpl {
             daemon_tx,
@@ -1296,6 +1612,8 @@ impl ManagementInterfaceServer {
                 StreamExt::into_future(server_abort_rx).await;
             },
             &rpc_socket_path,
+            #[cfg(target_os = "linux")]
+            access_settings_rx,
         )
         .map_err(Error::SetupError)?;
 
@@ -1304,7 +1622,11 @@ impl ManagementInterfaceServer {
             rpc_socket_path.as_ref().display()
         );
 
-        let broadcast = ManagementInterfaceEventBroadcaster { subscriptions };
+        let broadcast = ManagementInterfaceEventBroadcaster {
+            subscriptions,
+            #[cfg(target_os = "linux")]
+            access_settings: Arc::new(access_settings_tx),
+        };
 
         Ok(ManagementInterfaceServer {
             rpc_server_join_handle,
@@ -1343,13 +1665,16 @@ impl ManagementInterfaceServer {
 /// A handle that allows broadcasting messages to all subscribers of the management interface.
 #[derive(Clone)]
 pub struct ManagementInterfaceEventBroadcaster {
-    subscriptions: Arc<Mutex<Vec<EventsListenerSender>>>,
+    subscriptions: Arc<Mutex<Vec<EventsListener>>>,
+    /// Access control settings used by the gRPC server.
+    #[cfg(target_os = "linux")]
+    access_settings: Arc<tokio::sync::watch::Sender<Option<ManagementAccessSettings>>>,
 }
 
 impl ManagementInterfaceEventBroadcaster {
     fn notify(&self, value: types::DaemonEvent) {
         let mut subscriptions = self.subscriptions.lock().unwrap();
-        subscriptions.retain(|tx| tx.send(Ok(value.clone())).is_ok());
+        subscriptions.retain(|subscriber| subscriber.send(value.clone()));
     }
 
     /// Notify that the tunnel state changed.
@@ -1367,6 +1692,8 @@ impl ManagementInterfaceEventBroadcaster {
     ///
     /// Sends settings to all `settings` subscribers of the management interface.
     pub(crate) fn notify_settings(&self, settings: Settings) {
+        #[cfg(target_os = "linux")]
+        self.set_access_settings(settings.management_access.clone());
         log::debug!("Broadcasting new settings");
         self.notify(types::DaemonEvent {
             event: Some(daemon_event::Event::Settings(types::Settings::from(
@@ -1375,6 +1702,19 @@ impl ManagementInterfaceEventBroadcaster {
         })
     }
 
+    /// Set the roles that users and groups have when calling the management interface.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_access_settings(&self, settings: ManagementAccessSettings) {
+        self.access_settings.send_if_modified(|current| {
+            if current.as_ref() == Some(&settings) {
+                return false;
+            }
+            log::debug!("Updating management interface access control settings");
+            *current = Some(settings);
+            true
+        });
+    }
+
     /// Notify that the relay list changed.
     ///
     /// Sends relays to all subscribers of the management interface.
@@ -1387,6 +1727,36 @@ impl ManagementInterfaceEventBroadcaster {
         })
     }
 
+    /// Notify that relays were added, removed, or changed in a new relay list.
+    pub(crate) fn notify_relay_list_changes(&self, diff: RelayListDiff) {
+        log::debug!("Broadcasting relay list changes");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::RelayListChanges(
+                types::RelayListDiff::from(diff),
+            )),
+        })
+    }
+
+    /// Notify that the leak checker managed to send traffic outside the tunnel.
+    pub(crate) fn notify_leak_detected(&self, report: LeakTestReport) {
+        log::debug!("Broadcasting detected leak");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::LeakDetected(
+                types::LeakTestReport::from(report),
+            )),
+        })
+    }
+
+    /// Notify that the time left on the account dropped below an expiry reminder threshold.
+    pub(crate) fn notify_account_expiry_warning(&self, warning: AccountExpiryWarning) {
+        log::debug!("Broadcasting account expiry warning");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::AccountExpiryWarning(
+                types::AccountExpiryWarning::from(warning),
+            )),
+        })
+    }
+
     /// Notify that info about the latest available app version changed.
     /// Or some flag about the currently running version is changed.
     pub(crate) fn notify_app_version(&self, app_version_info: version::AppVersionInfo) {
@@ -1435,6 +1805,26 @@ impl ManagementInterfaceEventBroadcaster {
     }
 }
 
+/// Return whether the peer that made `request` may see the account number. Only admins may, since
+/// the account number is enough to log in and manage the account. Roles are only enforced on Linux.
+fn may_see_account_number<T>(request: &Request<T>) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        request.extensions().get::<Role>() == Some(&Role::Admin)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = request;
+        true
+    }
+}
+
+fn strip_account_number(state: &mut types::DeviceState) {
+    if let Some(device) = &mut state.device {
+        device.account_number.clear();
+    }
+}
+
 /// Converts [`crate::Error`] into a tonic status.
 fn map_daemon_error(error: crate::Error) -> Status {
     use crate::Error as DaemonError;
@@ -1450,13 +1840,20 @@ fn map_daemon_error(error: crate::Error) -> Status {
         DaemonError::RemoveDeviceError(error) => map_device_error(&error),
         DaemonError::UpdateDeviceError(error) => map_device_error(&error),
         DaemonError::VoucherSubmission(error) => map_device_error(&error),
-        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
         DaemonError::SplitTunnelError(error) => map_split_tunnel_error(error),
         DaemonError::AccountHistory(error) => map_account_history_error(error),
         DaemonError::NoAccountNumber | DaemonError::NoAccountNumberHistory => {
             Status::unauthenticated(error.to_string())
         }
         DaemonError::VersionCheckError(error) => map_version_check_error(error),
+        DaemonError::NotConnectedToWireguardRelay | DaemonError::ExportWireguardConfig(_) => {
+            Status::failed_precondition(error.display_chain())
+        }
+        DaemonError::IncompatibleRetryOrder => Status::invalid_argument(error.to_string()),
+        DaemonError::NotConnected => Status::failed_precondition(error.to_string()),
+        DaemonError::LeakTest(_) => Status::unknown(error.display_chain()),
+        DaemonError::MetricsServer(_) => Status::unavailable(error.display_chain()),
         error => Status::unknown(error.to_string()),
     }
 }
@@ -1484,6 +1881,12 @@ fn map_split_tunnel_error(error: talpid_core::split_tunnel::Error) -> Status {
     Status::unknown(error.to_string())
 }
 
+#[cfg(target_os = "linux")]
+/// Converts [`talpid_core::split_tunnel::Error`] into a tonic status.
+fn map_split_tunnel_error(error: talpid_core::split_tunnel::Error) -> Status {
+    Status::failed_precondition(error.to_string())
+}
+
 /// Converts a REST API error into a tonic status.
 fn map_rest_error(error: &RestError) -> Status {
     match error {
@@ -1540,3 +1943,115 @@ fn map_protobuf_type_err(err: types::FromProtobufTypeError) -> Status {
         types::FromProtobufTypeError::InvalidArgument(err) => Status::invalid_argument(err),
     }
 }
+
+/// Parses a LAN network to allow or disallow. Networks that would match every address are
+/// rejected, since they would make the firewall allow all traffic outside the tunnel.
+fn parse_lan_network(network: &str) -> Result<IpNetwork, Status> {
+    let network: IpNetwork = network
+        .parse()
+        .map_err(|_| Status::invalid_argument("invalid LAN network"))?;
+    if network.prefix() == 0 {
+        return Err(Status::invalid_argument(
+            "LAN network must not match every address",
+        ));
+    }
+    Ok(network)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::{DaemonCommandChannel, InternalDaemonEvent};
+    use mullvad_types::device::{AccountAndDevice, Device, DeviceState};
+    use talpid_types::net::wireguard::PrivateKey;
+
+    const ACCOUNT_NUMBER: &str = "1234123412341234";
+
+    fn logged_in() -> DeviceState {
+        DeviceState::LoggedIn(AccountAndDevice::new(
+            ACCOUNT_NUMBER.to_owned(),
+            Device {
+                id: "device-id".to_owned(),
+                name: "happy seagull".to_owned(),
+                pubkey: PrivateKey::new_from_random().public_key(),
+                hijack_dns: false,
+                created: chrono::Utc::now(),
+            },
+        ))
+    }
+
+    /// Only admins may see the account number of the device.
+    #[tokio::test]
+    async fn test_get_device_hides_account_number() {
+        let DaemonCommandChannel {
+            sender,
+            mut receiver,
+        } = DaemonCommandChannel::new();
+        tokio::spawn(async move {
+            while let Some(event) = receiver.next().await {
+                if let InternalDaemonEvent::Command(DaemonCommand::GetDevice(tx)) = event {
+                    let _ = tx.send(Ok(logged_in()));
+                }
+            }
+        });
+        let service = ManagementServiceImpl {
+            daemon_tx: sender,
+            subscriptions: Arc::default(),
+            app_upgrade_broadcast: tokio::sync::broadcast::channel(1).0,
+        };
+
+        for (role, expected) in [
+            (Some(Role::Admin), ACCOUNT_NUMBER),
+            (Some(Role::Operator), ""),
+            (Some(Role::ReadOnly), ""),
+            (None, ""),
+        ] {
+            let mut request = Request::new(());
+            if let Some(role) = role {
+                request.extensions_mut().insert(role);
+            }
+            let device = service.get_device(request).await.unwrap().into_inner();
+            assert_eq!(device.device.unwrap().account_number, expected);
+        }
+    }
+
+    #[test]
+    fn test_events_listener_hides_account_number() {
+        let device_event = types::DaemonEvent {
+            event: Some(daemon_event::Event::Device(types::DeviceEvent {
+                cause: i32::from(types::device_event::Cause::LoggedIn),
+                new_state: Some(types::DeviceState::from(logged_in())),
+            })),
+        };
+        let remove_event = types::DaemonEvent {
+            event: Some(daemon_event::Event::RemoveDevice(
+                types::RemoveDeviceEvent {
+                    account_number: ACCOUNT_NUMBER.to_owned(),
+                    new_device_list: vec![],
+                },
+            )),
+        };
+
+        for (hide_account_number, expected) in [(false, ACCOUNT_NUMBER), (true, "")] {
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            let listener = EventsListener {
+                tx,
+                hide_account_number,
+            };
+            assert!(listener.send(device_event.clone()));
+            assert!(listener.send(remove_event.clone()));
+
+            for _ in 0..2 {
+                let event = rx.try_recv().unwrap().unwrap();
+                let account_number = match event.event {
+                    Some(daemon_event::Event::Device(event)) => {
+                        event.new_state.unwrap().device.unwrap().account_number
+                    }
+                    Some(daemon_event::Event::RemoveDevice(event)) => event.account_number,
+                    event => panic!("Unexpected event: {event:?}"),
+                };
+                assert_eq!(account_number, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! HTTP endpoint on the loopback interface that exports daemon metrics in the
+//! [OpenMetrics](https://openmetrics.io/) text format. It is disabled by default and configured
+//! using [`mullvad_types::settings::MetricsSettings`].
+//!
+//! Requests are not authenticated, so the management access roles do not apply to the endpoint.
+//! Any local user can read the metrics while it is enabled.
+
+use crate::{DaemonCommand, DaemonEventSender};
+use futures::channel::oneshot;
+use mullvad_types::states::TunnelState;
+use std::{
+    fmt::{self, Write},
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    time::{Duration, Instant, SystemTime},
+};
+use talpid_core::mpsc::Sender;
+use talpid_types::{ErrorExt, net::wireguard::TunnelStats, tunnel::ActionAfterDisconnect};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// Maximum size of a request head that is accepted.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Time that a client has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+const TUNNEL_STATES: [&str; 5] = [
+    "disconnected",
+    "connecting",
+    "connected",
+    "disconnecting",
+    "error",
+];
+
+#[derive(thiserror::Error, Debug)]
+#[error("Failed to bind metrics endpoint to {0}")]
+pub struct Error(SocketAddr, #[source] io::Error);
+
+/// A snapshot of the values exported by the metrics endpoint.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    pub tunnel_state: &'static str,
+    pub time_in_state: Duration,
+    pub reconnects: u64,
+    /// Hostname of the relay that the tunnel is connecting or connected to.
+    pub relay: Option<String>,
+    /// Name of the API access method in use.
+    pub access_method: Option<String>,
+    pub relay_list_updated: SystemTime,
+    /// Time when the current WireGuard key was created.
+    pub key_created: Option<SystemTime>,
+    pub tunnel_stats: Option<TunnelStats>,
+}
+
+impl Metrics {
+    /// Encode the metrics in the OpenMetrics text format.
+    pub fn encode(&self, now: SystemTime) -> String {
+        let mut out = String::new();
+        // Writing to a `String` cannot fail
+        let _ = self.encode_inner(&mut out, now);
+        out
+    }
+
+    fn encode_inner(&self, out: &mut String, now: SystemTime) -> fmt::Result {
+        let age = |time: SystemTime| now.duration_since(time).unwrap_or_default().as_secs_f64();
+
+        family(
+            out,
+            "mullvad_tunnel_state",
+            "stateset",
+            None,
+            "Current state of the tunnel.",
+        )?;
+        for state in TUNNEL_STATES {
+            writeln!(
+                out,
+                "mullvad_tunnel_state{{mullvad_tunnel_state=\"{state}\"}} {}",
+                u8::from(state == self.tunnel_state)
+            )?;
+        }
+
+        family(
+            out,
+            "mullvad_tunnel_state_duration_seconds",
+            "gauge",
+            Some("seconds"),
+            "Time since the tunnel entered its current state.",
+        )?;
+        writeln!(
+            out,
+            "mullvad_tunnel_state_duration_seconds {}",
+            self.time_in_state.as_secs_f64()
+        )?;
+
+        family(
+            out,
+            "mullvad_tunnel_reconnects",
+            "counter",
+            None,
+            "Number of times the tunnel has reconnected since the daemon started.",
+        )?;
+        writeln!(out, "mullvad_tunnel_reconnects_total {}", self.reconnects)?;
+
+        family(
+            out,
+            "mullvad_relay",
+            "info",
+            None,
+            "Relay that the tunnel is connecting or connected to.",
+        )?;
+        if let Some(relay) = &self.relay {
+            writeln!(
+                out,
+                "mullvad_relay_info{{hostname=\"{}\"}} 1",
+                LabelValue(relay)
+            )?;
+        }
+
+        family(
+            out,
+            "mullvad_api_access_method",
+            "info",
+            None,
+            "API access method in use.",
+        )?;
+        if let Some(access_method) = &self.access_method {
+            writeln!(
+                out,
+                "mullvad_api_access_method_info{{name=\"{}\"}} 1",
+                LabelValue(access_method)
+            )?;
+        }
+
+        family(
+            out,
+            "mullvad_relay_list_age_seconds",
+            "gauge",
+            Some("seconds"),
+            "Time since the relay list was last updated.",
+        )?;
+        writeln!(
+            out,
+            "mullvad_relay_list_age_seconds {}",
+            age(self.relay_list_updated)
+        )?;
+
+        family(
+            out,
+            "mullvad_wireguard_key_age_seconds",
+            "gauge",
+            Some("seconds"),
+            "Time since the WireGuard key was rotated.",
+        )?;
+        if let Some(key_created) = self.key_created {
+            writeln!(
+                out,
+                "mullvad_wireguard_key_age_seconds {}",
+                age(key_created)
+            )?;
+        }
+
+        for (name, help) in [
+            (
+                "mullvad_tunnel_transmit_bytes",
+                "Bytes sent through the tunnel.",
+            ),
+            (
+                "mullvad_tunnel_receive_bytes",
+                "Bytes received through the tunnel.",
+            ),
+        ] {
+            family(out, name, "counter", Some("bytes"), help)?;
+            let Some(stats) = &self.tunnel_stats else {
+                continue;
+            };
+            let hops = std::iter::once(("exit", &stats.exit))
+                .chain(stats.entry.as_ref().map(|entry| ("entry", entry)));
+            for (hop, peer) in hops {
+                let bytes = if name == "mullvad_tunnel_transmit_bytes" {
+                    peer.tx_bytes
+                } else {
+                    peer.rx_bytes
+                };
+                writeln!(out, "{name}_total{{hop=\"{hop}\"}} {bytes}")?;
+            }
+        }
+
+        writeln!(out, "# EOF")
+    }
+}
+
+fn family(
+    out: &mut String,
+    name: &str,
+    metric_type: &str,
+    unit: Option<&str>,
+    help: &str,
+) -> fmt::Result {
+    writeln!(out, "# TYPE {name} {metric_type}")?;
+    if let Some(unit) = unit {
+        writeln!(out, "# UNIT {name} {unit}")?;
+    }
+    writeln!(out, "# HELP {name} {help}")
+}
+
+/// Escapes a label value as required by the OpenMetrics text format.
+struct LabelValue<'a>(&'a str);
+
+impl fmt::Display for LabelValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps track of tunnel state transitions for [`Metrics`].
+#[derive(Debug)]
+pub struct TunnelStateTracker {
+    entered: Instant,
+    reconnects: u64,
+}
+
+impl Default for TunnelStateTracker {
+    fn default() -> Self {
+        TunnelStateTracker {
+            entered: Instant::now(),
+            reconnects: 0,
+        }
+    }
+}
+
+impl TunnelStateTracker {
+    /// Call when the tunnel moves from `old` to `new`.
+    pub fn on_transition(&mut self, old: &TunnelState, new: &TunnelState) {
+        self.entered = Instant::now();
+        if matches!(new, TunnelState::Connecting { .. }) && is_reconnect(old) {
+            self.reconnects += 1;
+        }
+    }
+
+    pub fn time_in_state(&self) -> Duration {
+        self.entered.elapsed()
+    }
+
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+}
+
+/// Whether connecting after `state` is a reconnect, as opposed to an initial connection attempt.
+fn is_reconnect(state: &TunnelState) -> bool {
+    match state {
+        TunnelState::Disconnected { .. } => false,
+        TunnelState::Disconnecting(after_disconnect) => {
+            matches!(after_disconnect, ActionAfterDisconnect::Reconnect)
+        }
+        TunnelState::Connecting { .. } | TunnelState::Connected { .. } | TunnelState::Error(_) => {
+            true
+        }
+    }
+}
+
+/// Name of the tunnel state, as exported in [`Metrics::tunnel_state`].
+pub fn tunnel_state_name(state: &TunnelState) -> &'static str {
+    match state {
+        TunnelState::Disconnected { .. } => "disconnected",
+        TunnelState::Connecting { .. } => "connecting",
+        TunnelState::Connected { .. } => "connected",
+        TunnelState::Disconnecting(_) => "disconnecting",
+        TunnelState::Error(_) => "error",
+    }
+}
+
+/// A running metrics endpoint. It is stopped when this is dropped.
+pub struct MetricsServer {
+    task: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// Serve metrics on the given port on the loopback interface. Metrics are requested from the
+    /// daemon using [`DaemonCommand::GetMetrics`].
+    pub async fn start(
+        port: u16,
+        daemon_tx: DaemonEventSender<DaemonCommand>,
+    ) -> Result<Self, Error> {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|error| Error(address, error))?;
+        log::info!("Serving metrics on http://{address}/metrics");
+
+        let task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(error) => {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to accept metrics connection")
+                        );
+                        continue;
+                    }
+                };
+                let daemon_tx = daemon_tx.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = handle_connection(stream, daemon_tx).await {
+                        log::debug!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to serve metrics request")
+                        );
+                    }
+                });
+            }
+        });
+
+        Ok(MetricsServer { task })
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    daemon_tx: DaemonEventSender<DaemonCommand>,
+) -> io::Result<()> {
+    let Ok(request) = tokio::time::timeout(REQUEST_TIMEOUT, read_request_head(&mut stream)).await
+    else {
+        return Ok(());
+    };
+    let response = match parse_request_line(&request?) {
+        Some(("GET", "/metrics")) => match get_metrics(&daemon_tx).await {
+            Some(metrics) => response("200 OK", CONTENT_TYPE, &metrics.encode(SystemTime::now())),
+            None => response(
+                "503 Service Unavailable",
+                "text/plain",
+                "Daemon unavailable\n",
+            ),
+        },
+        Some(("GET", _)) => response("404 Not Found", "text/plain", "Not found\n"),
+        Some(_) => response(
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n",
+        ),
+        None => response("400 Bad Request", "text/plain", "Bad request\n"),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read until the end of the request head. The request body, if any, is ignored.
+async fn read_request_head(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_SIZE {
+            return Err(io::Error::other("Request is too large"));
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    Ok(request)
+}
+
+/// Return the method and path of an HTTP request, ignoring any query string.
+fn parse_request_line(request: &[u8]) -> Option<(&str, &str)> {
+    let line = request.split(|&byte| byte == b'\r').next()?;
+    let mut parts = std::str::from_utf8(line).ok()?.split(' ');
+    let method = parts.next()?;
+    let target = parts.next()?;
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    let path = target.split('?').next()?;
+    Some((method, path))
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+async fn get_metrics(daemon_tx: &DaemonEventSender<DaemonCommand>) -> Option<Metrics> {
+    let (tx, rx) = oneshot::channel();
+    daemon_tx.send(DaemonCommand::GetMetrics(tx)).ok()?;
+    rx.await.ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::wireguard::{PeerStats, PrivateKey};
+
+    #[test]
+    fn test_parse_request_line() {
+        assert_eq!(
+            parse_request_line(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(
+            parse_request_line(b"GET /metrics?x=1 HTTP/1.0\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(parse_request_line(b"GET /metrics\r\n\r\n"), None);
+        assert_eq!(parse_request_line(b"\xff\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_encode() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let peer = PeerStats {
+            public_key: PrivateKey::new_from_random().public_key(),
+            tx_bytes: 100,
+            rx_bytes: 200,
+            last_handshake: None,
+            tx_rate: None,
+            rx_rate: None,
+        };
+        let metrics = Metrics {
+            tunnel_state: "connected",
+            time_in_state: Duration::from_millis(1500),
+            reconnects: 2,
+            relay: Some("se-got-wg-001".to_owned()),
+            access_method: Some("My \"proxy\"".to_owned()),
+            relay_list_updated: now - Duration::from_secs(60),
+            key_created: None,
+            tunnel_stats: Some(TunnelStats {
+                entry: None,
+                exit: peer,
+                sampled_at: now,
+                connectivity: None,
+            }),
+        };
+
+        let encoded = metrics.encode(now);
+        let lines: Vec<_> = encoded.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(
+            lines,
+            [
+                "mullvad_tunnel_state{mullvad_tunnel_state=\"disconnected\"} 0",
+                "mullvad_tunnel_state{mullvad_tunnel_state=\"connecting\"} 0",
+                "mullvad_tunnel_state{mullvad_tunnel_state=\"connected\"} 1",
+                "mullvad_tunnel_state{mullvad_tunnel_state=\"disconnecting\"} 0",
+                "mullvad_tunnel_state{mullvad_tunnel_state=\"error\"} 0",
+                "mullvad_tunnel_state_duration_seconds 1.5",
+                "mullvad_tunnel_reconnects_total 2",
+                "mullvad_relay_info{hostname=\"se-got-wg-001\"} 1",
+                "mullvad_api_access_method_info{name=\"My \\\"proxy\\\"\"} 1",
+                "mullvad_relay_list_age_seconds 60",
+                "mullvad_tunnel_transmit_bytes_total{hop=\"exit\"} 100",
+                "mullvad_tunnel_receive_bytes_total{hop=\"exit\"} 200",
+            ]
+        );
+        assert!(encoded.ends_with("# EOF\n"));
+    }
+}
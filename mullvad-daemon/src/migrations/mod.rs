@@ -47,6 +47,7 @@ mod account_history;
 mod device;
 mod v1;
 mod v10;
+mod v11;
 mod v2;
 mod v3;
 mod v4;
@@ -209,6 +210,7 @@ async fn migrate_settings(
     )?;
 
     v10::migrate(settings)?;
+    v11::migrate(settings)?;
 
     Ok(migration_data)
 }
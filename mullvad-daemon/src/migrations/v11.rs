@@ -0,0 +1,146 @@
+use super::{Error, Result};
+use mullvad_types::settings::SettingsVersion;
+
+// ======================================================
+// Section for vendoring types and values that
+// this settings version depend on. See `mod.rs`.
+
+/// The networks that were allowed when `allow_lan` was enabled, before they became configurable.
+const ALLOWED_LAN_NETS: [&str; 6] = [
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "169.254.0.0/16",
+    "fe80::/10",
+    "fc00::/7",
+];
+
+// ======================================================
+
+/// The networks reachable when `allow_lan` is enabled used to be hardcoded. They are now stored in
+/// `allowed_lan_networks`, so that users can remove networks or add their own. This migration
+/// adds the key, containing the networks that were previously allowed.
+///
+/// Custom DNS options also gained a list of DNS over HTTPS/TLS servers, `encrypted_servers`. It is
+/// added as an empty list.
+pub fn migrate(settings: &mut serde_json::Value) -> Result<()> {
+    if !version_matches(settings) {
+        return Ok(());
+    }
+
+    log::info!("Migrating settings format to V12");
+
+    let settings_map = settings
+        .as_object_mut()
+        .ok_or(Error::InvalidSettingsContent)?;
+    if !settings_map.contains_key("allowed_lan_networks") {
+        settings_map.insert(
+            "allowed_lan_networks".to_owned(),
+            serde_json::json!(ALLOWED_LAN_NETS),
+        );
+    }
+
+    if let Some(custom_options) = settings
+        .get_mut("tunnel_options")
+        .and_then(|options| options.get_mut("dns_options"))
+        .and_then(|options| options.get_mut("custom_options"))
+        .and_then(|options| options.as_object_mut())
+    {
+        custom_options
+            .entry("encrypted_servers")
+            .or_insert_with(|| serde_json::json!([]));
+    }
+
+    settings["settings_version"] = serde_json::json!(SettingsVersion::V12);
+
+    Ok(())
+}
+
+fn version_matches(settings: &serde_json::Value) -> bool {
+    settings
+        .get("settings_version")
+        .map(|version| version == SettingsVersion::V11 as u64)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{migrate, version_matches};
+    use serde_json::json;
+
+    #[test]
+    fn test_v11_to_v12_migration() {
+        let mut settings = json!({
+            "allow_lan": true,
+            "settings_version": 11,
+        });
+        assert!(version_matches(&settings));
+
+        migrate(&mut settings).unwrap();
+
+        let expected = json!({
+            "allow_lan": true,
+            "allowed_lan_networks": [
+                "10.0.0.0/8",
+                "172.16.0.0/12",
+                "192.168.0.0/16",
+                "169.254.0.0/16",
+                "fe80::/10",
+                "fc00::/7",
+            ],
+            "settings_version": 12,
+        });
+        assert_eq!(settings, expected);
+        assert!(!version_matches(&settings));
+    }
+
+    /// The networks in the settings are not touched if they already exist
+    #[test]
+    fn test_v11_to_v12_migration_existing_networks() {
+        let mut settings = json!({
+            "allowed_lan_networks": ["10.20.0.0/16"],
+            "settings_version": 11,
+        });
+
+        migrate(&mut settings).unwrap();
+
+        let expected = json!({
+            "allowed_lan_networks": ["10.20.0.0/16"],
+            "settings_version": 12,
+        });
+        assert_eq!(settings, expected);
+    }
+
+    #[test]
+    fn test_v11_to_v12_migration_encrypted_dns() {
+        let mut settings = json!({
+            "allowed_lan_networks": [],
+            "tunnel_options": {
+                "dns_options": {
+                    "state": "custom",
+                    "custom_options": {
+                        "addresses": ["1.1.1.1"],
+                    },
+                },
+            },
+            "settings_version": 11,
+        });
+
+        migrate(&mut settings).unwrap();
+
+        let expected = json!({
+            "allowed_lan_networks": [],
+            "tunnel_options": {
+                "dns_options": {
+                    "state": "custom",
+                    "custom_options": {
+                        "addresses": ["1.1.1.1"],
+                        "encrypted_servers": [],
+                    },
+                },
+            },
+            "settings_version": 12,
+        });
+        assert_eq!(settings, expected);
+    }
+}
@@ -0,0 +1,215 @@
+//! Tracks the network that the device is on and evaluates the user's network rules against it.
+//! See [`mullvad_types::network_rule`].
+
+use mullvad_types::network_rule::{self, CurrentNetwork, NetworkAction, NetworkRule};
+
+/// Keeps track of the current network and the action of the first network rule that matches it.
+#[derive(Debug, Default)]
+pub struct NetworkRuleEngine {
+    rules: Vec<NetworkRule>,
+    network: Option<CurrentNetwork>,
+    action: Option<NetworkAction>,
+}
+
+impl NetworkRuleEngine {
+    pub fn new(rules: Vec<NetworkRule>) -> Self {
+        NetworkRuleEngine {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    /// Action of the rule matching the current network, if any.
+    pub fn action(&self) -> Option<NetworkAction> {
+        self.action
+    }
+
+    /// Replace the rules. Returns whether this changed the current action.
+    pub fn set_rules(&mut self, rules: Vec<NetworkRule>) -> bool {
+        self.rules = rules;
+        let previous_action = self.action;
+        self.evaluate();
+        previous_action != self.action
+    }
+
+    /// Update the current network. `None` means that there is no default route, in which case
+    /// the previous network is kept so that the tunnel isn't toggled while the device is briefly
+    /// offline.
+    ///
+    /// Returns whether the current action should be (re)applied. This is the case if the action
+    /// changed, or if the device moved to a different network that some rule matches.
+    pub fn on_network_change(&mut self, network: Option<CurrentNetwork>) -> bool {
+        let Some(network) = network else {
+            return false;
+        };
+        if self.network.as_ref() == Some(&network) {
+            return false;
+        }
+        log::debug!(
+            "Current network: interface {}, gateway {}",
+            network.interface,
+            network
+                .gateway_mac
+                .map(|mac| mac.to_string())
+                .unwrap_or_else(|| "unknown".to_owned())
+        );
+        self.network = Some(network);
+
+        let previous_action = self.action;
+        self.evaluate();
+        self.action.is_some() || previous_action.is_some()
+    }
+
+    fn evaluate(&mut self) {
+        self.action = self
+            .network
+            .as_ref()
+            .and_then(|network| network_rule::evaluate(&self.rules, network));
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use monitor::spawn_monitor;
+
+#[cfg(target_os = "macos")]
+mod monitor {
+    use crate::{DaemonEventSender, InternalDaemonEvent};
+    use futures::StreamExt;
+    use mullvad_types::network_rule::{CurrentNetwork, MacAddress};
+    use talpid_core::mpsc::Sender;
+    use talpid_routing::RouteManagerHandle;
+    use talpid_types::ErrorExt;
+
+    /// Send [`InternalDaemonEvent::NetworkChanged`] with the current network on startup and
+    /// whenever the non-tunnel default route changes.
+    pub fn spawn_monitor(route_manager: RouteManagerHandle, event_tx: DaemonEventSender) {
+        tokio::spawn(async move {
+            let mut events = match route_manager.default_route_listener().await {
+                Ok(events) => Box::pin(events),
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to listen for default route changes")
+                    );
+                    return;
+                }
+            };
+
+            loop {
+                let network = current_network(&route_manager).await;
+                if event_tx
+                    .send(InternalDaemonEvent::NetworkChanged(network))
+                    .is_err()
+                {
+                    return;
+                }
+                if events.next().await.is_none() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Return the network of the non-tunnel default route, preferring IPv4 over IPv6.
+    async fn current_network(route_manager: &RouteManagerHandle) -> Option<CurrentNetwork> {
+        let (v4_route, v6_route) = route_manager
+            .get_default_routes()
+            .await
+            .inspect_err(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to obtain default routes")
+                );
+            })
+            .ok()?;
+        let (v4_gateway, v6_gateway) = route_manager
+            .get_default_gateway()
+            .await
+            .unwrap_or_default();
+
+        let (route, gateway) = match v4_route {
+            Some(route) => (route, v4_gateway),
+            None => (v6_route?, v6_gateway),
+        };
+        Some(CurrentNetwork {
+            interface: route.interface,
+            gateway_mac: gateway.map(|gateway| MacAddress(gateway.mac_address.into_bytes())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::network_rule::{MacAddress, NetworkMatch};
+
+    const HOME_GATEWAY: MacAddress = MacAddress([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+
+    fn rules() -> Vec<NetworkRule> {
+        vec![
+            NetworkRule {
+                network: NetworkMatch::GatewayMac(HOME_GATEWAY),
+                action: NetworkAction::Disconnect,
+            },
+            NetworkRule {
+                network: NetworkMatch::Any,
+                action: NetworkAction::Connect,
+            },
+        ]
+    }
+
+    fn network(interface: &str, gateway_mac: Option<MacAddress>) -> Option<CurrentNetwork> {
+        Some(CurrentNetwork {
+            interface: interface.to_owned(),
+            gateway_mac,
+        })
+    }
+
+    #[test]
+    fn test_network_change() {
+        let mut engine = NetworkRuleEngine::new(rules());
+        assert_eq!(engine.action(), None);
+
+        assert!(engine.on_network_change(network("en0", Some(HOME_GATEWAY))));
+        assert_eq!(engine.action(), Some(NetworkAction::Disconnect));
+
+        // Same network again
+        assert!(!engine.on_network_change(network("en0", Some(HOME_GATEWAY))));
+
+        // Going offline keeps the current state
+        assert!(!engine.on_network_change(None));
+        assert_eq!(engine.action(), Some(NetworkAction::Disconnect));
+
+        assert!(engine.on_network_change(network("en0", None)));
+        assert_eq!(engine.action(), Some(NetworkAction::Connect));
+
+        // A different network with the same action is reapplied
+        assert!(engine.on_network_change(network("en1", None)));
+        assert_eq!(engine.action(), Some(NetworkAction::Connect));
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let mut engine = NetworkRuleEngine::default();
+        assert!(!engine.on_network_change(network("en0", Some(HOME_GATEWAY))));
+        assert!(!engine.on_network_change(network("en1", None)));
+        assert_eq!(engine.action(), None);
+    }
+
+    #[test]
+    fn test_set_rules() {
+        let mut engine = NetworkRuleEngine::default();
+        engine.on_network_change(network("en0", Some(HOME_GATEWAY)));
+
+        assert!(engine.set_rules(rules()));
+        assert_eq!(engine.action(), Some(NetworkAction::Disconnect));
+        assert!(!engine.set_rules(rules()));
+
+        let block_unknown = vec![NetworkRule {
+            network: NetworkMatch::Interface("en1".to_owned()),
+            action: NetworkAction::Block,
+        }];
+        assert!(engine.set_rules(block_unknown));
+        assert_eq!(engine.action(), None);
+    }
+}
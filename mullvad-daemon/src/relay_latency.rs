@@ -0,0 +1,117 @@
+//! Measures the latency to relays so that the relay selector can prefer nearby relays when
+//! [`RelaySelectionMode::LowestLatency`] is used.
+//!
+//! [`RelaySelectionMode::LowestLatency`]: mullvad_types::relay_constraints::RelaySelectionMode::LowestLatency
+
+use futures::{StreamExt, channel::mpsc};
+use mullvad_relay_selector::RelaySelector;
+use mullvad_types::{relay_list::Relay, states::TunnelState};
+use std::{
+    net::{SocketAddr, TcpStream},
+    time::{Duration, Instant},
+};
+
+/// The port to connect to when measuring the latency of a relay. Both OpenVPN and WireGuard
+/// (UDP-over-TCP) relays accept TCP connections on this port.
+const PROBE_PORT: u16 = 443;
+
+/// How long to wait for a relay to accept a connection before giving up.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct LatencyMonitorHandle {
+    tx: mpsc::UnboundedSender<()>,
+}
+
+impl LatencyMonitorHandle {
+    /// Measure the latency of all relays that the relay selector wants measurements for.
+    ///
+    /// Nothing is measured unless `tunnel_state` is disconnected and not locked down, since the
+    /// measurements would otherwise be affected by the tunnel or blocked by the firewall.
+    pub fn measure(&self, tunnel_state: &TunnelState) {
+        if !can_measure(tunnel_state) {
+            log::trace!("Not measuring relay latencies in the current tunnel state");
+            return;
+        }
+        if self.tx.unbounded_send(()).is_err() {
+            log::error!("Relay latency monitor has stopped unexpectedly");
+        }
+    }
+}
+
+pub struct LatencyMonitor {
+    relay_selector: RelaySelector,
+    rx: mpsc::UnboundedReceiver<()>,
+}
+
+impl LatencyMonitor {
+    pub fn spawn(relay_selector: RelaySelector) -> LatencyMonitorHandle {
+        let (tx, rx) = mpsc::unbounded();
+        let monitor = LatencyMonitor { relay_selector, rx };
+        tokio::spawn(monitor.run());
+        LatencyMonitorHandle { tx }
+    }
+
+    async fn run(mut self) {
+        while self.rx.next().await.is_some() {
+            // Coalesce requests that arrived while the previous measurement was running
+            while let Ok(Some(())) = self.rx.try_next() {}
+
+            let relay_selector = self.relay_selector.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                relay_selector.measure_latencies(&probe_tcp_connect)
+            })
+            .await;
+            if let Err(error) = result {
+                log::error!("Relay latency measurement panicked: {error}");
+            }
+        }
+    }
+}
+
+/// Whether probes can reach the relays outside of a tunnel in `tunnel_state`.
+fn can_measure(tunnel_state: &TunnelState) -> bool {
+    match tunnel_state {
+        #[cfg(not(target_os = "android"))]
+        TunnelState::Disconnected { locked_down, .. } => !locked_down,
+        #[cfg(target_os = "android")]
+        TunnelState::Disconnected { .. } => true,
+        _ => false,
+    }
+}
+
+/// Measure the time it takes to establish a TCP connection to `relay`.
+fn probe_tcp_connect(relay: &Relay) -> Option<Duration> {
+    let addr = SocketAddr::from((relay.ipv4_addr_in, PROBE_PORT));
+    let start = Instant::now();
+    match TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) {
+        Ok(_) => Some(start.elapsed()),
+        Err(error) => {
+            log::trace!("Failed to connect to {}: {error}", relay.hostname);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::tunnel::ActionAfterDisconnect;
+
+    #[test]
+    fn test_can_measure() {
+        assert!(can_measure(&TunnelState::Disconnected {
+            location: None,
+            #[cfg(not(target_os = "android"))]
+            locked_down: false,
+        }));
+        #[cfg(not(target_os = "android"))]
+        assert!(!can_measure(&TunnelState::Disconnected {
+            location: None,
+            locked_down: true,
+        }));
+        assert!(!can_measure(&TunnelState::Disconnecting(
+            ActionAfterDisconnect::Nothing
+        )));
+    }
+}
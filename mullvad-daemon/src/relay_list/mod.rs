@@ -6,6 +6,7 @@ use futures::{
     future::{Fuse, FusedFuture},
 };
 use std::{
+    collections::HashSet,
     path::{Path, PathBuf},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
@@ -13,7 +14,11 @@ use tokio::fs::File;
 
 use mullvad_api::{RelayListProxy, availability::ApiAvailability, rest::MullvadRestHandle};
 use mullvad_relay_selector::RelaySelector;
-use mullvad_types::relay_list::RelayList;
+use mullvad_types::{
+    relay_constraints::{GeographicLocationConstraint, RelaySettings},
+    relay_list::{RelayList, RelayListDiff},
+    settings::Settings,
+};
 use talpid_future::retry::{ExponentialBackoff, Jittered, retry_future};
 use talpid_types::ErrorExt;
 
@@ -41,6 +46,42 @@ pub enum Error {
     RelaySelector(#[from] mullvad_relay_selector::Error),
 }
 
+/// Log a warning for each relay that is no longer available, but that is selected in the relay or
+/// bridge constraints, or that is part of a custom list.
+pub fn warn_about_unavailable_relays(diff: &RelayListDiff, settings: &Settings) {
+    let unavailable: HashSet<&str> = diff.unavailable().collect();
+    if unavailable.is_empty() {
+        return;
+    }
+
+    let mut locations = vec![&settings.bridge_settings.normal.location];
+    if let RelaySettings::Normal(constraints) = &settings.relay_settings {
+        locations.push(&constraints.location);
+        if constraints.wireguard_constraints.multihop() {
+            locations.push(&constraints.wireguard_constraints.entry_location);
+        }
+    }
+    let selected_hostnames = locations
+        .into_iter()
+        .filter_map(|location| location.as_ref().option()?.get_hostname());
+    for hostname in selected_hostnames.filter(|hostname| unavailable.contains(hostname.as_str())) {
+        log::warn!("The selected relay {hostname} is no longer available");
+    }
+
+    for custom_list in settings.custom_lists.iter() {
+        let hostnames = custom_list
+            .locations
+            .iter()
+            .filter_map(GeographicLocationConstraint::get_hostname);
+        for hostname in hostnames.filter(|hostname| unavailable.contains(hostname.as_str())) {
+            log::warn!(
+                "The relay {hostname} in the custom list \"{}\" is no longer available",
+                custom_list.name
+            );
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct RelayListUpdaterHandle {
     tx: mpsc::Sender<()>,
@@ -66,7 +107,7 @@ pub struct RelayListUpdater {
     api_client: RelayListProxy,
     cache_path: PathBuf,
     relay_selector: RelaySelector,
-    on_update: Box<dyn Fn(&RelayList) + Send + 'static>,
+    on_update: Box<dyn Fn(&RelayList, RelayListDiff) + Send + 'static>,
     last_check: SystemTime,
     api_availability: ApiAvailability,
 }
@@ -76,7 +117,7 @@ impl RelayListUpdater {
         selector: RelaySelector,
         api_handle: MullvadRestHandle,
         cache_dir: &Path,
-        on_update: impl Fn(&RelayList) + Send + 'static,
+        on_update: impl Fn(&RelayList, RelayListDiff) + Send + 'static,
     ) -> RelayListUpdaterHandle {
         let (tx, cmd_rx) = mpsc::channel(1);
         let api_availability = api_handle.availability.clone();
@@ -196,8 +237,16 @@ impl RelayListUpdater {
             );
         }
 
+        // Changes from an empty list, e.g. if no relay list has been bundled, are not interesting
+        let old_relay_list = self.relay_selector.get_relays();
+        let diff = if old_relay_list.relays().next().is_some() {
+            old_relay_list.diff(&new_relay_list)
+        } else {
+            RelayListDiff::default()
+        };
+
         self.relay_selector.set_relays(new_relay_list.clone());
-        (self.on_update)(&new_relay_list);
+        (self.on_update)(&new_relay_list, diff);
         Ok(())
     }
 
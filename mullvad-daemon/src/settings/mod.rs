@@ -1,6 +1,7 @@
 use futures::TryFutureExt;
 use mullvad_types::{
     custom_list::Error as CustomListError,
+    management_access::ManagementAccessSettings,
     relay_constraints::{RelayConstraints, RelaySettings, WireguardConstraints},
     settings::{DnsState, Settings},
 };
@@ -17,6 +18,7 @@ use tokio::{
 };
 
 pub mod patch;
+pub mod profiles;
 
 const SETTINGS_FILE: &str = "settings.json";
 
@@ -177,6 +179,9 @@ impl SettingsPersister {
                     // has no effect.
                     #[cfg(not(target_os = "android"))]
                     block_when_disconnected: true,
+                    // Likewise, do not grant everyone full access to the daemon when the
+                    // configured roles are unknown.
+                    management_access: ManagementAccessSettings::restricted(),
                     ..Self::default_settings()
                 };
 
@@ -238,9 +243,13 @@ impl SettingsPersister {
         Ok(())
     }
 
-    /// Resets default settings
+    /// Resets default settings. The management access roles are kept, since clients must not be
+    /// able to lift their own restrictions.
     pub async fn reset(&mut self) -> Result<(), Error> {
-        self.settings = Self::default_settings();
+        self.settings = Settings {
+            management_access: self.settings.management_access.clone(),
+            ..Self::default_settings()
+        };
         let path = self.path.clone();
         self.save()
             .or_else(|e| async move {
@@ -504,6 +513,16 @@ impl Display for SettingsSummary<'_> {
                     (false, false) => f.write_str("custom, no addrs")?,
                     (false, true) => f.write_str("custom, local")?,
                 }
+                if !self
+                    .settings
+                    .tunnel_options
+                    .dns_options
+                    .custom_options
+                    .encrypted_servers
+                    .is_empty()
+                {
+                    f.write_str(", encrypted")?;
+                }
             }
         }
         Ok(())
@@ -699,6 +718,12 @@ mod test {
             settings.block_when_disconnected,
             "The daemon should block the internet if settings are corrupt"
         );
+
+        assert_eq!(
+            settings.management_access,
+            ManagementAccessSettings::restricted(),
+            "Only root should have full access to the daemon if settings are corrupt"
+        );
     }
 
     #[tokio::test]
@@ -12,11 +12,14 @@
 //!
 //! Permitted settings and merge strategies are defined in the [PERMITTED_SUBKEYS] constant.
 //!
+//! Settings profiles (see [super::profiles]) are validated and merged the same way, but permit the
+//! larger set of settings in [PROFILE_SUBKEYS].
+//!
 //! This implementation must be kept in sync with the
 //! [spec](../../../docs/settings-patch-format.md).
 
 use super::SettingsPersister;
-use mullvad_types::settings::Settings;
+use mullvad_types::settings::{Settings, profile::SettingDiff};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -127,6 +130,38 @@ const PERMITTED_SUBKEYS: &PermittedKey = &PermittedKey::object(&[(
     ]))
     .merge_strategy(MergeStrategy::Custom(merge_relay_overrides)),
 )]);
+
+/// Settings that can be saved in a profile. Profiles are only ever created from the user's own
+/// settings, so unlike patches, they may contain connection settings such as custom DNS. Settings
+/// tied to the account or device, or holding other state, are still prohibited.
+const PROFILE_SUBKEYS: &PermittedKey = &PermittedKey::object(&[
+    ("relay_settings", PermittedKey::any()),
+    ("bridge_settings", PermittedKey::any()),
+    ("obfuscation_settings", PermittedKey::any()),
+    ("retry_order", PermittedKey::any()),
+    ("bridge_state", PermittedKey::any()),
+    ("allow_lan", PermittedKey::any()),
+    ("allowed_lan_networks", PermittedKey::any()),
+    ("block_when_disconnected", PermittedKey::any()),
+    ("auto_connect", PermittedKey::any()),
+    (
+        "tunnel_options",
+        PermittedKey::object(&[
+            ("openvpn", PermittedKey::any()),
+            (
+                "wireguard",
+                PermittedKey::object(&[
+                    ("mtu", PermittedKey::any()),
+                    ("quantum_resistant", PermittedKey::any()),
+                    ("daita", PermittedKey::any()),
+                ]),
+            ),
+            ("generic", PermittedKey::any()),
+            ("dns_options", PermittedKey::any()),
+        ]),
+    ),
+]);
+
 /// Prohibit stack overflow via excessive recursion. It might be possible to forgo this when
 /// tail-call optimization can be enforced?
 const RECURSE_LIMIT: usize = 15;
@@ -182,13 +217,21 @@ pub async fn merge_validate_patch(
 }
 
 fn merge_validate_patch_inner(settings: &Settings, json_patch: &str) -> Result<Settings, Error> {
-    let mut settings_value: serde_json::Value =
-        serde_json::to_value(settings).map_err(Error::SerializeSettings)?;
     let patch_value: serde_json::Value =
         serde_json::from_str(json_patch).map_err(Error::ParsePatch)?;
+    merge_validate_value(PERMITTED_SUBKEYS, settings, &patch_value)
+}
 
-    validate_patch_value(PERMITTED_SUBKEYS, &patch_value, 0)?;
-    merge_patch_to_value(PERMITTED_SUBKEYS, &mut settings_value, &patch_value, 0)?;
+fn merge_validate_value(
+    permitted_key: &'static PermittedKey,
+    settings: &Settings,
+    patch_value: &serde_json::Value,
+) -> Result<Settings, Error> {
+    let mut settings_value: serde_json::Value =
+        serde_json::to_value(settings).map_err(Error::SerializeSettings)?;
+
+    validate_patch_value(permitted_key, patch_value, 0)?;
+    merge_patch_to_value(permitted_key, &mut settings_value, patch_value, 0)?;
 
     let new_settings: Settings =
         serde_json::from_value(settings_value).map_err(Error::DeserializePatched)?;
@@ -196,6 +239,86 @@ fn merge_validate_patch_inner(settings: &Settings, json_patch: &str) -> Result<S
     Ok(new_settings)
 }
 
+/// Export the settings that can be saved in a profile.
+pub fn export_profile(settings: &Settings) -> Result<serde_json::Value, Error> {
+    let settings_value = serde_json::to_value(settings).map_err(Error::SerializeSettings)?;
+    Ok(select_permitted(PROFILE_SUBKEYS, &settings_value))
+}
+
+/// Return the settings that result from applying `profile`, as exported by [export_profile], to
+/// `settings`. Nothing is returned unless the entire profile is valid.
+pub fn merge_validate_profile(
+    settings: &Settings,
+    profile: &serde_json::Value,
+) -> Result<Settings, Error> {
+    merge_validate_value(PROFILE_SUBKEYS, settings, profile)
+}
+
+/// List the settings in `profile` whose values differ from those in `settings`.
+pub fn diff_profile(
+    settings: &Settings,
+    profile: &serde_json::Value,
+) -> Result<Vec<SettingDiff>, Error> {
+    validate_patch_value(PROFILE_SUBKEYS, profile, 0)?;
+    let current = export_profile(settings)?;
+    let mut diffs = vec![];
+    diff_values(&current, profile, "", &mut diffs, 0)?;
+    Ok(diffs)
+}
+
+/// Return the parts of `value` that are permitted by `permitted_key`. Only objects are filtered,
+/// anything else is kept as it is.
+fn select_permitted(
+    permitted_key: &'static PermittedKey,
+    value: &serde_json::Value,
+) -> serde_json::Value {
+    match (&permitted_key.key_type, value) {
+        (PermittedKeyValue::Object(subkeys), serde_json::Value::Object(map)) => subkeys
+            .iter()
+            .filter_map(|(key, subkey)| {
+                let sub_value = map.get(*key)?;
+                Some(((*key).to_owned(), select_permitted(subkey, sub_value)))
+            })
+            .collect(),
+        _ => value.clone(),
+    }
+}
+
+/// Add the values in `new` that differ from `current` to `diffs`. Objects are compared key by
+/// key, but keys that are missing from `new` are ignored since applying it would not change them.
+fn diff_values(
+    current: &serde_json::Value,
+    new: &serde_json::Value,
+    path: &str,
+    diffs: &mut Vec<SettingDiff>,
+    recurse_level: usize,
+) -> Result<(), Error> {
+    if recurse_level >= RECURSE_LIMIT {
+        return Err(Error::RecursionLimit);
+    }
+
+    match (current, new) {
+        (serde_json::Value::Object(current), serde_json::Value::Object(new)) => {
+            for (key, new_value) in new {
+                let current_value = current.get(key).unwrap_or(&serde_json::Value::Null);
+                let path = if path.is_empty() {
+                    key.to_owned()
+                } else {
+                    format!("{path}.{key}")
+                };
+                diff_values(current_value, new_value, &path, diffs, recurse_level + 1)?;
+            }
+        }
+        (current, new) if current != new => diffs.push(SettingDiff {
+            key: path.to_owned(),
+            current: serde_json::to_string(current).map_err(Error::SerializeValue)?,
+            profile: serde_json::to_string(new).map_err(Error::SerializeValue)?,
+        }),
+        _ => (),
+    }
+    Ok(())
+}
+
 /// Replace overrides for existing values in the array if there's a matching hostname. For hostnames
 /// that do not exist, just append the overrides.
 fn merge_relay_overrides(
@@ -555,3 +678,76 @@ fn test_patch_relay_override() {
 
     assert_eq!(current, expected);
 }
+
+#[test]
+fn test_profile_export() {
+    let mut settings = Settings::default();
+    settings.allow_lan = true;
+    settings
+        .relay_overrides
+        .push(mullvad_types::relay_constraints::RelayOverride::empty(
+            "test".to_owned(),
+        ));
+
+    let exported = export_profile(&settings).expect("profile export failed");
+    let exported = exported.as_object().unwrap();
+
+    assert_eq!(exported["allow_lan"], serde_json::Value::Bool(true));
+    assert!(exported["tunnel_options"].get("dns_options").is_some());
+    // Settings that cannot be saved in a profile are left out
+    assert!(!exported.contains_key("relay_overrides"));
+    assert!(!exported.contains_key("api_access_methods"));
+    assert!(
+        exported["tunnel_options"]["wireguard"]
+            .get("rotation_interval")
+            .is_none()
+    );
+}
+
+#[test]
+fn test_profile_apply() {
+    let mut profile_settings = Settings::default();
+    profile_settings.allow_lan = true;
+    profile_settings.auto_connect = true;
+    let profile = export_profile(&profile_settings).unwrap();
+
+    let new_settings = merge_validate_profile(&Settings::default(), &profile).unwrap();
+    assert!(new_settings.allow_lan);
+    assert!(new_settings.auto_connect);
+
+    // Prohibited settings are rejected
+    let profile: serde_json::Value =
+        serde_json::from_str(r#"{ "allow_lan": true, "api_access_methods": {} }"#).unwrap();
+    assert!(matches!(
+        merge_validate_profile(&Settings::default(), &profile),
+        Err(Error::UnknownOrProhibitedKey(key)) if key == "api_access_methods"
+    ));
+}
+
+#[test]
+fn test_profile_diff() {
+    let settings = Settings::default();
+    let profile: serde_json::Value = serde_json::from_str(
+        r#"{ "allow_lan": true, "auto_connect": false, "tunnel_options": { "wireguard": { "mtu": 1280 } } }"#,
+    )
+    .unwrap();
+
+    let mut diffs = diff_profile(&settings, &profile).unwrap();
+    diffs.sort_by(|a, b| a.key.cmp(&b.key));
+
+    assert_eq!(
+        diffs,
+        [
+            SettingDiff {
+                key: "allow_lan".to_owned(),
+                current: "false".to_owned(),
+                profile: "true".to_owned(),
+            },
+            SettingDiff {
+                key: "tunnel_options.wireguard.mtu".to_owned(),
+                current: "null".to_owned(),
+                profile: "1280".to_owned(),
+            },
+        ]
+    );
+}
@@ -0,0 +1,172 @@
+//! Named settings profiles, which save a subset of the current settings so that they can be
+//! applied again later, all at once. Profiles are stored in the settings patch format, and are
+//! validated and merged using the same code as patches. See [super::patch].
+
+use mullvad_types::settings::profile::SettingsProfile;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+const PROFILES_FILE: &str = "settings-profiles.json";
+
+/// Maximum length of a profile name, in bytes.
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid profile name")]
+    InvalidName,
+
+    #[error("No profile named \"{0}\"")]
+    NotFound(String),
+
+    #[error("Unable to serialize settings profiles")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Unable to write settings profiles to {0}")]
+    Write(String, #[source] io::Error),
+
+    #[error("Unable to remove settings profiles file {0}")]
+    Delete(String, #[source] io::Error),
+
+    #[error("Invalid settings profile")]
+    Patch(#[source] super::patch::Error),
+
+    #[error("Settings error")]
+    Settings(#[source] super::Error),
+}
+
+/// Converts an [Error] to a management interface status
+impl From<Error> for mullvad_management_interface::Status {
+    fn from(error: Error) -> mullvad_management_interface::Status {
+        use mullvad_management_interface::Status;
+
+        match error {
+            Error::InvalidName => Status::invalid_argument(error.to_string()),
+            Error::NotFound(_) => Status::not_found(error.to_string()),
+            Error::Patch(error) => Status::from(error),
+            Error::Settings(error) => Status::from(error),
+            Error::Write(..) | Error::Delete(..) => Status::failed_precondition(error.to_string()),
+            Error::Serialize(_) => Status::internal(error.to_string()),
+        }
+    }
+}
+
+pub struct Profiles {
+    path: PathBuf,
+    profiles: BTreeMap<String, serde_json::Value>,
+}
+
+impl Profiles {
+    /// Load the profiles from `settings_dir`. If they cannot be read, start out without any
+    /// profiles.
+    pub async fn load(settings_dir: &Path) -> Self {
+        let path = settings_dir.join(PROFILES_FILE);
+        let profiles = match fs::read(&path).await {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse settings profiles")
+                );
+                BTreeMap::new()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read settings profiles")
+                );
+                BTreeMap::new()
+            }
+        };
+        Profiles { path, profiles }
+    }
+
+    /// Return all profiles, ordered by name.
+    pub fn list(&self) -> Vec<SettingsProfile> {
+        self.profiles
+            .iter()
+            .map(|(name, profile)| SettingsProfile {
+                name: name.to_owned(),
+                patch: serde_json::to_string_pretty(profile).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&serde_json::Value, Error> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| Error::NotFound(name.to_owned()))
+    }
+
+    /// Save `profile` as `name`, replacing any profile with the same name.
+    pub async fn save(&mut self, name: String, profile: serde_json::Value) -> Result<(), Error> {
+        if !is_valid_name(&name) {
+            return Err(Error::InvalidName);
+        }
+        let mut profiles = self.profiles.clone();
+        profiles.insert(name, profile);
+        self.write(&profiles).await?;
+        self.profiles = profiles;
+        Ok(())
+    }
+
+    pub async fn delete(&mut self, name: &str) -> Result<(), Error> {
+        let mut profiles = self.profiles.clone();
+        if profiles.remove(name).is_none() {
+            return Err(Error::NotFound(name.to_owned()));
+        }
+        self.write(&profiles).await?;
+        self.profiles = profiles;
+        Ok(())
+    }
+
+    /// Remove all profiles, including the file they are stored in.
+    pub async fn clear(&mut self) -> Result<(), Error> {
+        self.profiles.clear();
+        match fs::remove_file(&self.path).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                Err(Error::Delete(self.path.display().to_string(), error))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn write(&self, profiles: &BTreeMap<String, serde_json::Value>) -> Result<(), Error> {
+        let content = serde_json::to_string_pretty(profiles).map_err(Error::Serialize)?;
+        let write_error = |error| Error::Write(self.path.display().to_string(), error);
+
+        let mut file = mullvad_fs::AtomicFile::new(&self.path)
+            .await
+            .map_err(write_error)?;
+        file.write_all(content.as_bytes())
+            .await
+            .map_err(write_error)?;
+        file.finalize().await.map_err(write_error)
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.trim().is_empty() && name.len() <= MAX_NAME_LEN && !name.chars().any(char::is_control)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        assert!(is_valid_name("office"));
+        assert!(is_valid_name("Travel (DAITA)"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("   "));
+        assert!(!is_valid_name("line\nbreak"));
+        assert!(!is_valid_name(&"a".repeat(MAX_NAME_LEN + 1)));
+    }
+}
@@ -44,6 +44,7 @@ struct InnerParametersGenerator {
     account_manager: AccountManagerHandle,
 
     last_generated_relays: Option<LastSelectedRelays>,
+    last_wireguard_config: Option<wireguard::ConnectionConfig>,
 }
 
 impl ParametersGenerator {
@@ -60,6 +61,7 @@ impl ParametersGenerator {
             account_manager,
 
             last_generated_relays: None,
+            last_wireguard_config: None,
         })))
     }
 
@@ -84,6 +86,12 @@ impl ParametersGenerator {
         }
     }
 
+    /// Gets the connection config of the last generated tunnel parameters, if they were for a
+    /// WireGuard relay.
+    pub async fn get_last_wireguard_config(&self) -> Option<wireguard::ConnectionConfig> {
+        self.0.lock().await.last_wireguard_config.clone()
+    }
+
     /// Gets the location associated with the last generated tunnel parameters.
     pub async fn get_last_location(&self) -> Option<GeoIpLocation> {
         let inner = self.0.lock().await;
@@ -147,6 +155,7 @@ impl InnerParametersGenerator {
         let selected_relay = self
             .relay_selector
             .get_relay(retry_attempt as usize, ip_availability)?;
+        self.last_wireguard_config = None;
 
         match selected_relay {
             #[cfg(not(target_os = "android"))]
@@ -196,7 +205,10 @@ impl InnerParametersGenerator {
                     server_override,
                 });
 
-                Ok(self.create_wireguard_tunnel_parameters(endpoint, data, obfuscator_config))
+                let parameters =
+                    self.create_wireguard_tunnel_parameters(endpoint, data, obfuscator_config);
+                self.last_wireguard_config = Some(parameters.connection.clone());
+                Ok(parameters.into())
             }
             GetRelay::Custom(custom_relay) => {
                 self.last_generated_relays = None;
@@ -234,7 +246,7 @@ impl InnerParametersGenerator {
         endpoint: MullvadWireguardEndpoint,
         data: PrivateAccountAndDevice,
         obfuscator_config: Option<ObfuscatorConfig>,
-    ) -> TunnelParameters {
+    ) -> wireguard::TunnelParameters {
         let tunnel_ipv4 = data.device.wg_data.addresses.ipv4_address.ip();
         let tunnel_ipv6 = data.device.wg_data.addresses.ipv6_address.ip();
         let tunnel = wireguard::TunnelConfig {
@@ -260,7 +272,6 @@ impl InnerParametersGenerator {
             generic_options: self.tunnel_options.generic.clone(),
             obfuscation: obfuscator_config,
         }
-        .into()
     }
 
     async fn device(&self) -> Result<PrivateAccountAndDevice, Error> {
@@ -4,7 +4,6 @@ use mullvad_types::version::{AppUpgradeDownloadProgress, AppUpgradeError, AppUpg
 use mullvad_update::app::{
     AppDownloader, AppDownloaderParameters, DownloadError, DownloadedInstaller, bin_path,
 };
-use rand::seq::SliceRandom;
 use std::io;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
@@ -29,7 +28,7 @@ pub enum Error {
     #[error("Download was cancelled or panicked")]
     JoinError(#[from] tokio::task::JoinError),
 
-    #[error("Could not select URL for app update")]
+    #[error("No URLs found for app update")]
     NoUrlFound,
 }
 
@@ -90,11 +89,15 @@ where
     D: AppDownloader + Send + 'static,
     D: From<AppDownloaderParameters<ProgressUpdater>>,
 {
-    let url = select_cdn_url(&version.urls)
-        .ok_or(Error::NoUrlFound)?
-        .to_owned();
+    if version.urls.is_empty() {
+        return Err(Error::NoUrlFound);
+    }
 
-    log::info!("Downloading app version '{}' from {url}", version.version);
+    log::info!(
+        "Downloading app version '{}' from one of {}",
+        version.version,
+        version.urls.join(", ")
+    );
 
     let download_dir = if cfg!(test) {
         PathBuf::new()
@@ -108,16 +111,25 @@ where
 
     let params = AppDownloaderParameters {
         app_version: version.version,
-        app_url: url.clone(),
+        app_urls: version.urls,
         app_size: version.size,
-        app_progress: ProgressUpdater::new(server_from_url(&url), event_tx.clone()),
+        app_progress: ProgressUpdater::new(event_tx.clone()),
         app_sha256: version.sha256,
         cache_dir: download_dir,
     };
     let downloader = D::from(params);
 
     let _ = event_tx.send(AppUpgradeEvent::DownloadStarting);
-    let installer = downloader.download_executable().await.inspect_err(|_| {
+    let installer = downloader.download_executable().await.inspect_err(|err| {
+        if let DownloadError::FetchApp { failures, .. } = err {
+            for failure in failures {
+                log::error!(
+                    "Failed to download from {}: {:#}",
+                    failure.url,
+                    failure.error
+                );
+            }
+        }
         let _ = event_tx.send(AppUpgradeEvent::Error(AppUpgradeError::DownloadFailed));
     })?;
     let _ = event_tx.send(AppUpgradeEvent::VerifyingInstaller);
@@ -153,6 +165,7 @@ pub async fn clear_download_dir() -> Result<PathBuf> {
 }
 
 pub struct ProgressUpdater {
+    /// Server that is currently being downloaded from
     server: String,
     event_tx: broadcast::Sender<AppUpgradeEvent>,
     complete_frac: f32,
@@ -161,9 +174,9 @@ pub struct ProgressUpdater {
 }
 
 impl ProgressUpdater {
-    fn new(server: String, event_tx: broadcast::Sender<AppUpgradeEvent>) -> Self {
+    fn new(event_tx: broadcast::Sender<AppUpgradeEvent>) -> Self {
         Self {
-            server,
+            server: String::new(),
             event_tx,
             complete_frac: 0.,
             start_time: Instant::now(),
@@ -173,8 +186,9 @@ impl ProgressUpdater {
 }
 
 impl mullvad_update::fetch::ProgressUpdater for ProgressUpdater {
-    fn set_url(&mut self, _url: &str) {
-        // ignored since we already know the URL
+    fn set_url(&mut self, url: &str) {
+        // Called whenever the download moves to another mirror
+        self.server = server_from_url(url);
     }
 
     fn set_progress(&mut self, fraction_complete: f32) {
@@ -227,12 +241,6 @@ fn estimate_time_left(
     Some(elapsed.mul_f32(remaining_frac / completed_frac_since_start))
 }
 
-/// Select a mirror to download from
-/// Currently, the selection is random
-fn select_cdn_url(urls: &[String]) -> Option<&str> {
-    urls.choose(&mut rand::thread_rng()).map(String::as_str)
-}
-
 /// Extract domain name from a URL
 fn server_from_url(url: &str) -> String {
     let url = url.strip_prefix("https://").unwrap_or(url);
@@ -648,7 +648,10 @@ mod test {
         async fn download_executable(
             self,
         ) -> std::result::Result<impl DownloadedInstaller, DownloadError> {
-            Err::<Self, _>(DownloadError::FetchApp(anyhow::anyhow!("Download failed")))
+            Err::<Self, _>(DownloadError::FetchApp {
+                error: anyhow::anyhow!("Download failed"),
+                failures: vec![],
+            })
         }
     }
 
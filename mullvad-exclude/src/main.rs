@@ -14,7 +14,7 @@ use std::{
 };
 
 #[cfg(target_os = "linux")]
-use talpid_types::cgroup::{SPLIT_TUNNEL_CGROUP_NAME, find_net_cls_mount};
+use talpid_types::cgroup::find_split_tunnel_hierarchy;
 
 #[cfg(target_os = "linux")]
 const PROGRAM_NAME: &str = "mullvad-exclude";
@@ -40,11 +40,11 @@ enum Error {
     #[error("An argument contains interior nul bytes")]
     ArgumentNul(#[source] NulError),
 
-    #[error("Failed to find net_cls controller")]
-    FindNetClsController(#[source] io::Error),
+    #[error("Failed to find cgroup hierarchy")]
+    FindCgroupHierarchy(#[source] io::Error),
 
-    #[error("No net_cls controller")]
-    NoNetClsController,
+    #[error("Neither cgroup v2 nor the cgroup v1 net_cls controller is available")]
+    NoCgroupHierarchy,
 }
 
 fn main() {
@@ -85,13 +85,11 @@ fn run() -> Result<Infallible, Error> {
         .collect::<Result<Vec<CString>, NulError>>()
         .map_err(Error::ArgumentNul)?;
 
-    let cgroup_dir = find_net_cls_mount()
-        .map_err(Error::FindNetClsController)?
-        .ok_or(Error::NoNetClsController)?;
+    let hierarchy = find_split_tunnel_hierarchy()
+        .map_err(Error::FindCgroupHierarchy)?
+        .ok_or(Error::NoCgroupHierarchy)?;
 
-    let procs_path = cgroup_dir
-        .join(SPLIT_TUNNEL_CGROUP_NAME)
-        .join("cgroup.procs");
+    let procs_path = hierarchy.exclusion_cgroup().join("cgroup.procs");
 
     let file = fs::OpenOptions::new()
         .write(true)
@@ -25,6 +25,7 @@ impl TunnelObfuscatorRuntime {
         let settings = ObfuscationSettings::Shadowsocks(shadowsocks::Settings {
             shadowsocks_endpoint: peer,
             wireguard_endpoint: SocketAddr::from((Ipv4Addr::LOCALHOST, 51820)),
+            password: None,
         });
         Self { settings }
     }
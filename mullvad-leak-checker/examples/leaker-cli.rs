@@ -1,5 +1,5 @@
 use clap::{Parser, Subcommand};
-use mullvad_leak_checker::traceroute::TracerouteOpt;
+use mullvad_leak_checker::{dns::DnsLeakOpt, traceroute::TracerouteOpt};
 
 #[derive(Parser)]
 pub struct Opt {
@@ -12,6 +12,10 @@ pub enum LeakMethod {
     /// Check for leaks by binding to a non-tunnel interface and probing for reachable nodes.
     Traceroute(#[clap(flatten)] TracerouteOpt),
 
+    /// Check for DNS leaks by sending queries on a non-tunnel interface and through the system
+    /// resolver.
+    Dns(#[clap(flatten)] DnsLeakOpt),
+
     /// Ask `am.i.mullvad.net` whether you are leaking.
     #[cfg(feature = "am-i-mullvad")]
     AmIMullvad(#[clap(flatten)] mullvad_leak_checker::am_i_mullvad::AmIMullvadOpt),
@@ -28,6 +32,7 @@ async fn main() -> anyhow::Result<()> {
 
     let leak_status = match &opt.method {
         LeakMethod::Traceroute(opt) => mullvad_leak_checker::traceroute::run_leak_test(opt).await,
+        LeakMethod::Dns(opt) => mullvad_leak_checker::dns::run_leak_test(opt).await,
         #[cfg(feature = "am-i-mullvad")]
         LeakMethod::AmIMullvad(opt) => mullvad_leak_checker::am_i_mullvad::run_leak_test(opt).await,
     };
@@ -5,7 +5,7 @@ use serde::Deserialize;
 
 use crate::{LeakInfo, LeakStatus};
 
-#[derive(Clone, clap::Args)]
+#[derive(Clone, Default, clap::Args)]
 pub struct AmIMullvadOpt {
     /// Try to bind to a specific interface
     #[clap(short, long)]
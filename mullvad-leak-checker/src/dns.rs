@@ -0,0 +1,322 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, anyhow, ensure};
+use futures::future::join_all;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{net::UdpSocket, time::timeout};
+
+use crate::{Interface, LeakInfo, LeakStatus, traceroute::bind_socket_to_interface, util::Ip};
+
+#[derive(Clone, clap::Args)]
+pub struct DnsLeakOpt {
+    /// Interface that DNS queries must not be able to leave through
+    #[clap(short, long)]
+    pub interface: Interface,
+
+    /// DNS servers to send queries to directly on the interface. Defaults to a few public
+    /// resolvers.
+    #[clap(long = "server")]
+    pub servers: Vec<IpAddr>,
+
+    /// Port to send the queries on the interface to.
+    #[clap(long, default_value_t = DNS_PORT)]
+    pub port: u16,
+
+    /// Domain whose name servers answer every query with the address of the resolver that sent
+    /// it. A unique name in it is looked up using the system resolver, to find out which resolvers
+    /// are used.
+    #[clap(long, requires = "allowed_resolvers")]
+    pub resolver_echo_domain: Option<String>,
+
+    /// Resolvers that are allowed to answer queries sent through the system resolver.
+    #[clap(long = "allow-resolver")]
+    pub allowed_resolvers: Vec<IpAddr>,
+}
+
+pub const DNS_PORT: u16 = 53;
+
+/// Servers that queries are sent to on the interface unless others are specified.
+const DEFAULT_SERVERS: [IpAddr; 4] = [
+    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+    IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0xfe, 0, 0, 0, 0, 0, 0xfe)),
+];
+
+/// Domain of the names that are sent directly to DNS servers. Only whether the server answers is
+/// of interest, and resolvers answer queries for `.invalid` without forwarding them (RFC 6761).
+const PROBE_DOMAIN: &str = "leak-check.invalid";
+
+/// Time to wait for an answer to a query.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Size of the DNS message header.
+const HEADER_LEN: usize = 12;
+
+/// [try_run_leak_test], but on an error, assume we aren't leaking.
+pub async fn run_leak_test(opt: &DnsLeakOpt) -> LeakStatus {
+    try_run_leak_test(opt)
+        .await
+        .inspect_err(|e| log::debug!("Leak test errored, assuming no leak. {e:?}"))
+        .unwrap_or(LeakStatus::NoLeak)
+}
+
+/// Run a DNS leak test.
+///
+/// Queries for unique names are sent directly to DNS servers from sockets bound to `interface`.
+/// Receiving an answer means that DNS traffic can leave through the interface, bypassing the
+/// firewall rules. If `resolver_echo_domain` is set, a unique name in that domain is also looked
+/// up using the system resolver, and the resolvers that forwarded the query are compared against
+/// `allowed_resolvers`.
+///
+/// Since the names are unique, the answers can not come from a cache.
+pub async fn try_run_leak_test(opt: &DnsLeakOpt) -> anyhow::Result<LeakStatus> {
+    let servers = if opt.servers.is_empty() {
+        &DEFAULT_SERVERS[..]
+    } else {
+        &opt.servers[..]
+    };
+
+    let probes = servers.iter().map(|&server| async move {
+        let address = SocketAddr::new(server, opt.port);
+        match probe_server_on_interface(&opt.interface, address).await {
+            Ok(answered) => answered.then_some(server),
+            Err(e) => {
+                log::debug!("Failed to send DNS query to {address}: {e:#}");
+                None
+            }
+        }
+    });
+    let system_resolvers = async {
+        let Some(domain) = &opt.resolver_echo_domain else {
+            return anyhow::Ok(vec![]);
+        };
+        let resolvers = lookup_resolvers(domain).await?;
+        Ok(resolvers
+            .into_iter()
+            .filter(|resolver| !opt.allowed_resolvers.contains(resolver))
+            .collect())
+    };
+
+    let (reachable_servers, resolvers) = futures::join!(join_all(probes), system_resolvers);
+    let reachable_servers: Vec<IpAddr> = reachable_servers.into_iter().flatten().collect();
+    let resolvers = resolvers?;
+
+    if reachable_servers.is_empty() && resolvers.is_empty() {
+        return Ok(LeakStatus::NoLeak);
+    }
+
+    Ok(LeakStatus::LeakDetected(LeakInfo::DnsLeak {
+        reachable_servers,
+        resolvers,
+        interface: opt.interface.clone(),
+    }))
+}
+
+/// Send a query for a unique name to `server` from a socket bound to `interface`, and return
+/// whether it was answered.
+async fn probe_server_on_interface(
+    interface: &Interface,
+    server: SocketAddr,
+) -> anyhow::Result<bool> {
+    let (ip_version, domain) = match server {
+        SocketAddr::V4(..) => (Ip::v4(), Domain::IPV4),
+        SocketAddr::V6(..) => (Ip::v6(), Domain::IPV6),
+    };
+
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))
+        .context("Failed to open UDP socket")?;
+    bind_socket_to_interface(&socket, interface, ip_version)
+        .context("Failed to bind UDP socket to interface")?;
+    socket
+        .set_nonblocking(true)
+        .context("Failed to set UDP socket to nonblocking")?;
+    let socket = UdpSocket::from_std(socket.into()).context("Failed to register UDP socket")?;
+
+    query_server(&socket, server).await
+}
+
+/// Send a query for a unique name to `server`, and return whether it was answered within
+/// [QUERY_TIMEOUT].
+async fn query_server(socket: &UdpSocket, server: SocketAddr) -> anyhow::Result<bool> {
+    let (id, name) = unique_name(PROBE_DOMAIN);
+    let query = encode_query(id, &name)?;
+
+    log::debug!("Sending DNS query for {name} to {server}");
+    socket
+        .send_to(&query, server)
+        .await
+        .with_context(|| anyhow!("Failed to send DNS query to {server}"))?;
+
+    let recv_answer = async {
+        let mut buf = [0u8; 512];
+        loop {
+            let (len, source) = socket.recv_from(&mut buf).await?;
+            if source == server && is_answer(&buf[..len], id) {
+                return std::io::Result::Ok(());
+            }
+        }
+    };
+
+    match timeout(QUERY_TIMEOUT, recv_answer).await {
+        Ok(Ok(())) => {
+            log::debug!("{server} answered DNS query for {name}");
+            Ok(true)
+        }
+        Ok(Err(e)) => Err(e).context("Failed to receive DNS answer"),
+        Err(_timeout) => Ok(false),
+    }
+}
+
+/// Look up a unique name in `domain` using the system resolver, and return the addresses in the
+/// answer, which are those of the resolvers that the query was forwarded by.
+async fn lookup_resolvers(domain: &str) -> anyhow::Result<Vec<IpAddr>> {
+    let (_, name) = unique_name(domain);
+    log::debug!("Looking up {name} using the system resolver");
+
+    let addresses = timeout(QUERY_TIMEOUT, tokio::net::lookup_host((name.as_str(), 0)))
+        .await
+        .map_err(|_timeout| anyhow!("Timed out while looking up {name}"))?
+        .with_context(|| anyhow!("Failed to look up {name}"))?;
+
+    let mut resolvers: Vec<IpAddr> = addresses.map(|address| address.ip()).collect();
+    resolvers.sort();
+    resolvers.dedup();
+    Ok(resolvers)
+}
+
+/// Return a query ID and a name in `domain` that has not been used before.
+fn unique_name(domain: &str) -> (u16, String) {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let label = format!("{nanos:x}-{:x}-{count:x}", std::process::id());
+    let id = (nanos as u16) ^ (count as u16);
+    (id, format!("{label}.{domain}"))
+}
+
+/// Encode a recursive query for the A records of `name`.
+fn encode_query(id: u16, name: &str) -> anyhow::Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(HEADER_LEN + name.len() + 6);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Flags: standard query, recursion desired
+    query.extend_from_slice(&[0x01, 0x00]);
+    // One question, no other records
+    query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        let len = u8::try_from(label.len())
+            .ok()
+            .filter(|&len| len < 64)
+            .with_context(|| anyhow!("Label is too long: {label}"))?;
+        query.push(len);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+
+    // QTYPE A, QCLASS IN
+    query.extend_from_slice(&[0, 1, 0, 1]);
+    ensure!(query.len() <= 512, "Name is too long: {name}");
+    Ok(query)
+}
+
+/// Return whether `message` is a response to the query with ID `id`. The response code does not
+/// matter, since any response means that the query reached the server.
+fn is_answer(message: &[u8], id: u16) -> bool {
+    const QR_BIT: u8 = 0x80;
+
+    message.len() >= HEADER_LEN && message[..2] == id.to_be_bytes() && message[2] & QR_BIT == QR_BIT
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Start a DNS server on localhost that answers every query with an empty response, unless
+    /// `silent` is set.
+    async fn stub_server(silent: bool) -> SocketAddr {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, source)) = socket.recv_from(&mut buf).await {
+                if silent || len < HEADER_LEN {
+                    continue;
+                }
+                let mut response = buf[..len].to_vec();
+                // Set QR and RA, and the response code to NXDOMAIN
+                response[2] |= 0x80;
+                response[3] = 0x83;
+                let _ = socket.send_to(&response, source).await;
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let query = encode_query(0x1234, "a.example.").unwrap();
+        assert_eq!(
+            query,
+            [
+                0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, //
+                1, b'a', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0, //
+                0, 1, 0, 1,
+            ]
+        );
+        assert!(encode_query(0, &format!("{}.example", "a".repeat(64))).is_err());
+
+        let (id, name) = unique_name(PROBE_DOMAIN);
+        let (other_id, other_name) = unique_name(PROBE_DOMAIN);
+        assert!(name.ends_with(".leak-check.invalid"));
+        assert!(name != other_name || id != other_id);
+    }
+
+    #[tokio::test]
+    async fn test_query_answered() {
+        let server = stub_server(false).await;
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        assert!(query_server(&socket, server).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_query_not_answered() {
+        let server = stub_server(true).await;
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        assert!(!query_server(&socket, server).await.unwrap());
+    }
+
+    /// Queries sent on the loopback interface reach the stub server, which is reported as a leak.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_leak_on_interface() {
+        let server = stub_server(false).await;
+        let opt = DnsLeakOpt {
+            interface: Interface::Name("lo".to_owned()),
+            servers: vec![server.ip()],
+            port: server.port(),
+            resolver_echo_domain: None,
+            allowed_resolvers: vec![],
+        };
+
+        match try_run_leak_test(&opt).await.unwrap() {
+            LeakStatus::LeakDetected(LeakInfo::DnsLeak {
+                reachable_servers,
+                resolvers,
+                ..
+            }) => {
+                assert_eq!(reachable_servers, [server.ip()]);
+                assert!(resolvers.is_empty());
+            }
+            status => panic!("Expected a DNS leak, got {status:?}"),
+        }
+    }
+}
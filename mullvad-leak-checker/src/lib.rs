@@ -2,6 +2,7 @@ use std::{fmt, net::IpAddr};
 
 #[cfg(feature = "am-i-mullvad")]
 pub mod am_i_mullvad;
+pub mod dns;
 pub mod traceroute;
 mod util;
 
@@ -16,7 +17,17 @@ pub enum LeakStatus {
 pub enum LeakInfo {
     /// Managed to reach another network node on the physical interface, bypassing firewall rules.
     NodeReachableOnInterface {
-        reachable_nodes: Vec<IpAddr>,
+        reachable_nodes: Vec<ReachableNode>,
+        interface: Interface,
+    },
+
+    /// DNS queries got answered by servers other than the allowed ones.
+    DnsLeak {
+        /// DNS servers that answered queries sent to them on `interface`.
+        reachable_servers: Vec<IpAddr>,
+        /// Resolvers other than the allowed ones that forwarded queries sent through the system
+        /// resolver.
+        resolvers: Vec<IpAddr>,
         interface: Interface,
     },
 
@@ -25,6 +36,14 @@ pub enum LeakInfo {
     AmIMullvad { ip: IpAddr },
 }
 
+/// A network node that responded to a probe packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReachableNode {
+    pub address: IpAddr,
+    /// The TTL of the probe that the node responded to, if it is known.
+    pub ttl: Option<u16>,
+}
+
 #[derive(Clone)]
 pub enum Interface {
     Name(String),
@@ -36,6 +55,34 @@ pub enum Interface {
     Index(std::num::NonZeroU32),
 }
 
+impl Interface {
+    /// Get the name of the interface, looking it up if the interface is referred to by index or
+    /// LUID.
+    pub fn name(&self) -> anyhow::Result<String> {
+        match self {
+            Interface::Name(name) => Ok(name.clone()),
+
+            #[cfg(target_os = "windows")]
+            Interface::Luid(luid) => {
+                use anyhow::Context;
+                talpid_windows::net::alias_from_luid(luid)
+                    .context("Failed to get interface alias")?
+                    .into_string()
+                    .map_err(|_| anyhow::anyhow!("Network interface alias was not UTF-8"))
+            }
+
+            #[cfg(target_os = "macos")]
+            &Interface::Index(index) => {
+                use anyhow::{Context, anyhow};
+                nix::net::if_::if_indextoname(std::ffi::c_uint::from(index))
+                    .with_context(|| anyhow!("Failed to get name of iface with index {index}"))?
+                    .into_string()
+                    .map_err(|_| anyhow!("Network interface name was not UTF-8"))
+            }
+        }
+    }
+}
+
 impl From<String> for Interface {
     fn from(name: String) -> Self {
         Interface::Name(name)
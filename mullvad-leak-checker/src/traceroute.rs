@@ -1,6 +1,6 @@
 use std::{net::IpAddr, ops::Range, time::Duration};
 
-use crate::{Interface, LeakStatus};
+use crate::{Interface, LeakStatus, util::Ip};
 
 /// Traceroute implementation for windows.
 #[cfg(target_os = "windows")]
@@ -10,6 +10,13 @@ mod windows;
 #[cfg(unix)]
 mod unix;
 
+#[cfg(target_os = "android")]
+type Impl = unix::android::TracerouteAndroid;
+#[cfg(target_os = "linux")]
+type Impl = unix::linux::TracerouteLinux;
+#[cfg(target_os = "macos")]
+type Impl = unix::macos::TracerouteMacos;
+
 #[derive(Clone, clap::Args)]
 pub struct TracerouteOpt {
     /// Try to bind to a specific interface
@@ -77,17 +84,31 @@ pub async fn run_leak_test(opt: &TracerouteOpt) -> LeakStatus {
 /// root/admin privileges.
 pub async fn try_run_leak_test(opt: &TracerouteOpt) -> anyhow::Result<LeakStatus> {
     #[cfg(unix)]
-    return {
-        #[cfg(target_os = "android")]
-        type Impl = unix::android::TracerouteAndroid;
-        #[cfg(target_os = "linux")]
-        type Impl = unix::linux::TracerouteLinux;
-        #[cfg(target_os = "macos")]
-        type Impl = unix::macos::TracerouteMacos;
-
-        unix::try_run_leak_test::<Impl>(opt).await
-    };
+    return unix::try_run_leak_test::<Impl>(opt).await;
 
     #[cfg(target_os = "windows")]
     return windows::traceroute_using_ping(opt).await;
 }
+
+/// Bind `socket` to `interface` in the same way as the sockets used for probing, so that other leak
+/// tests can send traffic on the same interface.
+pub(crate) fn bind_socket_to_interface(
+    socket: &socket2::Socket,
+    interface: &Interface,
+    ip_version: Ip,
+) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    return <Impl as unix::Traceroute>::bind_socket_to_interface(socket, interface, ip_version);
+
+    #[cfg(target_os = "windows")]
+    return {
+        use anyhow::Context;
+        use std::net::SocketAddr;
+
+        let interface_ip = crate::util::get_interface_ip(interface, ip_version)?;
+        log::debug!("Binding socket to {interface_ip} ({interface:?})");
+        socket
+            .bind(&SocketAddr::new(interface_ip, 0).into())
+            .context("Failed to bind socket to interface address")
+    };
+}
@@ -24,7 +24,7 @@ use socket2::Socket;
 use tokio::time::{Instant, sleep};
 
 use crate::{
-    Interface, LeakInfo, LeakStatus,
+    Interface, LeakInfo, LeakStatus, ReachableNode,
     traceroute::{RECV_GRACE_TIME, TracerouteOpt, unix::parse_icmp_probe},
     util::Ip,
 };
@@ -192,7 +192,7 @@ async fn recv_ttl_responses(
         // identify that this is a response to the ping we sent.
         skip_if!(source_addr != destination, "Unknown source");
 
-        let error_source = match control_message {
+        let reachable_node = match control_message {
             ControlMessageOwned::Ipv4RecvErr(socket_error, source_addr) => {
                 let libc::sock_extended_err {
                     ee_errno,  // Error Number: Should be EHOSTUNREACH
@@ -223,12 +223,13 @@ async fn recv_ttl_responses(
                 log::debug!("addr: {error_source}");
 
                 // Ensure that this is the original Echo packet that we sent.
-                skip_if!(
-                    parse_icmp_probe(Ip::V4(packet)).is_err(),
-                    "Not a response to us"
-                );
+                let probe = parse_icmp_probe(Ip::V4(packet));
+                skip_if!(probe.is_err(), "Not a response to us");
 
-                IpAddr::from(error_source.ip())
+                ReachableNode {
+                    address: IpAddr::from(error_source.ip()),
+                    ttl: probe.ok(),
+                }
             }
             ControlMessageOwned::Ipv6RecvErr(socket_error, source_addr) => {
                 let libc::sock_extended_err {
@@ -260,12 +261,13 @@ async fn recv_ttl_responses(
                 log::debug!("addr: {error_source}");
 
                 // Ensure that this is the original Echo packet that we sent.
-                skip_if!(
-                    parse_icmp_probe(Ip::V6(packet)).is_err(),
-                    "Not a response to us"
-                );
+                let probe = parse_icmp_probe(Ip::V6(packet));
+                skip_if!(probe.is_err(), "Not a response to us");
 
-                IpAddr::from(error_source.ip())
+                ReachableNode {
+                    address: IpAddr::from(error_source.ip()),
+                    ttl: probe.ok(),
+                }
             }
             other_message => {
                 log::debug!("Unhandled control message: {other_message:?}");
@@ -275,7 +277,7 @@ async fn recv_ttl_responses(
 
         log::debug!("Got a probe response, we are leaking!");
         timeout_at.get_or_insert_with(|| Instant::now() + RECV_GRACE_TIME);
-        reachable_nodes.push(error_source);
+        reachable_nodes.push(reachable_node);
     }
 
     debug_assert!(!reachable_nodes.is_empty());
@@ -21,7 +21,7 @@ use tokio::{
 };
 
 use crate::{
-    Interface, LeakInfo, LeakStatus,
+    Interface, LeakInfo, LeakStatus, ReachableNode,
     traceroute::{RECV_GRACE_TIME, TracerouteOpt},
     util::Ip,
 };
@@ -153,7 +153,12 @@ async fn recv_ttl_responses(
 
         let result = match opt.destination {
             // Reading on an ICMPv6 raw socket returns ICMPv6 packets.
-            IpAddr::V6(..) => parse_icmp_time_exceeded_raw(Ip::V6(packet)).map(|_| source),
+            IpAddr::V6(..) => {
+                parse_icmp_time_exceeded_raw(Ip::V6(packet)).map(|ttl| ReachableNode {
+                    address: source,
+                    ttl,
+                })
+            }
 
             // Reading on an ICMPv4 raw socket returns whole IP packets.
             IpAddr::V4(..) => {
@@ -163,11 +168,12 @@ async fn recv_ttl_responses(
         .map_err(|e| anyhow!("Ignoring packet (len={n}, ip.src={source}): {e}"));
 
         match result {
-            Ok(ip) => {
+            Ok(node) => {
                 log::debug!("Got a probe response, we are leaking!");
                 timeout_at.get_or_insert_with(|| Instant::now() + RECV_GRACE_TIME);
-                if !reachable_nodes.contains(&ip) {
-                    reachable_nodes.push(ip);
+                // the first packet is sent twice on macOS, so we may get two responses.
+                if !reachable_nodes.contains(&node) {
+                    reachable_nodes.push(node);
                 }
             }
 
@@ -207,24 +213,28 @@ fn parse_ip(packet: &[u8]) -> anyhow::Result<Ip<Ipv4Packet<'_>, Ipv6Packet<'_>>>
 }
 
 /// Try to parse an [Ipv4Packet] as an ICMP/TimeExceeded response to a packet sent by
-/// [send_udp_probes] or [send_icmp_probes]. If successful, returns the [Ipv4Addr] of the packet
-/// source.
+/// [send_udp_probes] or [send_icmp_probes]. If successful, returns the packet source as a
+/// [ReachableNode].
 ///
 /// If the packet fails to parse, or is not a reply to a packet sent by us, this function returns
 /// an error.
-fn parse_icmp4_time_exceeded(ip_packet: &Ipv4Packet<'_>) -> anyhow::Result<IpAddr> {
+fn parse_icmp4_time_exceeded(ip_packet: &Ipv4Packet<'_>) -> anyhow::Result<ReachableNode> {
     let ip_protocol = ip_packet.get_next_level_protocol();
     ensure!(ip_protocol == IpNextHeaderProtocols::Icmp, "Not ICMP");
-    parse_icmp_time_exceeded_raw(Ip::V4(ip_packet.payload()))?;
-    Ok(ip_packet.get_source().into())
+    let ttl = parse_icmp_time_exceeded_raw(Ip::V4(ip_packet.payload()))?;
+    Ok(ReachableNode {
+        address: ip_packet.get_source().into(),
+        ttl,
+    })
 }
 
 /// Try to parse some bytes into an ICMP or ICMP6 TimeExceeded response to a probe packet sent by
 /// [send_udp_probes] or [send_icmp_probes].
 ///
-/// If the packet fails to parse, or is not a reply to a packet sent by us, this function returns
-/// an error.
-fn parse_icmp_time_exceeded_raw(ip_payload: Ip<&[u8], &[u8]>) -> anyhow::Result<()> {
+/// The TTL of the probe is returned if it can be determined, which is only the case for ICMP
+/// probes. If the packet fails to parse, or is not a reply to a packet sent by us, this function
+/// returns an error.
+fn parse_icmp_time_exceeded_raw(ip_payload: Ip<&[u8], &[u8]>) -> anyhow::Result<Option<u16>> {
     let icmpv4_packet;
     let icmpv6_packet;
     let icmp_packet: &[u8] = match ip_payload {
@@ -284,12 +294,12 @@ fn parse_icmp_time_exceeded_raw(ip_payload: Ip<&[u8], &[u8]>) -> anyhow::Result<
                 }
             }
 
-            Ok(())
+            Ok(None)
         }
 
-        IpNextHeaderProtocols::Icmp => parse_icmp_probe(Ip::V4(original_ip_payload)),
+        IpNextHeaderProtocols::Icmp => parse_icmp_probe(Ip::V4(original_ip_payload)).map(Some),
 
-        IpNextHeaderProtocols::Icmpv6 => parse_icmp_probe(Ip::V6(original_ip_payload)),
+        IpNextHeaderProtocols::Icmpv6 => parse_icmp_probe(Ip::V6(original_ip_payload)).map(Some),
 
         _ => bail!("Not UDP/ICMP"),
     }
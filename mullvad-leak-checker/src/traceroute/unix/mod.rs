@@ -167,7 +167,8 @@ async fn send_icmp_probes<Impl: Traceroute>(
                     icmp_code: IcmpCode(0),
                     checksum: 0,
                     identifier: 1,
-                    sequence_number: 1,
+                    // Used to tell which probe a TimeExceeded response belongs to.
+                    sequence_number: ttl,
                     payload: PROBE_PAYLOAD.to_vec(),
                 };
 
@@ -190,7 +191,7 @@ async fn send_icmp_probes<Impl: Traceroute>(
                     icmpv6_code: Icmpv6Code(0),
                     checksum: 0,
                     identifier: 1,
-                    sequence_number: 1,
+                    sequence_number: ttl,
                     payload: PROBE_PAYLOAD.to_vec(),
                 };
 
@@ -303,11 +304,11 @@ async fn send_udp_probes(opt: &TracerouteOpt, socket: &mut AsyncUdpSocket) -> an
 }
 
 /// Try to parse bytes as an ICMP/ICMP6 Echo Request matching the probe packets send by
-/// [send_icmp_probes].
-fn parse_icmp_probe(icmp_bytes: Ip<&[u8], &[u8]>) -> anyhow::Result<()> {
+/// [send_icmp_probes]. If successful, returns the TTL that the probe was sent with.
+fn parse_icmp_probe(icmp_bytes: Ip<&[u8], &[u8]>) -> anyhow::Result<u16> {
     let echo_packet_v4;
     let echo_packet_v6;
-    let echo_payload = match icmp_bytes {
+    let (echo_payload, ttl) = match icmp_bytes {
         Ip::V4(icmpv4_bytes) => {
             echo_packet_v4 =
                 icmp::echo_request::EchoRequestPacket::new(icmpv4_bytes).ok_or_else(too_small)?;
@@ -317,7 +318,10 @@ fn parse_icmp_probe(icmp_bytes: Ip<&[u8], &[u8]>) -> anyhow::Result<()> {
                 "Not ICMP/EchoRequest"
             );
 
-            echo_packet_v4.payload()
+            (
+                echo_packet_v4.payload(),
+                echo_packet_v4.get_sequence_number(),
+            )
         }
         Ip::V6(icmpv6_bytes) => {
             echo_packet_v6 =
@@ -328,7 +332,10 @@ fn parse_icmp_probe(icmp_bytes: Ip<&[u8], &[u8]>) -> anyhow::Result<()> {
                 "Not ICMP6/EchoRequest"
             );
 
-            echo_packet_v6.payload()
+            (
+                echo_packet_v6.payload(),
+                echo_packet_v6.get_sequence_number(),
+            )
         }
     };
 
@@ -345,7 +352,7 @@ fn parse_icmp_probe(icmp_bytes: Ip<&[u8], &[u8]>) -> anyhow::Result<()> {
         bail!("Wrong ICMP6/Echo payload: {echo_payload:?}");
     }
 
-    Ok(())
+    Ok(ttl)
 }
 
 fn too_small() -> anyhow::Error {
@@ -6,7 +6,7 @@ use futures::{FutureExt, StreamExt, select, stream::FuturesUnordered};
 use tokio::time::sleep;
 
 use crate::{
-    LeakInfo, LeakStatus,
+    LeakInfo, LeakStatus, ReachableNode,
     traceroute::{DEFAULT_TTL_RANGE, LEAK_TIMEOUT, PROBE_INTERVAL, SEND_TIMEOUT, TracerouteOpt},
     util::{Ip, get_interface_ip},
 };
@@ -78,17 +78,20 @@ pub async fn traceroute_using_ping(opt: &TracerouteOpt) -> anyhow::Result<LeakSt
                 .parse()
                 .context("`ping.exe` outputted an invalid IP address")?;
 
-            anyhow::Ok(Some(ip))
+            anyhow::Ok(Some(ReachableNode {
+                address: ip,
+                ttl: Some(ttl),
+            }))
         });
     }
 
     let wait_for_first_leak = async move {
         while let Some(result) = ping_tasks.next().await {
-            let Some(ip) = result? else { continue };
+            let Some(node) = result? else { continue };
 
             return Ok(LeakStatus::LeakDetected(
                 LeakInfo::NodeReachableOnInterface {
-                    reachable_nodes: vec![ip],
+                    reachable_nodes: vec![node],
                     interface: opt.interface.clone(),
                 },
             ));
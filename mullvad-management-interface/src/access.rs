@@ -0,0 +1,339 @@
+//! Role-based access control for the management interface. The credentials of the peer are read
+//! from the socket (`SO_PEERCRED`) when it connects, and every call is checked against the role
+//! that the settings give its user and groups. The role of the peer is added to the extensions of
+//! calls that are permitted.
+
+use futures::{Stream, future::BoxFuture};
+use mullvad_types::management_access::{ManagementAccessSettings, Role};
+use nix::unistd::{Gid, Uid, User, getgrouplist};
+use std::{
+    ffi::CString,
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    net::{UnixListener, UnixStream},
+    sync::watch,
+};
+use tonic::{Status, body::BoxBody, codegen::http, transport::server::UdsConnectInfo};
+use tower::{Layer, Service};
+
+/// Receives the current access control settings. Calls are held until the settings are known.
+pub type AccessSettingsReceiver = watch::Receiver<Option<ManagementAccessSettings>>;
+
+const SERVICE_PATH_PREFIX: &str = "/mullvad_daemon.management_interface.ManagementService/";
+
+/// Return the role needed to call the gRPC method at `path`. Unknown methods require
+/// [`Role::Admin`].
+fn required_role(path: &str) -> Role {
+    let Some(method) = path.strip_prefix(SERVICE_PATH_PREFIX) else {
+        return Role::Admin;
+    };
+    match method {
+        "ConnectTunnel" | "DisconnectTunnel" | "ReconnectTunnel" => Role::Operator,
+        "GetTunnelState"
+        | "GetTunnelStats"
+        | "TunnelStatsListen"
+        | "GetConnectionHistory"
+        | "EventsListen"
+        | "GetCurrentVersion"
+        | "GetVersionInfo"
+        | "IsPerformingPostUpgrade"
+        | "GetRelayLocations"
+        | "GetRelayListChanges"
+        | "GetSettings"
+        | "ExportJsonSettings"
+        | "GetAccountData"
+        | "GetDevice"
+        | "GetWireguardKey"
+        | "GetCurrentApiAccessMethod"
+        | "SplitTunnelIsEnabled"
+        | "GetSplitTunnelProcesses"
+        | "GetExcludedProcesses"
+        | "ListSettingsProfiles"
+        | "DiffSettingsProfile"
+        | "GetFeatureIndicators"
+        | "AppUpgradeEventsListen"
+        | "GetAppUpgradeCacheDir" => Role::ReadOnly,
+        _ => Role::Admin,
+    }
+}
+
+/// Return the role of a peer running as user `uid` with the primary group `gid`, or `None` if its
+/// groups were needed but could not be looked up.
+async fn peer_role(settings: &ManagementAccessSettings, uid: u32, gid: u32) -> Option<Role> {
+    if let Some(role) = settings.user_role(uid) {
+        return Some(role);
+    }
+    if settings.groups.is_empty() {
+        return Some(settings.default_role);
+    }
+    // Looking up groups may be slow, e.g. if they are stored in a directory service
+    match tokio::task::spawn_blocking(move || groups(uid, gid)).await {
+        Ok(Ok(groups)) => Some(settings.role(uid, &groups)),
+        Ok(Err(error)) => {
+            log::error!("Failed to look up the groups of uid {uid}: {error}");
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Return the primary group `gid` and the supplementary groups of the user `uid`.
+fn groups(uid: u32, gid: u32) -> nix::Result<Vec<u32>> {
+    let user = User::from_uid(Uid::from_raw(uid))?.ok_or(nix::Error::ENOENT)?;
+    let name = CString::new(user.name).map_err(|_| nix::Error::EINVAL)?;
+    let groups = getgrouplist(&name, Gid::from_raw(gid))?;
+    Ok(groups.into_iter().map(Gid::as_raw).collect())
+}
+
+/// Stream of connections to the management interface socket.
+///
+/// Removes the socket file when dropped.
+pub(crate) struct Incoming {
+    path: PathBuf,
+    listener: UnixListener,
+}
+
+impl Incoming {
+    /// Bind a socket at `path` that anyone may connect to.
+    pub(crate) fn bind(path: &Path) -> io::Result<Self> {
+        let listener = UnixListener::bind(path)?;
+        fs::set_permissions(path, PermissionsExt::from_mode(0o766))?;
+        Ok(Incoming {
+            path: path.to_owned(),
+            listener,
+        })
+    }
+}
+
+impl Stream for Incoming {
+    type Item = io::Result<UnixStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.listener
+            .poll_accept(cx)
+            .map(|result| Some(result.map(|(stream, _addr)| stream)))
+    }
+}
+
+impl Drop for Incoming {
+    fn drop(&mut self) {
+        if fs::remove_file(&self.path).is_ok() {
+            log::trace!("Removed socket file at: {}", self.path.display());
+        }
+    }
+}
+
+/// Layer that rejects calls that the role of the peer does not permit.
+#[derive(Clone)]
+pub(crate) struct AccessControlLayer {
+    settings: AccessSettingsReceiver,
+}
+
+impl AccessControlLayer {
+    pub(crate) fn new(settings: AccessSettingsReceiver) -> Self {
+        AccessControlLayer { settings }
+    }
+}
+
+impl<S> Layer<S> for AccessControlLayer {
+    type Service = AccessControl<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AccessControl {
+            inner,
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AccessControl<S> {
+    inner: S,
+    settings: AccessSettingsReceiver,
+}
+
+impl<S, B> Service<http::Request<B>> for AccessControl<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        // The service that was polled ready must be the one that is called
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let mut settings = self.settings.clone();
+
+        Box::pin(async move {
+            let required = required_role(request.uri().path());
+            let Some(peer) = request
+                .extensions()
+                .get::<UdsConnectInfo>()
+                .and_then(|info| info.peer_cred)
+            else {
+                log::warn!("Rejecting management interface call from unknown peer");
+                return Ok(Status::permission_denied("Unknown peer").into_http());
+            };
+
+            let settings = match settings.wait_for(Option::is_some).await {
+                Ok(settings) => settings.clone(),
+                Err(_) => None,
+            };
+            let role = match settings {
+                Some(settings) => peer_role(&settings, peer.uid(), peer.gid()).await,
+                None => None,
+            };
+            let Some(role) = role.filter(|role| *role >= required) else {
+                log::debug!(
+                    "Denying {} to uid {} gid {} with role {role:?}",
+                    request.uri().path(),
+                    peer.uid(),
+                    peer.gid(),
+                );
+                return Ok(
+                    Status::permission_denied(format!("This requires the {required} role"))
+                        .into_http(),
+                );
+            };
+
+            // Let the handlers restrict what they return to the peer
+            request.extensions_mut().insert(role);
+
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::BTreeMap, convert::Infallible};
+    use tonic::{Code, transport::server::Connected};
+    use tower::ServiceExt;
+
+    /// Send a call to `method` through the access control layer, from a peer with the credentials
+    /// of this process. The response has the role that the layer added to the call, if any.
+    async fn call(
+        settings: AccessSettingsReceiver,
+        method: &str,
+        peer: bool,
+    ) -> http::Response<BoxBody> {
+        let inner = tower::service_fn(|request: http::Request<()>| async move {
+            let mut response = http::Response::new(tonic::body::empty_body());
+            if let Some(role) = request.extensions().get::<Role>() {
+                response.extensions_mut().insert(*role);
+            }
+            Ok::<_, Infallible>(response)
+        });
+        let service = AccessControlLayer::new(settings).layer(inner);
+
+        let mut request = http::Request::builder()
+            .uri(format!("{SERVICE_PATH_PREFIX}{method}"))
+            .body(())
+            .unwrap();
+        if peer {
+            let (stream, _other) = UnixStream::pair().unwrap();
+            request.extensions_mut().insert(stream.connect_info());
+        }
+        service.oneshot(request).await.unwrap()
+    }
+
+    fn is_denied(response: &http::Response<BoxBody>) -> bool {
+        Status::from_header_map(response.headers())
+            .is_some_and(|status| status.code() == Code::PermissionDenied)
+    }
+
+    #[test]
+    fn test_required_role() {
+        let method = |name: &str| format!("{SERVICE_PATH_PREFIX}{name}");
+
+        assert_eq!(required_role(&method("GetTunnelState")), Role::ReadOnly);
+        assert_eq!(required_role(&method("EventsListen")), Role::ReadOnly);
+        assert_eq!(required_role(&method("ConnectTunnel")), Role::Operator);
+        assert_eq!(required_role(&method("SetAllowLan")), Role::Admin);
+        assert_eq!(required_role(&method("GetWwwAuthToken")), Role::Admin);
+        assert_eq!(required_role(&method("NoSuchMethod")), Role::Admin);
+        assert_eq!(required_role("/other.Service/GetTunnelState"), Role::Admin);
+    }
+
+    #[tokio::test]
+    async fn test_role_added_to_call() {
+        let (_tx, settings) = watch::channel(Some(ManagementAccessSettings::default()));
+        let response = call(settings, "SetAllowLan", true).await;
+        assert!(!is_denied(&response));
+        assert_eq!(response.extensions().get::<Role>(), Some(&Role::Admin));
+    }
+
+    #[tokio::test]
+    async fn test_deny_insufficient_role() {
+        let uid = nix::unistd::getuid().as_raw();
+        if uid == 0 {
+            // Root is always an admin
+            return;
+        }
+        let access = ManagementAccessSettings {
+            users: BTreeMap::from([(uid, Role::ReadOnly)]),
+            ..ManagementAccessSettings::default()
+        };
+        let (_tx, settings) = watch::channel(Some(access));
+
+        let response = call(settings.clone(), "GetTunnelState", true).await;
+        assert_eq!(response.extensions().get::<Role>(), Some(&Role::ReadOnly));
+
+        let response = call(settings, "ConnectTunnel", true).await;
+        assert!(is_denied(&response));
+        assert_eq!(response.extensions().get::<Role>(), None);
+    }
+
+    #[tokio::test]
+    async fn test_deny_unknown_peer() {
+        let (_tx, settings) = watch::channel(Some(ManagementAccessSettings::default()));
+        let response = call(settings, "GetTunnelState", false).await;
+        assert!(is_denied(&response));
+    }
+
+    /// Calls must be denied if the settings never become known, even for root.
+    #[tokio::test]
+    async fn test_deny_without_settings() {
+        let (tx, settings) = watch::channel(None);
+        drop(tx);
+        let response = call(settings, "GetTunnelState", true).await;
+        assert!(is_denied(&response));
+    }
+
+    /// The groups of users with a role in `users` are not needed, so a failed lookup must not lock
+    /// them out.
+    #[tokio::test]
+    async fn test_peer_role_without_groups() {
+        const UNKNOWN_UID: u32 = 4_000_000_000;
+        let settings = ManagementAccessSettings {
+            default_role: Role::ReadOnly,
+            users: BTreeMap::from([(UNKNOWN_UID, Role::Operator)]),
+            groups: BTreeMap::from([(100, Role::Admin)]),
+        };
+        assert_eq!(
+            peer_role(&settings, UNKNOWN_UID, 100).await,
+            Some(Role::Operator)
+        );
+        assert_eq!(peer_role(&settings, UNKNOWN_UID + 1, 100).await, None);
+    }
+
+    #[test]
+    fn test_groups() {
+        let groups = groups(0, 0).expect("root should exist");
+        assert!(groups.contains(&0));
+    }
+}
@@ -3,12 +3,16 @@
 use crate::types;
 #[cfg(not(target_os = "android"))]
 use futures::{Stream, StreamExt};
+#[cfg(not(target_os = "android"))]
+use ipnetwork::IpNetwork;
 #[cfg(all(daita, not(target_os = "android")))]
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
     access_method::AccessMethodSetting,
+    account::AccountExpiryWarning,
     device::{DeviceEvent, RemoveDeviceEvent},
-    relay_list::RelayList,
+    leak_test::LeakTestReport,
+    relay_list::{RelayList, RelayListDiff},
     settings::Settings,
     states::TunnelState,
     version::AppVersionInfo,
@@ -17,18 +21,26 @@ use mullvad_types::{
 #[cfg(not(target_os = "android"))]
 use mullvad_types::{
     access_method::{self, AccessMethod},
-    account::{AccountData, AccountNumber, VoucherSubmission},
+    account::{AccountData, AccountNumber, ExpiryReminders, VoucherSubmission},
+    connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter},
     custom_list::{CustomList, Id},
     device::{Device, DeviceId, DeviceState},
     features::FeatureIndicators,
+    network_rule::NetworkRule,
     relay_constraints::{
         AllowedIps, BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        RetryStep,
+    },
+    settings::{
+        DnsOptions, MetricsSettings, SplitTunnelMode,
+        profile::{SettingDiff, SettingsProfile},
     },
-    settings::DnsOptions,
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
 };
 #[cfg(not(target_os = "android"))]
 use std::{path::Path, str::FromStr};
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::wireguard::{ConnectivityCheckOptions, TunnelStats};
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 #[cfg(not(target_os = "android"))]
@@ -51,6 +63,9 @@ pub enum DaemonEvent {
     Device(DeviceEvent),
     RemoveDevice(RemoveDeviceEvent),
     NewAccessMethod(AccessMethodSetting),
+    RelayListChanges(RelayListDiff),
+    LeakDetected(LeakTestReport),
+    AccountExpiryWarning(AccountExpiryWarning),
 }
 
 impl TryFrom<types::daemon_event::Event> for DaemonEvent {
@@ -81,6 +96,17 @@ impl TryFrom<types::daemon_event::Event> for DaemonEvent {
                     .map(DaemonEvent::NewAccessMethod)
                     .map_err(Error::InvalidResponse)
             }
+            types::daemon_event::Event::RelayListChanges(diff) => RelayListDiff::try_from(diff)
+                .map(DaemonEvent::RelayListChanges)
+                .map_err(Error::InvalidResponse),
+            types::daemon_event::Event::LeakDetected(report) => LeakTestReport::try_from(report)
+                .map(DaemonEvent::LeakDetected)
+                .map_err(Error::InvalidResponse),
+            types::daemon_event::Event::AccountExpiryWarning(warning) => {
+                AccountExpiryWarning::try_from(warning)
+                    .map(DaemonEvent::AccountExpiryWarning)
+                    .map_err(Error::InvalidResponse)
+            }
         }
     }
 }
@@ -124,6 +150,57 @@ impl MullvadProxyClient {
         }))
     }
 
+    /// Returns traffic statistics for the current tunnel, or `None` if there is no WireGuard
+    /// tunnel.
+    pub async fn get_tunnel_stats(&mut self) -> Result<Option<TunnelStats>> {
+        let stats = match self.0.get_tunnel_stats(()).await {
+            Ok(stats) => stats.into_inner(),
+            Err(status) if status.code() == Code::NotFound => return Ok(None),
+            Err(status) => return Err(status.into()),
+        };
+        TunnelStats::try_from(stats)
+            .map(Some)
+            .map_err(Error::InvalidResponse)
+    }
+
+    /// Listen for traffic statistics, sampled every `interval`. Transfer rates are computed
+    /// relative to the previous sample.
+    pub async fn tunnel_stats_listen<'a>(
+        &mut self,
+        interval: std::time::Duration,
+    ) -> Result<impl Stream<Item = Result<TunnelStats>> + 'a> {
+        let interval = types::Duration::try_from(interval).map_err(|_| Error::DurationTooLarge)?;
+        let listener = self.0.tunnel_stats_listen(interval).await?.into_inner();
+
+        Ok(listener.map(|item| TunnelStats::try_from(item?).map_err(Error::InvalidResponse)))
+    }
+
+    /// Return the config of the connected WireGuard relay in the wg-quick format.
+    pub async fn export_wireguard_config(&mut self) -> Result<String> {
+        Ok(self.0.export_wireguard_config(()).await?.into_inner())
+    }
+
+    /// Return the recorded tunnel state transitions that match `filter`, oldest first.
+    pub async fn get_connection_history(
+        &mut self,
+        filter: ConnectionHistoryFilter,
+    ) -> Result<Vec<ConnectionHistoryEntry>> {
+        self.0
+            .get_connection_history(types::ConnectionHistoryFilter::from(filter))
+            .await?
+            .into_inner()
+            .entries
+            .into_iter()
+            .map(|entry| ConnectionHistoryEntry::try_from(entry).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
+    /// Try to send traffic outside the tunnel, and report whether it succeeded.
+    pub async fn run_leak_test(&mut self) -> Result<LeakTestReport> {
+        let report = self.0.run_leak_test(()).await?.into_inner();
+        LeakTestReport::try_from(report).map_err(Error::InvalidResponse)
+    }
+
     /// DEPRECATED: Prefer to use `prepare_restart_v2`.
     pub async fn prepare_restart(&mut self) -> Result<()> {
         self.0.prepare_restart(()).await?;
@@ -159,6 +236,11 @@ impl MullvadProxyClient {
         mullvad_types::relay_list::RelayList::try_from(list).map_err(Error::InvalidResponse)
     }
 
+    pub async fn get_relay_list_changes(&mut self) -> Result<RelayListDiff> {
+        let diff = self.0.get_relay_list_changes(()).await?.into_inner();
+        RelayListDiff::try_from(diff).map_err(Error::InvalidResponse)
+    }
+
     pub async fn get_api_access_methods(&mut self) -> Result<Vec<AccessMethodSetting>> {
         let access_method_settings = self
             .0
@@ -245,6 +327,20 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    /// Set the order in which WireGuard connection methods are tried. `None` restores the default
+    /// order.
+    pub async fn set_retry_order(&mut self, retry_order: Option<Vec<RetryStep>>) -> Result<()> {
+        let retry_order = types::RetryOrder {
+            steps: retry_order
+                .into_iter()
+                .flatten()
+                .map(types::RetryStep::from)
+                .collect(),
+        };
+        self.0.set_retry_order(retry_order).await?;
+        Ok(())
+    }
+
     pub async fn get_settings(&mut self) -> Result<Settings> {
         let settings = self.0.get_settings(()).await?.into_inner();
         Settings::try_from(settings).map_err(Error::InvalidResponse)
@@ -260,6 +356,18 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn add_allowed_lan_network(&mut self, network: IpNetwork) -> Result<()> {
+        self.0.add_allowed_lan_network(network.to_string()).await?;
+        Ok(())
+    }
+
+    pub async fn remove_allowed_lan_network(&mut self, network: IpNetwork) -> Result<()> {
+        self.0
+            .remove_allowed_lan_network(network.to_string())
+            .await?;
+        Ok(())
+    }
+
     pub async fn set_show_beta_releases(&mut self, state: bool) -> Result<()> {
         self.0.set_show_beta_releases(state).await?;
         Ok(())
@@ -275,6 +383,20 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_network_rules(&mut self, rules: Vec<NetworkRule>) -> Result<()> {
+        self.0
+            .set_network_rules(types::NetworkRules::from(rules))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_metrics_settings(&mut self, settings: MetricsSettings) -> Result<()> {
+        self.0
+            .set_metrics_settings(types::MetricsSettings::from(settings))
+            .await?;
+        Ok(())
+    }
+
     pub async fn set_openvpn_mssfix(&mut self, mssfix: Option<u16>) -> Result<()> {
         self.0
             .set_openvpn_mssfix(mssfix.map(u32::from).unwrap_or(0))
@@ -289,6 +411,15 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_wireguard_connectivity_check(
+        &mut self,
+        options: ConnectivityCheckOptions,
+    ) -> Result<()> {
+        let options = types::ConnectivityCheckOptions::from(options);
+        self.0.set_wireguard_connectivity_check(options).await?;
+        Ok(())
+    }
+
     pub async fn set_enable_ipv6(&mut self, state: bool) -> Result<()> {
         self.0.set_enable_ipv6(state).await?;
         Ok(())
@@ -392,6 +523,13 @@ impl MullvadProxyClient {
         VoucherSubmission::try_from(result).map_err(Error::InvalidResponse)
     }
 
+    pub async fn set_expiry_reminders(&mut self, reminders: ExpiryReminders) -> Result<()> {
+        self.0
+            .set_expiry_reminders(types::ExpiryReminders::from(reminders))
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_device(&mut self) -> Result<DeviceState> {
         let state = self
             .0
@@ -592,6 +730,12 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) -> Result<()> {
+        let mode = types::SplitTunnelMode::from(mode);
+        self.0.set_split_tunnel_mode(mode).await?;
+        Ok(())
+    }
+
     #[cfg(target_os = "windows")]
     pub async fn get_excluded_processes(&mut self) -> Result<Vec<ExcludedProcess>> {
         let procs = self.0.get_excluded_processes(()).await?.into_inner();
@@ -614,6 +758,48 @@ impl MullvadProxyClient {
         Ok(blob.into_inner())
     }
 
+    /// Save the current settings as a profile named `name`, replacing any existing profile with
+    /// the same name.
+    pub async fn save_settings_profile(&mut self, name: String) -> Result<()> {
+        self.0.save_settings_profile(name).await?;
+        Ok(())
+    }
+
+    pub async fn apply_settings_profile(&mut self, name: String) -> Result<()> {
+        self.0.apply_settings_profile(name).await?;
+        Ok(())
+    }
+
+    pub async fn list_settings_profiles(&mut self) -> Result<Vec<SettingsProfile>> {
+        Ok(self
+            .0
+            .list_settings_profiles(())
+            .await?
+            .into_inner()
+            .profiles
+            .into_iter()
+            .map(SettingsProfile::from)
+            .collect())
+    }
+
+    pub async fn delete_settings_profile(&mut self, name: String) -> Result<()> {
+        self.0.delete_settings_profile(name).await?;
+        Ok(())
+    }
+
+    /// Return the settings in the profile named `name` that differ from the current settings.
+    pub async fn diff_settings_profile(&mut self, name: String) -> Result<Vec<SettingDiff>> {
+        Ok(self
+            .0
+            .diff_settings_profile(name)
+            .await?
+            .into_inner()
+            .diffs
+            .into_iter()
+            .map(SettingDiff::from)
+            .collect())
+    }
+
     pub async fn get_feature_indicators(&mut self) -> Result<FeatureIndicators> {
         Ok(FeatureIndicators::from(
             self.0.get_feature_indicators(()).await?.into_inner(),
@@ -1,19 +1,24 @@
+#[cfg(target_os = "linux")]
+pub mod access;
 pub mod client;
 pub mod types;
 
 use parity_tokio_ipc::Endpoint as IpcEndpoint;
 #[cfg(unix)]
 use std::{env, fs, os::unix::fs::PermissionsExt};
+use std::{future::Future, io};
+#[cfg(not(target_os = "linux"))]
 use std::{
-    future::Future,
-    io,
     pin::Pin,
     task::{Context, Poll},
 };
+#[cfg(not(target_os = "linux"))]
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tonic::transport::Server;
+#[cfg(not(target_os = "linux"))]
+use tonic::transport::server::Connected;
 #[cfg(not(target_os = "android"))]
 use tonic::transport::{Endpoint, Uri};
-use tonic::transport::{Server, server::Connected};
 #[cfg(not(target_os = "android"))]
 use tower::service_fn;
 
@@ -149,18 +154,29 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
     service: T,
     abort_rx: F,
     rpc_socket_path: impl AsRef<std::path::Path>,
+    #[cfg(target_os = "linux")] access_settings: access::AccessSettingsReceiver,
 ) -> std::result::Result<ServerJoinHandle, Error> {
-    use futures::stream::TryStreamExt;
-    use parity_tokio_ipc::SecurityAttributes;
-
-    let mut endpoint = IpcEndpoint::new(rpc_socket_path.as_ref().to_string_lossy().to_string());
-    endpoint.set_security_attributes(
-        SecurityAttributes::allow_everyone_create()
-            .map_err(Error::SecurityAttributes)?
-            .set_mode(0o766)
-            .map_err(Error::SecurityAttributes)?,
-    );
-    let incoming = endpoint.incoming().map_err(Error::StartServerError)?;
+    #[cfg(not(target_os = "linux"))]
+    let incoming = {
+        use futures::stream::TryStreamExt;
+        use parity_tokio_ipc::SecurityAttributes;
+
+        let mut endpoint = IpcEndpoint::new(rpc_socket_path.as_ref().to_string_lossy().to_string());
+        endpoint.set_security_attributes(
+            SecurityAttributes::allow_everyone_create()
+                .map_err(Error::SecurityAttributes)?
+                .set_mode(0o766)
+                .map_err(Error::SecurityAttributes)?,
+        );
+        endpoint
+            .incoming()
+            .map_err(Error::StartServerError)?
+            .map_ok(StreamBox)
+    };
+    // The peer credentials are needed for access control, so bind the socket directly
+    #[cfg(target_os = "linux")]
+    let incoming =
+        access::Incoming::bind(rpc_socket_path.as_ref()).map_err(Error::StartServerError)?;
 
     #[cfg(unix)]
     if let Some(group_name) = &*MULLVAD_MANAGEMENT_SOCKET_GROUP {
@@ -173,10 +189,15 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
             .map_err(Error::PermissionsError)?;
     }
 
+    #[cfg(not(target_os = "linux"))]
+    let mut server = Server::builder();
+    #[cfg(target_os = "linux")]
+    let mut server = Server::builder().layer(access::AccessControlLayer::new(access_settings));
+
     Ok(tokio::spawn(async move {
-        if let Err(execution_error) = Server::builder()
+        if let Err(execution_error) = server
             .add_service(ManagementServiceServer::new(service))
-            .serve_with_incoming_shutdown(incoming.map_ok(StreamBox), abort_rx)
+            .serve_with_incoming_shutdown(incoming, abort_rx)
             .await
             .map_err(Error::GrpcTransportError)
         {
@@ -186,8 +207,10 @@ pub fn spawn_rpc_server<T: ManagementService, F: Future<Output = ()> + Send + 's
     }))
 }
 
+#[cfg(not(target_os = "linux"))]
 #[derive(Debug)]
 struct StreamBox<T: AsyncRead + AsyncWrite>(pub T);
+#[cfg(not(target_os = "linux"))]
 impl<T: AsyncRead + AsyncWrite> Connected for StreamBox<T> {
     type ConnectInfo = Option<()>;
 
@@ -195,6 +218,7 @@ impl<T: AsyncRead + AsyncWrite> Connected for StreamBox<T> {
         None
     }
 }
+#[cfg(not(target_os = "linux"))]
 impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for StreamBox<T> {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -204,6 +228,7 @@ impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for StreamBox<T> {
         Pin::new(&mut self.0).poll_read(cx, buf)
     }
 }
+#[cfg(not(target_os = "linux"))]
 impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWrite for StreamBox<T> {
     fn poll_write(
         mut self: Pin<&mut Self>,
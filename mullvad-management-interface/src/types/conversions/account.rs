@@ -1,8 +1,11 @@
 use crate::types;
 use chrono::DateTime;
-use mullvad_types::account::{AccountData, VoucherSubmission};
+use mullvad_types::account::{
+    AccountData, AccountExpiryWarning, ExpiryReminders, VoucherSubmission,
+};
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
+use std::time::Duration;
 
 use super::FromProtobufTypeError;
 
@@ -66,6 +69,77 @@ impl TryFrom<types::AccountData> for AccountData {
     }
 }
 
+impl From<AccountExpiryWarning> for types::AccountExpiryWarning {
+    fn from(warning: AccountExpiryWarning) -> Self {
+        types::AccountExpiryWarning {
+            expiry: Some(types::Timestamp {
+                seconds: warning.expiry.timestamp(),
+                nanos: 0,
+            }),
+            remaining: Some(duration_to_proto(warning.remaining)),
+        }
+    }
+}
+
+impl TryFrom<types::AccountExpiryWarning> for AccountExpiryWarning {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(warning: types::AccountExpiryWarning) -> Result<Self, FromProtobufTypeError> {
+        let expiry = warning
+            .expiry
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing expiry"))?;
+        let expiry = DateTime::from_timestamp(expiry.seconds, expiry.nanos as u32)
+            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))?;
+        let remaining = warning
+            .remaining
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing remaining time",
+            ))
+            .and_then(duration_from_proto)?;
+
+        Ok(AccountExpiryWarning { expiry, remaining })
+    }
+}
+
+impl From<ExpiryReminders> for types::ExpiryReminders {
+    fn from(reminders: ExpiryReminders) -> Self {
+        types::ExpiryReminders {
+            thresholds: reminders
+                .thresholds()
+                .iter()
+                .copied()
+                .map(duration_to_proto)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<types::ExpiryReminders> for ExpiryReminders {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(reminders: types::ExpiryReminders) -> Result<Self, FromProtobufTypeError> {
+        let thresholds = reminders
+            .thresholds
+            .into_iter()
+            .map(duration_from_proto)
+            .collect::<Result<_, _>>()?;
+        ExpiryReminders::new(thresholds)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid expiry reminders"))
+    }
+}
+
+fn duration_to_proto(duration: Duration) -> types::Duration {
+    types::Duration::try_from(duration).unwrap_or(types::Duration {
+        seconds: i64::MAX,
+        nanos: 0,
+    })
+}
+
+fn duration_from_proto(duration: types::Duration) -> Result<Duration, FromProtobufTypeError> {
+    Duration::try_from(duration)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))
+}
+
 #[cfg(target_os = "android")]
 impl TryFrom<types::PlayPurchase> for PlayPurchase {
     type Error = FromProtobufTypeError;
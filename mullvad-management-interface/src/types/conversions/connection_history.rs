@@ -0,0 +1,64 @@
+use crate::types::{self, FromProtobufTypeError, proto};
+use chrono::{DateTime, Utc};
+use mullvad_types::{
+    connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter},
+    states::TunnelState,
+};
+
+impl From<ConnectionHistoryFilter> for proto::ConnectionHistoryFilter {
+    fn from(filter: ConnectionHistoryFilter) -> Self {
+        proto::ConnectionHistoryFilter {
+            since: filter.since.map(to_timestamp),
+            until: filter.until.map(to_timestamp),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectionHistoryFilter> for ConnectionHistoryFilter {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(filter: proto::ConnectionHistoryFilter) -> Result<Self, Self::Error> {
+        Ok(ConnectionHistoryFilter {
+            since: filter.since.map(from_timestamp).transpose()?,
+            until: filter.until.map(from_timestamp).transpose()?,
+        })
+    }
+}
+
+impl From<ConnectionHistoryEntry> for proto::ConnectionHistoryEntry {
+    fn from(entry: ConnectionHistoryEntry) -> Self {
+        proto::ConnectionHistoryEntry {
+            time: Some(to_timestamp(entry.time)),
+            state: Some(proto::TunnelState::from(entry.state)),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectionHistoryEntry> for ConnectionHistoryEntry {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(entry: proto::ConnectionHistoryEntry) -> Result<Self, Self::Error> {
+        let time = entry
+            .time
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing time"))?;
+        let state = entry.state.ok_or(FromProtobufTypeError::InvalidArgument(
+            "missing tunnel state",
+        ))?;
+        Ok(ConnectionHistoryEntry {
+            time: from_timestamp(time)?,
+            state: TunnelState::try_from(state)?,
+        })
+    }
+}
+
+fn to_timestamp(time: DateTime<Utc>) -> types::Timestamp {
+    types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn from_timestamp(timestamp: types::Timestamp) -> Result<DateTime<Utc>, FromProtobufTypeError> {
+    DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32)
+        .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+}
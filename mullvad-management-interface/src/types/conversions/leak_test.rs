@@ -0,0 +1,86 @@
+use crate::types::{FromProtobufTypeError, conversions::arg_from_str, proto};
+use mullvad_types::leak_test::{AmIMullvad, LeakTestReport, ReachableNode};
+
+impl From<LeakTestReport> for proto::LeakTestReport {
+    fn from(report: LeakTestReport) -> Self {
+        proto::LeakTestReport {
+            leaking: report.is_leaking(),
+            interface: report.interface,
+            destination: report.destination.to_string(),
+            reachable_nodes: report
+                .reachable_nodes
+                .into_iter()
+                .map(proto::leak_test_report::ReachableNode::from)
+                .collect(),
+            reachable_dns_servers: report
+                .reachable_dns_servers
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            am_i_mullvad: report
+                .am_i_mullvad
+                .map(|result| proto::leak_test_report::AmIMullvad {
+                    not_mullvad_ip: match result {
+                        AmIMullvad::Mullvad => None,
+                        AmIMullvad::NotMullvad { ip } => Some(ip.to_string()),
+                    },
+                }),
+        }
+    }
+}
+
+impl From<ReachableNode> for proto::leak_test_report::ReachableNode {
+    fn from(node: ReachableNode) -> Self {
+        proto::leak_test_report::ReachableNode {
+            address: node.address.to_string(),
+            ttl: node.ttl.map(u32::from),
+        }
+    }
+}
+
+impl TryFrom<proto::LeakTestReport> for LeakTestReport {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(report: proto::LeakTestReport) -> Result<Self, Self::Error> {
+        let am_i_mullvad = report
+            .am_i_mullvad
+            .map(|result| match result.not_mullvad_ip {
+                Some(ip) => Ok(AmIMullvad::NotMullvad {
+                    ip: arg_from_str(&ip, "invalid am.i.mullvad.net IP address")?,
+                }),
+                None => Ok(AmIMullvad::Mullvad),
+            })
+            .transpose()?;
+
+        Ok(LeakTestReport {
+            interface: report.interface,
+            destination: arg_from_str(&report.destination, "invalid leak test destination")?,
+            reachable_nodes: report
+                .reachable_nodes
+                .into_iter()
+                .map(ReachableNode::try_from)
+                .collect::<Result<_, _>>()?,
+            reachable_dns_servers: report
+                .reachable_dns_servers
+                .iter()
+                .map(|server| arg_from_str(server, "invalid DNS server address"))
+                .collect::<Result<_, _>>()?,
+            am_i_mullvad,
+        })
+    }
+}
+
+impl TryFrom<proto::leak_test_report::ReachableNode> for ReachableNode {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(node: proto::leak_test_report::ReachableNode) -> Result<Self, Self::Error> {
+        Ok(ReachableNode {
+            address: arg_from_str(&node.address, "invalid reachable node address")?,
+            ttl: node
+                .ttl
+                .map(u16::try_from)
+                .transpose()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid TTL"))?,
+        })
+    }
+}
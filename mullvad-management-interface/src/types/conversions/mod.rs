@@ -2,12 +2,15 @@ use std::str::FromStr;
 
 mod access_method;
 mod account;
+mod connection_history;
 mod custom_list;
 mod custom_tunnel;
 mod device;
 mod features;
+mod leak_test;
 mod location;
 mod net;
+mod network_rule;
 pub mod relay_constraints;
 mod relay_list;
 mod settings;
@@ -39,6 +42,14 @@ fn bytes_to_privkey(
     ))
 }
 
+fn bytes_to_preshared_key(
+    bytes: &[u8],
+) -> Result<talpid_types::net::wireguard::PresharedKey, FromProtobufTypeError> {
+    Ok(talpid_types::net::wireguard::PresharedKey::from(Box::new(
+        *bytes_to_wg_key(bytes, "invalid preshared key")?,
+    )))
+}
+
 fn bytes_to_wg_key<'a>(
     bytes: &'a [u8],
     error_msg: &'static str,
@@ -0,0 +1,69 @@
+use crate::types::{FromProtobufTypeError, proto};
+use mullvad_types::network_rule::{NetworkAction, NetworkMatch, NetworkRule};
+
+impl From<NetworkRule> for proto::NetworkRule {
+    fn from(rule: NetworkRule) -> Self {
+        use proto::network_rule::{Action, Network};
+
+        let network = match rule.network {
+            NetworkMatch::Interface(interface) => Network::Interface(interface),
+            NetworkMatch::GatewayMac(mac) => Network::GatewayMac(mac.to_string()),
+            NetworkMatch::Any => Network::Any(()),
+        };
+        let action = match rule.action {
+            NetworkAction::Connect => Action::Connect,
+            NetworkAction::Disconnect => Action::Disconnect,
+            NetworkAction::Block => Action::Block,
+        };
+        Self {
+            network: Some(network),
+            action: i32::from(action),
+        }
+    }
+}
+
+impl TryFrom<proto::NetworkRule> for NetworkRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: proto::NetworkRule) -> Result<Self, Self::Error> {
+        use proto::network_rule::{Action, Network};
+
+        let network = match rule
+            .network
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing network"))?
+        {
+            Network::Interface(interface) => NetworkMatch::Interface(interface),
+            Network::GatewayMac(mac) => {
+                NetworkMatch::GatewayMac(super::arg_from_str(&mac, "invalid gateway MAC address")?)
+            }
+            Network::Any(()) => NetworkMatch::Any,
+        };
+        let action = match Action::try_from(rule.action) {
+            Ok(Action::Connect) => NetworkAction::Connect,
+            Ok(Action::Disconnect) => NetworkAction::Disconnect,
+            Ok(Action::Block) => NetworkAction::Block,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid network rule action",
+                ));
+            }
+        };
+        Ok(NetworkRule { network, action })
+    }
+}
+
+impl From<Vec<NetworkRule>> for proto::NetworkRules {
+    fn from(rules: Vec<NetworkRule>) -> Self {
+        Self {
+            rules: rules.into_iter().map(proto::NetworkRule::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::NetworkRules> for Vec<NetworkRule> {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rules: proto::NetworkRules) -> Result<Self, Self::Error> {
+        rules.rules.into_iter().map(NetworkRule::try_from).collect()
+    }
+}
@@ -7,8 +7,8 @@ use mullvad_types::{
         allowed_ip::{self, AllowedIps},
     },
 };
-use std::str::FromStr;
-use talpid_types::net::proxy::CustomProxy;
+use std::{net::SocketAddr, str::FromStr};
+use talpid_types::net::{obfuscation::ObfuscatorConfig, proxy::CustomProxy};
 
 impl TryFrom<&proto::WireguardConstraints>
     for mullvad_types::relay_constraints::WireguardConstraints
@@ -98,6 +98,14 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                     CustomTunnelEndpoint {
                         host: settings.host,
                         config,
+                        obfuscation: settings
+                            .obfuscation
+                            .map(try_obfuscator_config_from_proto)
+                            .transpose()?,
+                        preshared_key: settings
+                            .preshared_key
+                            .map(|key| super::bytes_to_preshared_key(&key))
+                            .transpose()?,
                     },
                 ))
             }
@@ -110,6 +118,11 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                 let providers = try_providers_constraint_from_proto(&settings.providers)?;
                 let ownership = try_ownership_constraint_from_i32(settings.ownership)?;
                 let tunnel_protocol = try_tunnel_type_from_i32(settings.tunnel_type)?;
+                let selection_mode = proto::RelaySelectionMode::try_from(settings.selection_mode)
+                    .map(mullvad_constraints::RelaySelectionMode::from)
+                    .map_err(|_| {
+                        FromProtobufTypeError::InvalidArgument("invalid relay selection mode")
+                    })?;
 
                 let openvpn_constraints =
                     mullvad_constraints::OpenVpnConstraints::try_from(
@@ -131,6 +144,7 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                         tunnel_protocol,
                         wireguard_constraints,
                         openvpn_constraints,
+                        selection_mode,
                     },
                 ))
             }
@@ -151,20 +165,31 @@ impl From<mullvad_types::relay_constraints::BridgeState> for proto::BridgeState
     }
 }
 
+impl From<mullvad_types::relay_constraints::RelaySelectionMode> for proto::RelaySelectionMode {
+    fn from(mode: mullvad_types::relay_constraints::RelaySelectionMode) -> Self {
+        use mullvad_types::relay_constraints::RelaySelectionMode;
+        match mode {
+            RelaySelectionMode::Random => proto::RelaySelectionMode::Random,
+            RelaySelectionMode::LowestLatency => proto::RelaySelectionMode::LowestLatency,
+        }
+    }
+}
+
+impl From<proto::RelaySelectionMode> for mullvad_types::relay_constraints::RelaySelectionMode {
+    fn from(mode: proto::RelaySelectionMode) -> Self {
+        use mullvad_types::relay_constraints::RelaySelectionMode;
+        match mode {
+            proto::RelaySelectionMode::Random => RelaySelectionMode::Random,
+            proto::RelaySelectionMode::LowestLatency => RelaySelectionMode::LowestLatency,
+        }
+    }
+}
+
 impl From<&mullvad_types::relay_constraints::ObfuscationSettings> for proto::ObfuscationSettings {
     fn from(settings: &mullvad_types::relay_constraints::ObfuscationSettings) -> Self {
-        use mullvad_types::relay_constraints::SelectedObfuscation;
-        let selected_obfuscation = i32::from(match settings.selected_obfuscation {
-            SelectedObfuscation::Auto => proto::obfuscation_settings::SelectedObfuscation::Auto,
-            SelectedObfuscation::Off => proto::obfuscation_settings::SelectedObfuscation::Off,
-            SelectedObfuscation::Udp2Tcp => {
-                proto::obfuscation_settings::SelectedObfuscation::Udp2tcp
-            }
-            SelectedObfuscation::Shadowsocks => {
-                proto::obfuscation_settings::SelectedObfuscation::Shadowsocks
-            }
-            SelectedObfuscation::Quic => proto::obfuscation_settings::SelectedObfuscation::Quic,
-        });
+        let selected_obfuscation = i32::from(
+            proto::obfuscation_settings::SelectedObfuscation::from(settings.selected_obfuscation),
+        );
         Self {
             selected_obfuscation,
             udp2tcp: Some(proto::Udp2TcpObfuscationSettings::from(&settings.udp2tcp)),
@@ -241,6 +266,8 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                 relay_settings::Endpoint::Custom(proto::CustomRelaySettings {
                     host: endpoint.host,
                     config: Some(proto::ConnectionConfig::from(endpoint.config)),
+                    obfuscation: endpoint.obfuscation.map(proto::CustomObfuscation::from),
+                    preshared_key: endpoint.preshared_key.map(|key| key.as_bytes().to_vec()),
                 })
             }
             MullvadRelaySettings::Normal(constraints) => {
@@ -287,6 +314,10 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                             .option()
                             .map(proto::TransportPort::from),
                     }),
+
+                    selection_mode: i32::from(proto::RelaySelectionMode::from(
+                        constraints.selection_mode,
+                    )),
                 })
             }
         };
@@ -450,21 +481,8 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
     type Error = FromProtobufTypeError;
 
     fn try_from(settings: proto::ObfuscationSettings) -> Result<Self, Self::Error> {
-        use mullvad_types::relay_constraints::SelectedObfuscation;
-        use proto::obfuscation_settings::SelectedObfuscation as IpcSelectedObfuscation;
         let selected_obfuscation =
-            match IpcSelectedObfuscation::try_from(settings.selected_obfuscation) {
-                Ok(IpcSelectedObfuscation::Auto) => SelectedObfuscation::Auto,
-                Ok(IpcSelectedObfuscation::Off) => SelectedObfuscation::Off,
-                Ok(IpcSelectedObfuscation::Udp2tcp) => SelectedObfuscation::Udp2Tcp,
-                Ok(IpcSelectedObfuscation::Shadowsocks) => SelectedObfuscation::Shadowsocks,
-                Ok(IpcSelectedObfuscation::Quic) => SelectedObfuscation::Quic,
-                Err(_) => {
-                    return Err(FromProtobufTypeError::InvalidArgument(
-                        "invalid obfuscation settings",
-                    ));
-                }
-            };
+            try_selected_obfuscation_from_i32(settings.selected_obfuscation)?;
 
         let udp2tcp = match settings.udp2tcp {
             Some(settings) => {
@@ -495,6 +513,158 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
     }
 }
 
+impl From<ObfuscatorConfig> for proto::CustomObfuscation {
+    fn from(config: ObfuscatorConfig) -> Self {
+        use proto::obfuscation_endpoint::ObfuscationType;
+
+        let endpoint = config.get_obfuscator_endpoint().address;
+        let (obfuscation_type, password, hostname, auth_token) = match config {
+            ObfuscatorConfig::Udp2Tcp { .. } => {
+                (ObfuscationType::Udp2tcp, None, String::new(), String::new())
+            }
+            ObfuscatorConfig::Shadowsocks { password, .. } => (
+                ObfuscationType::Shadowsocks,
+                password,
+                String::new(),
+                String::new(),
+            ),
+            ObfuscatorConfig::Quic {
+                hostname,
+                auth_token,
+                ..
+            } => (ObfuscationType::Quic, None, hostname, auth_token),
+        };
+        proto::CustomObfuscation {
+            obfuscation_type: i32::from(obfuscation_type),
+            address: endpoint.ip().to_string(),
+            port: u32::from(endpoint.port()),
+            password,
+            hostname,
+            auth_token,
+        }
+    }
+}
+
+fn try_obfuscator_config_from_proto(
+    obfuscation: proto::CustomObfuscation,
+) -> Result<ObfuscatorConfig, FromProtobufTypeError> {
+    use proto::obfuscation_endpoint::ObfuscationType;
+
+    let address = obfuscation
+        .address
+        .parse()
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid obfuscation address"))?;
+    let port = u16::try_from(obfuscation.port)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid obfuscation port"))?;
+    let endpoint = SocketAddr::new(address, port);
+
+    match ObfuscationType::try_from(obfuscation.obfuscation_type) {
+        Ok(ObfuscationType::Udp2tcp) => Ok(ObfuscatorConfig::Udp2Tcp { endpoint }),
+        Ok(ObfuscationType::Shadowsocks) => Ok(ObfuscatorConfig::Shadowsocks {
+            endpoint,
+            password: obfuscation.password,
+            wireguard_port: None,
+        }),
+        Ok(ObfuscationType::Quic) => {
+            if obfuscation.hostname.is_empty() {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "missing QUIC hostname",
+                ));
+            }
+            // The token is put in an Authorization header by the obfuscator
+            if obfuscation.auth_token.is_empty() || obfuscation.auth_token.starts_with("Bearer") {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid QUIC authentication token",
+                ));
+            }
+            Ok(ObfuscatorConfig::Quic {
+                hostname: obfuscation.hostname,
+                endpoint,
+                auth_token: obfuscation.auth_token,
+                wireguard_port: None,
+            })
+        }
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+            "invalid obfuscation type",
+        )),
+    }
+}
+
+impl From<mullvad_types::relay_constraints::SelectedObfuscation>
+    for proto::obfuscation_settings::SelectedObfuscation
+{
+    fn from(obfuscation: mullvad_types::relay_constraints::SelectedObfuscation) -> Self {
+        use mullvad_types::relay_constraints::SelectedObfuscation;
+        match obfuscation {
+            SelectedObfuscation::Auto => Self::Auto,
+            SelectedObfuscation::Off => Self::Off,
+            SelectedObfuscation::Udp2Tcp => Self::Udp2tcp,
+            SelectedObfuscation::Shadowsocks => Self::Shadowsocks,
+            SelectedObfuscation::Quic => Self::Quic,
+        }
+    }
+}
+
+fn try_selected_obfuscation_from_i32(
+    obfuscation: i32,
+) -> Result<mullvad_types::relay_constraints::SelectedObfuscation, FromProtobufTypeError> {
+    use mullvad_types::relay_constraints::SelectedObfuscation;
+    use proto::obfuscation_settings::SelectedObfuscation as IpcSelectedObfuscation;
+    match IpcSelectedObfuscation::try_from(obfuscation) {
+        Ok(IpcSelectedObfuscation::Auto) => Ok(SelectedObfuscation::Auto),
+        Ok(IpcSelectedObfuscation::Off) => Ok(SelectedObfuscation::Off),
+        Ok(IpcSelectedObfuscation::Udp2tcp) => Ok(SelectedObfuscation::Udp2Tcp),
+        Ok(IpcSelectedObfuscation::Shadowsocks) => Ok(SelectedObfuscation::Shadowsocks),
+        Ok(IpcSelectedObfuscation::Quic) => Ok(SelectedObfuscation::Quic),
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+            "invalid obfuscation settings",
+        )),
+    }
+}
+
+impl From<mullvad_types::relay_constraints::RetryStep> for proto::RetryStep {
+    fn from(step: mullvad_types::relay_constraints::RetryStep) -> Self {
+        Self {
+            obfuscation: i32::from(proto::obfuscation_settings::SelectedObfuscation::from(
+                step.obfuscation,
+            )),
+            ip_version: step
+                .ip_version
+                .option()
+                .map(|ipv| i32::from(proto::IpVersion::from(ipv))),
+            port: step.port.map(u32::from).option(),
+        }
+    }
+}
+
+impl TryFrom<proto::RetryStep> for mullvad_types::relay_constraints::RetryStep {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(step: proto::RetryStep) -> Result<Self, Self::Error> {
+        let ip_version = match step.ip_version {
+            Some(version) => Some(talpid_types::net::IpVersion::from(
+                proto::IpVersion::try_from(version).map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid IP protocol version")
+                })?,
+            )),
+            None => None,
+        };
+        let port = match step.port {
+            Some(port) => Some(
+                u16::try_from(port)
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid port"))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            obfuscation: try_selected_obfuscation_from_i32(step.obfuscation)?,
+            ip_version: Constraint::from(ip_version),
+            port: Constraint::from(port),
+        })
+    }
+}
+
 impl TryFrom<&proto::Udp2TcpObfuscationSettings>
     for mullvad_types::relay_constraints::Udp2TcpObfuscationSettings
 {
@@ -643,3 +813,76 @@ fn convert_ownership_constraint(
         },
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_custom_obfuscation_round_trip() {
+        let configs = [
+            ObfuscatorConfig::Udp2Tcp {
+                endpoint: "192.0.2.1:443".parse().unwrap(),
+            },
+            ObfuscatorConfig::Shadowsocks {
+                endpoint: "[2001:db8::1]:8388".parse().unwrap(),
+                password: Some("hunter2".to_owned()),
+                wireguard_port: None,
+            },
+            ObfuscatorConfig::Shadowsocks {
+                endpoint: "192.0.2.1:8388".parse().unwrap(),
+                password: None,
+                wireguard_port: None,
+            },
+            ObfuscatorConfig::Quic {
+                hostname: "vpn.example.com".to_owned(),
+                endpoint: "192.0.2.1:443".parse().unwrap(),
+                auth_token: "token".to_owned(),
+                wireguard_port: None,
+            },
+        ];
+
+        for config in configs {
+            let proto = proto::CustomObfuscation::from(config.clone());
+            assert_eq!(try_obfuscator_config_from_proto(proto).unwrap(), config);
+        }
+    }
+
+    #[test]
+    fn test_invalid_custom_obfuscation() {
+        let valid = proto::CustomObfuscation::from(ObfuscatorConfig::Quic {
+            hostname: "vpn.example.com".to_owned(),
+            endpoint: "192.0.2.1:443".parse().unwrap(),
+            auth_token: "token".to_owned(),
+            wireguard_port: None,
+        });
+
+        let invalid = [
+            proto::CustomObfuscation {
+                address: "vpn.example.com".to_owned(),
+                ..valid.clone()
+            },
+            proto::CustomObfuscation {
+                port: 65536,
+                ..valid.clone()
+            },
+            proto::CustomObfuscation {
+                obfuscation_type: -1,
+                ..valid.clone()
+            },
+            proto::CustomObfuscation {
+                hostname: String::new(),
+                ..valid.clone()
+            },
+            proto::CustomObfuscation {
+                auth_token: "Bearer token".to_owned(),
+                ..valid.clone()
+            },
+        ];
+
+        assert!(try_obfuscator_config_from_proto(valid).is_ok());
+        for obfuscation in invalid {
+            assert!(try_obfuscator_config_from_proto(obfuscation).is_err());
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    net::{Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     ops::RangeInclusive,
     str::FromStr,
 };
@@ -432,3 +432,97 @@ impl TryFrom<proto::PortRange> for RangeInclusive<u16> {
         Ok(first..=last)
     }
 }
+
+impl From<mullvad_types::relay_list::RelayListDiff> for proto::RelayListDiff {
+    fn from(diff: mullvad_types::relay_list::RelayListDiff) -> Self {
+        proto::RelayListDiff {
+            added: diff.added,
+            removed: diff.removed,
+            activated: diff.activated,
+            deactivated: diff.deactivated,
+            address_changes: diff
+                .address_changes
+                .into_iter()
+                .map(|change| proto::RelayAddressChange {
+                    hostname: change.hostname,
+                    old_address: change.old.map(|address| address.to_string()),
+                    new_address: change.new.map(|address| address.to_string()),
+                })
+                .collect(),
+            feature_changes: diff
+                .feature_changes
+                .into_iter()
+                .map(|change| proto::RelayFeatureChange {
+                    hostname: change.hostname,
+                    feature: proto::relay_feature_change::Feature::from(change.feature) as i32,
+                    enabled: change.enabled,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<mullvad_types::relay_list::RelayFeature> for proto::relay_feature_change::Feature {
+    fn from(feature: mullvad_types::relay_list::RelayFeature) -> Self {
+        use mullvad_types::relay_list::RelayFeature;
+        match feature {
+            RelayFeature::Daita => proto::relay_feature_change::Feature::Daita,
+            RelayFeature::Quic => proto::relay_feature_change::Feature::Quic,
+        }
+    }
+}
+
+impl TryFrom<proto::RelayListDiff> for mullvad_types::relay_list::RelayListDiff {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(diff: proto::RelayListDiff) -> Result<Self, Self::Error> {
+        use mullvad_types::relay_list::{RelayAddressChange, RelayFeature, RelayFeatureChange};
+
+        let parse_address = |address: Option<String>| {
+            address
+                .map(|address| IpAddr::from_str(&address))
+                .transpose()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid relay address"))
+        };
+        let address_changes = diff
+            .address_changes
+            .into_iter()
+            .map(|change| {
+                Ok(RelayAddressChange {
+                    hostname: change.hostname,
+                    old: parse_address(change.old_address)?,
+                    new: parse_address(change.new_address)?,
+                })
+            })
+            .collect::<Result<_, FromProtobufTypeError>>()?;
+        let feature_changes = diff
+            .feature_changes
+            .into_iter()
+            .map(|change| {
+                let feature = match proto::relay_feature_change::Feature::try_from(change.feature) {
+                    Ok(proto::relay_feature_change::Feature::Daita) => RelayFeature::Daita,
+                    Ok(proto::relay_feature_change::Feature::Quic) => RelayFeature::Quic,
+                    Err(_) => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid relay feature",
+                        ));
+                    }
+                };
+                Ok(RelayFeatureChange {
+                    hostname: change.hostname,
+                    feature,
+                    enabled: change.enabled,
+                })
+            })
+            .collect::<Result<_, FromProtobufTypeError>>()?;
+
+        Ok(mullvad_types::relay_list::RelayListDiff {
+            added: diff.added,
+            removed: diff.removed,
+            activated: diff.activated,
+            deactivated: diff.deactivated,
+            address_changes,
+            feature_changes,
+        })
+    }
+}
@@ -1,9 +1,12 @@
-use crate::types::{FromProtobufTypeError, proto};
+use crate::types::{FromProtobufTypeError, conversions::arg_from_str, proto};
 use mullvad_types::settings::CURRENT_SETTINGS_VERSION;
-use talpid_types::ErrorExt;
+use talpid_types::{
+    ErrorExt,
+    net::dns::{EncryptedDnsProtocol, EncryptedDnsServer, SplitDnsRule},
+};
 impl From<&mullvad_types::settings::Settings> for proto::Settings {
     fn from(settings: &mullvad_types::settings::Settings) -> Self {
-        #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+        #[cfg(not(target_os = "ios"))]
         let split_tunnel = {
             let apps = settings
                 .split_tunnel
@@ -21,9 +24,10 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
             Some(proto::SplitTunnelSettings {
                 enable_exclusions: settings.split_tunnel.enable_exclusions,
                 apps,
+                mode: Some(proto::SplitTunnelMode::from(settings.split_tunnel.mode)),
             })
         };
-        #[cfg(target_os = "linux")]
+        #[cfg(target_os = "ios")]
         let split_tunnel = None;
 
         Self {
@@ -33,11 +37,22 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
             )),
             bridge_state: Some(proto::BridgeState::from(settings.bridge_state)),
             allow_lan: settings.allow_lan,
+            allowed_lan_networks: settings
+                .allowed_lan_networks
+                .iter()
+                .map(|network| network.to_string())
+                .collect(),
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: settings.block_when_disconnected,
             #[cfg(target_os = "android")]
             block_when_disconnected: false,
             auto_connect: settings.auto_connect,
+            network_rules: settings
+                .network_rules
+                .iter()
+                .cloned()
+                .map(proto::NetworkRule::from)
+                .collect(),
             tunnel_options: Some(proto::TunnelOptions::from(&settings.tunnel_options)),
             show_beta_releases: settings.show_beta_releases,
             obfuscation_settings: Some(proto::ObfuscationSettings::from(
@@ -57,6 +72,19 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
                 .map(proto::RelayOverride::from)
                 .collect(),
             recents: settings.recents.clone().map(proto::Recents::from),
+            metrics: Some(proto::MetricsSettings::from(settings.metrics)),
+            expiry_reminders: Some(proto::ExpiryReminders::from(
+                settings.expiry_reminders.clone(),
+            )),
+            retry_order: Some(proto::RetryOrder {
+                steps: settings
+                    .retry_order
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .map(proto::RetryStep::from)
+                    .collect(),
+            }),
         }
     }
 }
@@ -85,7 +113,46 @@ impl From<&mullvad_types::settings::DnsOptions> for proto::DnsOptions {
                     .iter()
                     .map(|addr| addr.to_string())
                     .collect(),
+                encrypted_servers: options
+                    .custom_options
+                    .encrypted_servers
+                    .iter()
+                    .map(proto::EncryptedDnsServer::from)
+                    .collect(),
             }),
+            split_dns_rules: options
+                .split_dns_rules
+                .iter()
+                .map(proto::SplitDnsRule::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&EncryptedDnsServer> for proto::EncryptedDnsServer {
+    fn from(server: &EncryptedDnsServer) -> Self {
+        let protocol = match server.protocol {
+            EncryptedDnsProtocol::Https => proto::encrypted_dns_server::Protocol::Https,
+            EncryptedDnsProtocol::Tls => proto::encrypted_dns_server::Protocol::Tls,
+        };
+        proto::EncryptedDnsServer {
+            protocol: i32::from(protocol),
+            hostname: server.hostname.clone(),
+            bootstrap_address: server.bootstrap_address.to_string(),
+            port: u32::from(server.port),
+        }
+    }
+}
+
+impl From<&SplitDnsRule> for proto::SplitDnsRule {
+    fn from(rule: &SplitDnsRule) -> Self {
+        proto::SplitDnsRule {
+            domain: rule.domain.clone(),
+            servers: rule
+                .servers
+                .iter()
+                .map(|server| server.to_string())
+                .collect(),
         }
     }
 }
@@ -107,6 +174,9 @@ impl From<&mullvad_types::settings::TunnelOptions> for proto::TunnelOptions {
                 daita: Some(proto::DaitaSettings::from(options.wireguard.daita.clone())),
                 #[cfg(not(daita))]
                 daita: None,
+                connectivity_check: Some(proto::ConnectivityCheckOptions::from(
+                    options.wireguard.connectivity_check.clone(),
+                )),
             }),
             generic: Some(proto::tunnel_options::GenericOptions {
                 enable_ipv6: options.generic.enable_ipv6,
@@ -162,7 +232,28 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .ok_or(FromProtobufTypeError::InvalidArgument(
                     "missing api access methods settings",
                 ))?;
-        #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+        let metrics = settings
+            .metrics
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing metrics settings",
+            ))?;
+        let retry_order = settings
+            .retry_order
+            .map(|order| {
+                order
+                    .steps
+                    .into_iter()
+                    .map(mullvad_types::relay_constraints::RetryStep::try_from)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?
+            .filter(|steps| !steps.is_empty());
+        let expiry_reminders = settings
+            .expiry_reminders
+            .map(mullvad_types::account::ExpiryReminders::try_from)
+            .transpose()?
+            .unwrap_or_default();
+        #[cfg(not(target_os = "ios"))]
         let split_tunnel = settings
             .split_tunnel
             .ok_or(FromProtobufTypeError::InvalidArgument(
@@ -178,9 +269,23 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
             )?,
             bridge_state,
             allow_lan: settings.allow_lan,
+            allowed_lan_networks: settings
+                .allowed_lan_networks
+                .iter()
+                .map(|network| {
+                    network
+                        .parse()
+                        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid LAN network"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: settings.block_when_disconnected,
             auto_connect: settings.auto_connect,
+            network_rules: settings
+                .network_rules
+                .into_iter()
+                .map(mullvad_types::network_rule::NetworkRule::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
             tunnel_options: mullvad_types::settings::TunnelOptions::try_from(tunnel_options)?,
             relay_overrides: settings
                 .relay_overrides
@@ -188,11 +293,12 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .map(mullvad_types::relay_constraints::RelayOverride::try_from)
                 .collect::<Result<Vec<_>, _>>()?,
             show_beta_releases: settings.show_beta_releases,
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
-            split_tunnel: mullvad_types::settings::SplitTunnelSettings::from(split_tunnel),
+            #[cfg(not(target_os = "ios"))]
+            split_tunnel: mullvad_types::settings::SplitTunnelSettings::try_from(split_tunnel)?,
             obfuscation_settings: mullvad_types::relay_constraints::ObfuscationSettings::try_from(
                 obfuscation_settings,
             )?,
+            retry_order,
             // NOTE: This field is set based on mullvad-types. It's not based on the actual settings
             // version.
             settings_version: CURRENT_SETTINGS_VERSION,
@@ -203,10 +309,78 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 api_access_methods_settings,
             )?,
             recents: Some(vec![]),
+            metrics: mullvad_types::settings::MetricsSettings::try_from(metrics)?,
+            expiry_reminders,
+            // NOTE: Access control is configured in the settings file and is not sent to clients.
+            management_access: Default::default(),
+        })
+    }
+}
+
+impl From<mullvad_types::settings::MetricsSettings> for proto::MetricsSettings {
+    fn from(settings: mullvad_types::settings::MetricsSettings) -> Self {
+        proto::MetricsSettings {
+            enabled: settings.enabled,
+            port: u32::from(settings.port),
+        }
+    }
+}
+
+impl TryFrom<proto::MetricsSettings> for mullvad_types::settings::MetricsSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::MetricsSettings) -> Result<Self, Self::Error> {
+        let port = u16::try_from(settings.port)
+            .ok()
+            .filter(|port| *port != 0)
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "invalid metrics port",
+            ))?;
+        Ok(mullvad_types::settings::MetricsSettings {
+            enabled: settings.enabled,
+            port,
         })
     }
 }
 
+impl From<mullvad_types::settings::profile::SettingsProfile> for proto::SettingsProfile {
+    fn from(profile: mullvad_types::settings::profile::SettingsProfile) -> Self {
+        proto::SettingsProfile {
+            name: profile.name,
+            patch: profile.patch,
+        }
+    }
+}
+
+impl From<proto::SettingsProfile> for mullvad_types::settings::profile::SettingsProfile {
+    fn from(profile: proto::SettingsProfile) -> Self {
+        mullvad_types::settings::profile::SettingsProfile {
+            name: profile.name,
+            patch: profile.patch,
+        }
+    }
+}
+
+impl From<mullvad_types::settings::profile::SettingDiff> for proto::SettingDiff {
+    fn from(diff: mullvad_types::settings::profile::SettingDiff) -> Self {
+        proto::SettingDiff {
+            key: diff.key,
+            current: diff.current,
+            profile: diff.profile,
+        }
+    }
+}
+
+impl From<proto::SettingDiff> for mullvad_types::settings::profile::SettingDiff {
+    fn from(diff: proto::SettingDiff) -> Self {
+        mullvad_types::settings::profile::SettingDiff {
+            key: diff.key,
+            current: diff.current,
+            profile: diff.profile,
+        }
+    }
+}
+
 pub fn try_bridge_state_from_i32(
     bridge_state: i32,
 ) -> Result<mullvad_types::relay_constraints::BridgeState, FromProtobufTypeError> {
@@ -224,13 +398,52 @@ pub fn try_bridge_state_from_i32(
     }
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
-impl From<proto::SplitTunnelSettings> for mullvad_types::settings::SplitTunnelSettings {
-    fn from(value: proto::SplitTunnelSettings) -> Self {
-        use mullvad_types::settings::{SplitApp, SplitTunnelSettings};
-        SplitTunnelSettings {
+#[cfg(not(target_os = "ios"))]
+impl TryFrom<proto::SplitTunnelSettings> for mullvad_types::settings::SplitTunnelSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(value: proto::SplitTunnelSettings) -> Result<Self, Self::Error> {
+        use mullvad_types::settings::{SplitApp, SplitTunnelMode, SplitTunnelSettings};
+        let mode = value
+            .mode
+            .map(SplitTunnelMode::try_from)
+            .transpose()?
+            .unwrap_or_default();
+        Ok(SplitTunnelSettings {
             enable_exclusions: value.enable_exclusions,
             apps: value.apps.into_iter().map(SplitApp::from).collect(),
+            mode,
+        })
+    }
+}
+
+impl From<mullvad_types::settings::SplitTunnelMode> for proto::SplitTunnelMode {
+    fn from(mode: mullvad_types::settings::SplitTunnelMode) -> Self {
+        use mullvad_types::settings::SplitTunnelMode;
+        let mode = match mode {
+            SplitTunnelMode::Exclude => proto::split_tunnel_mode::Mode::Exclude,
+            SplitTunnelMode::Include => proto::split_tunnel_mode::Mode::Include,
+        };
+        proto::SplitTunnelMode {
+            mode: i32::from(mode),
+        }
+    }
+}
+
+impl TryFrom<proto::SplitTunnelMode> for mullvad_types::settings::SplitTunnelMode {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(mode: proto::SplitTunnelMode) -> Result<Self, Self::Error> {
+        match proto::split_tunnel_mode::Mode::try_from(mode.mode) {
+            Ok(proto::split_tunnel_mode::Mode::Exclude) => {
+                Ok(mullvad_types::settings::SplitTunnelMode::Exclude)
+            }
+            Ok(proto::split_tunnel_mode::Mode::Include) => {
+                Ok(mullvad_types::settings::SplitTunnelMode::Include)
+            }
+            Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid split tunnel mode",
+            )),
         }
     }
 }
@@ -295,6 +508,11 @@ impl TryFrom<proto::TunnelOptions> for mullvad_types::settings::TunnelOptions {
                     .ok_or(FromProtobufTypeError::InvalidArgument(
                         "missing daita settings",
                     ))?,
+                connectivity_check: wireguard_options
+                    .connectivity_check
+                    .map(net::wireguard::ConnectivityCheckOptions::try_from)
+                    .transpose()?
+                    .unwrap_or_default(),
             },
             generic: net::GenericTunnelOptions {
                 enable_ipv6: generic_options.enable_ipv6,
@@ -357,11 +575,69 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
                         })
                     })
                     .collect::<Result<Vec<_>, _>>()?,
+                encrypted_servers: custom_options
+                    .encrypted_servers
+                    .into_iter()
+                    .map(EncryptedDnsServer::try_from)
+                    .collect::<Result<Vec<_>, _>>()?,
             },
+            split_dns_rules: options
+                .split_dns_rules
+                .into_iter()
+                .map(SplitDnsRule::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
         })
     }
 }
 
+impl TryFrom<proto::EncryptedDnsServer> for EncryptedDnsServer {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(server: proto::EncryptedDnsServer) -> Result<Self, Self::Error> {
+        let protocol = match proto::encrypted_dns_server::Protocol::try_from(server.protocol) {
+            Ok(proto::encrypted_dns_server::Protocol::Https) => EncryptedDnsProtocol::Https,
+            Ok(proto::encrypted_dns_server::Protocol::Tls) => EncryptedDnsProtocol::Tls,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid encrypted DNS protocol",
+                ));
+            }
+        };
+        if server.hostname.is_empty() {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "missing encrypted DNS server host name",
+            ));
+        }
+        let port = u16::try_from(server.port)
+            .ok()
+            .filter(|port| *port != 0)
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "invalid encrypted DNS server port",
+            ))?;
+
+        Ok(EncryptedDnsServer {
+            protocol,
+            hostname: server.hostname,
+            bootstrap_address: arg_from_str(&server.bootstrap_address, "invalid IP address")?,
+            port,
+        })
+    }
+}
+
+impl TryFrom<proto::SplitDnsRule> for SplitDnsRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: proto::SplitDnsRule) -> Result<Self, Self::Error> {
+        let servers = rule
+            .servers
+            .iter()
+            .map(|server| arg_from_str(server, "invalid IP address"))
+            .collect::<Result<Vec<_>, _>>()?;
+        SplitDnsRule::new(&rule.domain, servers)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid split DNS rule"))
+    }
+}
+
 impl From<Vec<mullvad_types::settings::Recent>> for proto::Recents {
     fn from(recents: Vec<mullvad_types::settings::Recent>) -> Self {
         proto::Recents {
@@ -385,3 +661,46 @@ impl From<mullvad_types::settings::Recent> for proto::Recent {
         }
     }
 }
+
+#[cfg(all(test, not(target_os = "ios")))]
+mod test {
+    use super::*;
+    use mullvad_types::settings::{SplitApp, SplitTunnelMode, SplitTunnelSettings};
+
+    #[test]
+    fn test_split_tunnel_settings_round_trip() {
+        for mode in [SplitTunnelMode::Exclude, SplitTunnelMode::Include] {
+            let settings = SplitTunnelSettings {
+                enable_exclusions: true,
+                apps: [SplitApp::from("/usr/bin/firefox".to_owned())]
+                    .into_iter()
+                    .collect(),
+                mode,
+            };
+            let proto = proto::SplitTunnelSettings {
+                enable_exclusions: settings.enable_exclusions,
+                apps: vec!["/usr/bin/firefox".to_owned()],
+                mode: Some(proto::SplitTunnelMode::from(mode)),
+            };
+            assert_eq!(SplitTunnelSettings::try_from(proto).unwrap(), settings);
+        }
+    }
+
+    /// Clients that do not know about the mode must get the old behavior.
+    #[test]
+    fn test_split_tunnel_mode_defaults_to_exclude() {
+        let proto = proto::SplitTunnelSettings {
+            enable_exclusions: true,
+            apps: vec![],
+            mode: None,
+        };
+        let settings = SplitTunnelSettings::try_from(proto).unwrap();
+        assert_eq!(settings.mode, SplitTunnelMode::Exclude);
+    }
+
+    #[test]
+    fn test_invalid_split_tunnel_mode() {
+        let proto = proto::SplitTunnelMode { mode: -1 };
+        assert!(SplitTunnelMode::try_from(proto).is_err());
+    }
+}
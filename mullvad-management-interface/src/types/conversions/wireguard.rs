@@ -1,7 +1,9 @@
-use super::FromProtobufTypeError;
+use super::{FromProtobufTypeError, arg_from_str};
 use crate::types::proto;
 use chrono::DateTime;
 use prost_types::Timestamp;
+use std::time::{Duration, SystemTime};
+use talpid_types::net::wireguard::{ConnectivityCheckOptions, ConnectivityStatus};
 
 impl From<mullvad_types::wireguard::PublicKey> for proto::PublicKey {
     fn from(public_key: mullvad_types::wireguard::PublicKey) -> Self {
@@ -92,3 +94,220 @@ impl From<proto::DaitaSettings> for mullvad_types::wireguard::DaitaSettings {
         }
     }
 }
+
+impl From<talpid_types::net::wireguard::TunnelStats> for proto::TunnelStats {
+    fn from(stats: talpid_types::net::wireguard::TunnelStats) -> Self {
+        proto::TunnelStats {
+            entry: stats.entry.map(proto::PeerStats::from),
+            exit: Some(proto::PeerStats::from(stats.exit)),
+            sampled_at: Some(Timestamp::from(stats.sampled_at)),
+            connectivity: stats.connectivity.map(proto::ConnectivityStatus::from),
+        }
+    }
+}
+
+impl From<talpid_types::net::wireguard::PeerStats> for proto::PeerStats {
+    fn from(stats: talpid_types::net::wireguard::PeerStats) -> Self {
+        proto::PeerStats {
+            public_key: stats.public_key.as_bytes().to_vec(),
+            tx_bytes: stats.tx_bytes,
+            rx_bytes: stats.rx_bytes,
+            last_handshake: stats.last_handshake.map(Timestamp::from),
+            tx_rate: stats.tx_rate,
+            rx_rate: stats.rx_rate,
+        }
+    }
+}
+
+impl TryFrom<proto::TunnelStats> for talpid_types::net::wireguard::TunnelStats {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(stats: proto::TunnelStats) -> Result<Self, Self::Error> {
+        let sampled_at = stats
+            .sampled_at
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing 'sampled_at' timestamp",
+            ))?;
+        Ok(talpid_types::net::wireguard::TunnelStats {
+            entry: stats
+                .entry
+                .map(talpid_types::net::wireguard::PeerStats::try_from)
+                .transpose()?,
+            exit: stats
+                .exit
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing exit peer stats",
+                ))
+                .and_then(talpid_types::net::wireguard::PeerStats::try_from)?,
+            sampled_at: timestamp_to_system_time(sampled_at)?,
+            connectivity: stats
+                .connectivity
+                .map(ConnectivityStatus::try_from)
+                .transpose()?,
+        })
+    }
+}
+
+impl TryFrom<proto::PeerStats> for talpid_types::net::wireguard::PeerStats {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(stats: proto::PeerStats) -> Result<Self, Self::Error> {
+        Ok(talpid_types::net::wireguard::PeerStats {
+            public_key: super::bytes_to_pubkey(&stats.public_key)?,
+            tx_bytes: stats.tx_bytes,
+            rx_bytes: stats.rx_bytes,
+            last_handshake: stats
+                .last_handshake
+                .map(timestamp_to_system_time)
+                .transpose()?,
+            tx_rate: stats.tx_rate,
+            rx_rate: stats.rx_rate,
+        })
+    }
+}
+
+impl From<ConnectivityStatus> for proto::ConnectivityStatus {
+    fn from(status: ConnectivityStatus) -> Self {
+        proto::ConnectivityStatus {
+            last_rx: status.last_rx.map(Timestamp::from),
+            probing_since: status.probing_since.map(Timestamp::from),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectivityStatus> for ConnectivityStatus {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(status: proto::ConnectivityStatus) -> Result<Self, Self::Error> {
+        Ok(ConnectivityStatus {
+            last_rx: status.last_rx.map(timestamp_to_system_time).transpose()?,
+            probing_since: status
+                .probing_since
+                .map(timestamp_to_system_time)
+                .transpose()?,
+        })
+    }
+}
+
+impl From<ConnectivityCheckOptions> for proto::ConnectivityCheckOptions {
+    fn from(options: ConnectivityCheckOptions) -> Self {
+        let to_proto = |duration: Duration| {
+            prost_types::Duration::try_from(duration).unwrap_or(prost_types::Duration {
+                seconds: i64::MAX,
+                nanos: 0,
+            })
+        };
+        proto::ConnectivityCheckOptions {
+            rx_timeout: Some(to_proto(options.rx_timeout)),
+            traffic_timeout: Some(to_proto(options.traffic_timeout)),
+            probe_timeout: Some(to_proto(options.probe_timeout)),
+            probes: options.probes.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Missing timeouts are replaced by their default values.
+impl TryFrom<proto::ConnectivityCheckOptions> for ConnectivityCheckOptions {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(options: proto::ConnectivityCheckOptions) -> Result<Self, Self::Error> {
+        let default = ConnectivityCheckOptions::default();
+        let from_proto = |duration: Option<prost_types::Duration>, default: Duration| {
+            duration
+                .map(Duration::try_from)
+                .transpose()
+                .map(|duration| duration.unwrap_or(default))
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))
+        };
+        Ok(ConnectivityCheckOptions {
+            rx_timeout: from_proto(options.rx_timeout, default.rx_timeout)?,
+            traffic_timeout: from_proto(options.traffic_timeout, default.traffic_timeout)?,
+            probe_timeout: from_proto(options.probe_timeout, default.probe_timeout)?,
+            probes: options
+                .probes
+                .iter()
+                .map(|probe| arg_from_str(probe, "invalid connectivity probe"))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+fn timestamp_to_system_time(timestamp: Timestamp) -> Result<SystemTime, FromProtobufTypeError> {
+    SystemTime::try_from(timestamp)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::wireguard::{PeerStats, PublicKey, TunnelStats};
+
+    fn peer_stats(key: u8) -> PeerStats {
+        PeerStats {
+            public_key: PublicKey::from([key; 32]),
+            tx_bytes: 1000,
+            rx_bytes: 2000,
+            last_handshake: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            tx_rate: Some(12.5),
+            rx_rate: None,
+        }
+    }
+
+    #[test]
+    fn test_tunnel_stats_round_trip() {
+        let sampled_at = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_010_500);
+        let stats = [
+            TunnelStats {
+                entry: None,
+                exit: peer_stats(1),
+                sampled_at,
+                connectivity: None,
+            },
+            TunnelStats {
+                entry: Some(peer_stats(1)),
+                exit: peer_stats(2),
+                sampled_at,
+                connectivity: Some(ConnectivityStatus {
+                    last_rx: Some(sampled_at - Duration::from_secs(3)),
+                    probing_since: None,
+                }),
+            },
+        ];
+
+        for stats in stats {
+            let proto = proto::TunnelStats::from(stats.clone());
+            assert_eq!(TunnelStats::try_from(proto).unwrap(), stats);
+        }
+    }
+
+    #[test]
+    fn test_invalid_tunnel_stats() {
+        let valid = proto::TunnelStats::from(TunnelStats {
+            entry: None,
+            exit: peer_stats(1),
+            sampled_at: SystemTime::UNIX_EPOCH,
+            connectivity: None,
+        });
+
+        let invalid = [
+            proto::TunnelStats {
+                sampled_at: None,
+                ..valid.clone()
+            },
+            proto::TunnelStats {
+                exit: None,
+                ..valid.clone()
+            },
+            proto::TunnelStats {
+                exit: Some(proto::PeerStats {
+                    public_key: vec![0; 16],
+                    ..valid.exit.clone().unwrap()
+                }),
+                ..valid
+            },
+        ];
+        for stats in invalid {
+            assert!(TunnelStats::try_from(stats).is_err());
+        }
+    }
+}
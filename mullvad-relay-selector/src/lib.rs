@@ -11,5 +11,6 @@ pub use error::Error;
 pub use relay_selector::{
     AdditionalRelayConstraints, AdditionalWireguardConstraints, GetRelay, OPENVPN_RETRY_ORDER,
     RelaySelector, SelectedBridge, SelectedObfuscator, SelectorConfig, WIREGUARD_RETRY_ORDER,
-    detailer, matcher, matcher::filter_matching_relay_list, query, relays::WireguardConfig,
+    detailer, latency, matcher, matcher::filter_matching_relay_list, query,
+    relays::WireguardConfig,
 };
@@ -8,7 +8,7 @@ use std::{
 use mullvad_types::{
     constraints::Constraint,
     endpoint::MullvadWireguardEndpoint,
-    relay_constraints::{ShadowsocksSettings, Udp2TcpObfuscationSettings},
+    relay_constraints::{RelaySelectionMode, ShadowsocksSettings, Udp2TcpObfuscationSettings},
     relay_list::Relay,
 };
 use rand::{
@@ -18,7 +18,10 @@ use rand::{
 };
 use talpid_types::net::{IpVersion, obfuscation::ObfuscatorConfig};
 
-use crate::SelectedObfuscator;
+use crate::{
+    SelectedObfuscator,
+    relay_selector::latency::{LOWEST_LATENCY_CANDIDATES, RelayLatencies},
+};
 
 /// Port ranges available for WireGuard relays that have extra IPs for Shadowsocks.
 /// For relays that have no additional IPs, only ports provided by the relay list are available.
@@ -45,6 +48,40 @@ pub fn pick_random_relay(relays: &[Relay]) -> Option<&Relay> {
     pick_random_relay_weighted(relays.iter(), |relay| relay.weight)
 }
 
+/// Picks a relay according to `selection_mode`.
+///
+/// With [`RelaySelectionMode::LowestLatency`], the pick is restricted to the
+/// [`LOWEST_LATENCY_CANDIDATES`] relays with the lowest latency in `latencies`. Otherwise, or if
+/// none of the relays have been measured, this is equivalent to [pick_random_relay].
+pub fn pick_relay(
+    relays: &[Relay],
+    selection_mode: RelaySelectionMode,
+    latencies: &RelayLatencies,
+) -> Option<Relay> {
+    match selection_mode {
+        RelaySelectionMode::Random => pick_random_relay(relays).cloned(),
+        RelaySelectionMode::LowestLatency => {
+            let fastest = latencies.fastest(relays, LOWEST_LATENCY_CANDIDATES);
+            pick_random_relay(&fastest).cloned()
+        }
+    }
+}
+
+/// Picks a relay using [pick_relay], but don't pick `exclude`.
+pub fn pick_relay_excluding(
+    relays: &[Relay],
+    exclude: &Relay,
+    selection_mode: RelaySelectionMode,
+    latencies: &RelayLatencies,
+) -> Option<Relay> {
+    let relays: Vec<Relay> = relays
+        .iter()
+        .filter(|&relay| relay != exclude)
+        .cloned()
+        .collect();
+    pick_relay(&relays, selection_mode, latencies)
+}
+
 /// Pick a random relay from the given slice. Will return `None` if the given slice is empty.
 /// If all of the relays have a weight of 0, one will be picked at random without bias,
 /// otherwise roulette wheel selection will be used to pick only relays with non-zero
@@ -137,7 +174,11 @@ pub fn get_shadowsocks_obfuscator(
     )?;
 
     Ok(SelectedObfuscator {
-        config: ObfuscatorConfig::Shadowsocks { endpoint },
+        config: ObfuscatorConfig::Shadowsocks {
+            endpoint,
+            password: None,
+            wireguard_port: None,
+        },
         relay,
     })
 }
@@ -155,6 +196,7 @@ pub fn get_quic_obfuscator(relay: Relay, ip_version: IpVersion) -> Option<Select
             hostname,
             endpoint,
             auth_token,
+            wireguard_port: None,
         }
     };
 
@@ -0,0 +1,136 @@
+//! Latency measurements used by the relay selector when the user prefers
+//! [`RelaySelectionMode::LowestLatency`].
+//!
+//! The relay selector does not measure anything by itself. Instead, it is handed a
+//! [`LatencyProber`] by its owner (e.g. the daemon) through
+//! [`RelaySelector::measure_latencies`], and caches the results until they expire.
+//!
+//! [`RelaySelectionMode::LowestLatency`]: mullvad_types::relay_constraints::RelaySelectionMode::LowestLatency
+//! [`RelaySelector::measure_latencies`]: crate::RelaySelector::measure_latencies
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use itertools::Itertools;
+use mullvad_types::{location::Hostname, relay_list::Relay};
+
+/// How long a latency measurement is considered valid.
+pub const LATENCY_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// The number of relays with the lowest latency to pick among. Picking among a few relays rather
+/// than always using the fastest one spreads the load between relays which are about equally good.
+pub const LOWEST_LATENCY_CANDIDATES: usize = 5;
+
+/// The maximum number of relays to probe at once.
+pub(crate) const PROBE_CONCURRENCY: usize = 8;
+
+/// Measures the round-trip time to a relay.
+///
+/// Implementations are expected to block until the measurement has completed or timed out.
+pub trait LatencyProber: Send + Sync {
+    /// Return the round-trip time to `relay`, or `None` if the relay could not be reached.
+    fn probe(&self, relay: &Relay) -> Option<Duration>;
+}
+
+impl<F> LatencyProber for F
+where
+    F: Fn(&Relay) -> Option<Duration> + Send + Sync,
+{
+    fn probe(&self, relay: &Relay) -> Option<Duration> {
+        self(relay)
+    }
+}
+
+/// A single latency measurement.
+#[derive(Debug, Clone, Copy)]
+struct Measurement {
+    latency: Duration,
+    measured_at: SystemTime,
+}
+
+/// Cache of latency measurements, keyed by relay hostname.
+#[derive(Debug, Clone)]
+pub struct RelayLatencies {
+    measurements: HashMap<Hostname, Measurement>,
+    expiry: Duration,
+}
+
+impl Default for RelayLatencies {
+    fn default() -> Self {
+        Self::new(LATENCY_EXPIRY)
+    }
+}
+
+impl RelayLatencies {
+    /// Create an empty cache where measurements are valid for `expiry`.
+    pub fn new(expiry: Duration) -> Self {
+        RelayLatencies {
+            measurements: HashMap::new(),
+            expiry,
+        }
+    }
+
+    /// Record that `hostname` had a round-trip time of `latency` at `measured_at`.
+    pub fn insert(
+        &mut self,
+        hostname: impl Into<Hostname>,
+        latency: Duration,
+        measured_at: SystemTime,
+    ) {
+        self.measurements.insert(
+            hostname.into(),
+            Measurement {
+                latency,
+                measured_at,
+            },
+        );
+    }
+
+    /// Return the latency of `hostname`, unless it has never been measured or the measurement has
+    /// expired.
+    pub fn get(&self, hostname: &str) -> Option<Duration> {
+        let measurement = self.measurements.get(hostname)?;
+        let age = SystemTime::now()
+            .duration_since(measurement.measured_at)
+            .unwrap_or(Duration::ZERO);
+        (age < self.expiry).then_some(measurement.latency)
+    }
+
+    /// Whether `hostname` lacks a valid measurement.
+    pub fn is_stale(&self, hostname: &str) -> bool {
+        self.get(hostname).is_none()
+    }
+
+    /// Remove all expired measurements.
+    pub fn prune(&mut self) {
+        let now = SystemTime::now();
+        let expiry = self.expiry;
+        self.measurements.retain(|_, measurement| {
+            now.duration_since(measurement.measured_at)
+                .map(|age| age < expiry)
+                .unwrap_or(true)
+        });
+    }
+
+    /// Return the (at most) `n` relays in `relays` with the lowest latency.
+    ///
+    /// Relays without a valid measurement are never returned, unless none of `relays` has been
+    /// measured. In that case, all of `relays` are returned so that the caller can fall back to
+    /// picking among them as usual.
+    pub fn fastest(&self, relays: &[Relay], n: usize) -> Vec<Relay> {
+        let measured = relays
+            .iter()
+            .filter_map(|relay| Some((self.get(&relay.hostname)?, relay)))
+            .sorted_by_key(|(latency, _)| *latency)
+            .take(n)
+            .map(|(_, relay)| relay.clone())
+            .collect_vec();
+        if measured.is_empty() {
+            relays.to_vec()
+        } else {
+            measured
+        }
+    }
+}
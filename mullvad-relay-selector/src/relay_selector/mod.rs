@@ -2,12 +2,14 @@
 
 pub mod detailer;
 mod helpers;
+pub mod latency;
 pub mod matcher;
 mod parsed_relays;
 pub mod query;
 pub mod relays;
 
 use detailer::resolve_ip_version;
+use latency::{LatencyProber, PROBE_CONCURRENCY, RelayLatencies};
 use matcher::{filter_matching_bridges, filter_matching_relay_list};
 use parsed_relays::ParsedRelays;
 use relays::{Multihop, Singlehop, WireguardConfig};
@@ -31,8 +33,8 @@ use mullvad_types::{
     location::{Coordinates, Location},
     relay_constraints::{
         BridgeSettings, BridgeState, InternalBridgeConstraints, ObfuscationSettings,
-        OpenVpnConstraints, RelayConstraints, RelayOverride, RelaySettings, ResolvedBridgeSettings,
-        WireguardConstraints,
+        OpenVpnConstraints, RelayConstraints, RelayOverride, RelaySelectionMode, RelaySettings,
+        ResolvedBridgeSettings, RetryStep, WireguardConstraints,
     },
     relay_list::{Relay, RelayEndpointData, RelayList},
     settings::Settings,
@@ -84,6 +86,9 @@ pub static WIREGUARD_RETRY_ORDER: LazyLock<Vec<RelayQuery>> = LazyLock::new(|| {
 /// should prioritize on successive connection attempts. Note that these will *never* override user
 /// preferences. See [the documentation on `RelayQuery`][RelayQuery] for further details.
 ///
+/// Unlike [`WIREGUARD_RETRY_ORDER`], this cannot be replaced by [`SelectorConfig::retry_order`],
+/// since OpenVPN is being phased out.
+///
 /// This list should be kept in sync with the expected behavior defined in `docs/relay-selector.md`
 pub static OPENVPN_RETRY_ORDER: LazyLock<Vec<RelayQuery>> = LazyLock::new(|| {
     use query::builder::RelayQueryBuilder;
@@ -107,6 +112,7 @@ pub static OPENVPN_RETRY_ORDER: LazyLock<Vec<RelayQuery>> = LazyLock::new(|| {
 pub struct RelaySelector {
     config: Arc<Mutex<SelectorConfig>>,
     parsed_relays: Arc<Mutex<ParsedRelays>>,
+    latencies: Arc<Mutex<RelayLatencies>>,
 }
 
 #[derive(Clone)]
@@ -118,6 +124,7 @@ pub struct SelectorConfig {
     pub relay_overrides: Vec<RelayOverride>,
     // Wireguard specific data
     pub obfuscation_settings: ObfuscationSettings,
+    pub retry_order: Option<Vec<RetryStep>>,
     // OpenVPN specific data
     pub bridge_state: BridgeState,
     pub bridge_settings: BridgeSettings,
@@ -151,6 +158,7 @@ impl SelectorConfig {
             bridge_state: settings.bridge_state,
             bridge_settings: settings.bridge_settings.clone(),
             obfuscation_settings: settings.obfuscation_settings.clone(),
+            retry_order: settings.retry_order.clone(),
             custom_lists: settings.custom_lists.clone(),
             relay_overrides: settings.relay_overrides.clone(),
         }
@@ -278,6 +286,7 @@ impl Default for SelectorConfig {
             additional_constraints: AdditionalRelayConstraints::default(),
             bridge_settings: default_settings.bridge_settings,
             obfuscation_settings: default_settings.obfuscation_settings,
+            retry_order: default_settings.retry_order,
             bridge_state: default_settings.bridge_state,
             custom_lists: default_settings.custom_lists,
             relay_overrides: default_settings.relay_overrides,
@@ -396,6 +405,7 @@ impl<'a> TryFrom<NormalSelectorConfig<'a>> for RelayQuery {
             value.user_preferences.tunnel_protocol,
             wireguard_constraints,
             openvpn_constraints,
+            value.user_preferences.selection_mode,
         )
     }
 }
@@ -427,6 +437,7 @@ impl RelaySelector {
         RelaySelector {
             config: Arc::new(Mutex::new(config)),
             parsed_relays: Arc::new(Mutex::new(unsynchronized_parsed_relays)),
+            latencies: Arc::new(Mutex::new(RelayLatencies::default())),
         }
     }
 
@@ -438,6 +449,7 @@ impl RelaySelector {
                 &config.relay_overrides,
             ))),
             config: Arc::new(Mutex::new(config)),
+            latencies: Arc::new(Mutex::new(RelayLatencies::default())),
         }
     }
 
@@ -472,6 +484,87 @@ impl RelaySelector {
         self.parsed_relays.lock().unwrap().last_updated()
     }
 
+    /// Returns the relays whose latency should be measured for the current settings, excluding
+    /// relays that already have a valid measurement.
+    ///
+    /// Only the relays that the client would connect to directly are considered, i.e. exit relays
+    /// when using singlehop and entry relays when using multihop. If DAITA may use multihop
+    /// automatically, relays in any location are considered, since the entry may be anywhere.
+    /// Nothing needs to be measured unless the user has selected
+    /// [`RelaySelectionMode::LowestLatency`].
+    pub fn latency_probe_candidates(&self) -> Vec<Relay> {
+        let config = self.config.lock().unwrap();
+        let SpecializedSelectorConfig::Normal(normal_config) =
+            SpecializedSelectorConfig::from(&*config)
+        else {
+            return vec![];
+        };
+        if normal_config.user_preferences.selection_mode != RelaySelectionMode::LowestLatency {
+            return vec![];
+        }
+        let Ok(mut query) = RelayQuery::try_from(normal_config.clone()) else {
+            return vec![];
+        };
+        if query.tunnel_protocol() == TunnelType::Wireguard {
+            let entry_location = if query.using_daita() && query.use_multihop_if_necessary() {
+                Some(Constraint::Any)
+            } else if query.wireguard_constraints().multihop() {
+                Some(query.wireguard_constraints().entry_location.clone())
+            } else {
+                None
+            };
+            if let Some(entry_location) = entry_location {
+                if query.set_location(entry_location).is_err() {
+                    return vec![];
+                }
+            }
+        }
+
+        let relay_list = self.parsed_relays.lock().unwrap().parsed_list().clone();
+        let latencies = self.latencies.lock().unwrap();
+        filter_matching_relay_list(&query, &relay_list, normal_config.custom_lists)
+            .into_iter()
+            .filter(|relay| latencies.is_stale(&relay.hostname))
+            .collect()
+    }
+
+    /// Measure the latency to each relay returned by [`Self::latency_probe_candidates`] using
+    /// `prober`. The results are used when picking relays until they expire.
+    ///
+    /// This blocks until all relays have been probed.
+    pub fn measure_latencies(&self, prober: &impl LatencyProber) {
+        let candidates = self.latency_probe_candidates();
+        if candidates.is_empty() {
+            return;
+        }
+        log::debug!("Measuring latency to {} relays", candidates.len());
+
+        for chunk in candidates.chunks(PROBE_CONCURRENCY) {
+            let results = std::thread::scope(|scope| {
+                chunk
+                    .iter()
+                    .map(|relay| scope.spawn(move || (relay, prober.probe(relay))))
+                    .collect_vec()
+                    .into_iter()
+                    .filter_map(|handle| handle.join().ok())
+                    .collect_vec()
+            });
+
+            let mut latencies = self.latencies.lock().unwrap();
+            for (relay, latency) in results {
+                match latency {
+                    Some(latency) => {
+                        log::trace!("Latency to {}: {} ms", relay.hostname, latency.as_millis());
+                        latencies.insert(relay.hostname.clone(), latency, SystemTime::now());
+                    }
+                    None => log::trace!("Failed to measure latency to {}", relay.hostname),
+                }
+            }
+        }
+
+        self.latencies.lock().unwrap().prune();
+    }
+
     /// Returns a non-custom bridge based on the relay and bridge constraints, ignoring the bridge
     /// state.
     pub fn get_bridge_forced(&self) -> Option<Shadowsocks> {
@@ -521,7 +614,8 @@ impl RelaySelector {
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
                 let relay_list = &self.parsed_relays.lock().unwrap().parsed_list().clone();
-                Self::get_relay_inner(&query, relay_list, normal_config.custom_lists)
+                let latencies = &self.latencies.lock().unwrap().clone();
+                Self::get_relay_inner(&query, relay_list, normal_config.custom_lists, latencies)
             }
         }
     }
@@ -542,15 +636,36 @@ impl RelaySelector {
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
                 let tunnel_protocol = normal_config.user_preferences.tunnel_protocol;
+                let retry_order = config_guard.retry_order.clone();
                 drop(config_guard);
 
-                match tunnel_protocol {
-                    TunnelType::Wireguard => self.get_relay_with_custom_params(
+                match (tunnel_protocol, retry_order) {
+                    (TunnelType::Wireguard, Some(retry_order)) => {
+                        let retry_order: Vec<_> =
+                            retry_order.into_iter().map(RelayQuery::from).collect();
+                        self.get_relay_with_custom_params(
+                            retry_attempt,
+                            &retry_order,
+                            runtime_ip_availability,
+                        )
+                        .or_else(|_| {
+                            log::warn!(
+                                "No relay matches the configured retry order. Using the default \
+                                 retry order"
+                            );
+                            self.get_relay_with_custom_params(
+                                retry_attempt,
+                                &WIREGUARD_RETRY_ORDER,
+                                runtime_ip_availability,
+                            )
+                        })
+                    }
+                    (TunnelType::Wireguard, None) => self.get_relay_with_custom_params(
                         retry_attempt,
                         &WIREGUARD_RETRY_ORDER,
                         runtime_ip_availability,
                     ),
-                    TunnelType::OpenVpn => self.get_relay_with_custom_params(
+                    (TunnelType::OpenVpn, _) => self.get_relay_with_custom_params(
                         retry_attempt,
                         &OPENVPN_RETRY_ORDER,
                         runtime_ip_availability,
@@ -560,6 +675,29 @@ impl RelaySelector {
         }
     }
 
+    /// Returns whether any step in `retry_order` is compatible with the current constraints.
+    /// Incompatible steps are skipped when selecting a relay. Since the retry order only applies to
+    /// WireGuard, it is always considered compatible with other constraints.
+    pub fn is_compatible_retry_order(&self, retry_order: &[RetryStep]) -> bool {
+        let config_guard = self.config.lock().unwrap();
+        let SpecializedSelectorConfig::Normal(normal_config) =
+            SpecializedSelectorConfig::from(&*config_guard)
+        else {
+            return true;
+        };
+        let Ok(user_query) = RelayQuery::try_from(normal_config) else {
+            return false;
+        };
+        if user_query.tunnel_protocol() != TunnelType::Wireguard {
+            return true;
+        }
+        retry_order.iter().any(|step| {
+            RelayQuery::from(*step)
+                .intersection(user_query.clone())
+                .is_some()
+        })
+    }
+
     /// Returns a random relay and relay endpoint matching the current constraints defined by
     /// `retry_order` corresponding to `retry_attempt`.
     pub fn get_relay_with_custom_params(
@@ -579,6 +717,7 @@ impl RelaySelector {
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
                 let relay_list = self.parsed_relays.lock().unwrap().parsed_list().clone();
+                let latencies = self.latencies.lock().unwrap().clone();
                 // Merge user preferences with the relay selector's default preferences.
                 let query = Self::pick_and_merge_query(
                     retry_attempt,
@@ -586,8 +725,9 @@ impl RelaySelector {
                     runtime_ip_availability,
                     &normal_config,
                     &relay_list,
+                    &latencies,
                 )?;
-                Self::get_relay_inner(&query, &relay_list, normal_config.custom_lists)
+                Self::get_relay_inner(&query, &relay_list, normal_config.custom_lists, &latencies)
             }
         }
     }
@@ -612,6 +752,7 @@ impl RelaySelector {
         runtime_ip_availability: IpAvailability,
         user_config: &NormalSelectorConfig<'_>,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Result<RelayQuery, Error> {
         let mut user_query = RelayQuery::try_from(user_config.clone())?;
         apply_ip_availability(runtime_ip_availability, &mut user_query)?;
@@ -619,7 +760,7 @@ impl RelaySelector {
         retry_order
             .iter()
             .filter_map(|query| query.clone().intersection(user_query.clone()))
-            .filter(|query| Self::get_relay_inner(query, parsed_relays, user_config.custom_lists).is_ok())
+            .filter(|query| Self::get_relay_inner(query, parsed_relays, user_config.custom_lists, latencies).is_ok())
             .cycle() // If the above filters remove all relays, cycle will also return an empty iterator
             .nth(retry_attempt)
             .ok_or(Error::NoRelay)
@@ -634,6 +775,7 @@ impl RelaySelector {
     /// - `config`: Configuration settings that influence relay selection, including bridge state
     ///   and custom lists.
     /// - `parsed_relays`: The complete set of parsed relays available for selection.
+    /// - `latencies`: Latency measurements used if the query prefers relays with low latency.
     ///
     /// # Returns
     /// * A randomly selected relay that meets the specified constraints (and a random bridge/entry
@@ -645,12 +787,15 @@ impl RelaySelector {
         query: &RelayQuery,
         parsed_relays: &RelayList,
         custom_lists: &CustomListsSettings,
+        latencies: &RelayLatencies,
     ) -> Result<GetRelay, Error> {
         match query.tunnel_protocol() {
             TunnelType::Wireguard => {
-                Self::get_wireguard_relay_inner(query, custom_lists, parsed_relays)
+                Self::get_wireguard_relay_inner(query, custom_lists, parsed_relays, latencies)
+            }
+            TunnelType::OpenVpn => {
+                Self::get_openvpn_relay(query, custom_lists, parsed_relays, latencies)
             }
-            TunnelType::OpenVpn => Self::get_openvpn_relay(query, custom_lists, parsed_relays),
         }
     }
 
@@ -659,6 +804,7 @@ impl RelaySelector {
         query: &RelayQuery,
         parsed_relays: &RelayList,
         custom_lists: &CustomListsSettings,
+        latencies: &RelayLatencies,
     ) -> Result<GetRelay, Error> {
         // FIXME: A bit of defensive programming - calling `get_wireguard_relay_inner` with a query
         // that doesn't specify Wireguard as the desired tunnel type is not valid and will
@@ -666,7 +812,7 @@ impl RelaySelector {
         // nicer to lift this invariant to be checked by the type system instead.
         let mut query = query.clone();
         query.set_tunnel_protocol(TunnelType::Wireguard)?;
-        Self::get_wireguard_relay_inner(&query, custom_lists, parsed_relays)
+        Self::get_wireguard_relay_inner(&query, custom_lists, parsed_relays, latencies)
     }
 
     /// Derive a valid relay configuration from `query`.
@@ -686,9 +832,11 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Result<GetRelay, Error> {
         assert_eq!(query.tunnel_protocol(), TunnelType::Wireguard);
-        let inner = Self::get_wireguard_relay_config(query, custom_lists, parsed_relays)?;
+        let inner =
+            Self::get_wireguard_relay_config(query, custom_lists, parsed_relays, latencies)?;
         let endpoint = Self::get_wireguard_endpoint(query, parsed_relays, &inner)?;
         let obfuscator =
             Self::get_wireguard_obfuscator(query, inner.clone(), &endpoint, parsed_relays)?;
@@ -710,9 +858,15 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Result<WireguardConfig, Error> {
         let inner = if query.singlehop() {
-            match Self::get_wireguard_singlehop_config(query, custom_lists, parsed_relays) {
+            match Self::get_wireguard_singlehop_config(
+                query,
+                custom_lists,
+                parsed_relays,
+                latencies,
+            ) {
                 Some(exit) => WireguardConfig::from(exit),
                 None => {
                     // If we found no matching relays because DAITA was enabled, and
@@ -724,6 +878,7 @@ impl RelaySelector {
                             query,
                             custom_lists,
                             parsed_relays,
+                            latencies,
                         )?;
                         WireguardConfig::from(multihop)
                     } else {
@@ -737,9 +892,14 @@ impl RelaySelector {
             // entry relay with smarting routing enabled, even if multihop is turned on
             // Also implied: Multihop is enabled.
             let multihop = if query.using_daita() && query.use_multihop_if_necessary() {
-                Self::get_wireguard_auto_multihop_config(query, custom_lists, parsed_relays)?
+                Self::get_wireguard_auto_multihop_config(
+                    query,
+                    custom_lists,
+                    parsed_relays,
+                    latencies,
+                )?
             } else {
-                Self::get_wireguard_multihop_config(query, custom_lists, parsed_relays)?
+                Self::get_wireguard_multihop_config(query, custom_lists, parsed_relays, latencies)?
             };
             WireguardConfig::from(multihop)
         };
@@ -756,15 +916,18 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Option<Singlehop> {
         let candidates = filter_matching_relay_list(query, parsed_relays, custom_lists);
-        helpers::pick_random_relay(&candidates)
-            .cloned()
-            .map(Singlehop::new)
+        helpers::pick_relay(&candidates, query.selection_mode(), latencies).map(Singlehop::new)
     }
 
     /// Select a valid Wireguard exit relay, together with with an automatically chosen entry relay.
     ///
+    /// The entry relay is picked among those closest to the exit, unless the query prefers relays
+    /// with low latency and any of the entry candidates have been measured. In that case, the entry
+    /// is picked by latency regardless of its location.
+    ///
     /// # Returns
     /// * An `Err` if no entry/exit relay can be chosen
     /// * `Ok(Multihop)` otherwise
@@ -772,6 +935,7 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Result<Multihop, Error> {
         let mut exit_relay_query = query.clone();
 
@@ -782,16 +946,30 @@ impl RelaySelector {
 
         let exit_candidates =
             filter_matching_relay_list(&exit_relay_query, parsed_relays, custom_lists);
-        let exit = helpers::pick_random_relay(&exit_candidates).ok_or(Error::NoRelay)?;
+        let selection_mode = query.selection_mode();
+        let exit = helpers::pick_relay(&exit_candidates, selection_mode, latencies)
+            .ok_or(Error::NoRelay)?;
 
         // generate a list of potential entry relays, disregarding any location constraint
         let mut entry_query = query.clone();
         entry_query.set_location(Constraint::Any)?;
-        let mut entry_candidates =
-            filter_matching_relay_list(&entry_query, parsed_relays, custom_lists)
-                .into_iter()
-                .map(|entry| RelayWithDistance::new_with_distance_from(entry, &exit.location))
-                .collect_vec();
+        let entry_candidates =
+            filter_matching_relay_list(&entry_query, parsed_relays, custom_lists);
+
+        let any_entry_measured = entry_candidates
+            .iter()
+            .any(|relay| !latencies.is_stale(&relay.hostname));
+        if selection_mode == RelaySelectionMode::LowestLatency && any_entry_measured {
+            let entry =
+                helpers::pick_relay_excluding(&entry_candidates, &exit, selection_mode, latencies)
+                    .ok_or(Error::NoRelay)?;
+            return Ok(Multihop::new(entry, exit));
+        }
+
+        let mut entry_candidates = entry_candidates
+            .into_iter()
+            .map(|entry| RelayWithDistance::new_with_distance_from(entry, &exit.location))
+            .collect_vec();
 
         // sort entry relay candidates by distance, and pick one from those that are closest
         entry_candidates.sort_unstable_by(|a, b| a.distance.total_cmp(&b.distance));
@@ -807,12 +985,15 @@ impl RelaySelector {
             .map(|relay_with_distance| relay_with_distance.relay)
             .collect_vec();
         let entry =
-            helpers::pick_random_relay_excluding(&entry_candidates, exit).ok_or(Error::NoRelay)?;
+            helpers::pick_random_relay_excluding(&entry_candidates, &exit).ok_or(Error::NoRelay)?;
 
-        Ok(Multihop::new(entry.clone(), exit.clone()))
+        Ok(Multihop::new(entry.clone(), exit))
     }
 
     /// This function selects a valid entry and exit relay to be used in a multihop configuration.
+    /// Both relays are picked according to the query's [selection mode][RelayQuery::selection_mode].
+    /// Only the entry relays are measured when multihop is used, since the client connects to
+    /// them, so the exit is usually picked at random.
     ///
     /// # Returns
     /// * An `Err` if no exit relay can be chosen
@@ -823,6 +1004,7 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Result<Multihop, Error> {
         // Here, we modify the original query just a bit.
         // The actual query for an entry relay is identical as for an exit relay, with the
@@ -845,25 +1027,29 @@ impl RelaySelector {
         let entry_candidates =
             filter_matching_relay_list(&entry_relay_query, parsed_relays, custom_lists);
 
+        let selection_mode = query.selection_mode();
+        let pick_excluding = |relays: &[Relay], exclude: &Relay| {
+            helpers::pick_relay_excluding(relays, exclude, selection_mode, latencies)
+        };
+
         // We avoid picking the same relay for entry and exit by choosing one and excluding it when
         // choosing the other.
         let (exit, entry) = match (exit_candidates.as_slice(), entry_candidates.as_slice()) {
             // In the case where there is only one entry to choose from, we have to pick it before
             // the exit
             (exits, [entry]) if exits.contains(entry) => {
-                helpers::pick_random_relay_excluding(exits, entry).map(|exit| (exit, entry))
+                pick_excluding(exits, entry).map(|exit| (exit, entry.clone()))
             }
             // Vice versa for the case of only one exit
             ([exit], entries) if entries.contains(exit) => {
-                helpers::pick_random_relay_excluding(entries, exit).map(|entry| (exit, entry))
+                pick_excluding(entries, exit).map(|entry| (exit.clone(), entry))
             }
-            (exits, entries) => helpers::pick_random_relay(exits).and_then(|exit| {
-                helpers::pick_random_relay_excluding(entries, exit).map(|entry| (exit, entry))
-            }),
+            (exits, entries) => helpers::pick_relay(exits, selection_mode, latencies)
+                .and_then(|exit| pick_excluding(entries, &exit).map(|entry| (exit, entry))),
         }
         .ok_or(Error::NoRelay)?;
 
-        Ok(Multihop::new(entry.clone(), exit.clone()))
+        Ok(Multihop::new(entry, exit))
     }
 
     /// Constructs a [`MullvadEndpoint`] with details for how to connect to `relay`.
@@ -943,10 +1129,11 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Result<GetRelay, Error> {
         assert_eq!(query.tunnel_protocol(), TunnelType::OpenVpn);
-        let exit =
-            Self::choose_openvpn_relay(query, custom_lists, parsed_relays).ok_or(Error::NoRelay)?;
+        let exit = Self::choose_openvpn_relay(query, custom_lists, parsed_relays, latencies)
+            .ok_or(Error::NoRelay)?;
         let endpoint = Self::get_openvpn_endpoint(query, &exit, parsed_relays)?;
         let bridge = Self::get_openvpn_bridge(
             query,
@@ -1146,11 +1333,12 @@ impl RelaySelector {
         query: &RelayQuery,
         custom_lists: &CustomListsSettings,
         parsed_relays: &RelayList,
+        latencies: &RelayLatencies,
     ) -> Option<Relay> {
         // Filter among all valid relays
         let candidates = filter_matching_relay_list(query, parsed_relays, custom_lists);
         // Pick one of the valid relays.
-        helpers::pick_random_relay(&candidates).cloned()
+        helpers::pick_relay(&candidates, query.selection_mode(), latencies)
     }
 }
 
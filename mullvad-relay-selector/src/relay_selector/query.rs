@@ -35,8 +35,8 @@ use mullvad_types::{
     relay_constraints::{
         BridgeConstraints, BridgeSettings, BridgeState, BridgeType, LocationConstraint,
         ObfuscationSettings, OpenVpnConstraints, Ownership, Providers, RelayConstraints,
-        RelaySettings, SelectedObfuscation, ShadowsocksSettings, TransportPort,
-        Udp2TcpObfuscationSettings, WireguardConstraints, allowed_ip::AllowedIps,
+        RelaySelectionMode, RelaySettings, RetryStep, SelectedObfuscation, ShadowsocksSettings,
+        TransportPort, Udp2TcpObfuscationSettings, WireguardConstraints, allowed_ip::AllowedIps,
     },
     wireguard::QuantumResistantState,
 };
@@ -81,6 +81,7 @@ pub struct RelayQuery {
     tunnel_protocol: TunnelType,
     wireguard_constraints: WireguardRelayQuery,
     openvpn_constraints: OpenVpnRelayQuery,
+    selection_mode: RelaySelectionMode,
 }
 
 impl RelayQuery {
@@ -92,6 +93,7 @@ impl RelayQuery {
         tunnel_protocol: TunnelType,
         wireguard_constraints: WireguardRelayQuery,
         openvpn_constraints: OpenVpnRelayQuery,
+        selection_mode: RelaySelectionMode,
     ) -> Result<RelayQuery, Error> {
         let mut query = RelayQuery {
             location,
@@ -100,6 +102,7 @@ impl RelayQuery {
             tunnel_protocol,
             wireguard_constraints,
             openvpn_constraints,
+            selection_mode,
         };
         query.validate()?;
         Ok(query)
@@ -181,6 +184,10 @@ impl RelayQuery {
         self.set_if_valid(|query| query.wireguard_constraints = wireguard_constraints)
     }
 
+    pub fn selection_mode(&self) -> RelaySelectionMode {
+        self.selection_mode
+    }
+
     fn set_if_valid(&mut self, set_fn: impl FnOnce(&mut Self)) -> Result<(), Error> {
         let mut new = self.clone();
         (set_fn)(&mut new);
@@ -221,6 +228,7 @@ impl RelayQuery {
             tunnel_protocol: self.tunnel_protocol,
             wireguard_constraints: self.wireguard_constraints.into_constraints(),
             openvpn_constraints: self.openvpn_constraints.into_constraints(),
+            selection_mode: self.selection_mode,
         };
 
         (constraints, bridge_state, bridge_settings, obfuscation)
@@ -249,6 +257,7 @@ impl Default for RelayQuery {
             tunnel_protocol: TunnelType::default(),
             wireguard_constraints: WireguardRelayQuery::new(),
             openvpn_constraints: OpenVpnRelayQuery::new(),
+            selection_mode: RelaySelectionMode::Random,
         }
     }
 }
@@ -260,6 +269,39 @@ impl From<RelayQuery> for RelaySettings {
     }
 }
 
+impl From<RetryStep> for RelayQuery {
+    /// A WireGuard query for a step in a user-defined retry order. The port applies to the
+    /// obfuscation protocol if one is selected.
+    fn from(step: RetryStep) -> Self {
+        let mut wireguard_constraints = WireguardRelayQuery {
+            ip_version: step.ip_version,
+            ..WireguardRelayQuery::new()
+        };
+        wireguard_constraints.obfuscation = match step.obfuscation {
+            SelectedObfuscation::Auto => {
+                wireguard_constraints.port = step.port;
+                ObfuscationQuery::Auto
+            }
+            SelectedObfuscation::Off => {
+                wireguard_constraints.port = step.port;
+                ObfuscationQuery::Off
+            }
+            SelectedObfuscation::Udp2Tcp => {
+                ObfuscationQuery::Udp2tcp(Udp2TcpObfuscationSettings { port: step.port })
+            }
+            SelectedObfuscation::Shadowsocks => {
+                ObfuscationQuery::Shadowsocks(ShadowsocksSettings { port: step.port })
+            }
+            SelectedObfuscation::Quic => ObfuscationQuery::Quic,
+        };
+        RelayQuery {
+            tunnel_protocol: TunnelType::Wireguard,
+            wireguard_constraints,
+            ..RelayQuery::default()
+        }
+    }
+}
+
 /// A query for a relay with Wireguard-specific properties, such as `multihop` and [wireguard
 /// obfuscation][`SelectedObfuscation`].
 ///
@@ -522,8 +564,8 @@ pub mod builder {
     use mullvad_types::{
         constraints::Constraint,
         relay_constraints::{
-            BridgeConstraints, LocationConstraint, RelayConstraints, SelectedObfuscation,
-            ShadowsocksSettings, TransportPort, Udp2TcpObfuscationSettings,
+            BridgeConstraints, LocationConstraint, RelayConstraints, RelaySelectionMode,
+            SelectedObfuscation, ShadowsocksSettings, TransportPort, Udp2TcpObfuscationSettings,
         },
         wireguard::QuantumResistantState,
     };
@@ -575,6 +617,12 @@ pub mod builder {
             self
         }
 
+        /// Configure which [`RelaySelectionMode`] to use.
+        pub const fn selection_mode(mut self, selection_mode: RelaySelectionMode) -> Self {
+            self.query.selection_mode = selection_mode;
+            self
+        }
+
         /// Assemble the final [`RelayQuery`] that has been configured
         /// through `self`.
         pub fn build(mut self) -> RelayQuery {
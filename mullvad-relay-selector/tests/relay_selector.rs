@@ -3,10 +3,11 @@
 use std::{
     collections::HashSet,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    sync::LazyLock,
+    sync::{LazyLock, Mutex},
+    time::Duration,
 };
 use talpid_types::net::{
-    Endpoint, IpVersion,
+    Endpoint, IpAvailability, IpVersion,
     TransportProtocol::{Tcp, Udp},
     TunnelType,
     obfuscation::ObfuscatorConfig,
@@ -14,9 +15,12 @@ use talpid_types::net::{
 };
 
 use mullvad_relay_selector::{
-    Error, GetRelay, OPENVPN_RETRY_ORDER, RelaySelector, SelectedObfuscator, SelectorConfig,
-    WIREGUARD_RETRY_ORDER, WireguardConfig,
-    query::{BridgeQuery, ObfuscationQuery, OpenVpnRelayQuery, builder::RelayQueryBuilder},
+    AdditionalRelayConstraints, AdditionalWireguardConstraints, Error, GetRelay,
+    OPENVPN_RETRY_ORDER, RelaySelector, SelectedObfuscator, SelectorConfig, WIREGUARD_RETRY_ORDER,
+    WireguardConfig,
+    query::{
+        BridgeQuery, ObfuscationQuery, OpenVpnRelayQuery, RelayQuery, builder::RelayQueryBuilder,
+    },
 };
 use mullvad_types::{
     constraints::Constraint,
@@ -24,7 +28,8 @@ use mullvad_types::{
     location::Location,
     relay_constraints::{
         BridgeConstraints, BridgeState, GeographicLocationConstraint, Ownership, Providers,
-        RelayConstraints, RelayOverride, RelaySettings, TransportPort,
+        RelayConstraints, RelayOverride, RelaySelectionMode, RelaySettings, RetryStep,
+        TransportPort, WireguardConstraints,
     },
     relay_list::{
         BridgeEndpointData, Features, OpenVpnEndpoint, OpenVpnEndpointData, Quic, Relay,
@@ -517,6 +522,64 @@ fn test_openvpn_retry_order() {
     }
 }
 
+/// [`RetryStep::default_order`] describes the default retry order to users, so it must match
+/// [`WIREGUARD_RETRY_ORDER`].
+#[test]
+fn test_default_retry_steps() {
+    let retry_order: Vec<_> = RetryStep::default_order()
+        .into_iter()
+        .map(RelayQuery::from)
+        .collect();
+    assert_eq!(retry_order, *WIREGUARD_RETRY_ORDER);
+}
+
+/// Test that a retry order set by the user is used instead of [`WIREGUARD_RETRY_ORDER`], and that
+/// steps that conflict with the user's constraints are skipped.
+#[test]
+fn test_custom_retry_order() {
+    let mut relay_selector = default_relay_selector();
+    let retry_order: Vec<RetryStep> = vec![
+        "udp2tcp+ipv6".parse().unwrap(),
+        "quic".parse().unwrap(),
+        "shadowsocks".parse().unwrap(),
+    ];
+    relay_selector.set_config(SelectorConfig {
+        relay_settings: RelaySettings::Normal(RelayConstraints {
+            wireguard_constraints: WireguardConstraints {
+                ip_version: Constraint::Only(IpVersion::V4),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        retry_order: Some(retry_order.clone()),
+        ..Default::default()
+    });
+
+    assert!(relay_selector.is_compatible_retry_order(&retry_order));
+    assert!(!relay_selector.is_compatible_retry_order(&retry_order[..1]));
+
+    let obfuscators: Vec<_> = (0..4)
+        .map(|retry_attempt| {
+            match relay_selector.get_relay(retry_attempt, IpAvailability::Ipv4AndIpv6) {
+                Ok(GetRelay::Wireguard {
+                    obfuscator: Some(obfuscator),
+                    ..
+                }) => obfuscator.config,
+                relay => panic!("Expected an obfuscated WireGuard relay, got {relay:?}"),
+            }
+        })
+        .collect();
+    assert!(matches!(
+        obfuscators.as_slice(),
+        [
+            ObfuscatorConfig::Quic { .. },
+            ObfuscatorConfig::Shadowsocks { .. },
+            ObfuscatorConfig::Quic { .. },
+            ObfuscatorConfig::Shadowsocks { .. },
+        ]
+    ));
+}
+
 /// If a Wireguard relay is only specified by it's hostname (and not tunnel type), the relay
 /// selector should still return a relay of the correct tunnel type (Wireguard).
 #[test]
@@ -888,7 +951,7 @@ fn test_selecting_wireguard_over_shadowsocks_extra_ips() {
         GetRelay::Wireguard {
             obfuscator:
                 Some(SelectedObfuscator {
-                    config: ObfuscatorConfig::Shadowsocks { endpoint },
+                    config: ObfuscatorConfig::Shadowsocks { endpoint, .. },
                     ..
                 }),
             inner: WireguardConfig::Singlehop { exit },
@@ -964,7 +1027,7 @@ fn test_selecting_wireguard_ignore_extra_ips_override_v4() {
         GetRelay::Wireguard {
             obfuscator:
                 Some(SelectedObfuscator {
-                    config: ObfuscatorConfig::Shadowsocks { endpoint },
+                    config: ObfuscatorConfig::Shadowsocks { endpoint, .. },
                     ..
                 }),
             inner: WireguardConfig::Singlehop { exit },
@@ -1011,7 +1074,7 @@ fn test_selecting_wireguard_ignore_extra_ips_override_v6() {
         GetRelay::Wireguard {
             obfuscator:
                 Some(SelectedObfuscator {
-                    config: ObfuscatorConfig::Shadowsocks { endpoint },
+                    config: ObfuscatorConfig::Shadowsocks { endpoint, .. },
                     ..
                 }),
             inner: WireguardConfig::Singlehop { exit },
@@ -1206,6 +1269,131 @@ fn test_providers() {
     }
 }
 
+/// Verify that the relay selector prefers relays with low latency when the user asks for it,
+/// using latencies measured by an injected prober.
+#[test]
+fn test_lowest_latency_selection() {
+    const FAST_RELAY: &str = "se10-wireguard";
+    let mut relay_selector = default_relay_selector();
+    relay_selector.set_config(SelectorConfig {
+        relay_settings: RelaySettings::Normal(RelayConstraints {
+            location: Constraint::Only(GeographicLocationConstraint::country("se").into()),
+            selection_mode: RelaySelectionMode::LowestLatency,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    // Only the fast relay responds to probes, so it is the only relay with a known latency.
+    let probed = Mutex::new(HashSet::new());
+    let prober = |relay: &Relay| {
+        probed.lock().unwrap().insert(relay.hostname.clone());
+        (relay.hostname == FAST_RELAY).then_some(Duration::from_millis(10))
+    };
+    relay_selector.measure_latencies(&prober);
+    assert!(probed.lock().unwrap().contains(FAST_RELAY));
+
+    // Relays with a valid measurement should not be probed again.
+    assert!(
+        !relay_selector
+            .latency_probe_candidates()
+            .iter()
+            .any(|relay| relay.hostname == FAST_RELAY)
+    );
+
+    for _ in 0..100 {
+        let relay = relay_selector
+            .get_relay(0, talpid_types::net::IpAvailability::Ipv4)
+            .unwrap();
+        assert_eq!(unwrap_relay(relay).hostname, FAST_RELAY);
+    }
+}
+
+/// Latency should neither be measured nor affect the selection in the default selection mode.
+#[test]
+fn test_random_selection_ignores_latency() {
+    let relay_selector = default_relay_selector();
+    assert!(relay_selector.latency_probe_candidates().is_empty());
+
+    let prober = |_: &Relay| -> Option<Duration> { panic!("No relay should be probed") };
+    relay_selector.measure_latencies(&prober);
+}
+
+/// With multihop and a single entry candidate, the exit should still be picked by latency if it
+/// has been measured.
+#[test]
+fn test_lowest_latency_multihop_single_entry() {
+    const FAST_RELAY: &str = "se10-wireguard";
+    let mut relay_selector = default_relay_selector();
+    let constraints = RelayConstraints {
+        location: Constraint::Only(GeographicLocationConstraint::country("se").into()),
+        selection_mode: RelaySelectionMode::LowestLatency,
+        ..Default::default()
+    };
+
+    // Measure the exit candidates while using singlehop
+    relay_selector.set_config(SelectorConfig {
+        relay_settings: RelaySettings::Normal(constraints.clone()),
+        ..Default::default()
+    });
+    let prober =
+        |relay: &Relay| (relay.hostname == FAST_RELAY).then_some(Duration::from_millis(10));
+    relay_selector.measure_latencies(&prober);
+
+    relay_selector.set_config(SelectorConfig {
+        relay_settings: RelaySettings::Normal(RelayConstraints {
+            wireguard_constraints: WireguardConstraints {
+                use_multihop: true,
+                entry_location: Constraint::Only(DAITA_RELAY_LOCATION.clone().into()),
+                ..Default::default()
+            },
+            ..constraints
+        }),
+        ..Default::default()
+    });
+
+    for _ in 0..100 {
+        let relay = relay_selector.get_relay(0, IpAvailability::Ipv4).unwrap();
+        assert_eq!(unwrap_relay(relay).hostname, FAST_RELAY);
+    }
+}
+
+/// When DAITA uses multihop automatically, the entry should be picked by latency among the DAITA
+/// relays in any location.
+#[test]
+fn test_lowest_latency_daita_auto_multihop() {
+    const FAST_RELAY: &str = "se11-wireguard";
+    let mut relay_selector = default_relay_selector();
+    relay_selector.set_config(SelectorConfig {
+        relay_settings: RelaySettings::Normal(RelayConstraints {
+            location: Constraint::Only(NON_DAITA_RELAY_LOCATION.clone().into()),
+            selection_mode: RelaySelectionMode::LowestLatency,
+            ..Default::default()
+        }),
+        additional_constraints: AdditionalRelayConstraints {
+            wireguard: AdditionalWireguardConstraints {
+                daita: true,
+                daita_use_multihop_if_necessary: true,
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    });
+
+    let candidates = relay_selector.latency_probe_candidates();
+    assert!(candidates.iter().all(supports_daita));
+    assert!(candidates.iter().any(|relay| relay.hostname == FAST_RELAY));
+
+    let prober =
+        |relay: &Relay| (relay.hostname == FAST_RELAY).then_some(Duration::from_millis(10));
+    relay_selector.measure_latencies(&prober);
+
+    for _ in 0..100 {
+        let relay = relay_selector.get_relay(0, IpAvailability::Ipv4).unwrap();
+        assert_eq!(unwrap_entry_relay(relay).hostname, FAST_RELAY);
+    }
+}
+
 /// Verify that bridges are automatically used when bridge mode is set to automatic.
 #[test]
 fn test_openvpn_auto_bridge() {
@@ -1,5 +1,6 @@
 use clap::Parser;
 use mullvad_api::{ApiEndpoint, DEVICE_NOT_FOUND, proxy::ApiConnectionMode};
+use mullvad_daemon::device::{DeviceCacher, key_storage::KeyStorage};
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_version::Version;
 use std::{path::PathBuf, process, str::FromStr, sync::LazyLock, time::Duration};
@@ -103,6 +104,8 @@ enum Cli {
     ResetFirewall,
     /// Remove the current device from the active account
     RemoveDevice,
+    /// Store the device private key in plain text, as older versions expect
+    RestorePlainDeviceKey,
     /// Checks whether the given version is older than the current version
     IsOlderVersion {
         /// Version string to compare the current version
@@ -122,6 +125,7 @@ async fn main() {
         Cli::PrepareRestart => prepare_restart().await,
         Cli::ResetFirewall => reset_firewall().await,
         Cli::RemoveDevice => remove_device().await,
+        Cli::RestorePlainDeviceKey => restore_plain_device_key().await,
         Cli::IsOlderVersion { old_version } => {
             match is_older_version(&old_version) {
                 // Returning exit status
@@ -155,6 +159,18 @@ async fn prepare_restart() -> Result<(), Error> {
         .await
         .map_err(Error::RpcConnectionError)?;
     rpc.prepare_restart().await.map_err(Error::DaemonRpcError)?;
+
+    // This is run by the installers before the installed version is replaced, including by older
+    // versions that cannot read an encrypted device key. The daemon encrypts the key again when
+    // it starts. Windows is excluded since downgrading it performs a full uninstall.
+    #[cfg(not(target_os = "windows"))]
+    {
+        let (_cache_path, settings_path) = get_paths()?;
+        DeviceCacher::with_key_storage(&settings_path, KeyStorage::Plain)
+            .await
+            .map_err(Error::WriteDeviceCacheError)?;
+    }
+
     Ok(())
 }
 
@@ -175,7 +191,7 @@ async fn reset_firewall() -> Result<(), Error> {
 
 async fn remove_device() -> Result<(), Error> {
     let (cache_path, settings_path) = get_paths()?;
-    let (cacher, state) = mullvad_daemon::device::DeviceCacher::new(&settings_path)
+    let (cacher, state) = DeviceCacher::new(&settings_path)
         .await
         .map_err(Error::ReadDeviceCacheError)?;
     if let Some(device) = state.into_device() {
@@ -217,6 +233,20 @@ async fn remove_device() -> Result<(), Error> {
     Ok(())
 }
 
+async fn restore_plain_device_key() -> Result<(), Error> {
+    // Ensure that the daemon isn't running
+    if MullvadProxyClient::new().await.is_ok() {
+        return Err(Error::DaemonIsRunning);
+    }
+
+    let (_cache_path, settings_path) = get_paths()?;
+    // The device cache is migrated when it is opened
+    DeviceCacher::with_key_storage(&settings_path, KeyStorage::Plain)
+        .await
+        .map_err(Error::ReadDeviceCacheError)?;
+    Ok(())
+}
+
 fn get_paths() -> Result<(PathBuf, PathBuf), Error> {
     let cache_path = mullvad_paths::cache_dir().map_err(Error::CachePathError)?;
     let settings_path = mullvad_paths::settings_dir().map_err(Error::SettingsPathError)?;
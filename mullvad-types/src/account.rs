@@ -1,5 +1,6 @@
 use chrono::{DateTime, offset::Utc};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Account identifier used for authentication.
 pub type AccountNumber = String;
@@ -29,6 +30,79 @@ impl AccountData {
     }
 }
 
+/// Emitted by the daemon when the time left on the account drops below one of the thresholds in
+/// [ExpiryReminders].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AccountExpiryWarning {
+    pub expiry: DateTime<Utc>,
+    /// Time left on the account when the warning was emitted. Zero if the account has expired.
+    pub remaining: Duration,
+}
+
+impl AccountExpiryWarning {
+    /// Return true if the account has no time left.
+    pub fn is_expired(&self) -> bool {
+        self.remaining.is_zero()
+    }
+}
+
+/// Amounts of time left on the account at which the daemon emits [AccountExpiryWarning]s. A
+/// threshold of zero means that a warning is emitted when the account expires.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Duration>")]
+pub struct ExpiryReminders(Vec<Duration>);
+
+impl ExpiryReminders {
+    /// Maximum number of thresholds
+    pub const MAX_THRESHOLDS: usize = 8;
+    /// Largest allowed threshold
+    pub const MAX_THRESHOLD: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+    /// Create reminders from a list of thresholds, in any order. Duplicates are removed.
+    pub fn new(mut thresholds: Vec<Duration>) -> Result<Self, ExpiryRemindersError> {
+        thresholds.sort_unstable_by(|a, b| b.cmp(a));
+        thresholds.dedup();
+        if thresholds.len() > Self::MAX_THRESHOLDS {
+            return Err(ExpiryRemindersError::TooMany);
+        }
+        if thresholds
+            .first()
+            .is_some_and(|max| *max > Self::MAX_THRESHOLD)
+        {
+            return Err(ExpiryRemindersError::TooLarge);
+        }
+        Ok(ExpiryReminders(thresholds))
+    }
+
+    /// Return the thresholds, largest first.
+    pub fn thresholds(&self) -> &[Duration] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<Duration>> for ExpiryReminders {
+    type Error = ExpiryRemindersError;
+
+    fn try_from(thresholds: Vec<Duration>) -> Result<Self, Self::Error> {
+        ExpiryReminders::new(thresholds)
+    }
+}
+
+impl Default for ExpiryReminders {
+    fn default() -> Self {
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        ExpiryReminders(vec![7 * DAY, 3 * DAY, DAY, Duration::ZERO])
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryRemindersError {
+    #[error("At most 8 reminders may be used")]
+    TooMany,
+    #[error("Reminders may be at most 365 days before the account expires")]
+    TooLarge,
+}
+
 /// Data structure that's returned from successful invocation of the mullvad API's
 /// `/v1/submit-voucher` RPC.
 #[derive(Deserialize, Serialize, Debug)]
@@ -0,0 +1,26 @@
+use crate::states::TunnelState;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A tunnel state that the daemon entered, and when it did so.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionHistoryEntry {
+    pub time: DateTime<Utc>,
+    pub state: TunnelState,
+}
+
+/// Selects the entries of the connection history that fall within a time interval.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionHistoryFilter {
+    /// Only include entries at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only include entries before this time.
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl ConnectionHistoryFilter {
+    pub fn matches(&self, entry: &ConnectionHistoryEntry) -> bool {
+        self.since.is_none_or(|since| entry.time >= since)
+            && self.until.is_none_or(|until| entry.time < until)
+    }
+}
@@ -4,7 +4,10 @@ use std::{
     fmt, io,
     net::{IpAddr, SocketAddr, ToSocketAddrs},
 };
-use talpid_types::net::{Endpoint, TunnelParameters, openvpn, proxy::CustomProxy, wireguard};
+use talpid_types::net::{
+    Endpoint, ObfuscationEndpoint, TunnelParameters, obfuscation::ObfuscatorConfig, openvpn,
+    proxy::CustomProxy, wireguard,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -19,11 +22,22 @@ pub enum Error {
 pub struct CustomTunnelEndpoint {
     pub host: String,
     pub config: ConnectionConfig,
+    /// Obfuscation server to reach the WireGuard server through. Ignored for OpenVPN.
+    #[serde(default)]
+    pub obfuscation: Option<ObfuscatorConfig>,
+    /// Preshared key of the WireGuard peer. Ignored for OpenVPN.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preshared_key: Option<wireguard::PresharedKey>,
 }
 
 impl CustomTunnelEndpoint {
     pub fn new(host: String, config: ConnectionConfig) -> Self {
-        Self { host, config }
+        Self {
+            host,
+            config,
+            obfuscation: None,
+            preshared_key: None,
+        }
     }
 
     pub fn endpoint(&self) -> Endpoint {
@@ -43,26 +57,37 @@ impl CustomTunnelEndpoint {
         config.set_ip(ip);
 
         let parameters = match config {
-            ConnectionConfig::OpenVpn(config) => openvpn::TunnelParameters {
-                config,
-                options: tunnel_options.openvpn,
-                generic_options: tunnel_options.generic,
-                proxy,
-                #[cfg(target_os = "linux")]
-                fwmark: crate::TUNNEL_FWMARK,
+            ConnectionConfig::OpenVpn(config) => {
+                if self.obfuscation.is_some() {
+                    log::info!("Ignoring obfuscation for custom OpenVPN tunnel");
+                }
+                openvpn::TunnelParameters {
+                    config,
+                    options: tunnel_options.openvpn,
+                    generic_options: tunnel_options.generic,
+                    proxy,
+                    #[cfg(target_os = "linux")]
+                    fwmark: crate::TUNNEL_FWMARK,
+                }
+                .into()
             }
-            .into(),
-            ConnectionConfig::Wireguard(connection) => {
+            ConnectionConfig::Wireguard(mut connection) => {
+                connection.peer.psk = self.preshared_key.clone();
                 let mut options = tunnel_options.wireguard.into_talpid_tunnel_options();
                 if options.quantum_resistant {
                     options.quantum_resistant = false;
                     log::info!("Ignoring quantum resistant option for custom tunnel");
                 }
+                // The obfuscation server forwards traffic to the port of the WireGuard server
+                let wireguard_port = connection.peer.endpoint.port();
                 wireguard::TunnelParameters {
                     connection,
                     options,
                     generic_options: tunnel_options.generic,
-                    obfuscation: None,
+                    obfuscation: self
+                        .obfuscation
+                        .clone()
+                        .map(|obfuscation| obfuscation.with_wireguard_port(wireguard_port)),
                 }
                 .into()
             }
@@ -81,13 +106,19 @@ impl fmt::Display for CustomTunnelEndpoint {
                 config.endpoint.address.port(),
                 config.endpoint.protocol
             ),
-            ConnectionConfig::Wireguard(connection) => write!(
-                f,
-                "WireGuard relay - {}:{} with public key {}",
-                self.host,
-                connection.peer.endpoint.port(),
-                connection.peer.public_key
-            ),
+            ConnectionConfig::Wireguard(connection) => {
+                write!(
+                    f,
+                    "WireGuard relay - {}:{} with public key {}",
+                    self.host,
+                    connection.peer.endpoint.port(),
+                    connection.peer.public_key
+                )?;
+                if let Some(obfuscation) = &self.obfuscation {
+                    write!(f, " via {}", ObfuscationEndpoint::from(obfuscation))?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -130,9 +130,9 @@ pub fn compute_feature_indicators(
     endpoint: &TunnelEndpoint,
     server_ip_override: bool,
 ) -> FeatureIndicators {
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+    #[cfg(not(target_os = "ios"))]
     let split_tunneling = settings.split_tunnel.enable_exclusions;
-    #[cfg(not(any(windows, target_os = "android", target_os = "macos")))]
+    #[cfg(target_os = "ios")]
     let split_tunneling = false;
 
     #[cfg(not(target_os = "android"))]
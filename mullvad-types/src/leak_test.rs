@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Result of trying to send traffic outside the tunnel while connected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeakTestReport {
+    /// Name of the non-tunnel interface that the probes were sent on.
+    pub interface: String,
+    /// Destination of the traceroute probes, which is the relay that we are connected to.
+    pub destination: IpAddr,
+    /// Network nodes that responded to traceroute probes sent on `interface`.
+    pub reachable_nodes: Vec<ReachableNode>,
+    /// DNS servers that answered queries sent on `interface`.
+    pub reachable_dns_servers: Vec<IpAddr>,
+    /// Result of asking <https://am.i.mullvad.net> whether we are connected to Mullvad. This is
+    /// `None` if the check was not run.
+    pub am_i_mullvad: Option<AmIMullvad>,
+}
+
+impl LeakTestReport {
+    /// Whether any of the checks managed to leak traffic.
+    pub fn is_leaking(&self) -> bool {
+        !self.reachable_nodes.is_empty()
+            || !self.reachable_dns_servers.is_empty()
+            || matches!(self.am_i_mullvad, Some(AmIMullvad::NotMullvad { .. }))
+    }
+}
+
+/// A network node that responded to a probe packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReachableNode {
+    pub address: IpAddr,
+    /// The TTL of the probe that the node responded to, if it is known.
+    pub ttl: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AmIMullvad {
+    /// Traffic exits through a Mullvad relay.
+    Mullvad,
+    /// Traffic exits from `ip`, which does not belong to Mullvad.
+    NotMullvad { ip: IpAddr },
+}
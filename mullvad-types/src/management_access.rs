@@ -0,0 +1,136 @@
+//! Roles that restrict what local users may do through the management interface. The role of a
+//! client is decided by the user and groups of the process on the other end of the socket.
+//! This is only enforced on Linux.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// User ID of the superuser, which is always an admin.
+const ROOT_UID: u32 = 0;
+
+/// What a client of the management interface is allowed to do. Each role may do everything that
+/// the roles before it may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// May read the tunnel state, settings, and other information, and listen for events. The
+    /// account number is hidden from anyone but admins.
+    ReadOnly,
+    /// May also connect, disconnect, and reconnect the tunnel.
+    Operator,
+    /// May do anything, including changing settings and managing the account.
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::ReadOnly => f.write_str("read_only"),
+            Role::Operator => f.write_str("operator"),
+            Role::Admin => f.write_str("admin"),
+        }
+    }
+}
+
+/// Maps local users and groups to roles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManagementAccessSettings {
+    /// Role of clients that match none of `users` and `groups`.
+    pub default_role: Role,
+    /// Roles by user ID. These take precedence over `groups`.
+    pub users: BTreeMap<u32, Role>,
+    /// Roles by group ID. Both the primary and supplementary groups of the client are considered,
+    /// and the highest role of any of them applies.
+    pub groups: BTreeMap<u32, Role>,
+}
+
+impl Default for ManagementAccessSettings {
+    fn default() -> Self {
+        ManagementAccessSettings {
+            default_role: Role::Admin,
+            users: BTreeMap::new(),
+            groups: BTreeMap::new(),
+        }
+    }
+}
+
+impl ManagementAccessSettings {
+    /// Settings that make everyone but root read-only. These are used when the configured
+    /// settings cannot be trusted, e.g. when the settings file is corrupt.
+    pub fn restricted() -> Self {
+        ManagementAccessSettings {
+            default_role: Role::ReadOnly,
+            users: BTreeMap::new(),
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// Return the role of a client running as user `uid`, which is a member of `groups`.
+    pub fn role(&self, uid: u32, groups: &[u32]) -> Role {
+        if let Some(role) = self.user_role(uid) {
+            return role;
+        }
+        groups
+            .iter()
+            .filter_map(|gid| self.groups.get(gid))
+            .max()
+            .copied()
+            .unwrap_or(self.default_role)
+    }
+
+    /// Return the role of user `uid` if it does not depend on the groups of the user, i.e. if the
+    /// user is root or has a role in `users`.
+    pub fn user_role(&self, uid: u32) -> Option<Role> {
+        if uid == ROOT_UID {
+            return Some(Role::Admin);
+        }
+        self.users.get(&uid).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_role_precedence() {
+        let settings = ManagementAccessSettings {
+            default_role: Role::ReadOnly,
+            users: BTreeMap::from([(1000, Role::Admin), (1001, Role::ReadOnly)]),
+            groups: BTreeMap::from([(100, Role::Operator)]),
+        };
+
+        assert_eq!(settings.role(0, &[0]), Role::Admin);
+        assert_eq!(settings.role(0, &[100]), Role::Admin);
+        assert_eq!(settings.role(1000, &[100]), Role::Admin);
+        assert_eq!(settings.role(1001, &[100]), Role::ReadOnly);
+        assert_eq!(settings.role(1002, &[100]), Role::Operator);
+        assert_eq!(settings.role(1002, &[1002, 100]), Role::Operator);
+        assert_eq!(settings.role(1002, &[1002]), Role::ReadOnly);
+
+        assert_eq!(settings.user_role(0), Some(Role::Admin));
+        assert_eq!(settings.user_role(1001), Some(Role::ReadOnly));
+        assert_eq!(settings.user_role(1002), None);
+    }
+
+    #[test]
+    fn test_highest_group_role() {
+        let settings = ManagementAccessSettings {
+            default_role: Role::Operator,
+            users: BTreeMap::new(),
+            groups: BTreeMap::from([(100, Role::ReadOnly), (101, Role::Admin)]),
+        };
+
+        assert_eq!(settings.role(1000, &[100]), Role::ReadOnly);
+        assert_eq!(settings.role(1000, &[100, 101]), Role::Admin);
+        assert_eq!(settings.role(1000, &[1000]), Role::Operator);
+    }
+
+    #[test]
+    fn test_default_allows_everyone() {
+        let settings = ManagementAccessSettings::default();
+        assert_eq!(settings.role(1000, &[1000]), Role::Admin);
+        assert!(Role::ReadOnly < Role::Operator && Role::Operator < Role::Admin);
+    }
+}
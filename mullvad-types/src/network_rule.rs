@@ -0,0 +1,198 @@
+//! Rules that decide whether the tunnel should be connected, depending on which network the
+//! device is on. The rules are evaluated in order whenever the non-tunnel default route changes,
+//! and the action of the first matching rule is taken.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// Link-layer (MAC) address, e.g. of the default gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddress(pub [u8; 6]);
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("Invalid MAC address: {0}")]
+pub struct InvalidMacAddress(String);
+
+impl FromStr for MacAddress {
+    type Err = InvalidMacAddress;
+
+    /// Parse a MAC address on the form `aa:bb:cc:dd:ee:ff` or `aa-bb-cc-dd-ee-ff`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidMacAddress(s.to_owned());
+
+        let mut bytes = [0u8; 6];
+        let mut octets = s.split([':', '-']);
+        for byte in &mut bytes {
+            let octet = octets.next().ok_or_else(invalid)?;
+            if octet.len() != 2 {
+                return Err(invalid());
+            }
+            *byte = u8::from_str_radix(octet, 16).map_err(|_| invalid())?;
+        }
+        if octets.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(MacAddress(bytes))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The network that the non-tunnel default route currently goes through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrentNetwork {
+    /// Name of the interface of the default route.
+    pub interface: String,
+    /// MAC address of the default gateway, if known.
+    pub gateway_mac: Option<MacAddress>,
+}
+
+/// Condition that a [`NetworkRule`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMatch {
+    /// The default route goes through the interface with the given name.
+    Interface(String),
+    /// The default gateway has the given MAC address.
+    GatewayMac(MacAddress),
+    /// Any network.
+    Any,
+}
+
+impl NetworkMatch {
+    pub fn matches(&self, network: &CurrentNetwork) -> bool {
+        match self {
+            NetworkMatch::Interface(interface) => *interface == network.interface,
+            NetworkMatch::GatewayMac(mac) => network.gateway_mac == Some(*mac),
+            NetworkMatch::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for NetworkMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkMatch::Interface(interface) => write!(f, "interface {interface}"),
+            NetworkMatch::GatewayMac(mac) => write!(f, "gateway {mac}"),
+            NetworkMatch::Any => f.write_str("any network"),
+        }
+    }
+}
+
+/// What to do when a [`NetworkRule`] matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum NetworkAction {
+    /// Connect the tunnel.
+    Connect,
+    /// Disconnect the tunnel.
+    Disconnect,
+    /// Disconnect the tunnel and block all traffic.
+    Block,
+}
+
+impl fmt::Display for NetworkAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkAction::Connect => f.write_str("connect"),
+            NetworkAction::Disconnect => f.write_str("disconnect"),
+            NetworkAction::Block => f.write_str("block"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkRule {
+    pub network: NetworkMatch,
+    pub action: NetworkAction,
+}
+
+impl fmt::Display for NetworkRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}", self.action, self.network)
+    }
+}
+
+/// Return the action of the first rule in `rules` that matches `network`.
+pub fn evaluate(rules: &[NetworkRule], network: &CurrentNetwork) -> Option<NetworkAction> {
+    rules
+        .iter()
+        .find(|rule| rule.network.matches(network))
+        .map(|rule| rule.action)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_mac_address() {
+        let mac = MacAddress([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0xff]);
+        assert_eq!("00:1a:2b:3c:4d:ff".parse(), Ok(mac));
+        assert_eq!("00-1A-2B-3C-4D-FF".parse(), Ok(mac));
+        assert_eq!(mac.to_string(), "00:1a:2b:3c:4d:ff");
+
+        assert!("00:1a:2b:3c:4d".parse::<MacAddress>().is_err());
+        assert!("00:1a:2b:3c:4d:ff:00".parse::<MacAddress>().is_err());
+        assert!("0:1a:2b:3c:4d:ff".parse::<MacAddress>().is_err());
+        assert!("00:1a:2b:3c:4d:fg".parse::<MacAddress>().is_err());
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let home_gateway = MacAddress([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+        let rules = [
+            NetworkRule {
+                network: NetworkMatch::GatewayMac(home_gateway),
+                action: NetworkAction::Disconnect,
+            },
+            NetworkRule {
+                network: NetworkMatch::Interface("en0".to_owned()),
+                action: NetworkAction::Block,
+            },
+            NetworkRule {
+                network: NetworkMatch::Any,
+                action: NetworkAction::Connect,
+            },
+        ];
+
+        let home = CurrentNetwork {
+            interface: "en0".to_owned(),
+            gateway_mac: Some(home_gateway),
+        };
+        assert_eq!(evaluate(&rules, &home), Some(NetworkAction::Disconnect));
+
+        let other_wifi = CurrentNetwork {
+            interface: "en0".to_owned(),
+            gateway_mac: None,
+        };
+        assert_eq!(evaluate(&rules, &other_wifi), Some(NetworkAction::Block));
+
+        let ethernet = CurrentNetwork {
+            interface: "en1".to_owned(),
+            gateway_mac: None,
+        };
+        assert_eq!(evaluate(&rules, &ethernet), Some(NetworkAction::Connect));
+        assert_eq!(evaluate(&rules[..2], &ethernet), None);
+    }
+}
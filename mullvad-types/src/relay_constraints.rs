@@ -96,6 +96,16 @@ pub struct LocationConstraintFormatter<'a> {
     pub custom_lists: &'a CustomListsSettings,
 }
 
+impl LocationConstraint {
+    /// Return the hostname of the relay if this constraint selects a single relay.
+    pub fn get_hostname(&self) -> Option<&Hostname> {
+        match self {
+            LocationConstraint::Location(location) => location.get_hostname(),
+            LocationConstraint::CustomList { .. } => None,
+        }
+    }
+}
+
 impl From<GeographicLocationConstraint> for LocationConstraint {
     fn from(location: GeographicLocationConstraint) -> Self {
         Self::Location(location)
@@ -126,6 +136,7 @@ pub struct RelayConstraints {
     pub tunnel_protocol: TunnelType,
     pub wireguard_constraints: WireguardConstraints,
     pub openvpn_constraints: OpenVpnConstraints,
+    pub selection_mode: RelaySelectionMode,
 }
 
 pub struct RelayConstraintsFormatter<'a> {
@@ -157,7 +168,46 @@ impl fmt::Display for RelayConstraintsFormatter<'_> {
                 })
         )?;
         writeln!(f, "Provider(s): {}", self.constraints.providers)?;
-        write!(f, "Ownership: {}", self.constraints.ownership)
+        writeln!(f, "Ownership: {}", self.constraints.ownership)?;
+        write!(f, "Selection mode: {}", self.constraints.selection_mode)
+    }
+}
+
+/// Decides how a `RelaySelector` picks a relay among those matching the [`RelayConstraints`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum RelaySelectionMode {
+    /// Pick a random relay, weighted by the weights in the relay list.
+    #[default]
+    Random,
+    /// Pick among the relays with the lowest measured latency. Falls back to [`Self::Random`]
+    /// while no latency measurements are available.
+    LowestLatency,
+}
+
+impl Intersection for RelaySelectionMode {
+    fn intersection(self, other: Self) -> Option<Self>
+    where
+        Self: PartialEq,
+        Self: Sized,
+    {
+        // `Random` is the absence of a preference, so it never conflicts with another mode.
+        match (self, other) {
+            (left, RelaySelectionMode::Random) => Some(left),
+            (RelaySelectionMode::Random, right) => Some(right),
+            (left, right) if left == right => Some(left),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RelaySelectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelaySelectionMode::Random => "random".fmt(f),
+            RelaySelectionMode::LowestLatency => "lowest latency".fmt(f),
+        }
     }
 }
 
@@ -709,6 +759,126 @@ pub struct ObfuscationSettings {
     pub shadowsocks: ShadowsocksSettings,
 }
 
+/// A step in the order in which connection parameters are tried on successive connection attempts
+/// with WireGuard. Each step is combined with the user's constraints, and steps that conflict with
+/// them are skipped.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryStep {
+    /// Obfuscation to use. [`SelectedObfuscation::Auto`] leaves it to the other settings.
+    pub obfuscation: SelectedObfuscation,
+    pub ip_version: Constraint<IpVersion>,
+    /// Port to connect to. If obfuscation is used, this is the port of the obfuscation protocol.
+    pub port: Constraint<u16>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum InvalidRetryStep {
+    #[error("Unknown parameter: {0}")]
+    Unknown(String),
+    #[error("Parameter is given more than once: {0}")]
+    Duplicate(String),
+    #[error("A port cannot be specified for QUIC")]
+    QuicPort,
+}
+
+impl RetryStep {
+    /// The order that is used unless another one has been configured. This matches
+    /// `WIREGUARD_RETRY_ORDER` in the relay selector.
+    pub fn default_order() -> Vec<RetryStep> {
+        let step = |obfuscation, ip_version| RetryStep {
+            obfuscation,
+            ip_version,
+            port: Constraint::Any,
+        };
+        vec![
+            step(SelectedObfuscation::Auto, Constraint::Any),
+            step(SelectedObfuscation::Auto, Constraint::Only(IpVersion::V6)),
+            step(SelectedObfuscation::Shadowsocks, Constraint::Any),
+            step(SelectedObfuscation::Quic, Constraint::Any),
+            step(SelectedObfuscation::Udp2Tcp, Constraint::Any),
+            step(
+                SelectedObfuscation::Udp2Tcp,
+                Constraint::Only(IpVersion::V6),
+            ),
+        ]
+    }
+}
+
+/// Parse a step such as `udp2tcp+ipv6+443`, which consists of any of an obfuscation method, an IP
+/// version, and a port, separated by `+`. `any` is a step without any parameters.
+impl FromStr for RetryStep {
+    type Err = InvalidRetryStep;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut step = RetryStep::default();
+        if s == "any" {
+            return Ok(step);
+        }
+
+        for parameter in s.split('+') {
+            let duplicate = || InvalidRetryStep::Duplicate(parameter.to_owned());
+            let obfuscation = match parameter {
+                "off" => Some(SelectedObfuscation::Off),
+                "udp2tcp" => Some(SelectedObfuscation::Udp2Tcp),
+                "shadowsocks" => Some(SelectedObfuscation::Shadowsocks),
+                "quic" => Some(SelectedObfuscation::Quic),
+                _ => None,
+            };
+            let ip_version = match parameter {
+                "ipv4" => Some(IpVersion::V4),
+                "ipv6" => Some(IpVersion::V6),
+                _ => None,
+            };
+
+            if let Some(obfuscation) = obfuscation {
+                if step.obfuscation != SelectedObfuscation::Auto {
+                    return Err(duplicate());
+                }
+                step.obfuscation = obfuscation;
+            } else if let Some(ip_version) = ip_version {
+                if step.ip_version.is_only() {
+                    return Err(duplicate());
+                }
+                step.ip_version = Constraint::Only(ip_version);
+            } else if let Ok(port) = parameter.parse::<u16>() {
+                if step.port.is_only() {
+                    return Err(duplicate());
+                }
+                step.port = Constraint::Only(port);
+            } else {
+                return Err(InvalidRetryStep::Unknown(parameter.to_owned()));
+            }
+        }
+
+        if step.obfuscation == SelectedObfuscation::Quic && step.port.is_only() {
+            return Err(InvalidRetryStep::QuicPort);
+        }
+        Ok(step)
+    }
+}
+
+impl fmt::Display for RetryStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parameters = vec![];
+        if self.obfuscation != SelectedObfuscation::Auto {
+            parameters.push(self.obfuscation.to_string());
+        }
+        if let Constraint::Only(ip_version) = self.ip_version {
+            parameters.push(ip_version.to_string().to_lowercase());
+        }
+        if let Constraint::Only(port) = self.port {
+            parameters.push(port.to_string());
+        }
+
+        if parameters.is_empty() {
+            f.write_str("any")
+        } else {
+            f.write_str(&parameters.join("+"))
+        }
+    }
+}
+
 /// Limits the set of bridge servers to use in `mullvad-daemon`.
 #[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize, Intersection)]
 #[serde(default)]
@@ -861,4 +1031,35 @@ mod tests {
             GeographicLocationConstraint::hostname("se", "got", "se-got-wg-101")
         );
     }
+
+    #[test]
+    fn parse_retry_step() {
+        let step: RetryStep = "udp2tcp+ipv6+443".parse().unwrap();
+        assert_eq!(
+            step,
+            RetryStep {
+                obfuscation: SelectedObfuscation::Udp2Tcp,
+                ip_version: Constraint::Only(IpVersion::V6),
+                port: Constraint::Only(443),
+            }
+        );
+        assert_eq!(step.to_string(), "udp2tcp+ipv6+443");
+        assert_eq!("any".parse::<RetryStep>(), Ok(RetryStep::default()));
+        assert_eq!(RetryStep::default().to_string(), "any");
+
+        for step in RetryStep::default_order() {
+            assert_eq!(step.to_string().parse::<RetryStep>(), Ok(step));
+        }
+
+        assert_eq!(
+            "quic+udp2tcp".parse::<RetryStep>(),
+            Err(InvalidRetryStep::Duplicate("udp2tcp".to_owned()))
+        );
+        assert_eq!(
+            "quic+443".parse::<RetryStep>(),
+            Err(InvalidRetryStep::QuicPort)
+        );
+        assert!("ipv5".parse::<RetryStep>().is_err());
+        assert!("".parse::<RetryStep>().is_err());
+    }
 }
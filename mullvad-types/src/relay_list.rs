@@ -1,6 +1,8 @@
 use crate::location::{CityCode, CountryCode, Location};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{HashMap, HashSet},
+    fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     ops::RangeInclusive,
 };
@@ -44,6 +46,140 @@ impl RelayList {
             .flat_map(|country| country.cities)
             .flat_map(|city| city.relays)
     }
+
+    /// Return the changes from this relay list to `new`. Relays are identified by their hostname.
+    pub fn diff(&self, new: &RelayList) -> RelayListDiff {
+        let old_relays: HashMap<&str, &Relay> = self
+            .relays()
+            .map(|relay| (relay.hostname.as_str(), relay))
+            .collect();
+        let new_hostnames: HashSet<&str> =
+            new.relays().map(|relay| relay.hostname.as_str()).collect();
+
+        let mut diff = RelayListDiff {
+            removed: self
+                .relays()
+                .filter(|relay| !new_hostnames.contains(relay.hostname.as_str()))
+                .map(|relay| relay.hostname.clone())
+                .collect(),
+            ..RelayListDiff::default()
+        };
+
+        for new_relay in new.relays() {
+            let hostname = &new_relay.hostname;
+            let Some(old_relay) = old_relays.get(hostname.as_str()) else {
+                diff.added.push(hostname.clone());
+                continue;
+            };
+
+            match (old_relay.active, new_relay.active) {
+                (false, true) => diff.activated.push(hostname.clone()),
+                (true, false) => diff.deactivated.push(hostname.clone()),
+                _ => (),
+            }
+
+            if old_relay.ipv4_addr_in != new_relay.ipv4_addr_in {
+                diff.address_changes.push(RelayAddressChange {
+                    hostname: hostname.clone(),
+                    old: Some(IpAddr::from(old_relay.ipv4_addr_in)),
+                    new: Some(IpAddr::from(new_relay.ipv4_addr_in)),
+                });
+            }
+            if old_relay.ipv6_addr_in != new_relay.ipv6_addr_in {
+                diff.address_changes.push(RelayAddressChange {
+                    hostname: hostname.clone(),
+                    old: old_relay.ipv6_addr_in.map(IpAddr::from),
+                    new: new_relay.ipv6_addr_in.map(IpAddr::from),
+                });
+            }
+
+            for feature in [RelayFeature::Daita, RelayFeature::Quic] {
+                let enabled = feature.is_enabled(new_relay);
+                if feature.is_enabled(old_relay) != enabled {
+                    diff.feature_changes.push(RelayFeatureChange {
+                        hostname: hostname.clone(),
+                        feature,
+                        enabled,
+                    });
+                }
+            }
+        }
+
+        diff
+    }
+}
+
+/// Changes between two versions of the relay list, as returned by [`RelayList::diff`]. Relays are
+/// referred to by their hostname.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RelayListDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub activated: Vec<String>,
+    pub deactivated: Vec<String>,
+    pub address_changes: Vec<RelayAddressChange>,
+    pub feature_changes: Vec<RelayFeatureChange>,
+}
+
+impl RelayListDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == RelayListDiff::default()
+    }
+
+    /// Relays that can no longer be used, since they were either removed or deactivated.
+    pub fn unavailable(&self) -> impl Iterator<Item = &str> {
+        self.removed
+            .iter()
+            .chain(&self.deactivated)
+            .map(String::as_str)
+    }
+}
+
+/// The IPv4 or IPv6 in-address of a relay changed. An IPv6 address may be added or removed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RelayAddressChange {
+    pub hostname: String,
+    pub old: Option<IpAddr>,
+    pub new: Option<IpAddr>,
+}
+
+/// A feature was enabled or disabled on a relay.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RelayFeatureChange {
+    pub hostname: String,
+    pub feature: RelayFeature,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayFeature {
+    Daita,
+    Quic,
+}
+
+impl RelayFeature {
+    fn is_enabled(self, relay: &Relay) -> bool {
+        match self {
+            RelayFeature::Daita => {
+                relay.features.daita()
+                    || matches!(
+                        &relay.endpoint_data,
+                        RelayEndpointData::Wireguard(data) if data.daita
+                    )
+            }
+            RelayFeature::Quic => relay.features.quic().is_some(),
+        }
+    }
+}
+
+impl fmt::Display for RelayFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayFeature::Daita => f.write_str("DAITA"),
+            RelayFeature::Quic => f.write_str("QUIC"),
+        }
+    }
 }
 
 /// A list of [`RelayListCity`]s within a country. Used by [`RelayList`].
@@ -374,3 +510,101 @@ impl ShadowsocksEndpointData {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn relay(hostname: &str, ipv4_addr_in: &str, daita: bool) -> Relay {
+        Relay {
+            hostname: hostname.to_owned(),
+            ipv4_addr_in: ipv4_addr_in.parse().unwrap(),
+            ipv6_addr_in: None,
+            overridden_ipv4: false,
+            overridden_ipv6: false,
+            include_in_country: true,
+            active: true,
+            owned: true,
+            provider: "provider0".to_owned(),
+            weight: 1,
+            endpoint_data: RelayEndpointData::Wireguard(WireguardRelayEndpointData {
+                public_key: wireguard::PublicKey::from_base64(
+                    "BLNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=",
+                )
+                .unwrap(),
+                daita,
+                shadowsocks_extra_addr_in: vec![],
+            }),
+            location: Location {
+                country: "Sweden".to_owned(),
+                country_code: "se".to_owned(),
+                city: "Gothenburg".to_owned(),
+                city_code: "got".to_owned(),
+                latitude: 57.71,
+                longitude: 11.97,
+            },
+            features: Features::default(),
+        }
+    }
+
+    fn relay_list(relays: Vec<Relay>) -> RelayList {
+        RelayList {
+            countries: vec![RelayListCountry {
+                name: "Sweden".to_owned(),
+                code: "se".to_owned(),
+                cities: vec![RelayListCity {
+                    name: "Gothenburg".to_owned(),
+                    code: "got".to_owned(),
+                    latitude: 57.71,
+                    longitude: 11.97,
+                    relays,
+                }],
+            }],
+            ..RelayList::empty()
+        }
+    }
+
+    #[test]
+    fn test_relay_list_diff() {
+        let mut deactivated = relay("se-got-wg-003", "10.0.0.3", false);
+        let old = relay_list(vec![
+            relay("se-got-wg-001", "10.0.0.1", false),
+            relay("se-got-wg-002", "10.0.0.2", false),
+            deactivated.clone(),
+        ]);
+        deactivated.active = false;
+        let new = relay_list(vec![
+            relay("se-got-wg-002", "10.0.1.2", true),
+            deactivated,
+            relay("se-got-wg-004", "10.0.0.4", false),
+        ]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, ["se-got-wg-004"]);
+        assert_eq!(diff.removed, ["se-got-wg-001"]);
+        assert!(diff.activated.is_empty());
+        assert_eq!(diff.deactivated, ["se-got-wg-003"]);
+        assert_eq!(
+            diff.address_changes,
+            [RelayAddressChange {
+                hostname: "se-got-wg-002".to_owned(),
+                old: Some("10.0.0.2".parse().unwrap()),
+                new: Some("10.0.1.2".parse().unwrap()),
+            }]
+        );
+        assert_eq!(
+            diff.feature_changes,
+            [RelayFeatureChange {
+                hostname: "se-got-wg-002".to_owned(),
+                feature: RelayFeature::Daita,
+                enabled: true,
+            }]
+        );
+        assert_eq!(
+            diff.unavailable().collect::<Vec<_>>(),
+            ["se-got-wg-001", "se-got-wg-003"]
+        );
+
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
+use talpid_types::net::dns::{EncryptedDnsServer, SplitDnsRule};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -16,6 +17,9 @@ pub struct DnsOptions {
     pub state: DnsState,
     pub default_options: DefaultDnsOptions,
     pub custom_options: CustomDnsOptions,
+    /// Domains whose names are resolved using other DNS servers, regardless of `state`. Only
+    /// supported on Linux and macOS, where the queries are routed by the local DNS resolver.
+    pub split_dns_rules: Vec<SplitDnsRule>,
 }
 
 /// Default DNS config
@@ -34,6 +38,9 @@ pub struct DefaultDnsOptions {
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct CustomDnsOptions {
     pub addresses: Vec<IpAddr>,
+    /// Servers that are queried over HTTPS or TLS. Only supported on macOS, where the queries are
+    /// made by the local DNS resolver.
+    pub encrypted_servers: Vec<EncryptedDnsServer>,
 }
 
 impl DefaultDnsOptions {
@@ -1,26 +1,32 @@
 use crate::{
     access_method,
+    account::ExpiryReminders,
     constraints::Constraint,
     custom_list::CustomListsSettings,
+    management_access::ManagementAccessSettings,
+    network_rule::NetworkRule,
     relay_constraints::{
         BridgeSettings, BridgeState, GeographicLocationConstraint, LocationConstraint,
         ObfuscationSettings, RelayConstraints, RelayOverride, RelaySettings,
-        RelaySettingsFormatter, SelectedObfuscation, WireguardConstraints,
+        RelaySettingsFormatter, RetryStep, SelectedObfuscation, WireguardConstraints,
     },
     wireguard,
 };
+use ipnetwork::IpNetwork;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
+#[cfg(not(target_os = "ios"))]
 use std::collections::HashSet;
-use talpid_types::net::{GenericTunnelOptions, openvpn};
+use talpid_types::net::{ALLOWED_LAN_NETS, GenericTunnelOptions, openvpn};
+pub use talpid_types::split_tunnel::SplitTunnelMode;
 
 mod dns;
+pub mod profile;
 
 /// The version used by the current version of the code. Should always be the
 /// latest version that exists in `SettingsVersion`.
 /// This should be bumped when a new version is introduced along with a migration
 /// being added to `mullvad-daemon`.
-pub const CURRENT_SETTINGS_VERSION: SettingsVersion = SettingsVersion::V11;
+pub const CURRENT_SETTINGS_VERSION: SettingsVersion = SettingsVersion::V12;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Clone, Copy)]
 #[repr(u32)]
@@ -35,6 +41,7 @@ pub enum SettingsVersion {
     V9 = 9,
     V10 = 10,
     V11 = 11,
+    V12 = 12,
 }
 
 impl<'de> Deserialize<'de> for SettingsVersion {
@@ -53,6 +60,7 @@ impl<'de> Deserialize<'de> for SettingsVersion {
             v if v == SettingsVersion::V9 as u32 => Ok(SettingsVersion::V9),
             v if v == SettingsVersion::V10 as u32 => Ok(SettingsVersion::V10),
             v if v == SettingsVersion::V11 as u32 => Ok(SettingsVersion::V11),
+            v if v == SettingsVersion::V12 as u32 => Ok(SettingsVersion::V12),
             v => Err(serde::de::Error::custom(format!(
                 "{v} is not a valid SettingsVersion"
             ))),
@@ -76,6 +84,9 @@ pub struct Settings {
     pub relay_settings: RelaySettings,
     pub bridge_settings: BridgeSettings,
     pub obfuscation_settings: ObfuscationSettings,
+    /// Order in which connection parameters are tried on successive connection attempts with
+    /// WireGuard. [`RetryStep::default_order`] is used if this is `None`.
+    pub retry_order: Option<Vec<RetryStep>>,
     pub bridge_state: BridgeState,
     /// All of the custom relay lists
     pub custom_lists: CustomListsSettings,
@@ -83,12 +94,19 @@ pub struct Settings {
     pub api_access_methods: access_method::Settings,
     /// If the daemon should allow communication with private (LAN) networks.
     pub allow_lan: bool,
+    /// Networks that can be reached outside the tunnel when `allow_lan` is enabled. Multicast and
+    /// broadcast traffic to the local network is always allowed when `allow_lan` is enabled.
+    /// This is only enforced on Linux.
+    pub allowed_lan_networks: Vec<IpNetwork>,
     /// Extra level of kill switch. When this setting is on, the disconnected state will block
     /// the firewall to not allow any traffic in or out.
     #[cfg(not(target_os = "android"))]
     pub block_when_disconnected: bool,
     /// If the daemon should connect the VPN tunnel directly on start or not.
     pub auto_connect: bool,
+    /// Rules that connect or disconnect the tunnel depending on the current network. The first
+    /// matching rule takes precedence over `auto_connect`. This is only enforced on macOS.
+    pub network_rules: Vec<NetworkRule>,
     /// Options that should be applied to tunnels of a specific type regardless of where the relays
     /// might be located.
     pub tunnel_options: TunnelOptions,
@@ -97,8 +115,15 @@ pub struct Settings {
     /// Whether to notify users of beta updates.
     pub show_beta_releases: bool,
     /// Split tunneling settings
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+    #[cfg(not(target_os = "ios"))]
     pub split_tunnel: SplitTunnelSettings,
+    /// Settings for the local metrics endpoint
+    pub metrics: MetricsSettings,
+    /// When to warn that the account is about to run out of time
+    pub expiry_reminders: ExpiryReminders,
+    /// Roles of local users and groups that use the management interface. This is only enforced
+    /// on Linux, and can only be changed by editing the settings file.
+    pub management_access: ManagementAccessSettings,
     /// Specifies settings schema version
     pub settings_version: SettingsVersion,
     /// Stores the user's recently connected locations. If None recents have been disabled by the user.
@@ -153,17 +178,56 @@ impl TryFrom<&RelaySettings> for Recent {
     }
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
+#[cfg(not(target_os = "ios"))]
 #[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
 pub struct SplitTunnelSettings {
     /// Toggles split tunneling on or off
     pub enable_exclusions: bool,
-    /// Set of applications to exclude from the tunnel.
+    /// Set of applications to exclude from the tunnel, or to include in it if `mode` is
+    /// [`SplitTunnelMode::Include`].
     pub apps: HashSet<SplitApp>,
+    /// Whether `apps` are excluded from or included in the tunnel.
+    #[serde(default)]
+    pub mode: SplitTunnelMode,
+}
+
+#[cfg(not(target_os = "ios"))]
+impl SplitTunnelSettings {
+    /// Return the split tunnel mode that is in effect. When split tunneling is disabled, all
+    /// traffic uses the tunnel, which is the same as excluding no apps.
+    pub fn effective_mode(&self) -> SplitTunnelMode {
+        if self.enable_exclusions {
+            self.mode
+        } else {
+            SplitTunnelMode::Exclude
+        }
+    }
+}
+
+/// The default port of the metrics endpoint.
+pub const DEFAULT_METRICS_PORT: u16 = 9366;
+
+/// Settings for the HTTP endpoint that exports daemon metrics in the OpenMetrics text format.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct MetricsSettings {
+    /// Whether to serve metrics.
+    pub enabled: bool,
+    /// TCP port on the loopback interface to serve metrics on.
+    pub port: u16,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        MetricsSettings {
+            enabled: false,
+            port: DEFAULT_METRICS_PORT,
+        }
+    }
 }
 
 /// An application whose traffic should be excluded from any active tunnel.
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(any(windows, target_os = "linux", target_os = "macos"))]
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SplitApp(std::path::PathBuf);
 
@@ -172,7 +236,7 @@ pub struct SplitApp(std::path::PathBuf);
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SplitApp(String);
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(any(windows, target_os = "linux", target_os = "macos"))]
 impl SplitApp {
     /// Convert the underlying path to a [`String`].
     /// This function will fail if the underlying path string is not valid UTF-8. See
@@ -189,6 +253,11 @@ impl SplitApp {
     pub fn display(&self) -> std::path::Display<'_> {
         self.0.display()
     }
+
+    /// Return the path of the application.
+    pub fn into_path(self) -> std::path::PathBuf {
+        self.0
+    }
 }
 
 #[cfg(target_os = "android")]
@@ -208,14 +277,14 @@ impl SplitApp {
     }
 }
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(any(windows, target_os = "linux", target_os = "macos"))]
 impl From<String> for SplitApp {
     fn from(value: String) -> Self {
         SplitApp::from(std::path::PathBuf::from(value))
     }
 }
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(any(windows, target_os = "linux", target_os = "macos"))]
 impl From<std::path::PathBuf> for SplitApp {
     fn from(value: std::path::PathBuf) -> Self {
         SplitApp(value)
@@ -249,18 +318,24 @@ impl Default for Settings {
                 selected_obfuscation: SelectedObfuscation::Auto,
                 ..Default::default()
             },
+            retry_order: None,
             bridge_state: BridgeState::Auto,
             custom_lists: CustomListsSettings::default(),
             api_access_methods: access_method::Settings::default(),
             allow_lan: false,
+            allowed_lan_networks: ALLOWED_LAN_NETS.to_vec(),
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: false,
             auto_connect: false,
+            network_rules: vec![],
             tunnel_options: TunnelOptions::default(),
             relay_overrides: vec![],
             show_beta_releases: false,
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+            #[cfg(not(target_os = "ios"))]
             split_tunnel: SplitTunnelSettings::default(),
+            metrics: MetricsSettings::default(),
+            expiry_reminders: ExpiryReminders::default(),
+            management_access: ManagementAccessSettings::default(),
             settings_version: CURRENT_SETTINGS_VERSION,
             recents: Some(vec![]),
         }
@@ -364,3 +439,25 @@ impl Default for TunnelOptions {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn test_split_tunnel_effective_mode() {
+        for mode in [SplitTunnelMode::Exclude, SplitTunnelMode::Include] {
+            let mut settings = SplitTunnelSettings {
+                enable_exclusions: true,
+                apps: HashSet::new(),
+                mode,
+            };
+            assert_eq!(settings.effective_mode(), mode);
+
+            // Disabling split tunneling must send all traffic through the tunnel
+            settings.enable_exclusions = false;
+            assert_eq!(settings.effective_mode(), SplitTunnelMode::Exclude);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// A named set of settings that can be applied all at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsProfile {
+    pub name: String,
+    /// The settings of the profile, in the settings patch format.
+    pub patch: String,
+}
+
+/// A setting in a profile whose value differs from the current settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingDiff {
+    /// Path to the setting, such as `tunnel_options.dns_options.state`.
+    pub key: String,
+    /// The current value, as JSON.
+    pub current: String,
+    /// The value in the profile, as JSON.
+    pub profile: String,
+}
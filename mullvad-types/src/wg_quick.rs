@@ -0,0 +1,610 @@
+//! Parsing and formatting of WireGuard configuration files in the format used by `wg-quick`.
+//!
+//! Only the subset of keys that can be represented by a [`CustomTunnelEndpoint`] and the
+//! WireGuard tunnel options are supported. Any other key is rejected, rather than silently
+//! ignored, since ignoring e.g. `PostUp` could result in a tunnel that behaves differently from
+//! what the user expects.
+
+use crate::{ConnectionConfig, CustomTunnelEndpoint};
+use ipnetwork::IpNetwork;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+use talpid_types::net::wireguard::{self, PresharedKey, PrivateKey, PublicKey};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Line {line}: Expected a section header or a key-value pair")]
+    InvalidLine { line: usize },
+
+    #[error("Line {line}: Unknown section [{section}]")]
+    UnknownSection { line: usize, section: String },
+
+    #[error("Line {line}: Key \"{key}\" is not in a section")]
+    KeyOutsideSection { line: usize, key: String },
+
+    #[error("Line {line}: Unsupported key \"{key}\" in [{section}]")]
+    UnsupportedKey {
+        line: usize,
+        section: &'static str,
+        key: String,
+    },
+
+    #[error("Line {line}: Key \"{key}\" is specified more than once")]
+    DuplicateKey { line: usize, key: &'static str },
+
+    #[error("Line {line}: Invalid value for \"{key}\"")]
+    InvalidValue { line: usize, key: &'static str },
+
+    #[error("Missing [Interface] section")]
+    MissingInterface,
+
+    #[error("Expected exactly one [Peer] section, found {0}")]
+    PeerCount(usize),
+
+    #[error("Missing required key \"{key}\" in [{section}]")]
+    MissingKey {
+        section: &'static str,
+        key: &'static str,
+    },
+
+    #[error("Could not derive an IPv4 gateway from the interface addresses")]
+    NoIpv4Gateway,
+
+    #[error("Multihop tunnels cannot be exported")]
+    Multihop,
+}
+
+/// A WireGuard configuration in the format used by `wg-quick`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgQuickConfig {
+    pub interface: Interface,
+    pub peer: Peer,
+}
+
+/// The `[Interface]` section of a [`WgQuickConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub private_key: PrivateKey,
+    pub addresses: Vec<IpNetwork>,
+    pub dns: Vec<IpAddr>,
+    pub mtu: Option<u16>,
+}
+
+/// The `[Peer]` section of a [`WgQuickConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    pub public_key: PublicKey,
+    pub preshared_key: Option<PresharedKey>,
+    /// Hostname or IP address of the peer.
+    pub endpoint_host: String,
+    pub endpoint_port: u16,
+    pub allowed_ips: Vec<IpNetwork>,
+}
+
+impl WgQuickConfig {
+    /// Create a config from the connection of a WireGuard tunnel.
+    pub fn from_connection_config(
+        config: &wireguard::ConnectionConfig,
+        dns: Vec<IpAddr>,
+        mtu: Option<u16>,
+    ) -> Result<Self, Error> {
+        if config.exit_peer.is_some() {
+            return Err(Error::Multihop);
+        }
+        Ok(WgQuickConfig {
+            interface: Interface {
+                private_key: config.tunnel.private_key.clone(),
+                addresses: config
+                    .tunnel
+                    .addresses
+                    .iter()
+                    .map(|address| IpNetwork::from(*address))
+                    .collect(),
+                dns,
+                mtu,
+            },
+            peer: Peer {
+                public_key: config.peer.public_key.clone(),
+                preshared_key: config.peer.psk.clone(),
+                endpoint_host: config.peer.endpoint.ip().to_string(),
+                endpoint_port: config.peer.endpoint.port(),
+                allowed_ips: config.peer.allowed_ips.clone(),
+            },
+        })
+    }
+
+    /// Convert the config to a custom tunnel endpoint.
+    ///
+    /// `wg-quick` configs do not specify the gateway of the tunnel. If `ipv4_gateway` is `None`,
+    /// the first host in the network of the first IPv4 interface address is used.
+    pub fn to_custom_tunnel_endpoint(
+        &self,
+        ipv4_gateway: Option<Ipv4Addr>,
+        ipv6_gateway: Option<Ipv6Addr>,
+    ) -> Result<CustomTunnelEndpoint, Error> {
+        let ipv4_gateway = match ipv4_gateway {
+            Some(gateway) => gateway,
+            None => self.derive_ipv4_gateway().ok_or(Error::NoIpv4Gateway)?,
+        };
+
+        Ok(CustomTunnelEndpoint {
+            host: self.peer.endpoint_host.clone(),
+            config: ConnectionConfig::Wireguard(wireguard::ConnectionConfig {
+                tunnel: wireguard::TunnelConfig {
+                    private_key: self.interface.private_key.clone(),
+                    addresses: self
+                        .interface
+                        .addresses
+                        .iter()
+                        .map(|address| address.ip())
+                        .collect(),
+                },
+                peer: wireguard::PeerConfig {
+                    public_key: self.peer.public_key.clone(),
+                    allowed_ips: self.peer.allowed_ips.clone(),
+                    // The host is resolved when connecting
+                    endpoint: SocketAddr::new(
+                        IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                        self.peer.endpoint_port,
+                    ),
+                    psk: None,
+                    #[cfg(daita)]
+                    constant_packet_size: false,
+                },
+                exit_peer: None,
+                ipv4_gateway,
+                ipv6_gateway,
+                #[cfg(target_os = "linux")]
+                fwmark: None,
+            }),
+            obfuscation: None,
+            preshared_key: self.peer.preshared_key.clone(),
+        })
+    }
+
+    fn derive_ipv4_gateway(&self) -> Option<Ipv4Addr> {
+        self.interface
+            .addresses
+            .iter()
+            .find_map(|address| match address {
+                IpNetwork::V4(network) if network.prefix() <= 30 => {
+                    Some(Ipv4Addr::from(u32::from(network.network()) + 1))
+                }
+                _ => None,
+            })
+    }
+}
+
+/// The section that the parser is currently in.
+enum Section {
+    None,
+    Interface,
+    Peer,
+}
+
+#[derive(Default)]
+struct PartialInterface {
+    private_key: Option<PrivateKey>,
+    addresses: Vec<IpNetwork>,
+    dns: Vec<IpAddr>,
+    mtu: Option<u16>,
+}
+
+#[derive(Default)]
+struct PartialPeer {
+    public_key: Option<PublicKey>,
+    preshared_key: Option<PresharedKey>,
+    endpoint: Option<(String, u16)>,
+    allowed_ips: Vec<IpNetwork>,
+}
+
+impl FromStr for WgQuickConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut section = Section::None;
+        let mut interface = None;
+        let mut peers = vec![];
+
+        for (index, line) in s.lines().enumerate() {
+            let line_number = index + 1;
+            let line = match line.split_once('#') {
+                Some((line, _comment)) => line,
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                let name = name.trim();
+                if name.eq_ignore_ascii_case("Interface") {
+                    interface.get_or_insert_with(PartialInterface::default);
+                    section = Section::Interface;
+                } else if name.eq_ignore_ascii_case("Peer") {
+                    peers.push(PartialPeer::default());
+                    section = Section::Peer;
+                } else {
+                    return Err(Error::UnknownSection {
+                        line: line_number,
+                        section: name.to_owned(),
+                    });
+                }
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or(Error::InvalidLine { line: line_number })?;
+
+            match section {
+                Section::None => {
+                    return Err(Error::KeyOutsideSection {
+                        line: line_number,
+                        key: key.to_owned(),
+                    });
+                }
+                Section::Interface => {
+                    let interface = interface.as_mut().expect("interface section was entered");
+                    parse_interface_key(interface, line_number, key, value)?;
+                }
+                Section::Peer => {
+                    let peer = peers.last_mut().expect("peer section was entered");
+                    parse_peer_key(peer, line_number, key, value)?;
+                }
+            }
+        }
+
+        let interface = interface.ok_or(Error::MissingInterface)?;
+        let peer = match <[PartialPeer; 1]>::try_from(peers) {
+            Ok([peer]) => peer,
+            Err(peers) => return Err(Error::PeerCount(peers.len())),
+        };
+        let (endpoint_host, endpoint_port) = peer.endpoint.ok_or(Error::MissingKey {
+            section: "Peer",
+            key: "Endpoint",
+        })?;
+
+        Ok(WgQuickConfig {
+            interface: Interface {
+                private_key: interface.private_key.ok_or(Error::MissingKey {
+                    section: "Interface",
+                    key: "PrivateKey",
+                })?,
+                addresses: interface.addresses,
+                dns: interface.dns,
+                mtu: interface.mtu,
+            },
+            peer: Peer {
+                public_key: peer.public_key.ok_or(Error::MissingKey {
+                    section: "Peer",
+                    key: "PublicKey",
+                })?,
+                preshared_key: peer.preshared_key,
+                endpoint_host,
+                endpoint_port,
+                allowed_ips: peer.allowed_ips,
+            },
+        })
+    }
+}
+
+fn parse_interface_key(
+    interface: &mut PartialInterface,
+    line: usize,
+    key: &str,
+    value: &str,
+) -> Result<(), Error> {
+    if key.eq_ignore_ascii_case("PrivateKey") {
+        let private_key = PrivateKey::from_base64(value).map_err(|_| Error::InvalidValue {
+            line,
+            key: "PrivateKey",
+        })?;
+        set_once(&mut interface.private_key, private_key, line, "PrivateKey")
+    } else if key.eq_ignore_ascii_case("Address") {
+        interface
+            .addresses
+            .extend(parse_list::<IpNetwork>(value, line, "Address")?);
+        Ok(())
+    } else if key.eq_ignore_ascii_case("DNS") {
+        // wg-quick also accepts search domains here, which we cannot represent
+        interface
+            .dns
+            .extend(parse_list::<IpAddr>(value, line, "DNS")?);
+        Ok(())
+    } else if key.eq_ignore_ascii_case("MTU") {
+        let mtu = value
+            .parse()
+            .map_err(|_| Error::InvalidValue { line, key: "MTU" })?;
+        set_once(&mut interface.mtu, mtu, line, "MTU")
+    } else {
+        Err(Error::UnsupportedKey {
+            line,
+            section: "Interface",
+            key: key.to_owned(),
+        })
+    }
+}
+
+fn parse_peer_key(
+    peer: &mut PartialPeer,
+    line: usize,
+    key: &str,
+    value: &str,
+) -> Result<(), Error> {
+    if key.eq_ignore_ascii_case("PublicKey") {
+        let public_key = PublicKey::from_base64(value).map_err(|_| Error::InvalidValue {
+            line,
+            key: "PublicKey",
+        })?;
+        set_once(&mut peer.public_key, public_key, line, "PublicKey")
+    } else if key.eq_ignore_ascii_case("PresharedKey") {
+        let preshared_key = PresharedKey::from_base64(value).map_err(|_| Error::InvalidValue {
+            line,
+            key: "PresharedKey",
+        })?;
+        set_once(&mut peer.preshared_key, preshared_key, line, "PresharedKey")
+    } else if key.eq_ignore_ascii_case("Endpoint") {
+        let endpoint = parse_endpoint(value).ok_or(Error::InvalidValue {
+            line,
+            key: "Endpoint",
+        })?;
+        set_once(&mut peer.endpoint, endpoint, line, "Endpoint")
+    } else if key.eq_ignore_ascii_case("AllowedIPs") {
+        peer.allowed_ips
+            .extend(parse_list::<IpNetwork>(value, line, "AllowedIPs")?);
+        Ok(())
+    } else {
+        Err(Error::UnsupportedKey {
+            line,
+            section: "Peer",
+            key: key.to_owned(),
+        })
+    }
+}
+
+fn set_once<T>(
+    field: &mut Option<T>,
+    value: T,
+    line: usize,
+    key: &'static str,
+) -> Result<(), Error> {
+    if field.is_some() {
+        return Err(Error::DuplicateKey { line, key });
+    }
+    *field = Some(value);
+    Ok(())
+}
+
+fn parse_list<T: FromStr>(value: &str, line: usize, key: &'static str) -> Result<Vec<T>, Error> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| item.parse().map_err(|_| Error::InvalidValue { line, key }))
+        .collect()
+}
+
+/// Parse an endpoint on the form `host:port`, where `host` may be a bracketed IPv6 address.
+fn parse_endpoint(value: &str) -> Option<(String, u16)> {
+    let (host, port) = value.rsplit_once(':')?;
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.strip_suffix(']')?,
+        None if host.contains(':') => return None,
+        None => host,
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_owned(), port.parse().ok()?))
+}
+
+impl fmt::Display for WgQuickConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Interface]")?;
+        writeln!(f, "PrivateKey = {}", self.interface.private_key.to_base64())?;
+        if !self.interface.addresses.is_empty() {
+            writeln!(f, "Address = {}", join(&self.interface.addresses))?;
+        }
+        if !self.interface.dns.is_empty() {
+            writeln!(f, "DNS = {}", join(&self.interface.dns))?;
+        }
+        if let Some(mtu) = self.interface.mtu {
+            writeln!(f, "MTU = {mtu}")?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "[Peer]")?;
+        writeln!(f, "PublicKey = {}", self.peer.public_key.to_base64())?;
+        if let Some(preshared_key) = &self.peer.preshared_key {
+            writeln!(f, "PresharedKey = {}", preshared_key.to_base64())?;
+        }
+        if !self.peer.allowed_ips.is_empty() {
+            writeln!(f, "AllowedIPs = {}", join(&self.peer.allowed_ips))?;
+        }
+        if self.peer.endpoint_host.contains(':') {
+            writeln!(
+                f,
+                "Endpoint = [{}]:{}",
+                self.peer.endpoint_host, self.peer.endpoint_port
+            )
+        } else {
+            writeln!(
+                f,
+                "Endpoint = {}:{}",
+                self.peer.endpoint_host, self.peer.endpoint_port
+            )
+        }
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::TunnelParameters;
+
+    const PRIVATE_KEY: &str = "n5kvcfgf+iPAi1Lsc87kx4rtXevkn15+A9wumAiTc5c=";
+    const PUBLIC_KEY: &str = "pqKffMcFRsdF/ieoeU1o/Pif7jb1bx8Tykf4Vs30yvU=";
+    const PRESHARED_KEY: &str = "Cm8eSnwzEPq91ZuWDcXN+XVIlyk6yvYUiWgNjA5pgp8=";
+
+    fn config() -> String {
+        format!(
+            "# Example configuration
+[Interface]
+PrivateKey = {PRIVATE_KEY}
+Address = 10.64.0.2/24, fc00:bbbb:bbbb:bb01::2/64
+DNS = 10.64.0.1
+MTU = 1380
+
+[Peer]
+PublicKey = {PUBLIC_KEY}
+AllowedIPs = 0.0.0.0/0, ::0/0 # Route everything
+Endpoint = vpn.example.com:51820
+"
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let config: WgQuickConfig = config().parse().unwrap();
+
+        assert_eq!(
+            config.interface.private_key,
+            PrivateKey::from_base64(PRIVATE_KEY).unwrap()
+        );
+        assert_eq!(
+            config.interface.addresses,
+            vec![
+                "10.64.0.2/24".parse().unwrap(),
+                "fc00:bbbb:bbbb:bb01::2/64".parse().unwrap()
+            ]
+        );
+        assert_eq!(
+            config.interface.dns,
+            vec!["10.64.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(config.interface.mtu, Some(1380));
+        assert_eq!(
+            config.peer.public_key,
+            PublicKey::from_base64(PUBLIC_KEY).unwrap()
+        );
+        assert_eq!(config.peer.preshared_key, None);
+        assert_eq!(config.peer.endpoint_host, "vpn.example.com");
+        assert_eq!(config.peer.endpoint_port, 51820);
+        assert_eq!(
+            config.peer.allowed_ips,
+            vec!["0.0.0.0/0".parse().unwrap(), "::0/0".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let config: WgQuickConfig = config().parse().unwrap();
+        let reparsed: WgQuickConfig = config.to_string().parse().unwrap();
+        assert_eq!(config, reparsed);
+    }
+
+    #[test]
+    fn test_parse_ipv6_endpoint() {
+        assert_eq!(
+            parse_endpoint("[2001:db8::1]:51820"),
+            Some(("2001:db8::1".to_owned(), 51820))
+        );
+        assert_eq!(parse_endpoint("2001:db8::1:51820"), None);
+        assert_eq!(parse_endpoint("example.com"), None);
+    }
+
+    #[test]
+    fn test_unsupported_key() {
+        let config = config().replace("MTU = 1380", "PostUp = iptables -A FORWARD");
+        assert_eq!(
+            config.parse::<WgQuickConfig>(),
+            Err(Error::UnsupportedKey {
+                line: 6,
+                section: "Interface",
+                key: "PostUp".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_missing_peer() {
+        let config = format!("[Interface]\nPrivateKey = {PRIVATE_KEY}\n");
+        assert_eq!(config.parse::<WgQuickConfig>(), Err(Error::PeerCount(0)));
+    }
+
+    #[test]
+    fn test_custom_tunnel_endpoint() {
+        let config: WgQuickConfig = config().parse().unwrap();
+        let endpoint = config.to_custom_tunnel_endpoint(None, None).unwrap();
+
+        assert_eq!(endpoint.host, "vpn.example.com");
+        let ConnectionConfig::Wireguard(connection) = endpoint.config else {
+            panic!("expected WireGuard config");
+        };
+        assert_eq!(connection.ipv4_gateway, Ipv4Addr::new(10, 64, 0, 1));
+        assert_eq!(connection.peer.endpoint.port(), 51820);
+        assert_eq!(
+            connection.tunnel.addresses,
+            vec![
+                "10.64.0.2".parse::<IpAddr>().unwrap(),
+                "fc00:bbbb:bbbb:bb01::2".parse().unwrap()
+            ]
+        );
+    }
+
+    /// A gateway cannot be guessed from a single-host address
+    #[test]
+    fn test_custom_tunnel_endpoint_requires_gateway() {
+        let config: WgQuickConfig = config()
+            .replace("10.64.0.2/24", "10.64.0.2/32")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            config.to_custom_tunnel_endpoint(None, None),
+            Err(Error::NoIpv4Gateway)
+        );
+
+        let gateway = Ipv4Addr::new(10, 64, 0, 1);
+        assert!(
+            config
+                .to_custom_tunnel_endpoint(Some(gateway), None)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_preshared_key() {
+        let config: WgQuickConfig = config()
+            .replace("[Peer]", &format!("[Peer]\nPresharedKey = {PRESHARED_KEY}"))
+            .replace("vpn.example.com", "192.0.2.1")
+            .parse()
+            .unwrap();
+        let preshared_key = PresharedKey::from_base64(PRESHARED_KEY).unwrap();
+        assert_eq!(config.peer.preshared_key, Some(preshared_key.clone()));
+
+        let endpoint = config.to_custom_tunnel_endpoint(None, None).unwrap();
+        assert_eq!(endpoint.preshared_key, Some(preshared_key.clone()));
+
+        let parameters = endpoint
+            .to_tunnel_parameters(Default::default(), None)
+            .unwrap();
+        let TunnelParameters::Wireguard(parameters) = parameters else {
+            panic!("expected WireGuard parameters");
+        };
+        assert_eq!(parameters.connection.peer.psk, Some(preshared_key));
+    }
+}
@@ -251,6 +251,8 @@ pub struct TunnelOptions {
     pub daita: DaitaSettings,
     /// Interval used for automatic key rotation
     pub rotation_interval: Option<RotationInterval>,
+    /// Timeouts and probes used to detect that the tunnel has stopped working
+    pub connectivity_check: wireguard::ConnectivityCheckOptions,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -262,6 +264,7 @@ impl Default for TunnelOptions {
             #[cfg(daita)]
             daita: DaitaSettings::default(),
             rotation_interval: None,
+            connectivity_check: wireguard::ConnectivityCheckOptions::default(),
         }
     }
 }
@@ -273,6 +276,7 @@ impl TunnelOptions {
             quantum_resistant: self.quantum_resistant.enabled(),
             #[cfg(daita)]
             daita: self.daita.enabled,
+            connectivity_check: self.connectivity_check,
         }
     }
 }
@@ -97,9 +97,13 @@ pub enum Opt {
         /// Replace signed files without asking for confirmation
         #[arg(long, short = 'y')]
         assume_yes: bool,
+        /// Add a signature to the metadata in `signed/` instead of signing `work/`. The metadata
+        /// is otherwise left unchanged
+        #[arg(long, conflicts_with_all = ["expiry", "assume_yes"])]
+        cosign: bool,
     },
 
-    /// Verify that payloads are signed by a given ed25519 pubkey
+    /// Verify that payloads are signed by enough of the trusted ed25519 pubkeys
     Verify {
         /// Platforms to remove releases for. All if none are specified
         platforms: Vec<Platform>,
@@ -144,6 +148,7 @@ async fn main() -> anyhow::Result<()> {
             platforms,
             expiry,
             assume_yes,
+            cosign,
         } => {
             let key_str = io_util::wait_for_input("Enter ed25519 secret: ")
                 .await
@@ -151,10 +156,17 @@ async fn main() -> anyhow::Result<()> {
             let secret = key::SecretKey::from_str(&key_str).context("Invalid secret")?;
 
             for platform in all_platforms_if_empty(platforms) {
-                platform
-                    .sign(secret.clone(), expiry, assume_yes)
-                    .await
-                    .context("Failed to sign file")?;
+                if cosign {
+                    platform
+                        .cosign(secret.clone())
+                        .await
+                        .context("Failed to co-sign file")?;
+                } else {
+                    platform
+                        .sign(secret.clone(), expiry, assume_yes)
+                        .await
+                        .context("Failed to sign file")?;
+                }
             }
             Ok(())
         }
@@ -200,6 +200,32 @@ impl Platform {
         Ok(())
     }
 
+    /// Add a signature to the metadata in `signed/`
+    pub async fn cosign(&self, secret: key::SecretKey) -> anyhow::Result<()> {
+        let signed_path = self.signed_path();
+
+        println!("Co-signing {}...", signed_path.display());
+
+        let data = fs::read(&signed_path)
+            .await
+            .context("Failed to read signed data")?;
+        let response = format::SignedResponse::deserialize_insecure(&data)?;
+        let signed_response = response.cosign(secret)?;
+
+        let signed_bytes = serde_json::to_string_pretty(&signed_response)
+            .context("Failed to serialize signed version")?;
+        create_dir_and_write(&signed_path, signed_bytes)
+            .await
+            .context("Failed to write signed data")?;
+        println!(
+            "Wrote response with {} signatures to {}",
+            signed_response.signatures.len(),
+            signed_path.display()
+        );
+
+        Ok(())
+    }
+
     /// Verify the integrity of the platform in `signed/`
     pub async fn verify(&self) -> anyhow::Result<()> {
         let signed_path = self.signed_path();
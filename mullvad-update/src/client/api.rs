@@ -5,8 +5,6 @@ use std::path::PathBuf;
 
 use anyhow::Context;
 use tokio::fs;
-#[cfg(test)]
-use vec1::Vec1;
 
 use crate::format;
 use crate::version::{VersionInfo, VersionParameters};
@@ -128,7 +126,7 @@ impl HttpVersionInfoProvider {
     async fn get_versions_with_keys(
         &self,
         lowest_metadata_version: usize,
-        verifying_keys: &Vec1<format::key::VerifyingKey>,
+        verifying_keys: &format::key::TrustedKeys,
     ) -> anyhow::Result<format::SignedResponse> {
         self.get_versions_inner(|raw_json| {
             format::SignedResponse::deserialize_and_verify_at_time(
@@ -237,7 +235,7 @@ mod test {
         let valid_key =
             crate::format::key::VerifyingKey::from_hex(include_str!("../../test-pubkey"))
                 .expect("valid key");
-        let verifying_keys = vec1![valid_key];
+        let verifying_keys = crate::format::key::TrustedKeys::from(vec1![valid_key]);
 
         // Start HTTP server
         let mut server = mockito::Server::new_async().await;
@@ -22,7 +22,12 @@ use crate::{
 #[derive(Debug, thiserror::Error)]
 pub enum DownloadError {
     #[error("Failed to download app")]
-    FetchApp(#[from] anyhow::Error),
+    FetchApp {
+        #[source]
+        error: anyhow::Error,
+        /// Failed download attempts, in the order that the mirrors were tried
+        failures: Vec<fetch::MirrorFailure>,
+    },
     #[error("Failed to verify app")]
     Verification(#[source] anyhow::Error),
     #[error("Failed to launch app")]
@@ -37,7 +42,8 @@ pub enum DownloadError {
 #[derive(Clone)]
 pub struct AppDownloaderParameters<AppProgress> {
     pub app_version: mullvad_version::Version,
-    pub app_url: String,
+    /// Mirrors that host the installer. They are tried in order of responsiveness.
+    pub app_urls: Vec<String>,
     pub app_size: usize,
     pub app_progress: AppProgress,
     pub app_sha256: [u8; 32],
@@ -126,14 +132,21 @@ pub struct InstallerFile<const VERIFIED: bool> {
 impl<AppProgress: ProgressUpdater> AppDownloader for HttpAppDownloader<AppProgress> {
     async fn download_executable(mut self) -> Result<impl DownloadedInstaller, DownloadError> {
         let bin_path = bin_path(&self.params.app_version, &self.params.cache_dir);
-        fetch::get_to_file(
-            &bin_path,
-            &self.params.app_url,
-            &mut self.params.app_progress,
-            fetch::SizeHint::Exact(self.params.app_size),
-        )
-        .await
-        .map_err(DownloadError::FetchApp)?;
+        let mut mirrors = fetch::Mirrors::new(self.params.app_urls);
+        mirrors.rank_by_responsiveness().await;
+        if let Err(error) = mirrors
+            .get_to_file(
+                &bin_path,
+                &mut self.params.app_progress,
+                fetch::SizeHint::Exact(self.params.app_size),
+            )
+            .await
+        {
+            return Err(DownloadError::FetchApp {
+                error,
+                failures: mirrors.into_failures(),
+            });
+        }
 
         Ok(InstallerFile::<false> {
             path: bin_path,
@@ -1,17 +1,20 @@
-//! A downloader that supports HTTP range requests and resuming downloads
+//! A downloader that supports HTTP range requests and resuming downloads, possibly from several
+//! mirrors
 
 use std::{
     error::Error,
     path::Path,
     pin::Pin,
     task::{Poll, ready},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use itertools::Itertools;
 use reqwest::header::{CONTENT_LENGTH, HeaderValue, RANGE};
 use tokio::{
     fs::{self, File},
     io::{self, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufWriter},
+    task::JoinSet,
 };
 
 use thiserror::Error;
@@ -21,6 +24,8 @@ const READ_TIMEOUT: Duration = Duration::from_secs(1);
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
 // Maximum number of retry attempts for timeouts
 const MAX_RETRY_ATTEMPTS: u32 = 4;
+/// How long to wait for a mirror to respond when measuring its responsiveness
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Custom error type for download operations
 #[derive(Error, Debug)]
@@ -158,6 +163,121 @@ impl SizeHint {
     }
 }
 
+/// A failed attempt to download from a mirror
+#[derive(Debug)]
+pub struct MirrorFailure {
+    pub url: String,
+    pub error: anyhow::Error,
+}
+
+/// Mirrors that host the same file. Downloads are attempted from one mirror at a time, and each
+/// mirror resumes from wherever the previous one failed.
+#[derive(Debug)]
+pub struct Mirrors {
+    urls: Vec<String>,
+    failures: Vec<MirrorFailure>,
+}
+
+impl Mirrors {
+    /// Create a set of mirrors, which are tried in the given order
+    pub fn new(urls: Vec<String>) -> Self {
+        Self {
+            urls,
+            failures: vec![],
+        }
+    }
+
+    /// Mirror URLs, in the order that they are tried
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// All failed download attempts so far
+    pub fn failures(&self) -> &[MirrorFailure] {
+        &self.failures
+    }
+
+    /// Consume the mirrors, returning all failed download attempts
+    pub fn into_failures(self) -> Vec<MirrorFailure> {
+        self.failures
+    }
+
+    /// Order the mirrors by how quickly they respond to a HEAD request. Mirrors that fail to
+    /// respond are tried last, in their original order.
+    pub async fn rank_by_responsiveness(&mut self) {
+        let client = match reqwest::Client::builder().timeout(PROBE_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(error) => {
+                log::warn!("Failed to initialize HTTP client for ranking mirrors: {error}");
+                return;
+            }
+        };
+
+        let mut probes = JoinSet::new();
+        for (index, url) in self.urls.iter().enumerate() {
+            let request = client.head(url).send();
+            probes.spawn(async move {
+                let start = Instant::now();
+                let responsive = request
+                    .await
+                    .is_ok_and(|response| response.status().is_success());
+                (index, responsive.then(|| start.elapsed()))
+            });
+        }
+
+        let mut latencies = vec![None; self.urls.len()];
+        while let Some(probe) = probes.join_next().await {
+            if let Ok((index, latency)) = probe {
+                latencies[index] = latency;
+            }
+        }
+
+        let mut ranked: Vec<_> = std::mem::take(&mut self.urls)
+            .into_iter()
+            .zip(latencies)
+            .collect();
+        ranked.sort_by_key(|(_url, latency)| latency.unwrap_or(Duration::MAX));
+        self.urls = ranked.into_iter().map(|(url, _latency)| url).collect();
+    }
+
+    /// Download to `file` from each mirror in turn, until one succeeds. Partial downloads are
+    /// resumed using the next mirror, and each failure is recorded in [Mirrors::failures].
+    ///
+    /// See [get_to_file] for details.
+    pub async fn get_to_file(
+        &mut self,
+        file: impl AsRef<Path>,
+        progress_updater: &mut impl ProgressUpdater,
+        size_hint: SizeHint,
+    ) -> anyhow::Result<()> {
+        if self.urls.is_empty() {
+            anyhow::bail!("No mirrors to download from");
+        }
+
+        let first_failure = self.failures.len();
+        for url in &self.urls {
+            match get_to_file(file.as_ref(), url, progress_updater, size_hint).await {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    log::warn!("Download from {url} failed: {error:#}");
+                    self.failures.push(MirrorFailure {
+                        url: url.clone(),
+                        error,
+                    });
+                }
+            }
+        }
+
+        anyhow::bail!(
+            "All mirrors failed: {}",
+            self.failures[first_failure..]
+                .iter()
+                .map(|failure| format!("{}: {:#}", failure.url, failure.error))
+                .join(", ")
+        )
+    }
+}
+
 /// Download `url` to `file`. If the file already exists, this appends to it, as long
 /// as the file pointed to by `url` is larger than it.
 ///
@@ -300,6 +420,14 @@ pub async fn get_to_writer(
 
     writer.shutdown().await.map_err(DownloadError::Flush)?;
 
+    // Some servers end the response early without an error. The remainder can be fetched later,
+    // possibly from another mirror.
+    if writer.written_nbytes != total_size {
+        return Err(DownloadError::SizeValidation(
+            "Server returned fewer than requested bytes".to_string(),
+        ));
+    }
+
     Ok(())
 }
 
@@ -584,6 +712,31 @@ mod test {
             .create();
     }
 
+    /// Create endpoints that serve a file at `url_path`, but end responses after `served_len`
+    /// bytes of the file
+    fn add_truncated_file_server_mock(
+        server: &mut mockito::Server,
+        url_path: &str,
+        data: &'static [u8],
+        served_len: usize,
+    ) {
+        server
+            .mock("HEAD", url_path)
+            .with_header(CONTENT_LENGTH, &data.len().to_string())
+            .create();
+
+        server
+            .mock("GET", url_path)
+            .with_body_from_request(move |request| {
+                let range = request.header(RANGE);
+                let range = range[0].to_str().expect("expected str");
+                let (begin, end) = parse_http_range(range).expect("invalid range");
+
+                data[begin..=end.min(served_len - 1)].to_vec()
+            })
+            .create();
+    }
+
     /// Parse a range header value, e.g. "bytes=0-31"
     fn parse_http_range(val: &str) -> anyhow::Result<(usize, usize)> {
         // parse: bytes=0-31
@@ -644,4 +797,113 @@ mod test {
 
         Ok(())
     }
+
+    /// Test that a download that fails on one mirror is resumed using the next one
+    #[tokio::test]
+    async fn test_mirror_failover() -> anyhow::Result<()> {
+        // Generate random data
+        let file_data = Box::leak(Box::new(vec![0u8; 1024 * 1024]));
+        rand::thread_rng().fill_bytes(file_data);
+        let half = file_data.len() / 2;
+
+        // This mirror only serves the first half of the file
+        let mut broken_server = mockito::Server::new_async().await;
+        let broken_url = format!("{}/my_file", broken_server.url());
+        add_truncated_file_server_mock(&mut broken_server, "/my_file", file_data, half);
+
+        // This mirror does not have the file at all
+        let missing_server = mockito::Server::new_async().await;
+        let missing_url = format!("{}/my_file", missing_server.url());
+
+        let mut server = mockito::Server::new_async().await;
+        let file_url = format!("{}/my_file", server.url());
+        server
+            .mock("HEAD", "/my_file")
+            .with_header(CONTENT_LENGTH, &file_data.len().to_string())
+            .create();
+        // Expect the remainder to be requested
+        let remainder = server
+            .mock("GET", "/my_file")
+            .match_header(
+                RANGE.as_str(),
+                format!("bytes={half}-{}", file_data.len() - 1).as_str(),
+            )
+            .with_body(&file_data[half..])
+            .expect(1)
+            .create();
+
+        let temp_dir = TempDir::new().await?;
+        let file_path = temp_dir.join("my_file");
+        let mut progress_updater = FakeProgressUpdater::default();
+
+        let mut mirrors = Mirrors::new(vec![
+            broken_url.clone(),
+            missing_url.clone(),
+            file_url.clone(),
+        ]);
+        mirrors
+            .get_to_file(
+                &file_path,
+                &mut progress_updater,
+                SizeHint::Exact(file_data.len()),
+            )
+            .await
+            .context("Download from mirrors failed")?;
+
+        remainder.assert();
+        assert_eq!(progress_updater.url, file_url);
+        assert_eq!(progress_updater.complete, 1.);
+        assert_eq!(&fs::read(&file_path).await?, file_data);
+
+        let failed_urls: Vec<_> = mirrors.failures().iter().map(|f| &f.url).collect();
+        assert_eq!(failed_urls, [&broken_url, &missing_url]);
+
+        Ok(())
+    }
+
+    /// Test that the download fails if all mirrors fail, and that each failure is recorded
+    #[tokio::test]
+    async fn test_mirror_all_fail() -> anyhow::Result<()> {
+        let server1 = mockito::Server::new_async().await;
+        let server2 = mockito::Server::new_async().await;
+        let urls = vec![
+            format!("{}/my_file", server1.url()),
+            format!("{}/my_file", server2.url()),
+        ];
+
+        let temp_dir = TempDir::new().await?;
+        let mut mirrors = Mirrors::new(urls.clone());
+        mirrors
+            .get_to_file(
+                temp_dir.join("my_file"),
+                &mut FakeProgressUpdater::default(),
+                SizeHint::Exact(1),
+            )
+            .await
+            .expect_err("Expected all mirrors to fail");
+
+        let failed_urls: Vec<_> = mirrors.failures().iter().map(|f| f.url.clone()).collect();
+        assert_eq!(failed_urls, urls);
+
+        Ok(())
+    }
+
+    /// Test that unresponsive mirrors are ranked last
+    #[tokio::test]
+    async fn test_rank_mirrors() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("HEAD", "/my_file")
+            .with_header(CONTENT_LENGTH, "1")
+            .create();
+        let file_url = format!("{}/my_file", server.url());
+
+        // Nothing listens on this port
+        let unresponsive_url = "http://127.0.0.1:1/my_file".to_owned();
+
+        let mut mirrors = Mirrors::new(vec![unresponsive_url.clone(), file_url.clone()]);
+        mirrors.rank_by_responsiveness().await;
+
+        assert_eq!(mirrors.urls(), [file_url, unresponsive_url]);
+    }
 }
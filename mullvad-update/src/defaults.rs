@@ -1,8 +1,7 @@
 //! Default keys and certificates that may be used for verifying data
 
-use crate::format::key::VerifyingKey;
+use crate::format::key::{TrustedKeys, VerifyingKey};
 use std::sync::LazyLock;
-use vec1::Vec1;
 
 /// Default repository URL for version metadata
 #[cfg(feature = "client")]
@@ -15,20 +14,29 @@ pub static PINNED_CERTIFICATE: LazyLock<reqwest::Certificate> = LazyLock::new(||
     reqwest::Certificate::from_pem(CERT_BYTES).expect("invalid cert")
 });
 
-/// Pubkeys used to verify metadata from the Mullvad API (production)
-pub static TRUSTED_METADATA_SIGNING_PUBKEYS: LazyLock<Vec1<VerifyingKey>> =
+/// Pubkeys used to verify metadata from the Mullvad API (production), and how many of them must
+/// have signed it
+pub static TRUSTED_METADATA_SIGNING_PUBKEYS: LazyLock<TrustedKeys> =
     LazyLock::new(|| parse_keys(include_str!("../trusted-metadata-signing-pubkeys")));
 
-fn parse_keys(keys: &str) -> Vec1<VerifyingKey> {
+/// Parse one hex-encoded key per line. A line `threshold: <n>` sets the number of keys that must
+/// sign the metadata, which is 1 by default.
+fn parse_keys(keys: &str) -> TrustedKeys {
     let mut v = vec![];
+    let mut threshold = 1;
     for key in keys.split('\n') {
         let key = key.trim();
         if key.starts_with('#') || key.is_empty() {
             continue;
         }
+        if let Some(n) = key.strip_prefix("threshold:") {
+            threshold = n.trim().parse().expect("invalid threshold");
+            continue;
+        }
         v.push(VerifyingKey::from_hex(key).expect("invalid pubkey"));
     }
-    v.try_into().expect("need at least one key")
+    let keys = v.try_into().expect("need at least one key");
+    TrustedKeys::new(keys, threshold).expect("invalid trusted keys")
 }
 
 #[cfg(test)]
@@ -44,8 +52,19 @@ fn test_parse_keys() {
 {key2}
 "#
     ));
-    assert_eq!(format!("{}", keys[0]), key1);
-    assert_eq!(format!("{}", keys[1]), key2);
+    assert_eq!(format!("{}", keys.keys()[0]), key1);
+    assert_eq!(format!("{}", keys.keys()[1]), key2);
+    assert_eq!(keys.threshold(), 1);
+
+    let keys = parse_keys(&format!(
+        r#"
+threshold: 2
+{key1}
+{key2}
+"#
+    ));
+    assert_eq!(keys.keys().len(), 2);
+    assert_eq!(keys.threshold(), 2);
 
     // Test that actual keys are validly parsed
     let _prod = &*TRUSTED_METADATA_SIGNING_PUBKEYS;
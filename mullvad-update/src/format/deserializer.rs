@@ -1,7 +1,6 @@
 //! Deserializer and verifier of version metadata
 
 use anyhow::Context;
-use vec1::Vec1;
 
 use super::Response;
 use super::key::*;
@@ -28,7 +27,7 @@ impl SignedResponse {
     ///
     /// This is typically only used for testing. Prefer [deserialize_and_verify].
     pub(crate) fn deserialize_and_verify_with_keys(
-        keys: &Vec1<VerifyingKey>,
+        keys: &TrustedKeys,
         bytes: &[u8],
         min_metadata_version: usize,
     ) -> Result<Self, anyhow::Error> {
@@ -53,7 +52,7 @@ impl SignedResponse {
     ///
     /// This is typically only used for testing. Prefer [deserialize_and_verify].
     pub(crate) fn deserialize_and_verify_at_time(
-        keys: &Vec1<VerifyingKey>,
+        keys: &TrustedKeys,
         bytes: &[u8],
         current_time: chrono::DateTime<chrono::Utc>,
         min_metadata_version: usize,
@@ -89,39 +88,56 @@ impl SignedResponse {
     }
 }
 
-/// Deserialize arbitrary JSON object with a signature attached.
-/// WARNING: This only verifies the signature, not expiration.
+/// Deserialize arbitrary JSON object with signatures attached.
+/// WARNING: This only verifies the signatures, not expiration.
 ///
-/// On success, this returns verified data and signature
+/// The data must be signed by at least [TrustedKeys::threshold] distinct trusted keys. Signatures
+/// by unknown keys are ignored, and a key that has signed more than once is only counted once.
+/// An invalid signature by a trusted key is always an error.
+///
+/// On success, this returns verified data and signatures
 pub(super) fn deserialize_and_verify(
-    keys: &Vec1<VerifyingKey>,
+    keys: &TrustedKeys,
     bytes: &[u8],
 ) -> anyhow::Result<PartialSignedResponse> {
     let partial_data: PartialSignedResponse =
         serde_json::from_slice(bytes).context("Invalid version JSON")?;
 
-    let valid_keys: Vec<_> = keys.into_iter().map(|k| k.0).collect();
-
-    // Check if one of the keys matches
-    let Some((key, sig)) = partial_data.signatures.iter().find_map(|sig| match sig {
-        // Check if ed25519 key matches
-        ResponseSignature::Ed25519 { keyid, sig } if valid_keys.contains(&keyid.0) => {
-            Some((keyid, sig))
-        }
-        // Ignore all non-matching key
-        _ => None,
-    }) else {
-        anyhow::bail!("Unrecognized key");
-    };
-
     // Serialize to canonical json format
     let canon_data = json_canon::to_vec(&partial_data.signed)
         .context("Failed to serialize to canonical JSON")?;
 
-    // Check if the data is signed by our key
-    key.0
-        .verify_strict(&canon_data, &sig.0)
-        .context("Signature verification failed")?;
+    let mut signed_by: Vec<&VerifyingKey> = vec![];
+    for sig in &partial_data.signatures {
+        let (key, sig) = match sig {
+            // Check if ed25519 key matches
+            ResponseSignature::Ed25519 { keyid, sig } if keys.keys().contains(keyid) => {
+                (keyid, sig)
+            }
+            // Ignore all non-matching keys
+            _ => continue,
+        };
+
+        // Check if the data is signed by our key
+        key.0
+            .verify_strict(&canon_data, &sig.0)
+            .with_context(|| format!("Signature verification failed for key {key}"))?;
+
+        if !signed_by.contains(&key) {
+            signed_by.push(key);
+        }
+    }
+
+    if signed_by.is_empty() {
+        anyhow::bail!("Unrecognized key");
+    }
+    if signed_by.len() < keys.threshold() {
+        anyhow::bail!(
+            "Signed by {} trusted keys, but {} are required",
+            signed_by.len(),
+            keys.threshold()
+        );
+    }
 
     Ok(PartialSignedResponse {
         signatures: partial_data.signatures,
@@ -147,7 +163,7 @@ mod test {
             ed25519_dalek::VerifyingKey::from_bytes(&pubkey.try_into().unwrap()).unwrap();
 
         SignedResponse::deserialize_and_verify_at_time(
-            &vec1![VerifyingKey(verifying_key)],
+            &TrustedKeys::from(vec1![VerifyingKey(verifying_key)]),
             include_bytes!("../../test-version-response.json"),
             // It's 1970 again
             chrono::DateTime::UNIX_EPOCH,
@@ -158,7 +174,7 @@ mod test {
 
         // Reject expired data
         SignedResponse::deserialize_and_verify_at_time(
-            &vec1![VerifyingKey(verifying_key)],
+            &TrustedKeys::from(vec1![VerifyingKey(verifying_key)]),
             include_bytes!("../../test-version-response.json"),
             // In the year 3000
             chrono::DateTime::from_str("3000-01-01T00:00:00Z").unwrap(),
@@ -169,7 +185,7 @@ mod test {
 
         // Reject expired version number
         SignedResponse::deserialize_and_verify_at_time(
-            &vec1![VerifyingKey(verifying_key)],
+            &TrustedKeys::from(vec1![VerifyingKey(verifying_key)]),
             include_bytes!("../../test-version-response.json"),
             chrono::DateTime::UNIX_EPOCH,
             usize::MAX,
@@ -5,6 +5,7 @@ use std::{fmt, str::FromStr};
 use anyhow::{Context, bail};
 use ed25519_dalek::ed25519::signature::Signer;
 use serde::{Deserialize, Serialize};
+use vec1::Vec1;
 use zeroize::Zeroize;
 
 /// ed25519 secret/signing key
@@ -120,6 +121,50 @@ impl Serialize for VerifyingKey {
     }
 }
 
+/// Set of trusted ed25519 verifying keys, and the number of them that must have signed some data
+/// for it to be trusted
+#[derive(Debug, Clone)]
+pub struct TrustedKeys {
+    keys: Vec1<VerifyingKey>,
+    threshold: usize,
+}
+
+impl TrustedKeys {
+    /// Require signatures from at least `threshold` of `keys`
+    pub fn new(keys: Vec1<VerifyingKey>, threshold: usize) -> anyhow::Result<Self> {
+        if threshold == 0 || threshold > keys.len() {
+            bail!(
+                "threshold must be between 1 and {}, found {threshold}",
+                keys.len()
+            );
+        }
+        if let Some(key) = keys
+            .iter()
+            .enumerate()
+            .find_map(|(i, key)| keys[..i].contains(key).then_some(key))
+        {
+            bail!("duplicate key: {key}");
+        }
+        Ok(Self { keys, threshold })
+    }
+
+    pub fn keys(&self) -> &[VerifyingKey] {
+        &self.keys
+    }
+
+    /// Number of distinct keys that must have signed the data
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+/// Require a signature from any one of the keys
+impl From<Vec1<VerifyingKey>> for TrustedKeys {
+    fn from(keys: Vec1<VerifyingKey>) -> Self {
+        Self { keys, threshold: 1 }
+    }
+}
+
 /// ed25519 signature
 #[derive(Debug, PartialEq)]
 pub struct Signature(pub ed25519_dalek::Signature);
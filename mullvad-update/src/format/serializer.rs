@@ -4,15 +4,20 @@
 //!
 //! ```ignore
 //! {
-//!     "signature": {
-//!         "keyid": "...",
-//!         "sig": "..."
-//!     }
+//!     "signatures": [
+//!         {
+//!             "keytype": "ed25519",
+//!             "keyid": "...",
+//!             "sig": "..."
+//!         }
+//!     ],
 //!     "signed": {
 //!         ...
 //!     }
 //! }
 //! ```
+//!
+//! Additional signatures can be added to already signed data using [SignedResponse::cosign].
 
 use anyhow::Context;
 use serde::Serialize;
@@ -38,6 +43,25 @@ impl SignedResponse {
             signed: response,
         })
     }
+
+    /// Add a signature using `key` to already signed data, replacing any existing signature by the
+    /// same key. The existing signatures are not verified.
+    pub fn cosign(mut self, key: key::SecretKey) -> anyhow::Result<SignedResponse> {
+        // Refuse to sign expired data
+        if self.signed.metadata_expiry < chrono::Utc::now() {
+            anyhow::bail!("Signing failed since the data has expired");
+        }
+
+        let partial_signed = sign(&key, &self.signed)?;
+
+        let pubkey = key.pubkey();
+        self.signatures.retain(
+            |sig| !matches!(sig, ResponseSignature::Ed25519 { keyid, .. } if keyid == &pubkey),
+        );
+        self.signatures.extend(partial_signed.signatures);
+
+        Ok(self)
+    }
 }
 
 /// Serialize JSON to bytes, with a signature attached, signed using `key`
@@ -71,6 +95,7 @@ fn sign<T: Serialize>(
 mod test {
     use super::*;
     use crate::format::deserializer::deserialize_and_verify;
+    use crate::format::key::TrustedKeys;
     use serde_json::json;
     use vec1::vec1;
 
@@ -96,16 +121,19 @@ mod test {
 
         let bytes = serde_json::to_vec(&partial)?;
 
-        deserialize_and_verify(&vec1![pubkey.clone()], &bytes)?;
+        deserialize_and_verify(&vec1![pubkey.clone()].into(), &bytes)?;
 
         // Verify that an irrelevant key is ignored
         let invalid_key = key::SecretKey::generate();
         let invalid_pubkey = invalid_key.pubkey();
 
-        deserialize_and_verify(&vec1![pubkey.clone(), invalid_pubkey.clone()], &bytes)?;
+        deserialize_and_verify(
+            &vec1![pubkey.clone(), invalid_pubkey.clone()].into(),
+            &bytes,
+        )?;
 
         // Wrong public key only fails
-        deserialize_and_verify(&vec1![invalid_pubkey], &bytes).unwrap_err();
+        deserialize_and_verify(&vec1![invalid_pubkey].into(), &bytes).unwrap_err();
 
         Ok(())
     }
@@ -134,20 +162,112 @@ mod test {
         let bytes = serde_json::to_vec(&partial)?;
 
         // Accept either (or both) keys
-        deserialize_and_verify(&vec1![pubkey.clone(), pubkey2.clone()], &bytes)?;
-        deserialize_and_verify(&vec1![pubkey2.clone()], &bytes)?;
-        deserialize_and_verify(&vec1![pubkey.clone()], &bytes)?;
+        deserialize_and_verify(&vec1![pubkey.clone(), pubkey2.clone()].into(), &bytes)?;
+        deserialize_and_verify(&vec1![pubkey2.clone()].into(), &bytes)?;
+        deserialize_and_verify(&vec1![pubkey.clone()].into(), &bytes)?;
 
         // Ignore irrelevant key
         deserialize_and_verify(
-            &vec1![pubkey.clone(), pubkey2.clone(), invalid_pubkey.clone()],
+            &vec1![pubkey.clone(), pubkey2.clone(), invalid_pubkey.clone()].into(),
             &bytes,
         )?;
-        deserialize_and_verify(&vec1![pubkey2, invalid_pubkey.clone()], &bytes)?;
-        deserialize_and_verify(&vec1![invalid_pubkey.clone(), pubkey], &bytes)?;
+        deserialize_and_verify(&vec1![pubkey2, invalid_pubkey.clone()].into(), &bytes)?;
+        deserialize_and_verify(&vec1![invalid_pubkey.clone(), pubkey].into(), &bytes)?;
 
         // Using wrong public key fails
-        deserialize_and_verify(&vec1![invalid_pubkey], &bytes).unwrap_err();
+        deserialize_and_verify(&vec1![invalid_pubkey].into(), &bytes).unwrap_err();
+
+        Ok(())
+    }
+
+    /// Test that data must be signed by a threshold of distinct trusted keys
+    #[test]
+    fn test_sign_threshold() -> anyhow::Result<()> {
+        let key1 = key::SecretKey::generate();
+        let key2 = key::SecretKey::generate();
+        let key3 = key::SecretKey::generate();
+        let unknown_key = key::SecretKey::generate();
+        let trusted = TrustedKeys::new(vec1![key1.pubkey(), key2.pubkey(), key3.pubkey()], 2)?;
+
+        let data = json!({
+            "stuff": "We both wrote this"
+        });
+        let signed_by = |keys: &[&key::SecretKey]| -> anyhow::Result<Vec<u8>> {
+            let mut partial = sign(keys[0], &data)?;
+            for key in &keys[1..] {
+                partial.signatures.extend(sign(key, &data)?.signatures);
+            }
+            Ok(serde_json::to_vec(&partial)?)
+        };
+
+        // Any two trusted keys are enough
+        deserialize_and_verify(&trusted, &signed_by(&[&key1, &key2])?)?;
+        deserialize_and_verify(&trusted, &signed_by(&[&key3, &key1])?)?;
+        deserialize_and_verify(&trusted, &signed_by(&[&key1, &key2, &key3])?)?;
+
+        // Missing signature
+        deserialize_and_verify(&trusted, &signed_by(&[&key1])?).unwrap_err();
+
+        // Duplicate signatures by the same key only count once
+        deserialize_and_verify(&trusted, &signed_by(&[&key1, &key1])?).unwrap_err();
+
+        // Signatures by unknown keys do not count
+        deserialize_and_verify(&trusted, &signed_by(&[&key1, &unknown_key])?).unwrap_err();
+        deserialize_and_verify(&trusted, &signed_by(&[&unknown_key, &key2, &key3])?)?;
+
+        Ok(())
+    }
+
+    /// Test that an invalid signature by a trusted key is rejected, even if the threshold is met
+    #[test]
+    fn test_sign_threshold_invalid_signature() -> anyhow::Result<()> {
+        let key1 = key::SecretKey::generate();
+        let key2 = key::SecretKey::generate();
+        let key3 = key::SecretKey::generate();
+        let trusted = TrustedKeys::new(vec1![key1.pubkey(), key2.pubkey(), key3.pubkey()], 2)?;
+
+        let data = json!({
+            "stuff": "We both wrote this"
+        });
+        let mut partial = sign(&key1, &data)?;
+        partial.signatures.extend(sign(&key2, &data)?.signatures);
+
+        // key3 signed something else
+        let other_data = json!({
+            "stuff": "Something else"
+        });
+        partial
+            .signatures
+            .extend(sign(&key3, &other_data)?.signatures);
+
+        deserialize_and_verify(&trusted, &serde_json::to_vec(&partial)?).unwrap_err();
+
+        Ok(())
+    }
+
+    /// Test that co-signing adds a signature to signed data
+    #[test]
+    fn test_cosign() -> anyhow::Result<()> {
+        let key1 = key::SecretKey::generate();
+        let key2 = key::SecretKey::generate();
+        let trusted = TrustedKeys::new(vec1![key1.pubkey(), key2.pubkey()], 2)?;
+
+        let response = Response {
+            metadata_expiry: chrono::Utc::now() + chrono::Duration::days(1),
+            ..Response::default()
+        };
+
+        let signed = SignedResponse::sign(key1.clone(), response)?;
+        let bytes = serde_json::to_vec(&signed)?;
+        deserialize_and_verify(&trusted, &bytes).unwrap_err();
+
+        let cosigned = signed.cosign(key2.clone())?;
+        let bytes = serde_json::to_vec(&cosigned)?;
+        deserialize_and_verify(&trusted, &bytes)?;
+
+        // Signing again with the same key replaces the signature
+        let cosigned = cosigned.cosign(key2)?;
+        assert_eq!(cosigned.signatures.len(), 2);
 
         Ok(())
     }
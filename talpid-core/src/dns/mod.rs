@@ -1,5 +1,6 @@
 use std::fmt;
 use std::net::IpAddr;
+use talpid_types::net::dns::{EncryptedDnsServer, SplitDnsRule};
 
 #[cfg(target_os = "linux")]
 use talpid_routing::RouteManagerHandle;
@@ -29,12 +30,15 @@ pub use self::imp::Error;
 #[derive(Debug, Clone, PartialEq)]
 pub struct DnsConfig {
     config: InnerDnsConfig,
+    /// Domains to resolve using other servers than those in `config`
+    split_config: Vec<SplitDnsRule>,
 }
 
 impl Default for DnsConfig {
     fn default() -> Self {
         Self {
             config: InnerDnsConfig::Default,
+            split_config: vec![],
         }
     }
 }
@@ -42,13 +46,32 @@ impl Default for DnsConfig {
 impl DnsConfig {
     /// Use the specified addresses for DNS resolution
     pub fn from_addresses(tunnel_config: &[IpAddr], non_tunnel_config: &[IpAddr]) -> Self {
+        Self::from_servers(tunnel_config, non_tunnel_config, &[])
+    }
+
+    /// Use the specified addresses and encrypted DNS servers for DNS resolution. The encrypted
+    /// servers are only used by the local DNS resolver on macOS.
+    pub fn from_servers(
+        tunnel_config: &[IpAddr],
+        non_tunnel_config: &[IpAddr],
+        encrypted_config: &[EncryptedDnsServer],
+    ) -> Self {
         DnsConfig {
             config: InnerDnsConfig::Override {
                 tunnel_config: tunnel_config.to_owned(),
                 non_tunnel_config: non_tunnel_config.to_owned(),
+                encrypted_config: encrypted_config.to_owned(),
             },
+            split_config: vec![],
         }
     }
+
+    /// Resolve names within the domains of `rules` using the servers of the matching rule. This
+    /// requires the local DNS resolver, so it is only supported on Linux and macOS.
+    pub fn with_split_rules(mut self, rules: &[SplitDnsRule]) -> Self {
+        self.split_config = rules.to_owned();
+        self
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -63,11 +86,15 @@ enum InnerDnsConfig {
         /// For the most part, the tunnel state machine will not handle any of this configuration
         /// on non-tunnel interface, only allow them in the firewall.
         non_tunnel_config: Vec<IpAddr>,
+        /// Servers to query over HTTPS or TLS
+        encrypted_config: Vec<EncryptedDnsServer>,
     },
 }
 
 impl DnsConfig {
-    pub(crate) fn resolve(
+    /// Resolve the config, using `default_tun_config` as the tunnel DNS servers unless other
+    /// servers have been specified.
+    pub fn resolve(
         &self,
         default_tun_config: &[IpAddr],
         #[cfg(target_os = "macos")] port: u16,
@@ -76,15 +103,20 @@ impl DnsConfig {
             InnerDnsConfig::Default => ResolvedDnsConfig {
                 tunnel_config: default_tun_config.to_owned(),
                 non_tunnel_config: vec![],
+                encrypted_config: vec![],
+                split_config: self.split_config.clone(),
                 #[cfg(target_os = "macos")]
                 port,
             },
             InnerDnsConfig::Override {
                 tunnel_config,
                 non_tunnel_config,
+                encrypted_config,
             } => ResolvedDnsConfig {
                 tunnel_config: tunnel_config.to_owned(),
                 non_tunnel_config: non_tunnel_config.to_owned(),
+                encrypted_config: encrypted_config.to_owned(),
+                split_config: self.split_config.clone(),
                 #[cfg(target_os = "macos")]
                 port,
             },
@@ -101,6 +133,10 @@ pub struct ResolvedDnsConfig {
     /// For the most part, the tunnel state machine will not handle any of this configuration
     /// on non-tunnel interface, only allow them in the firewall.
     non_tunnel_config: Vec<IpAddr>,
+    /// Servers to query over HTTPS or TLS
+    encrypted_config: Vec<EncryptedDnsServer>,
+    /// Domains to resolve using other servers
+    split_config: Vec<SplitDnsRule>,
     /// Port to use
     #[cfg(target_os = "macos")]
     port: u16,
@@ -114,6 +150,16 @@ impl fmt::Display for ResolvedDnsConfig {
         f.write_str(" Non-tunnel DNS: ")?;
         Self::fmt_addr_set(f, &self.non_tunnel_config)?;
 
+        if !self.encrypted_config.is_empty() {
+            f.write_str(" Encrypted DNS: ")?;
+            Self::fmt_addr_set(f, &self.encrypted_config)?;
+        }
+
+        if !self.split_config.is_empty() {
+            f.write_str(" Split DNS: ")?;
+            Self::fmt_addr_set(f, &self.split_config)?;
+        }
+
         #[cfg(target_os = "macos")]
         write!(f, " Port: {}", self.port)?;
 
@@ -122,7 +168,7 @@ impl fmt::Display for ResolvedDnsConfig {
 }
 
 impl ResolvedDnsConfig {
-    fn fmt_addr_set(f: &mut fmt::Formatter<'_>, addrs: &[IpAddr]) -> fmt::Result {
+    fn fmt_addr_set(f: &mut fmt::Formatter<'_>, addrs: &[impl fmt::Display]) -> fmt::Result {
         f.write_str("{")?;
         for (i, addr) in addrs.iter().enumerate() {
             if i > 0 {
@@ -145,13 +191,30 @@ impl ResolvedDnsConfig {
         &self.non_tunnel_config
     }
 
+    /// Servers to query over HTTPS or TLS. These can only be used by the local DNS resolver.
+    pub fn encrypted_config(&self) -> &[EncryptedDnsServer] {
+        &self.encrypted_config
+    }
+
+    /// Domains to resolve using other servers. These can only be used by the local DNS resolver.
+    pub fn split_config(&self) -> &[SplitDnsRule] {
+        &self.split_config
+    }
+
+    /// Servers of all split DNS rules
+    pub fn split_servers(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.split_config
+            .iter()
+            .flat_map(|rule| rule.servers.iter().copied())
+    }
+
     /// Consume `self` and return a vector of all addresses
     pub fn addresses(self) -> impl Iterator<Item = IpAddr> {
         self.non_tunnel_config.into_iter().chain(self.tunnel_config)
     }
 
     /// Return whether the config contains only (and at least one) loopback addresses, and zero
-    /// non-loopback addresses
+    /// non-loopback addresses, encrypted servers, or split DNS rules
     pub fn is_loopback(&self) -> bool {
         let (loopback_addrs, non_loopback_addrs) = self
             .tunnel_config
@@ -160,7 +223,10 @@ impl ResolvedDnsConfig {
             .copied()
             .partition::<Vec<_>, _>(|ip| ip.is_loopback());
 
-        !loopback_addrs.is_empty() && non_loopback_addrs.is_empty()
+        !loopback_addrs.is_empty()
+            && non_loopback_addrs.is_empty()
+            && self.encrypted_config.is_empty()
+            && self.split_config.is_empty()
     }
 }
 
@@ -11,14 +11,16 @@ use std::{
     ffi::CStr,
     fs, io,
     net::{IpAddr, Ipv4Addr},
+    os::unix::fs::MetadataExt,
     sync::LazyLock,
 };
 use talpid_types::{
-    cgroup::find_net_cls_mount,
+    cgroup::{CgroupHierarchy, find_split_tunnel_hierarchy},
     net::{
-        ALLOWED_LAN_MULTICAST_NETS, ALLOWED_LAN_NETS, AllowedEndpoint, AllowedTunnelTraffic,
-        Endpoint, TransportProtocol,
+        ALLOWED_LAN_MULTICAST_NETS, AllowedEndpoint, AllowedTunnelTraffic, Endpoint,
+        TransportProtocol,
     },
+    split_tunnel::SplitTunnelMode,
 };
 
 /// Priority for rules that tag split tunneling packets. Equals NF_IP_PRI_MANGLE.
@@ -57,9 +59,9 @@ pub enum Error {
     #[error("Unable to translate network interface name \"{0}\" into index")]
     LookupIfaceIndexError(String, #[source] crate::linux::IfaceIndexLookupError),
 
-    /// Failed to check if the net_cls mount exists.
-    #[error("An error occurred when checking for net_cls")]
-    FindNetClsMount(#[source] io::Error),
+    /// Failed to look for a cgroup hierarchy used for split tunneling.
+    #[error("An error occurred when looking for the split tunneling cgroup")]
+    FindCgroupHierarchy(#[source] io::Error),
 }
 
 /// TODO(linus): This crate is not supposed to be Mullvad-aware. So at some point this should be
@@ -106,20 +108,31 @@ enum End {
 /// The Linux implementation for the firewall and DNS.
 pub struct Firewall {
     fwmark: u32,
+    split_tunnel_mode: SplitTunnelMode,
 }
 
 impl Firewall {
     pub fn from_args(args: FirewallArguments) -> Result<Self> {
-        Firewall::new(args.fwmark)
+        let mut firewall = Firewall::new(args.fwmark)?;
+        firewall.set_split_tunnel_mode(args.split_tunnel_mode);
+        Ok(firewall)
     }
 
     pub fn new(fwmark: u32) -> Result<Self> {
-        Ok(Firewall { fwmark })
+        Ok(Firewall {
+            fwmark,
+            split_tunnel_mode: SplitTunnelMode::Exclude,
+        })
+    }
+
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) {
+        self.split_tunnel_mode = mode;
     }
 
     pub fn apply_policy(&mut self, policy: FirewallPolicy) -> Result<()> {
         let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
-        let batch = PolicyBatch::new(&table).finalize(&policy, self.fwmark)?;
+        let batch =
+            PolicyBatch::new(&table).finalize(&policy, self.fwmark, self.split_tunnel_mode)?;
         Self::send_and_process(&batch)?;
         Self::apply_kernel_config(&policy);
         self.verify_tables(&[TABLE_NAME])
@@ -241,6 +254,103 @@ impl Firewall {
     }
 }
 
+/// Identifies packets sent by processes in the split tunnel cgroup.
+#[derive(Debug, Clone, Copy)]
+enum SplitTunnelMatch {
+    /// Packets from a `net_cls` cgroup are tagged with [split_tunnel::NET_CLS_CLASSID].
+    NetClsClassId,
+    /// Packets whose socket belongs to the cgroup v2 with the given ID.
+    CgroupV2 { id: u64 },
+}
+
+impl SplitTunnelMatch {
+    /// The depth of the exclusion cgroup in the cgroup v2 hierarchy. It is created directly
+    /// beneath the root.
+    const CGROUP_V2_LEVEL: u32 = 1;
+
+    /// Find out how to identify excluded packets, or return `None` if there is no usable cgroup.
+    fn find() -> Result<Option<Self>> {
+        let hierarchy = find_split_tunnel_hierarchy().map_err(Error::FindCgroupHierarchy)?;
+        match hierarchy {
+            Some(CgroupHierarchy::NetCls(_)) => Ok(Some(SplitTunnelMatch::NetClsClassId)),
+            Some(hierarchy @ CgroupHierarchy::V2(_)) => {
+                // The ID of a cgroup v2 is the inode number of its directory. The directory is
+                // created by the split tunnel PID manager, so it may not exist yet.
+                match fs::metadata(hierarchy.exclusion_cgroup()) {
+                    Ok(metadata) => Ok(Some(SplitTunnelMatch::CgroupV2 { id: metadata.ino() })),
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+                    Err(error) => Err(Error::FindCgroupHierarchy(error)),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Add expressions to `rule` that match packets which should bypass the tunnel. In
+    /// [`SplitTunnelMode::Include`], these are the packets that do *not* belong to the cgroup.
+    ///
+    /// Note that packets whose cgroup v2 cannot be determined, e.g. because they have no socket,
+    /// never match. Such packets are sent through the tunnel regardless of the mode.
+    fn add_to_rule(self, rule: &mut Rule<'_>, mode: SplitTunnelMode) {
+        let cmp_op = Self::cmp_op(mode);
+        match self {
+            SplitTunnelMatch::NetClsClassId => {
+                rule.add_expr(&nft_expr!(meta cgroup));
+                rule.add_expr(&expr::Cmp::new(cmp_op, split_tunnel::NET_CLS_CLASSID));
+            }
+            SplitTunnelMatch::CgroupV2 { id } => {
+                rule.add_expr(&SocketCgroupV2 {
+                    level: Self::CGROUP_V2_LEVEL,
+                });
+                rule.add_expr(&expr::Cmp::new(cmp_op, &id.to_ne_bytes()[..]));
+            }
+        }
+    }
+
+    /// Return the comparison against the cgroup that matches packets which bypass the tunnel.
+    fn cmp_op(mode: SplitTunnelMode) -> expr::CmpOp {
+        match mode {
+            SplitTunnelMode::Exclude => expr::CmpOp::Eq,
+            SplitTunnelMode::Include => expr::CmpOp::Neq,
+        }
+    }
+}
+
+/// Loads the ID of the cgroup v2 ancestor at `level` of the socket that a packet belongs to.
+/// Equivalent to `socket cgroupv2 level <level>` in nft. `nftnl` does not provide this
+/// expression.
+struct SocketCgroupV2 {
+    level: u32,
+}
+
+impl SocketCgroupV2 {
+    /// Attributes of the socket expression. See `libnftnl/expr.h`.
+    const NFTNL_EXPR_SOCKET_KEY: u16 = 2;
+    const NFTNL_EXPR_SOCKET_DREG: u16 = 3;
+    const NFTNL_EXPR_SOCKET_LEVEL: u16 = 4;
+    /// See `enum nft_socket_keys` in `linux/netfilter/nf_tables.h`.
+    const NFT_SOCKET_CGROUPV2: u32 = 3;
+}
+
+impl expr::Expression for SocketCgroupV2 {
+    fn to_expr(&self, _rule: &Rule<'_>) -> *mut nftnl::nftnl_sys::nftnl_expr {
+        use nftnl::nftnl_sys as sys;
+
+        // SAFETY: The name is a valid C string, and the attributes match the socket expression
+        let expr = unsafe { sys::nftnl_expr_alloc(c"socket".as_ptr()) };
+        if expr.is_null() {
+            panic!("Failed to allocate memory for socket expression");
+        }
+        // SAFETY: `expr` is a valid socket expression
+        unsafe {
+            sys::nftnl_expr_set_u32(expr, Self::NFTNL_EXPR_SOCKET_KEY, Self::NFT_SOCKET_CGROUPV2);
+            sys::nftnl_expr_set_u32(expr, Self::NFTNL_EXPR_SOCKET_DREG, libc::NFT_REG_1 as u32);
+            sys::nftnl_expr_set_u32(expr, Self::NFTNL_EXPR_SOCKET_LEVEL, self.level);
+        }
+        expr
+    }
+}
+
 struct PolicyBatch<'a> {
     batch: Batch,
     in_chain: Chain<'a>,
@@ -309,19 +419,23 @@ impl<'a> PolicyBatch<'a> {
 
     /// Finalize the nftnl message batch by adding every firewall rule needed to satisfy the given
     /// policy.
-    pub fn finalize(mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<FinalizedBatch> {
+    pub fn finalize(
+        mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        split_tunnel_mode: SplitTunnelMode,
+    ) -> Result<FinalizedBatch> {
         self.add_loopback_rules()?;
 
-        // if cgroups v1 doesn't exist, split tunneling won't work.
-        // checking if the `net_cls` mount exists is a cheeky way of checking this.
-        if find_net_cls_mount()
-            .map_err(Error::FindNetClsMount)?
-            .is_some()
-        {
-            self.add_split_tunneling_rules(policy, fwmark)?;
-        } else {
-            // skipping add_split_tunneling_rules as it won't cause traffic to leak
-            log::warn!("net_cls mount not found, skipping add_split_tunneling_rules");
+        // skipping add_split_tunneling_rules won't cause traffic to leak
+        match SplitTunnelMatch::find()? {
+            Some(split_tunnel_match) => self.add_split_tunneling_rules(
+                policy,
+                fwmark,
+                split_tunnel_match,
+                split_tunnel_mode,
+            )?,
+            None => log::warn!("Split tunnel cgroup not found, skipping add_split_tunneling_rules"),
         }
 
         self.add_dhcp_client_rules();
@@ -334,40 +448,54 @@ impl<'a> PolicyBatch<'a> {
     /// Allow split-tunneled traffic outside the tunnel.
     ///
     /// This is acheived by setting `fwmark` on connections initated by processes in the cgroup
-    /// defined by [split_tunnel::NET_CLS_CLASSID].
-    fn add_split_tunneling_rules(&mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<()> {
+    /// identified by `split_tunnel_match`. In [`SplitTunnelMode::Include`], it is instead set on
+    /// connections initiated by processes outside of the cgroup, so that only processes in the
+    /// cgroup are routed through the tunnel. Unmarked traffic is subject to the policy as usual,
+    /// so included processes are blocked whenever the policy blocks traffic.
+    fn add_split_tunneling_rules(
+        &mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        split_tunnel_match: SplitTunnelMatch,
+        split_tunnel_mode: SplitTunnelMode,
+    ) -> Result<()> {
         // Send select DNS requests in the tunnel
         if let FirewallPolicy::Connected {
             tunnel, dns_config, ..
         } = policy
         {
-            for server in dns_config.tunnel_config() {
+            let split_tunnel_servers = dns_config
+                .split_servers()
+                .filter(|server| !super::is_local_address(server));
+            for server in dns_config
+                .tunnel_config()
+                .iter()
+                .copied()
+                .chain(split_tunnel_servers)
+            {
                 let allow_rule = allow_tunnel_dns_rule(
                     &self.mangle_chain,
                     &tunnel.interface,
                     TransportProtocol::Udp,
-                    *server,
+                    server,
                 )?;
                 self.batch.add(&allow_rule, nftnl::MsgType::Add);
                 let allow_rule = allow_tunnel_dns_rule(
                     &self.mangle_chain,
                     &tunnel.interface,
                     TransportProtocol::Tcp,
-                    *server,
+                    server,
                 )?;
                 self.batch.add(&allow_rule, nftnl::MsgType::Add);
             }
         }
 
-        // Split tunneled processes have their PIDs added to a net_cls cgroup.
-        // This causes all packets sent by that process to be marked with the
-        // cgroups classid (`NET_CLS_CLASSID`). This rule checks incoming packets for that classid.
-        // If the packet has the classid set then the packet will have two new marks applied to it.
-        // The `split_tunnel::MARK` as a connection tracking mark and the `fwmark` as packet
-        // metadata.
+        // Split tunneled processes have their PIDs added to a cgroup. This rule checks whether
+        // outgoing packets belong to that cgroup (or not, in include mode). If so, the packet will
+        // have two new marks applied to it. The `split_tunnel::MARK` as a connection tracking mark
+        // and the `fwmark` as packet metadata.
         let mut rule = Rule::new(&self.mangle_chain);
-        rule.add_expr(&nft_expr!(meta cgroup));
-        rule.add_expr(&nft_expr!(cmp == split_tunnel::NET_CLS_CLASSID));
+        split_tunnel_match.add_to_rule(&mut rule, split_tunnel_mode);
         // Loads `split_tunnel::MARK` into first nftnl register
         rule.add_expr(&nft_expr!(immediate data split_tunnel::MARK));
         // Sets `split_tunnel::MARK` as connection tracker mark
@@ -560,11 +688,12 @@ impl<'a> PolicyBatch<'a> {
     }
 
     fn add_policy_specific_rules(&mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<()> {
-        let allow_lan = match policy {
+        let allowed_lan_nets = match policy {
             FirewallPolicy::Connecting {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
             } => {
@@ -593,12 +722,13 @@ impl<'a> PolicyBatch<'a> {
                         self.add_block_cve_2019_14899(tunnel);
                     }
                 }
-                *allow_lan
+                allow_lan.then_some(allowed_lan_nets)
             }
             FirewallPolicy::Connected {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                allowed_lan_nets,
                 dns_config,
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
@@ -627,6 +757,17 @@ impl<'a> PolicyBatch<'a> {
                         *server,
                     )?;
                 }
+                // Split DNS servers are queried by the local resolver, over the tunnel unless
+                // they are on the LAN
+                for server in dns_config.split_servers() {
+                    for protocol in [TransportProtocol::Udp, TransportProtocol::Tcp] {
+                        if super::is_local_address(&server) {
+                            self.add_allow_local_dns_rule(&tunnel.interface, protocol, server)?;
+                        } else {
+                            self.add_allow_tunnel_dns_rule(&tunnel.interface, protocol, server)?;
+                        }
+                    }
+                }
 
                 // Important to block DNS *before* we allow the tunnel and allow LAN. So DNS
                 // can't leak to the wrong IPs in the tunnel or on the LAN.
@@ -635,10 +776,11 @@ impl<'a> PolicyBatch<'a> {
                 if *allow_lan {
                     self.add_block_cve_2019_14899(tunnel);
                 }
-                *allow_lan
+                allow_lan.then_some(allowed_lan_nets)
             }
             FirewallPolicy::Blocked {
                 allow_lan,
+                allowed_lan_nets,
                 allowed_endpoint,
             } => {
                 if let Some(endpoint) = allowed_endpoint {
@@ -647,12 +789,12 @@ impl<'a> PolicyBatch<'a> {
 
                 // Important to drop DNS before allowing LAN (to stop DNS leaking to the LAN)
                 self.add_drop_dns_rule();
-                *allow_lan
+                allow_lan.then_some(allowed_lan_nets)
             }
         };
 
-        if allow_lan {
-            self.add_allow_lan_rules();
+        if let Some(allowed_lan_nets) = allowed_lan_nets {
+            self.add_allow_lan_rules(allowed_lan_nets);
         }
 
         // Reject any remaining outgoing traffic
@@ -881,11 +1023,13 @@ impl<'a> PolicyBatch<'a> {
         }
     }
 
-    fn add_allow_lan_rules(&mut self) {
+    /// Allow traffic to and from `allowed_lan_nets`, as well as local multicast and broadcast
+    /// traffic.
+    fn add_allow_lan_rules(&mut self, allowed_lan_nets: &[IpNetwork]) {
         // Output and forward chains
         for chain in &[&self.out_chain, &self.forward_chain] {
             // LAN -> LAN
-            for net in &*ALLOWED_LAN_NETS {
+            for net in allowed_lan_nets {
                 let mut out_rule = Rule::new(chain);
                 check_net(&mut out_rule, End::Dst, *net);
                 add_verdict(&mut out_rule, &Verdict::Accept);
@@ -903,7 +1047,7 @@ impl<'a> PolicyBatch<'a> {
 
         // Input chain
         // LAN -> LAN
-        for net in &*ALLOWED_LAN_NETS {
+        for net in allowed_lan_nets {
             let mut in_rule = Rule::new(&self.in_chain);
             check_net(&mut in_rule, End::Src, *net);
             add_verdict(&mut in_rule, &Verdict::Accept);
@@ -1129,3 +1273,22 @@ fn batch_deprecated_tables(batch: &mut Batch) {
         batch.add(table, nftnl::MsgType::Del);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// In exclude mode, packets from the cgroup bypass the tunnel. In include mode, all other
+    /// packets do.
+    #[test]
+    fn test_split_tunnel_cmp_op() {
+        assert_eq!(
+            SplitTunnelMatch::cmp_op(SplitTunnelMode::Exclude),
+            expr::CmpOp::Eq
+        );
+        assert_eq!(
+            SplitTunnelMatch::cmp_op(SplitTunnelMode::Include),
+            expr::CmpOp::Neq
+        );
+    }
+}
@@ -11,7 +11,7 @@ use libc::{c_int, sysctlbyname};
 use pfctl::{DropAction, FilterRuleAction, Ip, Uid};
 use talpid_types::net::{
     ALLOWED_LAN_MULTICAST_NETS, ALLOWED_LAN_NETS, AllowedEndpoint, AllowedTunnelTraffic,
-    TransportProtocol,
+    TransportProtocol, dns::EncryptedDnsServer,
 };
 
 use super::{FirewallArguments, FirewallPolicy};
@@ -417,6 +417,17 @@ impl Firewall {
                         &mut self.get_allow_local_dns_rules_when_connected(tunnel, *server)?,
                     );
                 }
+                for server in dns_config.encrypted_config() {
+                    rules.push(self.get_allow_encrypted_dns_rule_when_connected(tunnel, server)?);
+                }
+                for server in dns_config.split_servers() {
+                    let mut split_rules = if super::is_local_address(&server) {
+                        self.get_allow_local_dns_rules_when_connected(tunnel, server)?
+                    } else {
+                        self.get_allow_tunnel_dns_rules_when_connected(tunnel, server)?
+                    };
+                    rules.append(&mut split_rules);
+                }
 
                 rules.push(self.get_allow_relay_rule(peer_endpoint)?);
 
@@ -565,6 +576,30 @@ impl Firewall {
         Ok(rules)
     }
 
+    /// Allow the local DNS resolver to reach an encrypted DNS server. Servers that do not have a
+    /// local address may only be reached through the tunnel.
+    fn get_allow_encrypted_dns_rule_when_connected(
+        &self,
+        tunnel: &crate::tunnel::TunnelMetadata,
+        server: &EncryptedDnsServer,
+    ) -> Result<pfctl::FilterRule> {
+        let mut builder = self.create_rule_builder(FilterRuleAction::Pass);
+        builder
+            .direction(pfctl::Direction::Out)
+            .quick(true)
+            .proto(pfctl::Proto::Tcp)
+            .keep_state(pfctl::StatePolicy::Keep)
+            .tcp_flags(Self::get_tcp_flags())
+            .user(Uid::from(super::ROOT_UID))
+            .to(pfctl::Endpoint::new(server.bootstrap_address, server.port));
+
+        if !super::is_local_address(&server.bootstrap_address) {
+            builder.interface(&tunnel.interface);
+        }
+
+        builder.build()
+    }
+
     /// Allow traffic to relay_endpoint on the correct ip/port/protocol, for the root-user only.
     fn get_allow_relay_rule(&self, relay_endpoint: &AllowedEndpoint) -> Result<pfctl::FilterRule> {
         let pfctl_proto = as_pfctl_proto(relay_endpoint.endpoint.protocol);
@@ -7,6 +7,8 @@ use std::{
     sync::LazyLock,
 };
 use talpid_types::net::{ALLOWED_LAN_NETS, AllowedEndpoint, AllowedTunnelTraffic};
+#[cfg(target_os = "linux")]
+use talpid_types::split_tunnel::SplitTunnelMode;
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -87,6 +89,9 @@ pub enum FirewallPolicy {
         tunnel: Option<crate::tunnel::TunnelMetadata>,
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// Networks that can be reached outside the tunnel if `allow_lan` is set.
+        #[cfg(target_os = "linux")]
+        allowed_lan_nets: Vec<IpNetwork>,
         /// Host that should be reachable while connecting.
         allowed_endpoint: AllowedEndpoint,
         /// Networks for which to permit in-tunnel traffic.
@@ -104,6 +109,9 @@ pub enum FirewallPolicy {
         tunnel: crate::tunnel::TunnelMetadata,
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// Networks that can be reached outside the tunnel if `allow_lan` is set.
+        #[cfg(target_os = "linux")]
+        allowed_lan_nets: Vec<IpNetwork>,
         /// Servers that are allowed to respond to DNS requests.
         #[cfg(not(target_os = "android"))]
         dns_config: ResolvedDnsConfig,
@@ -116,6 +124,9 @@ pub enum FirewallPolicy {
     Blocked {
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// Networks that can be reached outside the tunnel if `allow_lan` is set.
+        #[cfg(target_os = "linux")]
+        allowed_lan_nets: Vec<IpNetwork>,
         /// Host that should be reachable while in the blocked state.
         allowed_endpoint: Option<AllowedEndpoint>,
     },
@@ -285,6 +296,9 @@ pub struct FirewallArguments {
     /// the tunnel and _leaked_ during blocked states.
     #[cfg(target_os = "linux")]
     pub fwmark: u32,
+    /// Whether processes in the split tunnel cgroup are excluded from or included in the tunnel.
+    #[cfg(target_os = "linux")]
+    pub split_tunnel_mode: SplitTunnelMode,
 }
 
 /// State to enter during firewall init.
@@ -332,4 +346,11 @@ impl Firewall {
     pub fn persist(&mut self, persist: bool) {
         self.inner.persist(persist);
     }
+
+    /// Sets whether processes in the split tunnel cgroup are excluded from or included in the
+    /// tunnel. This takes effect the next time a policy is applied.
+    #[cfg(target_os = "linux")]
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) {
+        self.inner.set_split_tunnel_mode(mode);
+    }
 }
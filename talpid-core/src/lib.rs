@@ -40,7 +40,7 @@ pub mod tunnel_state_machine;
 mod linux;
 
 /// A resolver that's controlled by the tunnel state machine
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 pub(crate) mod resolver;
 
 /// Connectivity monitor for Android
@@ -3,7 +3,10 @@
 //!   domains receive a spoofed answer. This fools the OS into thinking that it has connectivity.
 //! * In the `Forwarding` state, queries are forwarded to a set of configured DNS servers. This
 //!   lets us use the routing table to determine where to send them, instead of them being forced
-//!   out on the primary interface (in some cases).
+//!   out on the primary interface (in some cases). Servers may also be queried over HTTPS or TLS,
+//!   and queries for names within some domains may be sent to other servers (split DNS).
+//!
+//! On Linux, the resolver is only used for split DNS.
 //!
 //! See [start_resolver].
 use std::{
@@ -43,7 +46,12 @@ use hickory_server::{
 use rand::random;
 use socket2::{Domain, Protocol, Socket, Type};
 use std::sync::LazyLock;
-use talpid_types::drop_guard::{OnDrop, on_drop};
+use talpid_types::{
+    drop_guard::{OnDrop, on_drop},
+    net::dns::{EncryptedDnsProtocol, EncryptedDnsServer, SplitDnsRule},
+};
+
+use crate::dns::ResolvedDnsConfig;
 use tokio::{
     net::{self, UdpSocket},
     task::JoinHandle,
@@ -54,6 +62,7 @@ use tokio::{
 /// This setting does not affect the error or blocked state. In those states, we will want to use
 /// the local DNS resoler to work around Apple's captive portals check. Exactly how this is done is
 /// documented elsewhere.
+#[cfg(target_os = "macos")]
 pub static LOCAL_DNS_RESOLVER: LazyLock<bool> = LazyLock::new(|| {
     let disable_local_dns_resolver = std::env::var("TALPID_DISABLE_LOCAL_DNS_RESOLVER")
         .map(|v| v != "0")
@@ -67,6 +76,7 @@ pub static LOCAL_DNS_RESOLVER: LazyLock<bool> = LazyLock::new(|| {
 });
 
 // Name of the loopback network device.
+#[cfg(target_os = "macos")]
 const LOOPBACK: &str = "lo0";
 
 /// The port we should bind the local DNS resolver to.
@@ -173,6 +183,10 @@ enum Config {
     Forwarding {
         /// Remote DNS server to use
         dns_servers: Vec<IpAddr>,
+        /// Remote DNS servers to query over HTTPS or TLS
+        encrypted_servers: Vec<EncryptedDnsServer>,
+        /// Domains to forward to other servers than the above
+        split_rules: Vec<SplitDnsRule>,
     },
 }
 
@@ -181,7 +195,11 @@ enum Resolver {
     Blocking,
 
     /// Forward DNS queries to a configured server
-    Forwarding(TokioAsyncResolver),
+    Forwarding {
+        resolver: TokioAsyncResolver,
+        /// Resolvers for the domains of split DNS rules
+        split_resolvers: Vec<(SplitDnsRule, TokioAsyncResolver)>,
+    },
 }
 
 impl Resolver {
@@ -194,7 +212,11 @@ impl Resolver {
             Resolver::Blocking => {
                 let _ = tx.send(Self::resolve_blocked(query));
             }
-            Resolver::Forwarding(resolver) => {
+            Resolver::Forwarding {
+                resolver,
+                split_resolvers,
+            } => {
+                let resolver = Self::split_resolver(split_resolvers, &query).unwrap_or(resolver);
                 let resolver = resolver.clone();
                 tokio::spawn(async move {
                     let lookup = Self::resolve_forward(resolver, query);
@@ -239,6 +261,19 @@ impl Resolver {
         ALLOWED_RECORD_TYPES.contains(&query.query_type()) && ALLOWED_DOMAINS.contains(query.name())
     }
 
+    /// Return the resolver of the most specific split DNS rule that the query matches.
+    fn split_resolver<'a>(
+        split_resolvers: &'a [(SplitDnsRule, TokioAsyncResolver)],
+        query: &LowerQuery,
+    ) -> Option<&'a TokioAsyncResolver> {
+        let name = query.name().to_string();
+        split_resolvers
+            .iter()
+            .filter(|(rule, _)| rule.matches(&name))
+            .max_by_key(|(rule, _)| rule.domain.len())
+            .map(|(_, resolver)| resolver)
+    }
+
     /// Forward DNS queries to the specified DNS resolver.
     async fn resolve_forward(
         resolver: TokioAsyncResolver,
@@ -273,11 +308,17 @@ impl ResolverHandle {
         self.listening_addr
     }
 
-    /// Set the DNS server to forward queries to `dns_servers`
-    pub async fn enable_forward(&self, dns_servers: Vec<IpAddr>) {
+    /// Forward queries to the DNS servers in `dns_config`
+    pub async fn enable_forward(&self, dns_config: ResolvedDnsConfig) {
+        let encrypted_servers = dns_config.encrypted_config().to_vec();
+        let split_rules = dns_config.split_config().to_vec();
         let (response_tx, response_rx) = oneshot::channel();
         let _ = self.tx.unbounded_send(ResolverMessage::SetConfig {
-            new_config: Config::Forwarding { dns_servers },
+            new_config: Config::Forwarding {
+                dns_servers: dns_config.addresses().collect(),
+                encrypted_servers,
+                split_rules,
+            },
             response_tx,
         });
 
@@ -396,6 +437,7 @@ impl LocalResolver {
     async fn new_random_socket(config: &LocalResolverConfig) -> Result<(UdpSocket, OnDrop), Error> {
         use std::net::Ipv4Addr;
 
+        #[cfg(target_os = "macos")]
         let random_loopback = || async move {
             let addr = Ipv4Addr::new(127, 1u8.max(random()), random(), random());
 
@@ -427,6 +469,13 @@ impl LocalResolver {
             Some((addr, cleanup_ifconfig))
         };
 
+        // All of 127/8 is assigned to the loopback interface on Linux, so no alias is needed
+        #[cfg(target_os = "linux")]
+        let random_loopback = || async move {
+            let addr = Ipv4Addr::new(127, 1u8.max(random()), random(), random());
+            Some((addr, OnDrop::noop()))
+        };
+
         for attempt in 0.. {
             let (socket_addr, on_drop) = match attempt {
                 ..3 if !config.use_random_loopback => continue,
@@ -490,6 +539,7 @@ impl LocalResolver {
                     log::debug!("Updating config: {new_config:?}");
 
                     self.update_config(new_config);
+                    #[cfg(target_os = "macos")]
                     flush_system_cache();
                     let _ = response_tx.send(());
                 }
@@ -518,10 +568,17 @@ impl LocalResolver {
     fn update_config(&mut self, config: Config) {
         match config {
             Config::Blocking => self.blocking(),
-            Config::Forwarding { mut dns_servers } => {
+            Config::Forwarding {
+                mut dns_servers,
+                encrypted_servers,
+                mut split_rules,
+            } => {
                 // make sure not to accidentally forward queries to ourselves
                 dns_servers.retain(|addr| *addr != self.bound_to.ip());
-                self.forwarding(dns_servers);
+                for rule in &mut split_rules {
+                    rule.servers.retain(|addr| *addr != self.bound_to.ip());
+                }
+                self.forwarding(dns_servers, encrypted_servers, split_rules);
             }
         }
     }
@@ -531,30 +588,95 @@ impl LocalResolver {
         self.inner_resolver = Resolver::Blocking;
     }
 
-    /// Turn into a forwarding resolver (forward DNS queries to [dns_servers]).
-    fn forwarding(&mut self, dns_servers: Vec<IpAddr>) {
-        let forward_server_config =
-            NameServerConfigGroup::from_ips_clear(&dns_servers, DNS_PORT, true);
-
-        let forward_config = ResolverConfig::from_parts(None, vec![], forward_server_config);
-        let resolver_opts = ResolverOpts::default();
+    /// Turn into a forwarding resolver (forward DNS queries to [dns_servers] in plaintext, and to
+    /// [encrypted_servers] over HTTPS or TLS). Queries matching a rule in [split_rules] are
+    /// instead forwarded to the servers of that rule.
+    fn forwarding(
+        &mut self,
+        dns_servers: Vec<IpAddr>,
+        encrypted_servers: Vec<EncryptedDnsServer>,
+        split_rules: Vec<SplitDnsRule>,
+    ) {
+        let mut forward_config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(&dns_servers, DNS_PORT, true),
+        );
+        if !encrypted_servers.is_empty() {
+            for server in encrypted_servers {
+                for name_server in encrypted_name_servers(server).into_inner() {
+                    forward_config.add_name_server(name_server);
+                }
+            }
+            forward_config.set_tls_client_config(Arc::new(tls_client_config()));
+        }
+        let resolver = TokioAsyncResolver::tokio(forward_config, ResolverOpts::default());
+
+        let split_resolvers = split_rules
+            .into_iter()
+            .map(|rule| {
+                let config = ResolverConfig::from_parts(
+                    None,
+                    vec![],
+                    NameServerConfigGroup::from_ips_clear(&rule.servers, DNS_PORT, true),
+                );
+                (
+                    rule,
+                    TokioAsyncResolver::tokio(config, ResolverOpts::default()),
+                )
+            })
+            .collect();
 
-        let resolver = TokioAsyncResolver::tokio(forward_config, resolver_opts);
+        self.inner_resolver = Resolver::Forwarding {
+            resolver,
+            split_resolvers,
+        };
+    }
+}
 
-        self.inner_resolver = Resolver::Forwarding(resolver);
+/// Configure a DNS over HTTPS or TLS server. The server is reached at its bootstrap address, and
+/// its certificate is verified against its host name.
+fn encrypted_name_servers(server: EncryptedDnsServer) -> NameServerConfigGroup {
+    let addresses = [server.bootstrap_address];
+    match server.protocol {
+        EncryptedDnsProtocol::Https => {
+            NameServerConfigGroup::from_ips_https(&addresses, server.port, server.hostname, true)
+        }
+        EncryptedDnsProtocol::Tls => {
+            NameServerConfigGroup::from_ips_tls(&addresses, server.port, server.hostname, true)
+        }
     }
 }
 
+fn tls_client_config() -> rustls::ClientConfig {
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_no_client_auth()
+}
+
 /// Flush the DNS cache.
+#[cfg(target_os = "macos")]
 fn flush_system_cache() {
     if let Err(error) = kill_mdnsresponder() {
         log::error!("Failed to kill mDNSResponder: {error}");
     }
 }
 
+#[cfg(target_os = "macos")]
 const MDNS_RESPONDER_PATH: &str = "/usr/sbin/mDNSResponder";
 
 /// Find and kill mDNSResponder. The OS will restart the service.
+#[cfg(target_os = "macos")]
 fn kill_mdnsresponder() -> io::Result<()> {
     if let Some(mdns_pid) = talpid_macos::process::pid_of_path(MDNS_RESPONDER_PATH) {
         nix::sys::signal::kill(
@@ -692,6 +814,7 @@ mod test {
         config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
     };
     use std::{net::UdpSocket, sync::Mutex, thread};
+    #[cfg(target_os = "macos")]
     use typed_builder::TypedBuilder;
 
     /// Can't have multiple local resolvers running at the same time, as they will try to bind to
@@ -722,7 +845,10 @@ mod test {
     ///
     /// # Note
     ///
-    /// This test does not test aliases on lo0, as that requires root privileges.
+    /// This test does not test aliases on lo0, as that requires root privileges. It only runs on
+    /// macOS, since Linux does not allow binding to `127.0.0.1` while the wildcard address is bound
+    /// without SO_REUSEADDR.
+    #[cfg(target_os = "macos")]
     #[test_log::test]
     fn test_bind() {
         let _mutex = LOCK.lock().unwrap();
@@ -831,6 +957,88 @@ mod test {
         UdpSocket::bind(addr).expect("Failed to bind to a port that should have been removed");
     }
 
+    /// Test that queries for names within the domain of a split DNS rule are forwarded to the
+    /// servers of that rule, and that all other queries are forwarded to the regular servers.
+    #[cfg(target_os = "linux")]
+    #[test_log::test]
+    fn test_split_dns() {
+        use crate::dns::DnsConfig;
+
+        const DEFAULT_UPSTREAM: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 2);
+        const SPLIT_UPSTREAM: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 3);
+        const DEFAULT_ANSWER: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+        const SPLIT_ANSWER: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 2);
+
+        let _mutex = LOCK.lock().unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+
+        rt.block_on(async move {
+            let _default_upstream = StubUpstream::spawn(DEFAULT_UPSTREAM, DEFAULT_ANSWER).await;
+            let _split_upstream = StubUpstream::spawn(SPLIT_UPSTREAM, SPLIT_ANSWER).await;
+
+            let handle = start_resolver().await;
+            let rule = SplitDnsRule::new("corp.example", vec![IpAddr::from(SPLIT_UPSTREAM)]);
+            let dns_config = DnsConfig::from_addresses(&[IpAddr::from(DEFAULT_UPSTREAM)], &[])
+                .with_split_rules(&[rule.unwrap()])
+                .resolve(&[]);
+            handle.enable_forward(dns_config).await;
+
+            let test_resolver = get_test_resolver(handle.listening_addr());
+            for (name, expected_answer) in [
+                ("corp.example.", SPLIT_ANSWER),
+                ("intranet.corp.example.", SPLIT_ANSWER),
+                ("notcorp.example.", DEFAULT_ANSWER),
+                ("mullvad.net.", DEFAULT_ANSWER),
+            ] {
+                let lookup = test_resolver.ipv4_lookup(name).await.unwrap();
+                let answers: Vec<Ipv4Addr> = lookup.iter().map(|a| a.0).collect();
+                assert_eq!(answers, [expected_answer], "unexpected answer for {name}");
+            }
+
+            handle.stop().await;
+        });
+    }
+
+    /// DNS server that answers every query with the same A record.
+    struct StubUpstream {
+        answer: Ipv4Addr,
+    }
+
+    impl StubUpstream {
+        /// Serve on `addr`. The server is stopped when the returned value is dropped.
+        async fn spawn(addr: Ipv4Addr, answer: Ipv4Addr) -> ServerFuture<Self> {
+            let socket = net::UdpSocket::bind(SocketAddr::from((addr, DNS_PORT)))
+                .await
+                .unwrap();
+            let mut server = ServerFuture::new(StubUpstream { answer });
+            server.register_socket(socket);
+            server
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RequestHandler for StubUpstream {
+        async fn handle_request<R: ResponseHandler>(
+            &self,
+            request: &Request,
+            mut response_handle: R,
+        ) -> ResponseInfo {
+            let name = request.query().original().name().clone();
+            let mut record = Record::with(name, RecordType::A, TTL_SECONDS);
+            record.set_data(Some(RData::A(rdata::A(self.answer))));
+
+            let response = MessageResponseBuilder::from_message_request(request).build(
+                Header::response_from_request(request.header()),
+                [&record],
+                std::iter::empty(),
+                std::iter::empty(),
+                std::iter::empty(),
+            );
+            response_handle.send_response(response).await.unwrap()
+        }
+    }
+
+    #[cfg(target_os = "macos")]
     #[derive(TypedBuilder)]
     struct BindParams {
         bind_addr: SocketAddr,
@@ -841,6 +1049,7 @@ mod test {
     }
 
     /// Helper function for creating and binding a UDP socket
+    #[cfg(target_os = "macos")]
     fn bind_sock(params: BindParams) -> io::Result<UdpSocket> {
         let sock = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
 
@@ -0,0 +1,400 @@
+//! Exclude applications from the tunnel by path.
+//!
+//! The kernel reports every call to `exec` through the process events connector, which is a
+//! netlink socket subscribed to the `CN_IDX_PROC` multicast group. Whenever a process starts
+//! executing one of the excluded applications, it is moved into the exclusion cgroup. Child
+//! processes inherit the cgroup of their parent, so they are excluded as well.
+//!
+//! Note that a process is able to send traffic in the short window between calling `exec` and
+//! being moved into the cgroup.
+//!
+//! Only processes that were excluded by the monitor are returned to the tunnel when their
+//! application is no longer excluded, so that processes excluded by PID are left alone.
+
+use super::{Error, PidManager};
+use std::{
+    collections::HashSet,
+    fs, io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+    thread,
+};
+use talpid_types::ErrorExt;
+
+/// Index and value of the process events connector. See `linux/connector.h`.
+const CN_IDX_PROC: u32 = 0x1;
+const CN_VAL_PROC: u32 = 0x1;
+/// Operation used to subscribe to process events. See `linux/cn_proc.h`.
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+/// Process event sent when a process calls `exec`. See `linux/cn_proc.h`.
+const PROC_EVENT_EXEC: u32 = 0x2;
+
+/// Size of `struct nlmsghdr`.
+const NLMSG_HDR_LEN: usize = mem::size_of::<libc::nlmsghdr>();
+/// Size of `struct cn_msg`, excluding the payload.
+const CN_MSG_HDR_LEN: usize = 20;
+/// Offset of `event_data.exec.process_tgid` in `struct proc_event`.
+const EXEC_EVENT_TGID_OFFSET: usize = 20;
+
+/// Moves processes running any of a set of applications into the exclusion cgroup.
+pub struct AppMonitor {
+    shared: Arc<Shared>,
+    listening: bool,
+}
+
+struct Shared {
+    pid_manager: Arc<PidManager>,
+    apps: Mutex<HashSet<PathBuf>>,
+    /// Processes that were excluded by the monitor
+    excluded_pids: Mutex<HashSet<i32>>,
+}
+
+impl AppMonitor {
+    /// Create a monitor that does not exclude any applications.
+    pub fn new(pid_manager: Arc<PidManager>) -> Self {
+        AppMonitor {
+            shared: Arc::new(Shared {
+                pid_manager,
+                apps: Mutex::new(HashSet::new()),
+                excluded_pids: Mutex::new(HashSet::new()),
+            }),
+            listening: false,
+        }
+    }
+
+    /// Set the applications to exclude from the tunnel.
+    ///
+    /// Processes that are already running any of `apps` are excluded immediately, and processes
+    /// running an application that is no longer excluded are returned to the tunnel.
+    pub fn set_apps(&mut self, apps: impl IntoIterator<Item = PathBuf>) -> Result<(), Error> {
+        // `/proc/<pid>/exe` always contains the canonical path
+        let apps: HashSet<PathBuf> = apps
+            .into_iter()
+            .map(|path| fs::canonicalize(&path).unwrap_or(path))
+            .collect();
+
+        if !apps.is_empty() {
+            if !self.shared.pid_manager.is_enabled() {
+                return Err(Error::Unavailable);
+            }
+            // Start listening before scanning running processes, so that no process is missed
+            if !self.listening {
+                spawn_listener(Arc::downgrade(&self.shared))?;
+                self.listening = true;
+            }
+        }
+
+        let previous_apps = mem::replace(&mut *self.shared.lock_apps(), apps.clone());
+        self.shared.update_running_processes(&previous_apps, &apps);
+        Ok(())
+    }
+
+    /// Keep `pid` excluded when its application is no longer excluded. This should be called
+    /// when a process is excluded by PID, since it may also be running an excluded application.
+    pub fn forget_process(&self, pid: i32) {
+        self.shared.lock_excluded_pids().remove(&pid);
+    }
+
+    /// Keep all processes excluded when their application is no longer excluded.
+    pub fn forget_processes(&self) {
+        self.shared.lock_excluded_pids().clear();
+    }
+}
+
+impl Shared {
+    fn lock_apps(&self) -> std::sync::MutexGuard<'_, HashSet<PathBuf>> {
+        self.apps
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_excluded_pids(&self) -> std::sync::MutexGuard<'_, HashSet<i32>> {
+        self.excluded_pids
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Exclude `pid` and remember that it was excluded by the monitor.
+    fn exclude(&self, pid: i32) -> Result<(), Error> {
+        self.pid_manager.add(pid)?;
+        self.lock_excluded_pids().insert(pid);
+        Ok(())
+    }
+
+    /// Return `pid` to the tunnel, unless it was excluded by something other than the monitor.
+    fn include(&self, pid: i32) -> Result<(), Error> {
+        if !self.lock_excluded_pids().remove(&pid) {
+            return Ok(());
+        }
+        self.pid_manager.remove(pid)
+    }
+
+    /// Exclude running processes that execute an application in `apps` but not in
+    /// `previous_apps`, and include processes for the reverse.
+    fn update_running_processes(&self, previous_apps: &HashSet<PathBuf>, apps: &HashSet<PathBuf>) {
+        if previous_apps.is_empty() && apps.is_empty() {
+            return;
+        }
+        let entries = match fs::read_dir("/proc") {
+            Ok(entries) => entries,
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to list running processes")
+                );
+                return;
+            }
+        };
+        let pids: HashSet<i32> = entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+            .collect();
+
+        // Forget processes that have exited, since their PIDs may be reused
+        self.lock_excluded_pids().retain(|pid| pids.contains(pid));
+
+        for pid in pids {
+            let Ok(exe) = process_exe(pid) else {
+                continue;
+            };
+            let result = match (previous_apps.contains(&exe), apps.contains(&exe)) {
+                (false, true) => self.exclude(pid),
+                (true, false) => self.include(pid),
+                _ => continue,
+            };
+            if let Err(error) = result {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg(&format!(
+                        "Failed to update split tunnel state of process {pid}"
+                    ))
+                );
+            }
+        }
+    }
+
+    fn on_exec(&self, pid: i32) {
+        let apps = self.lock_apps();
+        if apps.is_empty() {
+            return;
+        }
+        // The process may already have exited
+        let Ok(exe) = process_exe(pid) else {
+            return;
+        };
+        if !apps.contains(&exe) {
+            return;
+        }
+        log::debug!("Excluding process {pid} ({})", exe.display());
+        if let Err(error) = self.exclude(pid) {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg(&format!("Failed to exclude process {pid}"))
+            );
+        }
+    }
+}
+
+fn process_exe(pid: i32) -> io::Result<PathBuf> {
+    fs::read_link(format!("/proc/{pid}/exe"))
+}
+
+fn spawn_listener(shared: Weak<Shared>) -> Result<(), Error> {
+    let socket = ProcEventSocket::open().map_err(Error::ListenProcessEvents)?;
+    thread::Builder::new()
+        .name("split-tunnel-app-monitor".to_owned())
+        .spawn(move || listen(socket, shared))
+        .map_err(Error::ListenProcessEvents)?;
+    Ok(())
+}
+
+fn listen(socket: ProcEventSocket, shared: Weak<Shared>) {
+    let mut buffer = [0u8; 4096];
+    loop {
+        let result = socket.recv_exec_events(&mut buffer);
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        match result {
+            Ok(pids) => pids.into_iter().for_each(|pid| shared.on_exec(pid)),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(error) if error.raw_os_error() == Some(libc::ENOBUFS) => {
+                // Events were dropped, so look for processes that may have been missed
+                log::warn!("Process events were dropped");
+                let apps = shared.lock_apps().clone();
+                shared.update_running_processes(&HashSet::new(), &apps);
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to receive process events")
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Netlink socket subscribed to process events.
+struct ProcEventSocket {
+    fd: OwnedFd,
+}
+
+impl ProcEventSocket {
+    fn open() -> io::Result<Self> {
+        // SAFETY: This is safe to call with any arguments
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a newly created socket which is not owned by anything else
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: `sockaddr_nl` is valid when zeroed
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = CN_IDX_PROC;
+        // SAFETY: `addr` is a valid `sockaddr_nl` of the given size
+        let result = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                (&addr as *const libc::sockaddr_nl).cast(),
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let socket = ProcEventSocket { fd };
+        socket.subscribe()?;
+        Ok(socket)
+    }
+
+    /// Ask the kernel to start sending process events to this socket.
+    fn subscribe(&self) -> io::Result<()> {
+        let op = PROC_CN_MCAST_LISTEN.to_ne_bytes();
+        let len = NLMSG_HDR_LEN + CN_MSG_HDR_LEN + op.len();
+
+        let mut msg = Vec::with_capacity(len);
+        // struct nlmsghdr
+        msg.extend_from_slice(&(len as u32).to_ne_bytes());
+        msg.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+        msg.extend_from_slice(&0u16.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&std::process::id().to_ne_bytes());
+        // struct cn_msg
+        msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&(op.len() as u16).to_ne_bytes());
+        msg.extend_from_slice(&0u16.to_ne_bytes());
+        msg.extend_from_slice(&op);
+
+        // SAFETY: `msg` is valid for reads of `msg.len()` bytes
+        let sent = unsafe { libc::send(self.fd.as_raw_fd(), msg.as_ptr().cast(), msg.len(), 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Block until process events are received, and return the PIDs of all processes that
+    /// called `exec`.
+    fn recv_exec_events(&self, buffer: &mut [u8]) -> io::Result<Vec<i32>> {
+        // SAFETY: `buffer` is valid for writes of `buffer.len()` bytes
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(parse_exec_events(&buffer[..len as usize]))
+    }
+}
+
+/// Parse a buffer of netlink messages containing process events, and return the PIDs of all
+/// processes that called `exec`.
+fn parse_exec_events(mut buffer: &[u8]) -> Vec<i32> {
+    let mut pids = vec![];
+    while buffer.len() >= NLMSG_HDR_LEN {
+        let msg_len = read_u32(buffer, 0) as usize;
+        if msg_len < NLMSG_HDR_LEN || msg_len > buffer.len() {
+            break;
+        }
+        let payload = &buffer[NLMSG_HDR_LEN..msg_len];
+        if payload.len() >= CN_MSG_HDR_LEN + EXEC_EVENT_TGID_OFFSET + 4
+            && read_u32(payload, 0) == CN_IDX_PROC
+            && read_u32(payload, CN_MSG_HDR_LEN) == PROC_EVENT_EXEC
+        {
+            let tgid = read_u32(payload, CN_MSG_HDR_LEN + EXEC_EVENT_TGID_OFFSET);
+            pids.push(tgid as i32);
+        }
+        // Messages are aligned to 4 bytes
+        let next = (msg_len + 3) & !3;
+        buffer = buffer.get(next..).unwrap_or_default();
+    }
+    pids
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    let bytes = buffer[offset..offset + 4].try_into().unwrap();
+    u32::from_ne_bytes(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Build a netlink message containing a process event of the given kind.
+    fn proc_event_msg(what: u32, tgid: u32) -> Vec<u8> {
+        // struct proc_event: what, cpu, timestamp_ns, event_data (pid, tgid)
+        let mut event = vec![];
+        event.extend_from_slice(&what.to_ne_bytes());
+        event.extend_from_slice(&0u32.to_ne_bytes());
+        event.extend_from_slice(&0u64.to_ne_bytes());
+        event.extend_from_slice(&(tgid + 1).to_ne_bytes());
+        event.extend_from_slice(&tgid.to_ne_bytes());
+
+        let len = NLMSG_HDR_LEN + CN_MSG_HDR_LEN + event.len();
+        let mut msg = vec![];
+        msg.extend_from_slice(&(len as u32).to_ne_bytes());
+        msg.extend_from_slice(&[0u8; NLMSG_HDR_LEN - 4]);
+        msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        msg.extend_from_slice(&[0u8; 8]);
+        msg.extend_from_slice(&(event.len() as u16).to_ne_bytes());
+        msg.extend_from_slice(&0u16.to_ne_bytes());
+        msg.extend_from_slice(&event);
+        msg
+    }
+
+    #[test]
+    fn test_parse_exec_events() {
+        const PROC_EVENT_FORK: u32 = 0x1;
+
+        let mut buffer = proc_event_msg(PROC_EVENT_EXEC, 1234);
+        buffer.extend(proc_event_msg(PROC_EVENT_FORK, 42));
+        buffer.extend(proc_event_msg(PROC_EVENT_EXEC, 5678));
+
+        assert_eq!(parse_exec_events(&buffer), vec![1234, 5678]);
+    }
+
+    #[test]
+    fn test_parse_truncated_events() {
+        let buffer = proc_event_msg(PROC_EVENT_EXEC, 1234);
+        assert!(parse_exec_events(&buffer[..buffer.len() - 1]).is_empty());
+    }
+}
@@ -1,11 +1,15 @@
+mod app_monitor;
+
+pub use app_monitor::AppMonitor;
+
 use std::{
     env, fs,
     io::{self, BufRead, BufReader, Write},
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
 use talpid_types::{
     ErrorExt,
-    cgroup::{SPLIT_TUNNEL_CGROUP_NAME, find_net_cls_mount},
+    cgroup::{CgroupHierarchy, find_split_tunnel_hierarchy},
 };
 
 const DEFAULT_NET_CLS_DIR: &str = "/sys/fs/cgroup/net_cls";
@@ -21,9 +25,9 @@ pub const MARK: i32 = 0xf41;
 /// Errors related to split tunneling.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    /// Unable to create cgroup.
-    #[error("Unable to initialize net_cls cgroup instance")]
-    InitNetClsCGroup(#[source] nix::Error),
+    /// Neither cgroup v2 nor the cgroup v1 `net_cls` controller is available.
+    #[error("Split tunneling requires either cgroup v2 or the cgroup v1 net_cls controller")]
+    NoCgroupHierarchy(#[source] nix::Error),
 
     /// Unable to create cgroup.
     #[error("Unable to create cgroup for excluded processes")]
@@ -52,6 +56,10 @@ pub enum Error {
     /// Unable to read /proc/mounts
     #[error("Failed to read /proc/mounts")]
     ListMounts(#[source] io::Error),
+
+    /// Unable to listen for process events.
+    #[error("Failed to listen for process events")]
+    ListenProcessEvents(#[source] io::Error),
 }
 
 /// Manages PIDs in the Linux Cgroup excluded from the VPN tunnel.
@@ -60,18 +68,21 @@ pub struct PidManager {
 }
 
 enum Inner {
-    Ok { net_cls_path: PathBuf },
+    Ok { hierarchy: CgroupHierarchy },
     Failed { err: Error },
 }
 
 impl Default for PidManager {
     /// Creates a new PID Cgroup manager.
     ///
-    /// Finds the corresponding Cgroup to use. Will mount a `net_cls` filesystem
-    /// if none exists.
+    /// Finds the corresponding Cgroup to use. Uses a mounted `net_cls` controller or cgroup v2
+    /// hierarchy if one exists, and will otherwise try to mount a `net_cls` filesystem.
     fn default() -> Self {
         let inner = match Self::new_inner() {
-            Ok(net_cls_path) => Inner::Ok { net_cls_path },
+            Ok(hierarchy) => {
+                log::debug!("Using cgroup hierarchy for split tunneling: {hierarchy:?}");
+                Inner::Ok { hierarchy }
+            }
             Err(err) => {
                 log::error!(
                     "{}",
@@ -85,16 +96,16 @@ impl Default for PidManager {
 }
 
 impl PidManager {
-    fn new_inner() -> Result<PathBuf, Error> {
-        let net_cls_path = Self::create_cgroup()?;
-        Self::setup_exclusion_group(&net_cls_path)?;
-        Ok(net_cls_path)
+    fn new_inner() -> Result<CgroupHierarchy, Error> {
+        let hierarchy = Self::create_cgroup()?;
+        Self::setup_exclusion_group(&hierarchy)?;
+        Ok(hierarchy)
     }
 
     /// Set up cgroup used to track PIDs for split tunneling.
-    fn create_cgroup() -> Result<PathBuf, Error> {
-        if let Some(net_cls_path) = find_net_cls_mount().map_err(Error::ListMounts)? {
-            return Ok(net_cls_path);
+    fn create_cgroup() -> Result<CgroupHierarchy, Error> {
+        if let Some(hierarchy) = find_split_tunnel_hierarchy().map_err(Error::ListMounts)? {
+            return Ok(hierarchy);
         }
 
         let net_cls_dir = env::var(NET_CLS_DIR_OVERRIDE_ENV_VAR)
@@ -113,27 +124,33 @@ impl PidManager {
             nix::mount::MsFlags::empty(),
             Some("net_cls"),
         )
-        .map_err(Error::InitNetClsCGroup)?;
+        .map_err(Error::NoCgroupHierarchy)?;
 
-        Ok(net_cls_dir)
+        Ok(CgroupHierarchy::NetCls(net_cls_dir))
     }
 
-    fn setup_exclusion_group(net_cls_path: &Path) -> Result<(), Error> {
-        let exclusions_dir = net_cls_path.join(SPLIT_TUNNEL_CGROUP_NAME);
+    fn setup_exclusion_group(hierarchy: &CgroupHierarchy) -> Result<(), Error> {
+        let exclusions_dir = hierarchy.exclusion_cgroup();
         if !exclusions_dir.exists() {
             fs::create_dir(exclusions_dir.clone()).map_err(Error::CreateCGroup)?;
         }
 
-        let classid_path = exclusions_dir.join("net_cls.classid");
-        fs::write(classid_path, NET_CLS_CLASSID.to_string().as_bytes())
-            .map_err(Error::SetCGroupClassId)
+        match hierarchy {
+            CgroupHierarchy::NetCls(_) => {
+                let classid_path = exclusions_dir.join("net_cls.classid");
+                fs::write(classid_path, NET_CLS_CLASSID.to_string().as_bytes())
+                    .map_err(Error::SetCGroupClassId)
+            }
+            // Processes in the cgroup are identified by the firewall using the cgroup ID
+            CgroupHierarchy::V2(_) => Ok(()),
+        }
     }
 
-    fn get_net_cls_path(&self) -> Result<&Path, Error> {
+    fn get_hierarchy(&self) -> Result<&CgroupHierarchy, Error> {
         match &self.inner {
-            Inner::Ok { net_cls_path } => Ok(net_cls_path),
+            Inner::Ok { hierarchy } => Ok(hierarchy),
             Inner::Failed { err } => {
-                log::error!("Failed to get netcls path: {err}");
+                log::error!("Failed to get split tunnel cgroup: {err}");
                 Err(Error::Unavailable)
             }
         }
@@ -142,8 +159,8 @@ impl PidManager {
     /// Add a PID to the Cgroup to have it excluded from the tunnel.
     pub fn add(&self, pid: i32) -> Result<(), Error> {
         let exclusions_path = self
-            .get_net_cls_path()?
-            .join(SPLIT_TUNNEL_CGROUP_NAME)
+            .get_hierarchy()?
+            .exclusion_cgroup()
             .join("cgroup.procs");
 
         let mut file = fs::OpenOptions::new()
@@ -161,7 +178,7 @@ impl PidManager {
     pub fn remove(&self, pid: i32) -> Result<(), Error> {
         // FIXME: We remove PIDs from our cgroup here by adding
         //        them to the parent cgroup. This seems wrong.
-        let mut file = Self::open_parent_cgroup_handle(self.get_net_cls_path()?)
+        let mut file = Self::open_parent_cgroup_handle(self.get_hierarchy()?)
             .map_err(Error::RemoveCGroupPid)?;
 
         file.write_all(pid.to_string().as_bytes())
@@ -171,8 +188,8 @@ impl PidManager {
     /// Return a list of all PIDs currently in the Cgroup excluded from the tunnel.
     pub fn list(&self) -> Result<Vec<i32>, Error> {
         let exclusions_path = self
-            .get_net_cls_path()?
-            .join(SPLIT_TUNNEL_CGROUP_NAME)
+            .get_hierarchy()?
+            .exclusion_cgroup()
             .join("cgroup.procs");
 
         let file = fs::File::open(exclusions_path).map_err(Error::ListCGroupPids)?;
@@ -193,7 +210,7 @@ impl PidManager {
     pub fn clear(&self) -> Result<(), Error> {
         let pids = self.list()?;
 
-        let mut file = Self::open_parent_cgroup_handle(self.get_net_cls_path()?)
+        let mut file = Self::open_parent_cgroup_handle(self.get_hierarchy()?)
             .map_err(Error::RemoveCGroupPid)?;
         for pid in pids {
             file.write_all(pid.to_string().as_bytes())
@@ -208,11 +225,11 @@ impl PidManager {
         matches!(self.inner, Inner::Ok { .. })
     }
 
-    fn open_parent_cgroup_handle(net_cls_path: &Path) -> io::Result<fs::File> {
+    fn open_parent_cgroup_handle(hierarchy: &CgroupHierarchy) -> io::Result<fs::File> {
         fs::OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(false)
-            .open(net_cls_path.join("cgroup.procs"))
+            .open(hierarchy.mount_path().join("cgroup.procs"))
     }
 }
@@ -1,7 +1,10 @@
 use crate::logging;
 #[cfg(not(target_os = "android"))]
 use futures::channel::oneshot;
-use std::path;
+use std::{
+    path,
+    sync::{Arc, Mutex},
+};
 #[cfg(not(target_os = "android"))]
 use talpid_routing::RouteManagerHandle;
 #[cfg(target_os = "android")]
@@ -265,12 +268,40 @@ impl TunnelMonitor {
         }
     }
 
+    /// Returns a handle for reading the traffic statistics of the tunnel, if supported by the
+    /// tunnel type.
+    pub fn stats_handle(&self) -> Option<talpid_wireguard::TunnelStatsHandle> {
+        match &self.monitor {
+            #[cfg(not(target_os = "android"))]
+            InternalTunnelMonitor::OpenVpn(_) => None,
+            InternalTunnelMonitor::Wireguard(monitor) => Some(monitor.stats_handle()),
+        }
+    }
+
     /// Consumes the monitor and blocks until the tunnel exits or there is an error.
     pub fn wait(self) -> Result<()> {
         self.monitor.wait()
     }
 }
 
+/// Shared handle for reading the traffic statistics of the current tunnel, if any.
+#[derive(Clone, Default)]
+pub struct TunnelStatsHandle {
+    current: Arc<Mutex<Option<talpid_wireguard::TunnelStatsHandle>>>,
+}
+
+impl TunnelStatsHandle {
+    pub(crate) fn set(&self, handle: Option<talpid_wireguard::TunnelStatsHandle>) {
+        *self.current.lock().unwrap() = handle;
+    }
+
+    /// Returns the current traffic statistics, or `None` if there is no WireGuard tunnel.
+    pub async fn get(&self) -> Option<wireguard_types::TunnelStats> {
+        let handle = self.current.lock().unwrap().clone()?;
+        handle.get().await
+    }
+}
+
 enum InternalTunnelMonitor {
     #[cfg(not(target_os = "android"))]
     OpenVpn(talpid_openvpn::OpenVpnMonitor),
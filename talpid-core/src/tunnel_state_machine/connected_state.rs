@@ -135,6 +135,8 @@ impl ConnectedState {
             peer_endpoint,
             tunnel: self.metadata.clone(),
             allow_lan: shared_values.allow_lan,
+            #[cfg(target_os = "linux")]
+            allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
             #[cfg(not(target_os = "android"))]
             dns_config: Self::resolve_dns(&self.metadata, shared_values),
             #[cfg(target_os = "macos")]
@@ -156,6 +158,33 @@ impl ConnectedState {
     fn set_dns(&self, shared_values: &mut SharedTunnelStateValues) -> Result<(), BoxedError> {
         let dns_config: ResolvedDnsConfig = Self::resolve_dns(&self.metadata, shared_values);
 
+        // Split DNS rules can only be enforced by the local DNS resolver. If there are any, point
+        // the system at the resolver and let it forward queries to the configured servers.
+        #[cfg(target_os = "linux")]
+        let dns_config = if dns_config.split_config().is_empty() {
+            dns_config
+        } else {
+            match shared_values.split_dns_resolver() {
+                Ok(resolver) => {
+                    log::debug!("Enabling local DNS resolver for split DNS");
+                    let resolver_addr = resolver.listening_addr();
+                    shared_values
+                        .runtime
+                        .block_on(resolver.enable_forward(dns_config));
+                    crate::dns::DnsConfig::from_addresses(&[resolver_addr.ip()], &[]).resolve(&[])
+                }
+                Err(error) => {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg(
+                            "Failed to start local DNS resolver. Ignoring split DNS rules"
+                        )
+                    );
+                    dns_config
+                }
+            }
+        };
+
         #[cfg(not(target_os = "macos"))]
         shared_values
             .dns_monitor
@@ -167,6 +196,12 @@ impl ConnectedState {
         // DNS resolver.
         if !*LOCAL_DNS_RESOLVER {
             log::debug!("Not enabling local DNS resolver");
+            if !dns_config.encrypted_config().is_empty() {
+                log::warn!("Encrypted DNS servers require the local DNS resolver. Ignoring them");
+            }
+            if !dns_config.split_config().is_empty() {
+                log::warn!("Split DNS rules require the local DNS resolver. Ignoring them");
+            }
             shared_values
                 .dns_monitor
                 .set(&self.metadata.interface, dns_config)
@@ -175,11 +210,9 @@ impl ConnectedState {
             log::debug!("Enabling local DNS resolver");
             // Tell local DNS resolver to start forwarding DNS queries to whatever `dns_config`
             // specifies as DNS.
-            shared_values.runtime.block_on(
-                shared_values
-                    .filtering_resolver
-                    .enable_forward(dns_config.addresses().collect()),
-            );
+            shared_values
+                .runtime
+                .block_on(shared_values.filtering_resolver.enable_forward(dns_config));
         }
 
         Ok(())
@@ -191,6 +224,12 @@ impl ConnectedState {
             log::error!("{}", error.display_chain_with_msg("Unable to reset DNS"));
         }
 
+        // Stop forwarding split DNS queries
+        #[cfg(target_os = "linux")]
+        if let Some(resolver) = &shared_values.filtering_resolver {
+            shared_values.runtime.block_on(resolver.disable_forward());
+        }
+
         // On macOS, configure only the local DNS resolver
         #[cfg(target_os = "macos")]
         if !*LOCAL_DNS_RESOLVER {
@@ -345,6 +384,38 @@ impl ConnectedState {
                 shared_values.bypass_socket(fd, done_tx);
                 SameState(self)
             }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets, complete_tx)) => {
+                let consequence = if shared_values.set_allowed_lan_nets(allowed_lan_nets) {
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let consequence = if shared_values.set_split_tunnel_mode(mode) {
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
             #[cfg(windows)]
             Some(TunnelCommand::SetExcludedApps(result_tx, paths)) => {
                 shared_values.exclude_paths(paths, result_tx);
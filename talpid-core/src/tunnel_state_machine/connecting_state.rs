@@ -25,7 +25,7 @@ use crate::dns::DnsConfig;
 use crate::firewall::FirewallPolicy;
 #[cfg(target_os = "macos")]
 use crate::resolver::LOCAL_DNS_RESOLVER;
-use crate::tunnel::{self, TunnelMonitor};
+use crate::tunnel::{self, TunnelMonitor, TunnelStatsHandle};
 
 pub(crate) type TunnelCloseEvent = Fuse<oneshot::Receiver<Option<ErrorStateCause>>>;
 
@@ -145,6 +145,7 @@ impl ConnectingState {
                         &shared_values.resource_dir,
                         shared_values.tun_provider.clone(),
                         &shared_values.route_manager,
+                        shared_values.tunnel_stats.clone(),
                         retry_attempt,
                     );
 
@@ -194,6 +195,8 @@ impl ConnectingState {
             peer_endpoint,
             tunnel: tunnel_metadata.clone(),
             allow_lan: shared_values.allow_lan,
+            #[cfg(target_os = "linux")]
+            allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
             allowed_endpoint: shared_values.allowed_endpoint.clone(),
             allowed_tunnel_traffic,
             #[cfg(target_os = "macos")]
@@ -217,6 +220,7 @@ impl ConnectingState {
             })
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn start_tunnel(
         runtime: tokio::runtime::Handle,
         parameters: TunnelParameters,
@@ -224,6 +228,7 @@ impl ConnectingState {
         resource_dir: &Path,
         tun_provider: Arc<Mutex<TunProvider>>,
         route_manager: &RouteManagerHandle,
+        tunnel_stats: TunnelStatsHandle,
         retry_attempt: u32,
     ) -> Self {
         let (event_tx, event_rx) = mpsc::unbounded();
@@ -253,7 +258,9 @@ impl ConnectingState {
 
             let block_reason = match TunnelMonitor::start(&tunnel_parameters, &log_dir, args) {
                 Ok(monitor) => {
+                    tunnel_stats.set(monitor.stats_handle());
                     let reason = Self::wait_for_tunnel_monitor(monitor, retry_attempt);
+                    tunnel_stats.set(None);
                     log::debug!("Tunnel monitor exited with block reason: {:?}", reason);
                     reason
                 }
@@ -469,6 +476,26 @@ impl ConnectingState {
                 shared_values.bypass_socket(fd, done_tx);
                 SameState(self)
             }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets, complete_tx)) => {
+                let consequence = if shared_values.set_allowed_lan_nets(allowed_lan_nets) {
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let consequence = if shared_values.set_split_tunnel_mode(mode) {
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
             #[cfg(windows)]
             Some(TunnelCommand::SetExcludedApps(result_tx, paths)) => {
                 shared_values.exclude_paths(paths, result_tx);
@@ -8,6 +8,8 @@ use crate::firewall::FirewallPolicy;
 use crate::{dns, tunnel_state_machine::ErrorState};
 use futures::StreamExt;
 use talpid_types::ErrorExt;
+#[cfg(target_os = "linux")]
+use talpid_types::split_tunnel::SplitTunnelMode;
 #[cfg(target_os = "macos")]
 use talpid_types::tunnel::ErrorStateCause;
 
@@ -74,7 +76,9 @@ impl DisconnectedState {
         shared_values: &mut SharedTunnelStateValues,
         should_reset_firewall: bool,
     ) {
-        let result = if shared_values.block_when_disconnected.bool() {
+        let result = if shared_values.block_when_disconnected.bool()
+            || Self::blocks_included_apps(shared_values)
+        {
             #[cfg(target_os = "windows")]
             {
                 // Respect the persist flag of BlockWhenDisconnected.
@@ -85,6 +89,8 @@ impl DisconnectedState {
 
             let policy = FirewallPolicy::Blocked {
                 allow_lan: shared_values.allow_lan,
+                #[cfg(target_os = "linux")]
+                allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
                 allowed_endpoint: Some(shared_values.allowed_endpoint.clone()),
             };
 
@@ -106,6 +112,18 @@ impl DisconnectedState {
         }
     }
 
+    /// Whether only apps included by split tunneling must be blocked while disconnected. All
+    /// other traffic is allowed through the blocking policy by the split tunnel rules.
+    #[cfg(target_os = "linux")]
+    fn blocks_included_apps(shared_values: &SharedTunnelStateValues) -> bool {
+        shared_values.split_tunnel_mode == SplitTunnelMode::Include
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn blocks_included_apps(_: &SharedTunnelStateValues) -> bool {
+        false
+    }
+
     // NOTE: There is no firewall on Android, so it is pointless to pretend to enforce firewall
     // policies by calling a bunch of functions that will end up being a no-op. The proper
     // long-term fix would be to remove the firewall module completely,
@@ -238,6 +256,24 @@ impl TunnelState for DisconnectedState {
                 shared_values.bypass_socket(fd, done_tx);
                 SameState(self)
             }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets, complete_tx)) => {
+                if shared_values.set_allowed_lan_nets(allowed_lan_nets) {
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                if shared_values.set_split_tunnel_mode(mode) {
+                    // Included apps are blocked while disconnected, so the firewall has to be
+                    // updated even if lockdown mode is disabled.
+                    Self::set_firewall_policy(shared_values, true);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             #[cfg(windows)]
             Some(TunnelCommand::SetExcludedApps(result_tx, paths)) => {
                 shared_values.exclude_paths(paths, result_tx);
@@ -96,6 +96,16 @@ impl DisconnectingState {
             Some(TunnelCommand::BypassSocket(fd, done_tx)) => {
                 shared_values.bypass_socket(fd, done_tx);
             }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets, complete_tx)) => {
+                let _ = shared_values.set_allowed_lan_nets(allowed_lan_nets);
+                let _ = complete_tx.send(());
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let _ = shared_values.set_split_tunnel_mode(mode);
+                let _ = complete_tx.send(());
+            }
             #[cfg(windows)]
             Some(TunnelCommand::SetExcludedApps(result_tx, paths)) => {
                 shared_values.exclude_paths(paths, result_tx);
@@ -79,6 +79,8 @@ impl ErrorState {
     ) -> Result<(), FirewallPolicyError> {
         let policy = FirewallPolicy::Blocked {
             allow_lan: shared_values.allow_lan,
+            #[cfg(target_os = "linux")]
+            allowed_lan_nets: shared_values.allowed_lan_nets.clone(),
             allowed_endpoint: Some(shared_values.allowed_endpoint.clone()),
         };
 
@@ -232,6 +234,22 @@ impl TunnelState for ErrorState {
                 shared_values.bypass_socket(fd, done_tx);
                 SameState(self)
             }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::AllowedLanNets(allowed_lan_nets, complete_tx)) => {
+                if shared_values.set_allowed_lan_nets(allowed_lan_nets) {
+                    let _ = Self::set_firewall_policy(shared_values);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                if shared_values.set_split_tunnel_mode(mode) {
+                    let _ = Self::set_firewall_policy(shared_values);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             #[cfg(target_os = "android")]
             Some(TunnelCommand::SetExcludedApps(result_tx, paths)) => {
                 if shared_values.set_excluded_paths(paths) {
@@ -18,6 +18,7 @@ use crate::{
     firewall::{Firewall, FirewallArguments, InitialFirewallState},
     mpsc::Sender,
     offline,
+    tunnel::TunnelStatsHandle,
 };
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 use std::ffi::OsString;
@@ -33,6 +34,8 @@ use futures::{
     channel::{mpsc, oneshot},
     stream,
 };
+#[cfg(target_os = "linux")]
+use ipnetwork::IpNetwork;
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
 use std::{
@@ -43,6 +46,8 @@ use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
+#[cfg(target_os = "linux")]
+use talpid_types::split_tunnel::SplitTunnelMode;
 #[cfg(target_os = "android")]
 use talpid_types::{ErrorExt, android::AndroidContext};
 use talpid_types::{
@@ -93,6 +98,9 @@ pub enum Error {
 pub struct InitialTunnelState {
     /// Whether to allow LAN traffic when not in the (non-blocking) disconnected state.
     pub allow_lan: bool,
+    /// Networks that are reachable when LAN traffic is allowed.
+    #[cfg(target_os = "linux")]
+    pub allowed_lan_nets: Vec<IpNetwork>,
     /// Block traffic unless connected to the VPN.
     #[cfg(not(target_os = "android"))]
     pub block_when_disconnected: BlockWhenDisconnected,
@@ -109,6 +117,9 @@ pub struct InitialTunnelState {
     /// Apps to exclude from the tunnel.
     #[cfg(target_os = "android")]
     pub exclude_paths: Vec<String>,
+    /// Whether processes in the split tunnel cgroup are excluded from or included in the tunnel.
+    #[cfg(target_os = "linux")]
+    pub split_tunnel_mode: SplitTunnelMode,
 }
 
 /// Identifiers for various network resources that should be unique to a given instance of a tunnel
@@ -172,6 +183,7 @@ pub async fn spawn(
 
     #[cfg(windows)]
     let split_tunnel = state_machine.shared_values.split_tunnel.handle();
+    let tunnel_stats = state_machine.shared_values.tunnel_stats.clone();
 
     tokio::task::spawn_blocking(move || {
         state_machine.run(state_change_listener);
@@ -185,6 +197,7 @@ pub async fn spawn(
         shutdown_rx,
         #[cfg(windows)]
         split_tunnel,
+        tunnel_stats,
     })
 }
 
@@ -192,6 +205,9 @@ pub async fn spawn(
 pub enum TunnelCommand {
     /// Enable or disable LAN access in the firewall.
     AllowLan(bool, oneshot::Sender<()>),
+    /// Set the networks that are reachable when LAN access is enabled.
+    #[cfg(target_os = "linux")]
+    AllowedLanNets(Vec<IpNetwork>, oneshot::Sender<()>),
     /// Endpoint that should never be blocked. `()` is sent to the
     /// channel after attempting to set the firewall policy, regardless
     /// of whether it succeeded.
@@ -225,6 +241,10 @@ pub enum TunnelCommand {
         oneshot::Sender<Result<(), split_tunnel::Error>>,
         Vec<String>,
     ),
+    /// Set whether processes in the split tunnel cgroup are excluded from or included in the
+    /// tunnel.
+    #[cfg(target_os = "linux")]
+    SplitTunnelMode(SplitTunnelMode, oneshot::Sender<()>),
 }
 
 type TunnelCommandReceiver = stream::Fuse<mpsc::UnboundedReceiver<TunnelCommand>>;
@@ -386,6 +406,8 @@ impl TunnelStateMachine {
             allow_lan: args.settings.allow_lan,
             #[cfg(target_os = "linux")]
             fwmark: args.linux_ids.fwmark,
+            #[cfg(target_os = "linux")]
+            split_tunnel_mode: args.settings.split_tunnel_mode,
         };
 
         let firewall = Firewall::from_args(fw_args).map_err(Error::InitFirewallError)?;
@@ -456,6 +478,8 @@ impl TunnelStateMachine {
             route_manager: args.route_manager,
             _offline_monitor: offline_monitor,
             allow_lan: args.settings.allow_lan,
+            #[cfg(target_os = "linux")]
+            allowed_lan_nets: args.settings.allowed_lan_nets,
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: args.settings.block_when_disconnected,
             connectivity,
@@ -463,12 +487,17 @@ impl TunnelStateMachine {
             allowed_endpoint: args.settings.allowed_endpoint,
             tunnel_parameters_generator: Box::new(args.tunnel_parameters_generator),
             tun_provider: Arc::new(Mutex::new(args.tun_provider)),
+            tunnel_stats: TunnelStatsHandle::default(),
             log_dir: args.log_dir,
             resource_dir: args.resource_dir,
             #[cfg(target_os = "linux")]
+            split_tunnel_mode: args.settings.split_tunnel_mode,
+            #[cfg(target_os = "linux")]
             connectivity_check_was_enabled: None,
             #[cfg(target_os = "macos")]
             filtering_resolver,
+            #[cfg(target_os = "linux")]
+            filtering_resolver: None,
         };
 
         tokio::task::spawn_blocking(move || {
@@ -516,6 +545,10 @@ impl TunnelStateMachine {
         runtime.block_on(self.shared_values.split_tunnel.shutdown());
         #[cfg(target_os = "macos")]
         runtime.block_on(self.shared_values.filtering_resolver.stop());
+        #[cfg(target_os = "linux")]
+        if let Some(filtering_resolver) = self.shared_values.filtering_resolver.take() {
+            runtime.block_on(filtering_resolver.stop());
+        }
         runtime.block_on(self.shared_values.route_manager.stop());
     }
 }
@@ -550,6 +583,9 @@ struct SharedTunnelStateValues {
     _offline_monitor: offline::MonitorHandle,
     /// Should LAN access be allowed outside the tunnel.
     allow_lan: bool,
+    /// Networks that are reachable outside the tunnel when LAN access is allowed.
+    #[cfg(target_os = "linux")]
+    allowed_lan_nets: Vec<IpNetwork>,
     /// Should network access be allowed when in the disconnected state.
     #[cfg(not(target_os = "android"))]
     block_when_disconnected: BlockWhenDisconnected,
@@ -563,10 +599,15 @@ struct SharedTunnelStateValues {
     tunnel_parameters_generator: Box<dyn TunnelParametersGenerator>,
     /// The provider of tunnel devices.
     tun_provider: Arc<Mutex<TunProvider>>,
+    /// Traffic statistics of the current tunnel.
+    tunnel_stats: TunnelStatsHandle,
     /// Directory to store tunnel log file.
     log_dir: Option<PathBuf>,
     /// Resource directory path.
     resource_dir: PathBuf,
+    /// Whether processes in the split tunnel cgroup are excluded from or included in the tunnel.
+    #[cfg(target_os = "linux")]
+    split_tunnel_mode: SplitTunnelMode,
 
     /// NetworkManager's connecitivity check state.
     #[cfg(target_os = "linux")]
@@ -575,9 +616,27 @@ struct SharedTunnelStateValues {
     /// Filtering resolver handle
     #[cfg(target_os = "macos")]
     filtering_resolver: crate::resolver::ResolverHandle,
+    /// Local resolver used for split DNS. It is not started until split DNS rules are used, since
+    /// another resolver on the host may already be bound to port 53.
+    #[cfg(target_os = "linux")]
+    filtering_resolver: Option<crate::resolver::ResolverHandle>,
 }
 
 impl SharedTunnelStateValues {
+    /// Return a handle to the local resolver, starting it if it is not running.
+    #[cfg(target_os = "linux")]
+    fn split_dns_resolver(
+        &mut self,
+    ) -> Result<crate::resolver::ResolverHandle, crate::resolver::Error> {
+        let resolver = match self.filtering_resolver.take() {
+            Some(resolver) => resolver,
+            None => self
+                .runtime
+                .block_on(crate::resolver::start_resolver(Default::default()))?,
+        };
+        Ok(self.filtering_resolver.insert(resolver).clone())
+    }
+
     /// Return whether a split tunnel interface was added or removed
     #[cfg(target_os = "macos")]
     pub fn set_exclude_paths(&mut self, paths: Vec<OsString>) -> Result<bool, split_tunnel::Error> {
@@ -645,6 +704,28 @@ impl SharedTunnelStateValues {
         }
     }
 
+    #[cfg(target_os = "linux")]
+    pub fn set_allowed_lan_nets(&mut self, allowed_lan_nets: Vec<IpNetwork>) -> bool {
+        if self.allowed_lan_nets != allowed_lan_nets {
+            self.allowed_lan_nets = allowed_lan_nets;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Update the split tunnel mode used by the firewall. Returns whether the mode changed.
+    #[cfg(target_os = "linux")]
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) -> bool {
+        if self.split_tunnel_mode != mode {
+            self.split_tunnel_mode = mode;
+            self.firewall.set_split_tunnel_mode(mode);
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn set_dns_config(&mut self, dns_config: DnsConfig) -> bool {
         if self.dns_config != dns_config {
             self.dns_config = dns_config;
@@ -780,6 +861,7 @@ pub struct TunnelStateMachineHandle {
     shutdown_rx: oneshot::Receiver<()>,
     #[cfg(windows)]
     split_tunnel: split_tunnel::SplitTunnelHandle,
+    tunnel_stats: TunnelStatsHandle,
 }
 
 impl TunnelStateMachineHandle {
@@ -804,4 +886,9 @@ impl TunnelStateMachineHandle {
     pub fn split_tunnel(&self) -> &split_tunnel::SplitTunnelHandle {
         &self.split_tunnel
     }
+
+    /// Returns a handle for reading the traffic statistics of the current tunnel.
+    pub fn tunnel_stats(&self) -> &TunnelStatsHandle {
+        &self.tunnel_stats
+    }
 }
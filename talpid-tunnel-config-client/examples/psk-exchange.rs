@@ -33,6 +33,6 @@ async fn main() {
     .unwrap();
 
     println!("Private key: {ephemeral_private_key}");
-    // Use fmt::Debug since Serialize is not implemented for PresharedKey.
+    // Use fmt::Debug, which prints the PSK as base64.
     println!("PSK: {:?}", ephemeral_peer.psk.unwrap());
 }
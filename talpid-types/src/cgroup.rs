@@ -1,7 +1,52 @@
-use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::PathBuf};
+use std::{
+    ffi::OsStr,
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
 
 pub const SPLIT_TUNNEL_CGROUP_NAME: &str = "mullvad-exclusions";
 
+/// A cgroup hierarchy that can be used to exclude processes from the tunnel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CgroupHierarchy {
+    /// The cgroup v1 `net_cls` controller, mounted at the given path. Packets sent by excluded
+    /// processes are tagged with a class ID.
+    NetCls(PathBuf),
+    /// The cgroup v2 unified hierarchy, mounted at the given path. Packets sent by excluded
+    /// processes are matched using the cgroup of their socket.
+    V2(PathBuf),
+}
+
+impl CgroupHierarchy {
+    /// Return the path where the hierarchy is mounted.
+    pub fn mount_path(&self) -> &Path {
+        match self {
+            CgroupHierarchy::NetCls(path) | CgroupHierarchy::V2(path) => path,
+        }
+    }
+
+    /// Return the path of the cgroup that contains excluded processes.
+    pub fn exclusion_cgroup(&self) -> PathBuf {
+        self.mount_path().join(SPLIT_TUNNEL_CGROUP_NAME)
+    }
+}
+
+/// Find the cgroup hierarchy to use for split tunneling if one is mounted.
+///
+/// A mounted `net_cls` controller is preferred, since the kernel stops tracking the cgroup v2
+/// membership of sockets once `net_cls` is in use.
+pub fn find_split_tunnel_hierarchy() -> std::io::Result<Option<CgroupHierarchy>> {
+    let mounts = fs::read("/proc/mounts")?;
+    Ok(find_split_tunnel_hierarchy_inner(&mounts))
+}
+
+fn find_split_tunnel_hierarchy_inner(mounts: &[u8]) -> Option<CgroupHierarchy> {
+    find_net_cls_mount_inner(mounts)
+        .map(CgroupHierarchy::NetCls)
+        .or_else(|| find_cgroup2_mount_inner(mounts).map(CgroupHierarchy::V2))
+}
+
 /// Find the path of the cgroup v1 net_cls controller mount if it exists
 pub fn find_net_cls_mount() -> std::io::Result<Option<PathBuf>> {
     let mounts = fs::read("/proc/mounts")?;
@@ -11,10 +56,39 @@ pub fn find_net_cls_mount() -> std::io::Result<Option<PathBuf>> {
 fn find_net_cls_mount_inner(mounts: &[u8]) -> Option<PathBuf> {
     mounts
         .split(|byte| *byte == b'\n')
-        .find_map(parse_mount_line)
+        .filter_map(parse_mount_line)
+        .find(|mount| {
+            // The expected device type and fs type is "cgroup";
+            mount.filesystem_type == b"cgroup"
+                && mount
+                    .mount_options
+                    .split(|byte| *byte == b',')
+                    .any(|key| key == b"net_cls")
+        })
+        .map(|mount| mount.path())
 }
 
-fn parse_mount_line(line: &[u8]) -> Option<PathBuf> {
+fn find_cgroup2_mount_inner(mounts: &[u8]) -> Option<PathBuf> {
+    mounts
+        .split(|byte| *byte == b'\n')
+        .filter_map(parse_mount_line)
+        .find(|mount| mount.filesystem_type == b"cgroup2")
+        .map(|mount| mount.path())
+}
+
+struct MountEntry<'a> {
+    mount_path: &'a [u8],
+    filesystem_type: &'a [u8],
+    mount_options: &'a [u8],
+}
+
+impl MountEntry<'_> {
+    fn path(&self) -> PathBuf {
+        PathBuf::from(OsStr::from_bytes(self.mount_path))
+    }
+}
+
+fn parse_mount_line(line: &[u8]) -> Option<MountEntry<'_>> {
     // Each line contains multiple values separated by space.
     // `cgroup /sys/fs/cgroup/net_cls,net_prio cgroup
     // rw,nosuid,nodev,noexec,relatime,net_cls,net_prio 0 0`  Value meanings:
@@ -28,19 +102,12 @@ fn parse_mount_line(line: &[u8]) -> Option<PathBuf> {
     let mount_path = parts.next()?;
     let filesystem_type = parts.next()?;
     let mount_options = parts.next()?;
-    // The expected device type and fs type is "cgroup";
-    if filesystem_type != b"cgroup" {
-        return None;
-    }
-
-    if !mount_options
-        .split(|byte| *byte == b',')
-        .any(|key| key == b"net_cls")
-    {
-        return None;
-    }
 
-    Some(PathBuf::from(OsStr::from_bytes(mount_path)))
+    Some(MountEntry {
+        mount_path,
+        filesystem_type,
+        mount_options,
+    })
 }
 
 #[cfg(test)]
@@ -72,4 +139,39 @@ cgroup /nope
 
         assert_eq!(find_net_cls_mount_inner(input), None)
     }
+
+    #[test]
+    fn test_find_cgroup2_hierarchy() {
+        let input = br#"sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+cgroup2 /sys/fs/cgroup cgroup2 rw,nosuid,nodev,noexec,relatime,nsdelegate,memory_recursiveprot 0 0
+"#;
+
+        assert_eq!(
+            find_split_tunnel_hierarchy_inner(input),
+            Some(CgroupHierarchy::V2(PathBuf::from("/sys/fs/cgroup")))
+        )
+    }
+
+    #[test]
+    fn test_prefer_net_cls_hierarchy() {
+        let input = br#"cgroup2 /sys/fs/cgroup/unified cgroup2 rw,nosuid,nodev,noexec,relatime 0 0
+cgroup /sys/fs/cgroup/net_cls,net_prio cgroup rw,nosuid,nodev,noexec,relatime,net_cls,net_prio 0 0
+"#;
+
+        assert_eq!(
+            find_split_tunnel_hierarchy_inner(input),
+            Some(CgroupHierarchy::NetCls(PathBuf::from(
+                "/sys/fs/cgroup/net_cls,net_prio"
+            )))
+        )
+    }
+
+    #[test]
+    fn test_no_split_tunnel_hierarchy() {
+        let input =
+            br#"cgroup /sys/fs/cgroup/memory cgroup rw,nosuid,nodev,noexec,relatime,memory 0 0
+"#;
+
+        assert_eq!(find_split_tunnel_hierarchy_inner(input), None)
+    }
 }
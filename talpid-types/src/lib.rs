@@ -6,7 +6,6 @@ pub mod tunnel;
 #[cfg(target_os = "linux")]
 pub mod cgroup;
 
-#[cfg(target_os = "windows")]
 pub mod split_tunnel;
 
 pub mod drop_guard;
@@ -0,0 +1,252 @@
+//! DNS servers that are queried over an encrypted transport instead of plaintext UDP/TCP, and
+//! rules for sending queries for some domains to other DNS servers (split DNS).
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Path that DNS over HTTPS queries are sent to. This is the path used by most public resolvers,
+/// and the only one that is currently supported.
+pub const DOH_QUERY_PATH: &str = "/dns-query";
+
+/// Transport used to reach an [`EncryptedDnsServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptedDnsProtocol {
+    /// DNS over HTTPS (RFC 8484).
+    Https,
+    /// DNS over TLS (RFC 7858).
+    Tls,
+}
+
+impl EncryptedDnsProtocol {
+    /// Port that the protocol uses unless another one is specified.
+    pub const fn default_port(self) -> u16 {
+        match self {
+            EncryptedDnsProtocol::Https => 443,
+            EncryptedDnsProtocol::Tls => 853,
+        }
+    }
+}
+
+/// A DNS server that is queried over HTTPS or TLS.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EncryptedDnsServer {
+    pub protocol: EncryptedDnsProtocol,
+    /// Name of the server. The certificate presented by the server is verified against it.
+    pub hostname: String,
+    /// Address to connect to. Having this up front means that `hostname` never has to be resolved
+    /// in plaintext.
+    pub bootstrap_address: IpAddr,
+    pub port: u16,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum InvalidEncryptedDnsServer {
+    #[error("The URL must begin with https://")]
+    NotHttps,
+    #[error("Invalid host name: {0}")]
+    Hostname(String),
+    #[error("Invalid port: {0}")]
+    Port(String),
+    #[error("Unsupported path {0}. Only {DOH_QUERY_PATH} is supported")]
+    Path(String),
+}
+
+impl EncryptedDnsServer {
+    /// Create a DNS over HTTPS server from a URL such as `https://dns.example.com/dns-query`.
+    pub fn https(url: &str, bootstrap_address: IpAddr) -> Result<Self, InvalidEncryptedDnsServer> {
+        let rest = url
+            .strip_prefix("https://")
+            .ok_or(InvalidEncryptedDnsServer::NotHttps)?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        if !matches!(path, "" | "/" | DOH_QUERY_PATH) {
+            return Err(InvalidEncryptedDnsServer::Path(path.to_owned()));
+        }
+        Self::new(EncryptedDnsProtocol::Https, authority, bootstrap_address)
+    }
+
+    /// Create a DNS over TLS server from a host name, optionally followed by a port, such as
+    /// `dns.example.com` or `tls://dns.example.com:853`.
+    pub fn tls(server: &str, bootstrap_address: IpAddr) -> Result<Self, InvalidEncryptedDnsServer> {
+        let authority = server.strip_prefix("tls://").unwrap_or(server);
+        Self::new(EncryptedDnsProtocol::Tls, authority, bootstrap_address)
+    }
+
+    fn new(
+        protocol: EncryptedDnsProtocol,
+        authority: &str,
+        bootstrap_address: IpAddr,
+    ) -> Result<Self, InvalidEncryptedDnsServer> {
+        let (hostname, port) = match authority.split_once(':') {
+            Some((hostname, port)) => {
+                let port = port
+                    .parse()
+                    .ok()
+                    .filter(|port| *port != 0)
+                    .ok_or_else(|| InvalidEncryptedDnsServer::Port(port.to_owned()))?;
+                (hostname, port)
+            }
+            None => (authority, protocol.default_port()),
+        };
+
+        if !is_valid_domain(hostname) {
+            return Err(InvalidEncryptedDnsServer::Hostname(hostname.to_owned()));
+        }
+
+        Ok(EncryptedDnsServer {
+            protocol,
+            hostname: hostname.to_owned(),
+            bootstrap_address,
+            port,
+        })
+    }
+
+    /// Address and port that queries are sent to.
+    pub fn endpoint(&self) -> SocketAddr {
+        SocketAddr::new(self.bootstrap_address, self.port)
+    }
+}
+
+impl fmt::Display for EncryptedDnsServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = match self.protocol {
+            EncryptedDnsProtocol::Https => "https",
+            EncryptedDnsProtocol::Tls => "tls",
+        };
+        write!(f, "{scheme}://{}", self.hostname)?;
+        if self.port != self.protocol.default_port() {
+            write!(f, ":{}", self.port)?;
+        }
+        if self.protocol == EncryptedDnsProtocol::Https {
+            f.write_str(DOH_QUERY_PATH)?;
+        }
+        write!(f, " ({})", self.bootstrap_address)
+    }
+}
+
+/// Sends queries for names within `domain` to `servers`, instead of to the regular DNS servers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SplitDnsRule {
+    /// Lowercase domain without a trailing dot, e.g. `corp.example`. The rule applies to the
+    /// domain itself and all of its subdomains.
+    pub domain: String,
+    pub servers: Vec<IpAddr>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum InvalidSplitDnsRule {
+    #[error("Invalid domain: {0}")]
+    Domain(String),
+    #[error("At least one DNS server is required")]
+    NoServers,
+}
+
+impl SplitDnsRule {
+    pub fn new(domain: &str, servers: Vec<IpAddr>) -> Result<Self, InvalidSplitDnsRule> {
+        let domain = domain.strip_suffix('.').unwrap_or(domain);
+        if !is_valid_domain(domain) {
+            return Err(InvalidSplitDnsRule::Domain(domain.to_owned()));
+        }
+        if servers.is_empty() {
+            return Err(InvalidSplitDnsRule::NoServers);
+        }
+        Ok(SplitDnsRule {
+            domain: domain.to_ascii_lowercase(),
+            servers,
+        })
+    }
+
+    /// Return whether `name` is within the domain of this rule. A trailing dot in `name` is
+    /// ignored.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.strip_suffix('.').unwrap_or(name);
+        let Some(prefix_len) = name.len().checked_sub(self.domain.len()) else {
+            return false;
+        };
+        if !name.is_char_boundary(prefix_len) {
+            return false;
+        }
+        let (prefix, suffix) = name.split_at(prefix_len);
+        suffix.eq_ignore_ascii_case(&self.domain) && (prefix.is_empty() || prefix.ends_with('.'))
+    }
+}
+
+impl fmt::Display for SplitDnsRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.domain)?;
+        for (i, server) in self.servers.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{server}")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_domain(domain: &str) -> bool {
+    !domain.is_empty()
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_encrypted_dns_server() {
+        let bootstrap: IpAddr = "9.9.9.9".parse().unwrap();
+
+        let doh = EncryptedDnsServer::https("https://dns.quad9.net/dns-query", bootstrap).unwrap();
+        assert_eq!(doh.hostname, "dns.quad9.net");
+        assert_eq!(doh.endpoint(), "9.9.9.9:443".parse().unwrap());
+        assert_eq!(doh.to_string(), "https://dns.quad9.net/dns-query (9.9.9.9)");
+        assert_eq!(
+            EncryptedDnsServer::https("https://dns.quad9.net", bootstrap),
+            Ok(doh)
+        );
+
+        let dot = EncryptedDnsServer::tls("tls://dns.quad9.net:8853", bootstrap).unwrap();
+        assert_eq!(dot.endpoint(), "9.9.9.9:8853".parse().unwrap());
+        assert_eq!(dot.to_string(), "tls://dns.quad9.net:8853 (9.9.9.9)");
+
+        assert_eq!(
+            EncryptedDnsServer::https("http://dns.quad9.net/dns-query", bootstrap),
+            Err(InvalidEncryptedDnsServer::NotHttps)
+        );
+        assert_eq!(
+            EncryptedDnsServer::https("https://dns.quad9.net/resolve", bootstrap),
+            Err(InvalidEncryptedDnsServer::Path("/resolve".to_owned()))
+        );
+        assert!(EncryptedDnsServer::tls("dns.quad9.net:0", bootstrap).is_err());
+        assert!(EncryptedDnsServer::tls("dns quad9", bootstrap).is_err());
+        assert!(EncryptedDnsServer::tls("", bootstrap).is_err());
+    }
+
+    #[test]
+    fn test_split_dns_rules() {
+        let server: IpAddr = "192.168.1.1".parse().unwrap();
+        let corp = SplitDnsRule::new("Corp.Example.", vec![server]).unwrap();
+        assert_eq!(corp.domain, "corp.example");
+        assert!(corp.matches("corp.example"));
+        assert!(corp.matches("intranet.corp.example."));
+        assert!(corp.matches("INTRANET.CORP.EXAMPLE"));
+        assert!(!corp.matches("notcorp.example"));
+        assert!(!corp.matches("example"));
+
+        assert_eq!(
+            SplitDnsRule::new("corp.example", vec![]),
+            Err(InvalidSplitDnsRule::NoServers)
+        );
+        assert!(SplitDnsRule::new(".", vec![server]).is_err());
+        assert!(SplitDnsRule::new("corp example", vec![server]).is_err());
+    }
+}
@@ -13,6 +13,7 @@ use std::{
     sync::LazyLock,
 };
 
+pub mod dns;
 pub mod obfuscation;
 pub mod openvpn;
 pub mod proxy;
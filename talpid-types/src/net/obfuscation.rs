@@ -10,22 +10,45 @@ pub enum ObfuscatorConfig {
     },
     Shadowsocks {
         endpoint: SocketAddr,
+        /// Password of the Shadowsocks server, if it is not a Mullvad relay.
+        #[serde(default)]
+        password: Option<String>,
+        /// Port of the WireGuard server on the host of the Shadowsocks server, if it is not a
+        /// Mullvad relay.
+        #[serde(default)]
+        wireguard_port: Option<u16>,
     },
     Quic {
         hostname: String,
         endpoint: SocketAddr,
         auth_token: String,
+        /// Port of the WireGuard server on the host of the QUIC server, if it is not a Mullvad
+        /// relay.
+        #[serde(default)]
+        wireguard_port: Option<u16>,
     },
 }
 
 impl ObfuscatorConfig {
+    /// Forward traffic to the WireGuard server listening on `port` on the obfuscation server,
+    /// rather than to the port used by Mullvad relays. This has no effect on UDP-over-TCP, which
+    /// forwards traffic to a port chosen by the server.
+    pub fn with_wireguard_port(mut self, port: u16) -> Self {
+        match &mut self {
+            ObfuscatorConfig::Udp2Tcp { .. } => (),
+            ObfuscatorConfig::Shadowsocks { wireguard_port, .. }
+            | ObfuscatorConfig::Quic { wireguard_port, .. } => *wireguard_port = Some(port),
+        }
+        self
+    }
+
     pub fn get_obfuscator_endpoint(&self) -> Endpoint {
         match self {
             ObfuscatorConfig::Udp2Tcp { endpoint } => Endpoint {
                 address: *endpoint,
                 protocol: TransportProtocol::Tcp,
             },
-            ObfuscatorConfig::Shadowsocks { endpoint } => Endpoint {
+            ObfuscatorConfig::Shadowsocks { endpoint, .. } => Endpoint {
                 address: *endpoint,
                 protocol: TransportProtocol::Udp,
             },
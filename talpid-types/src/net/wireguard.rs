@@ -6,6 +6,8 @@ use std::{
     cmp, fmt,
     hash::{Hash, Hasher},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    time::{Duration, SystemTime},
 };
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -94,6 +96,184 @@ pub struct TunnelOptions {
     /// Enable DAITA during tunnel config
     #[cfg(daita)]
     pub daita: bool,
+    /// Options for detecting that the tunnel has stopped working
+    pub connectivity_check: ConnectivityCheckOptions,
+}
+
+/// Options for the connectivity monitor, which decides when a WireGuard tunnel has stopped working
+/// and must be reconnected. The tunnel is probed if no traffic is received for `rx_timeout` after
+/// sending traffic, or if no traffic flows at all for `traffic_timeout`. If no traffic is received
+/// within `probe_timeout` of sending the first probe, the tunnel is considered broken.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConnectivityCheckOptions {
+    pub rx_timeout: Duration,
+    pub traffic_timeout: Duration,
+    pub probe_timeout: Duration,
+    /// Hosts to probe. If this is empty, ICMP requests are sent to the tunnel gateway.
+    pub probes: Vec<ConnectivityProbe>,
+}
+
+impl ConnectivityCheckOptions {
+    pub const RX_TIMEOUT_RANGE: (Duration, Duration) =
+        (Duration::from_secs(2), Duration::from_secs(60));
+    pub const TRAFFIC_TIMEOUT_RANGE: (Duration, Duration) =
+        (Duration::from_secs(10), Duration::from_secs(600));
+    pub const PROBE_TIMEOUT_RANGE: (Duration, Duration) =
+        (Duration::from_secs(5), Duration::from_secs(120));
+    pub const MAX_PROBES: usize = 4;
+
+    /// Returns an error if any timeout is out of bounds, or if there are too many probes.
+    pub fn validate(&self) -> Result<(), InvalidConnectivityCheckOptions> {
+        let in_range =
+            |value: Duration, (min, max): (Duration, Duration)| (min..=max).contains(&value);
+
+        if !in_range(self.rx_timeout, Self::RX_TIMEOUT_RANGE) {
+            return Err(InvalidConnectivityCheckOptions::RxTimeout);
+        }
+        if !in_range(self.traffic_timeout, Self::TRAFFIC_TIMEOUT_RANGE) {
+            return Err(InvalidConnectivityCheckOptions::TrafficTimeout);
+        }
+        if !in_range(self.probe_timeout, Self::PROBE_TIMEOUT_RANGE) {
+            return Err(InvalidConnectivityCheckOptions::ProbeTimeout);
+        }
+        if self.probes.len() > Self::MAX_PROBES {
+            return Err(InvalidConnectivityCheckOptions::TooManyProbes);
+        }
+        Ok(())
+    }
+}
+
+impl Default for ConnectivityCheckOptions {
+    fn default() -> Self {
+        Self {
+            rx_timeout: Duration::from_secs(5),
+            traffic_timeout: Duration::from_secs(120),
+            probe_timeout: Duration::from_secs(15),
+            probes: vec![],
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidConnectivityCheckOptions {
+    #[error("The RX timeout must be between 2 and 60 seconds")]
+    RxTimeout,
+    #[error("The traffic timeout must be between 10 and 600 seconds")]
+    TrafficTimeout,
+    #[error("The probe timeout must be between 5 and 120 seconds")]
+    ProbeTimeout,
+    #[error("At most 4 probes may be used")]
+    TooManyProbes,
+}
+
+/// A way of provoking traffic through the tunnel, used by the connectivity monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectivityProbe {
+    /// Send ICMP echo requests to the host.
+    Icmp(Ipv4Addr),
+    /// Open TCP connections to the host.
+    Tcp(SocketAddr),
+    /// Send HTTP requests to the host.
+    Http(SocketAddr),
+}
+
+impl fmt::Display for ConnectivityProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectivityProbe::Icmp(address) => write!(f, "icmp:{address}"),
+            ConnectivityProbe::Tcp(address) => write!(f, "tcp:{address}"),
+            ConnectivityProbe::Http(address) => write!(f, "http:{address}"),
+        }
+    }
+}
+
+/// Parses probes in the format printed by [`ConnectivityProbe`]'s `Display` implementation, e.g.
+/// `icmp:10.64.0.1`, `tcp:1.1.1.1:443`, or `http:[2606:4700::1111]:80`.
+impl FromStr for ConnectivityProbe {
+    type Err = InvalidConnectivityProbe;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, address) = s.split_once(':').ok_or(InvalidConnectivityProbe)?;
+        match kind.to_ascii_lowercase().as_str() {
+            "icmp" => address.parse().map(ConnectivityProbe::Icmp),
+            "tcp" => address.parse().map(ConnectivityProbe::Tcp),
+            "http" => address.parse().map(ConnectivityProbe::Http),
+            _ => return Err(InvalidConnectivityProbe),
+        }
+        .map_err(|_| InvalidConnectivityProbe)
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error(
+    "Invalid probe. Expected 'icmp:<IPv4 address>', 'tcp:<IP address>:<port>', or \
+     'http:<IP address>:<port>'"
+)]
+pub struct InvalidConnectivityProbe;
+
+/// State of the connectivity monitor of a running tunnel.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConnectivityStatus {
+    /// When traffic was last received through the tunnel, or `None` if nothing has been received.
+    pub last_rx: Option<SystemTime>,
+    /// When the monitor started probing the tunnel, or `None` if it is not probing.
+    pub probing_since: Option<SystemTime>,
+}
+
+/// Traffic statistics for a WireGuard tunnel, sampled at `sampled_at`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TunnelStats {
+    /// Statistics for the entry hop. Only set when multihop is used.
+    pub entry: Option<PeerStats>,
+    /// Statistics for the exit hop, or the only hop when multihop is not used.
+    pub exit: PeerStats,
+    pub sampled_at: SystemTime,
+    /// State of the connectivity monitor, if it is running.
+    pub connectivity: Option<ConnectivityStatus>,
+}
+
+impl TunnelStats {
+    /// Compute the transfer rates of each hop relative to an earlier sample.
+    pub fn set_rates_since(&mut self, previous: &TunnelStats) {
+        let Ok(elapsed) = self.sampled_at.duration_since(previous.sampled_at) else {
+            return;
+        };
+        if elapsed.is_zero() {
+            return;
+        }
+        let elapsed = elapsed.as_secs_f64();
+        self.exit.set_rates_since(&previous.exit, elapsed);
+        if let (Some(entry), Some(previous_entry)) = (&mut self.entry, &previous.entry) {
+            entry.set_rates_since(previous_entry, elapsed);
+        }
+    }
+}
+
+/// Traffic statistics for a single WireGuard peer.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PeerStats {
+    pub public_key: PublicKey,
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    /// Time of the most recent handshake with the peer, if any.
+    pub last_handshake: Option<SystemTime>,
+    /// Bytes sent per second since the previous sample, if known.
+    pub tx_rate: Option<f64>,
+    /// Bytes received per second since the previous sample, if known.
+    pub rx_rate: Option<f64>,
+}
+
+impl PeerStats {
+    fn set_rates_since(&mut self, previous: &PeerStats, elapsed_secs: f64) {
+        if self.public_key != previous.public_key {
+            return;
+        }
+        // The counters are reset if the tunnel is recreated. Report no traffic in that case.
+        self.tx_rate = Some(self.tx_bytes.saturating_sub(previous.tx_bytes) as f64 / elapsed_secs);
+        self.rx_rate = Some(self.rx_bytes.saturating_sub(previous.rx_bytes) as f64 / elapsed_secs);
+    }
 }
 
 /// Wireguard x25519 private key
@@ -273,6 +453,14 @@ impl PresharedKey {
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0
     }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.as_bytes())
+    }
+
+    pub fn from_base64(key: &str) -> Result<Self, InvalidKey> {
+        key_from_base64(key).map(|key: [u8; 32]| PresharedKey(Box::new(key)))
+    }
 }
 
 impl From<Box<[u8; 32]>> for PresharedKey {
@@ -281,6 +469,24 @@ impl From<Box<[u8; 32]>> for PresharedKey {
     }
 }
 
+impl Serialize for PresharedKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_key(self.as_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PresharedKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_key(deserializer).map(|key: [u8; 32]| PresharedKey(Box::new(key)))
+    }
+}
+
 impl fmt::Debug for PresharedKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", &STANDARD.encode(self.as_bytes()))
@@ -314,3 +520,102 @@ fn key_from_base64<K: From<[u8; 32]>>(key: &str) -> Result<K, InvalidKey> {
     key.copy_from_slice(&bytes);
     Ok(From::from(key))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_connectivity_probe() {
+        for probe in [
+            ConnectivityProbe::Icmp(Ipv4Addr::new(10, 64, 0, 1)),
+            ConnectivityProbe::Tcp("1.1.1.1:443".parse().unwrap()),
+            ConnectivityProbe::Http("[2606:4700::1111]:80".parse().unwrap()),
+        ] {
+            assert_eq!(probe.to_string().parse::<ConnectivityProbe>(), Ok(probe));
+        }
+        assert!("icmp:::1".parse::<ConnectivityProbe>().is_err());
+        assert!("tcp:1.1.1.1".parse::<ConnectivityProbe>().is_err());
+        assert!("udp:1.1.1.1:53".parse::<ConnectivityProbe>().is_err());
+    }
+
+    fn peer_stats(key: u8, tx_bytes: u64, rx_bytes: u64) -> PeerStats {
+        PeerStats {
+            public_key: PublicKey::from([key; 32]),
+            tx_bytes,
+            rx_bytes,
+            last_handshake: None,
+            tx_rate: None,
+            rx_rate: None,
+        }
+    }
+
+    fn tunnel_stats(entry: Option<PeerStats>, exit: PeerStats, secs: u64) -> TunnelStats {
+        TunnelStats {
+            entry,
+            exit,
+            sampled_at: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+            connectivity: None,
+        }
+    }
+
+    #[test]
+    fn test_set_rates_since() {
+        let previous = tunnel_stats(Some(peer_stats(1, 100, 200)), peer_stats(2, 1000, 2000), 10);
+        let mut stats = tunnel_stats(Some(peer_stats(1, 300, 600)), peer_stats(2, 1500, 3000), 12);
+        stats.set_rates_since(&previous);
+
+        let entry = stats.entry.unwrap();
+        assert_eq!(entry.tx_rate, Some(100.0));
+        assert_eq!(entry.rx_rate, Some(200.0));
+        assert_eq!(stats.exit.tx_rate, Some(250.0));
+        assert_eq!(stats.exit.rx_rate, Some(500.0));
+    }
+
+    /// Samples taken at the same time, or out of order, say nothing about the rates.
+    #[test]
+    fn test_set_rates_since_without_elapsed_time() {
+        let previous = tunnel_stats(None, peer_stats(1, 0, 0), 10);
+        for secs in [10, 9] {
+            let mut stats = tunnel_stats(None, peer_stats(1, 100, 100), secs);
+            stats.set_rates_since(&previous);
+            assert_eq!(stats.exit.tx_rate, None);
+            assert_eq!(stats.exit.rx_rate, None);
+        }
+    }
+
+    /// The counters start over when the tunnel is recreated, which must not result in a huge or
+    /// negative rate.
+    #[test]
+    fn test_set_rates_since_counter_reset() {
+        let previous = tunnel_stats(None, peer_stats(1, 1000, 1000), 10);
+        let mut stats = tunnel_stats(None, peer_stats(1, 10, 10), 11);
+        stats.set_rates_since(&previous);
+        assert_eq!(stats.exit.tx_rate, Some(0.0));
+        assert_eq!(stats.exit.rx_rate, Some(0.0));
+    }
+
+    /// Counters of different peers can not be compared.
+    #[test]
+    fn test_set_rates_since_other_peer() {
+        let previous = tunnel_stats(None, peer_stats(1, 0, 0), 10);
+        let mut stats = tunnel_stats(None, peer_stats(2, 100, 100), 11);
+        stats.set_rates_since(&previous);
+        assert_eq!(stats.exit.tx_rate, None);
+        assert_eq!(stats.exit.rx_rate, None);
+    }
+
+    #[test]
+    fn test_validate_connectivity_check_options() {
+        assert!(ConnectivityCheckOptions::default().validate().is_ok());
+
+        let options = ConnectivityCheckOptions {
+            probe_timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.validate(),
+            Err(InvalidConnectivityCheckOptions::ProbeTimeout)
+        );
+    }
+}
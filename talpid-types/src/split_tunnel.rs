@@ -1,7 +1,32 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(target_os = "windows")]
 use std::path::PathBuf;
 
+/// Determines which traffic is affected by split tunneling.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitTunnelMode {
+    /// Traffic from the split apps bypasses the tunnel.
+    #[default]
+    Exclude,
+    /// Only traffic from the split apps is sent through the tunnel. All other traffic bypasses
+    /// it.
+    Include,
+}
+
+impl fmt::Display for SplitTunnelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitTunnelMode::Exclude => f.write_str("exclude"),
+            SplitTunnelMode::Include => f.write_str("include"),
+        }
+    }
+}
+
 /// A process that is being excluded from the tunnel.
-#[derive(Debug, Clone)]
+#[cfg(target_os = "windows")]
+#[derive(Debug, Clone, Serialize)]
 pub struct ExcludedProcess {
     /// Process identifier.
     pub pid: u32,
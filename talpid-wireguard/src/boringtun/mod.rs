@@ -154,6 +154,9 @@ impl Tunnel for BoringTun {
                     Stats {
                         tx_bytes: peer.tx_bytes.unwrap_or_default(),
                         rx_bytes: peer.rx_bytes.unwrap_or_default(),
+                        last_handshake: peer.last_handshake_time_sec.map(|secs| {
+                            std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)
+                        }),
                     },
                 )
             },
@@ -37,6 +37,8 @@ pub struct Config {
     pub quantum_resistant: bool,
     /// Enable DAITA
     pub daita: bool,
+    /// Timeouts and probes used by the connectivity monitor
+    pub connectivity_check: wireguard::ConnectivityCheckOptions,
 }
 
 /// Configuration errors
@@ -106,6 +108,7 @@ impl Config {
             daita: wg_options.daita,
             #[cfg(not(daita))]
             daita: false,
+            connectivity_check: wg_options.connectivity_check.clone(),
         };
 
         for peer in config.peers_mut() {
@@ -1,10 +1,13 @@
 use std::{
     net::Ipv4Addr,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, Ordering},
     },
-    time::Duration,
+    time::{Duration, SystemTime},
+};
+use talpid_types::net::wireguard::{
+    ConnectivityCheckOptions, ConnectivityProbe, ConnectivityStatus,
 };
 use tokio::{sync::broadcast, time::Instant};
 
@@ -26,18 +29,21 @@ use pinger::Pinger;
 /// timeout. A connection is considered to be established the first time an increase in incoming
 /// traffic is observed.
 ///
-/// The connectivity monitor will start sending pings and start the countdown to `PING_TIMEOUT` in
+/// The connectivity monitor will start sending pings and start the countdown to the ping timeout in
 /// the following cases:
 /// - In case that we have observed a bump in the outgoing traffic but no corresponding incoming
-///   traffic for longer than `BYTES_RX_TIMEOUT`, then the monitor will start pinging.
+///   traffic for longer than the RX timeout, then the monitor will start pinging.
 /// - In case that no increase in outgoing or incoming traffic has been observed for longer than
-///   `TRAFFIC_TIMEOUT`, then the monitor will start pinging as well.
+///   the traffic timeout, then the monitor will start pinging as well.
 ///
 /// Once a connection established, a connection is only considered broken once the connectivity
-/// monitor has started pinging and no traffic has been received for a duration of `PING_TIMEOUT`.
+/// monitor has started pinging and no traffic has been received for the duration of the ping
+/// timeout. The timeouts are given by [Timeouts], and default to `BYTES_RX_TIMEOUT`,
+/// `TRAFFIC_TIMEOUT`, and `PING_TIMEOUT`.
 pub struct Check {
     conn_state: ConnState,
     ping_state: PingState,
+    timeouts: Timeouts,
     cancel_receiver: CancelReceiver,
     retry_attempt: u32,
 }
@@ -92,9 +98,43 @@ impl CancelToken {
     }
 }
 
+/// Timeouts used by [Check] to decide when to send pings, and when to consider the tunnel broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Timeouts {
+    /// See `BYTES_RX_TIMEOUT`.
+    rx: Duration,
+    /// See `TRAFFIC_TIMEOUT`.
+    traffic: Duration,
+    /// See `PING_TIMEOUT`.
+    ping: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            rx: BYTES_RX_TIMEOUT,
+            traffic: TRAFFIC_TIMEOUT,
+            ping: PING_TIMEOUT,
+        }
+    }
+}
+
+impl From<&ConnectivityCheckOptions> for Timeouts {
+    fn from(options: &ConnectivityCheckOptions) -> Self {
+        Timeouts {
+            rx: options.rx_timeout,
+            traffic: options.traffic_timeout,
+            ping: options.probe_timeout,
+        }
+    }
+}
+
 impl Check {
+    /// Create a new [Check]. Pings are sent to `addr`, unless other hosts are specified in
+    /// `options`.
     pub fn new(
         addr: Ipv4Addr,
+        options: &ConnectivityCheckOptions,
         #[cfg(any(target_os = "macos", target_os = "linux"))] interface: String,
         retry_attempt: u32,
         cancel_receiver: CancelReceiver,
@@ -103,14 +143,22 @@ impl Check {
             conn_state: ConnState::new(Instant::now(), Default::default()),
             ping_state: PingState::new(
                 addr,
+                &options.probes,
                 #[cfg(any(target_os = "macos", target_os = "linux"))]
                 interface,
             )?,
+            timeouts: Timeouts::from(options),
             retry_attempt,
             cancel_receiver,
         })
     }
 
+    /// Returns a handle to the current state of the monitor, which is updated as long as the
+    /// [Check] is running.
+    pub fn status(&self) -> Arc<Mutex<ConnectivityStatus>> {
+        self.ping_state.status.clone()
+    }
+
     #[cfg(test)]
     /// Create a new [Check] with a custom initial state.
     pub(super) fn mock(conn_state: ConnState, ping_state: PingState) -> (Self, CancelToken) {
@@ -119,6 +167,7 @@ impl Check {
             Check {
                 conn_state,
                 ping_state,
+                timeouts: Timeouts::default(),
                 retry_attempt: 0,
                 cancel_receiver,
             },
@@ -135,7 +184,7 @@ impl Check {
         // Send initial ping to prod WireGuard into connecting.
         self.ping_state
             .pinger
-            .send_icmp()
+            .send_probe()
             .await
             .map_err(Error::PingError)?;
         self.establish_connectivity_inner(
@@ -174,6 +223,7 @@ impl Check {
                 if Self::check_connectivity_interval(
                     &mut self.conn_state,
                     &mut self.ping_state,
+                    &self.timeouts,
                     Instant::now(),
                     check_timeout,
                     tunnel_handle,
@@ -229,8 +279,9 @@ impl Check {
         Self::check_connectivity_interval(
             &mut self.conn_state,
             &mut self.ping_state,
+            &self.timeouts,
             now,
-            PING_TIMEOUT,
+            self.timeouts.ping,
             tunnel_handle,
         )
         .await
@@ -240,6 +291,7 @@ impl Check {
     async fn check_connectivity_interval(
         conn_state: &mut ConnState,
         ping_state: &mut PingState,
+        timeouts: &Timeouts,
         now: Instant,
         timeout: Duration,
         tunnel_handle: &dyn Tunnel,
@@ -252,10 +304,11 @@ impl Check {
             Some(new_stats) => {
                 if conn_state.update(now, new_stats) {
                     ping_state.reset().await;
+                    ping_state.status.lock().unwrap().last_rx = Some(SystemTime::now());
                     return Ok(true);
                 }
 
-                Self::maybe_send_ping(conn_state, ping_state, now).await?;
+                Self::maybe_send_ping(conn_state, ping_state, timeouts, now).await?;
                 Ok(!ping_state.ping_timed_out(timeout) && conn_state.connected())
             }
         }
@@ -276,12 +329,13 @@ impl Check {
     async fn maybe_send_ping(
         conn_state: &mut ConnState,
         ping_state: &mut PingState,
+        timeouts: &Timeouts,
         now: Instant,
     ) -> Result<(), Error> {
         // Only send out a ping if we haven't received a byte in a while or no traffic has flowed
-        // in the last 2 minutes, but if a ping already has been sent out, only send one out every
+        // in a longer while, but if a ping already has been sent out, only send one out every
         // 3 seconds.
-        if (conn_state.rx_timed_out() || conn_state.traffic_timed_out())
+        if (conn_state.rx_timed_out(timeouts) || conn_state.traffic_timed_out(timeouts))
             && ping_state
                 .initial_ping_timestamp
                 .map(|initial_ping_timestamp| {
@@ -291,11 +345,12 @@ impl Check {
         {
             ping_state
                 .pinger
-                .send_icmp()
+                .send_probe()
                 .await
                 .map_err(Error::PingError)?;
             if ping_state.initial_ping_timestamp.is_none() {
                 ping_state.initial_ping_timestamp = Some(now);
+                ping_state.status.lock().unwrap().probing_since = Some(SystemTime::now());
             }
             ping_state.num_pings_sent += 1;
         }
@@ -307,15 +362,19 @@ pub(super) struct PingState {
     initial_ping_timestamp: Option<Instant>,
     num_pings_sent: u32,
     pinger: Box<dyn Pinger>,
+    /// State exposed for diagnostics
+    status: Arc<Mutex<ConnectivityStatus>>,
 }
 
 impl PingState {
     pub(super) fn new(
         addr: Ipv4Addr,
+        probes: &[ConnectivityProbe],
         #[cfg(any(target_os = "macos", target_os = "linux"))] interface: String,
     ) -> Result<Self, Error> {
         let pinger = pinger::new_pinger(
             addr,
+            probes,
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             interface,
         )
@@ -329,6 +388,7 @@ impl PingState {
             initial_ping_timestamp: None,
             num_pings_sent: 0,
             pinger,
+            status: Default::default(),
         }
     }
 
@@ -342,6 +402,7 @@ impl PingState {
     async fn reset(&mut self) {
         self.initial_ping_timestamp = None;
         self.num_pings_sent = 0;
+        self.status.lock().unwrap().probing_since = None;
         self.pinger.reset().await;
     }
 }
@@ -437,9 +498,9 @@ impl ConnState {
     }
 
     // check if last time data was received is too long ago
-    pub fn rx_timed_out(&self) -> bool {
+    pub fn rx_timed_out(&self, timeouts: &Timeouts) -> bool {
         match self {
-            ConnState::Connecting { start, .. } => start.elapsed() >= BYTES_RX_TIMEOUT,
+            ConnState::Connecting { start, .. } => start.elapsed() >= timeouts.rx,
             ConnState::Connected {
                 rx_timestamp,
                 tx_timestamp,
@@ -447,23 +508,23 @@ impl ConnState {
             } => {
                 // if last sent bytes were sent after or at the same time as last received bytes
                 tx_timestamp >= rx_timestamp &&
-                    // and the response hasn't been seen for BYTES_RX_TIMEOUT
-                    rx_timestamp.elapsed() >= BYTES_RX_TIMEOUT
+                    // and the response hasn't been seen for the RX timeout
+                    rx_timestamp.elapsed() >= timeouts.rx
             }
         }
     }
 
     // check if no bytes have been sent or received in a while
-    pub fn traffic_timed_out(&self) -> bool {
+    pub fn traffic_timed_out(&self, timeouts: &Timeouts) -> bool {
         match self {
-            ConnState::Connecting { .. } => self.rx_timed_out(),
+            ConnState::Connecting { .. } => self.rx_timed_out(timeouts),
             ConnState::Connected {
                 rx_timestamp,
                 tx_timestamp,
                 ..
             } => {
-                rx_timestamp.elapsed() >= TRAFFIC_TIMEOUT
-                    || tx_timestamp.elapsed() >= TRAFFIC_TIMEOUT
+                rx_timestamp.elapsed() >= timeouts.traffic
+                    || tx_timestamp.elapsed() >= timeouts.traffic
             }
         }
     }
@@ -487,8 +548,8 @@ mod test {
         let conn_state = ConnState::new(now, Default::default());
 
         assert!(!conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(!conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connecting will timeout after not receiving any traffic after
@@ -499,8 +560,8 @@ mod test {
         let conn_state = ConnState::new(now, Default::default());
 
         assert!(!conn_state.connected());
-        assert!(conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connecting correctly transitions into ConnState::Connected if traffic is
@@ -515,13 +576,14 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(Instant::now(), stats);
 
         assert!(conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(!conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connected correctly times out after TRAFFIC_TIMEOUT when no traffic is
@@ -540,13 +602,14 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(connect_time, stats);
 
         assert!(conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connected correctly times out after BYTES_RX_TIMEOUT when no incoming
@@ -564,6 +627,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(start, stats);
@@ -575,13 +639,34 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 1,
+                last_handshake: None,
             },
         );
         conn_state.update(update_time, stats);
 
         assert!(conn_state.connected());
-        assert!(conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(!conn_state.traffic_timed_out(&Timeouts::default()));
+    }
+
+    /// Test if ConnState respects custom timeouts
+    #[test]
+    fn test_conn_state_custom_timeouts() {
+        let timeouts = Timeouts {
+            rx: Duration::from_secs(2),
+            traffic: Duration::from_secs(10),
+            ping: PING_TIMEOUT,
+        };
+        let start = Instant::now().checked_sub(Duration::from_secs(3)).unwrap();
+        let conn_state = ConnState::new(start, Default::default());
+
+        assert!(conn_state.rx_timed_out(&timeouts));
+        assert!(!conn_state.rx_timed_out(&Timeouts::default()));
+
+        let conn_state =
+            connected_state(Instant::now().checked_sub(Duration::from_secs(10)).unwrap());
+        assert!(conn_state.traffic_timed_out(&timeouts));
+        assert!(!conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     #[tokio::test]
@@ -599,9 +684,14 @@ mod test {
         // Mock the state - connectivity has been established
         checker.conn_state = connected_state(start);
         // A ping was sent to verify connectivity
-        Check::maybe_send_ping(&mut checker.conn_state, &mut checker.ping_state, start)
-            .await
-            .unwrap();
+        Check::maybe_send_ping(
+            &mut checker.conn_state,
+            &mut checker.ping_state,
+            &Timeouts::default(),
+            start,
+        )
+        .await
+        .unwrap();
         assert!(
             !checker
                 .check_connectivity(now, tunnel.as_ref())
@@ -671,6 +761,7 @@ mod test {
                     Stats {
                         tx_bytes: 0,
                         rx_bytes: 0,
+                        last_handshake: None,
                     },
                 );
                 MockTunnel::new(move || Ok(tunnel_stats.clone())).boxed()
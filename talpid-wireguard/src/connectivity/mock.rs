@@ -35,6 +35,7 @@ pub fn connected_state(timestamp: Instant) -> ConnState {
         Stats {
             tx_bytes: 0,
             rx_bytes: 0,
+            last_handshake: None,
         },
     );
     ConnState::Connected {
@@ -65,6 +66,7 @@ impl MockTunnel {
             Stats {
                 tx_bytes: 0,
                 rx_bytes: 0,
+                last_handshake: None,
             },
         );
         let peers = std::sync::Mutex::new(map);
@@ -89,6 +91,7 @@ impl MockTunnel {
                     Stats {
                         tx_bytes: 0,
                         rx_bytes: 0,
+                        last_handshake: None,
                     },
                 );
                 Ok(map)
@@ -129,7 +132,7 @@ impl Tunnel for MockTunnel {
 
 #[async_trait::async_trait]
 impl Pinger for MockPinger {
-    async fn send_icmp(&mut self) -> Result<(), pinger::Error> {
+    async fn send_probe(&mut self) -> Result<(), pinger::Error> {
         if let Some(callback) = self.on_send_ping.as_mut() {
             (callback)();
         }
@@ -18,6 +18,10 @@ pub enum Error {
     #[error("Failed to open ICMP socket")]
     Open(#[source] io::Error),
 
+    /// Failed to open TCP socket
+    #[error("Failed to open TCP socket")]
+    OpenTcp(#[source] io::Error),
+
     /// Failed to read from raw socket
     #[error("Failed to read ICMP socket")]
     Read(#[source] io::Error),
@@ -77,12 +81,8 @@ impl Pinger {
         .map_err(Error::Open)?;
         sock.set_nonblocking(true).map_err(Error::Open)?;
 
-        #[cfg(target_os = "linux")]
-        sock.bind_device(Some(interface_name.as_bytes()))
-            .map_err(Error::SocketOp)?;
-
-        #[cfg(target_os = "macos")]
-        Self::set_device_index(&sock, &interface_name)?;
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        super::bind_to_interface(&sock, &interface_name, false)?;
 
         let sock =
             UdpSocket::from_std(std::net::UdpSocket::from(sock)).map_err(Error::ConvertSocket)?;
@@ -95,18 +95,6 @@ impl Pinger {
         })
     }
 
-    #[cfg(target_os = "macos")]
-    fn set_device_index(socket: &Socket, interface_name: &str) -> Result<()> {
-        let index = nix::net::if_::if_nametoindex(interface_name).map_err(Error::DeviceIdx)?;
-        // Asserting that `index` is non-zero since otherwise `if_nametoindex` would have return
-        // an error
-        socket
-            .bind_device_by_index_v4(std::num::NonZeroU32::new(index))
-            .map_err(Error::BindSocketByDevice)?;
-
-        Ok(())
-    }
-
     async fn send_ping_request(&mut self, message: &[u8], destination: SocketAddr) -> Result<()> {
         let mut tries = 0;
         loop {
@@ -145,7 +133,7 @@ fn should_retry_send(_err: &io::Error) -> bool {
 
 #[async_trait::async_trait]
 impl super::Pinger for Pinger {
-    async fn send_icmp(&mut self) -> Result<()> {
+    async fn send_probe(&mut self) -> Result<()> {
         let mut message = [0u8; 50];
         self.construct_icmpv4_packet(&mut message)?;
         self.send_ping_request(&message, self.addr).await
@@ -1,24 +1,103 @@
 mod icmp;
+mod tcp;
 
 pub use icmp::Error;
 
-/// Trait for sending ICMP requests to get some traffic from a remote server
+use std::net::Ipv4Addr;
+use talpid_types::net::wireguard::ConnectivityProbe;
+
+/// Trait for sending requests to get some traffic from a remote server
 #[async_trait::async_trait]
 pub trait Pinger: Send {
-    /// Sends an ICMP packet
-    async fn send_icmp(&mut self) -> Result<(), Error>;
+    /// Sends an ICMP packet, or otherwise provokes a response from the remote server
+    async fn send_probe(&mut self) -> Result<(), Error>;
     /// Clears all resources used by the pinger.
     async fn reset(&mut self) {}
 }
 
-/// Create a new pinger
+/// Create a new pinger. If `probes` is empty, ICMP packets are sent to `gateway`.
 pub fn new_pinger(
-    addr: std::net::Ipv4Addr,
+    gateway: Ipv4Addr,
+    probes: &[ConnectivityProbe],
     #[cfg(any(target_os = "linux", target_os = "macos"))] interface_name: String,
 ) -> Result<Box<dyn Pinger>, Error> {
-    Ok(Box::new(icmp::Pinger::new(
-        addr,
-        #[cfg(any(target_os = "linux", target_os = "macos"))]
-        interface_name,
-    )?))
+    if probes.is_empty() {
+        return Ok(Box::new(icmp::Pinger::new(
+            gateway,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            interface_name,
+        )?));
+    }
+
+    let mut pingers: Vec<Box<dyn Pinger>> = Vec::with_capacity(probes.len());
+    for probe in probes {
+        pingers.push(match *probe {
+            ConnectivityProbe::Icmp(addr) => Box::new(icmp::Pinger::new(
+                addr,
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                interface_name.clone(),
+            )?),
+            ConnectivityProbe::Tcp(addr) => Box::new(tcp::Prober::new(
+                addr,
+                false,
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                interface_name.clone(),
+            )),
+            ConnectivityProbe::Http(addr) => Box::new(tcp::Prober::new(
+                addr,
+                true,
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                interface_name.clone(),
+            )),
+        });
+    }
+    Ok(Box::new(MultiPinger(pingers)))
+}
+
+/// Sends probes to several hosts at once
+struct MultiPinger(Vec<Box<dyn Pinger>>);
+
+#[async_trait::async_trait]
+impl Pinger for MultiPinger {
+    async fn send_probe(&mut self) -> Result<(), Error> {
+        for pinger in &mut self.0 {
+            pinger.send_probe().await?;
+        }
+        Ok(())
+    }
+
+    async fn reset(&mut self) {
+        for pinger in &mut self.0 {
+            pinger.reset().await;
+        }
+    }
+}
+
+/// Bind `socket` to the tunnel interface, so that probes are never sent outside the tunnel.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn bind_to_interface(
+    socket: &socket2::Socket,
+    interface_name: &str,
+    #[cfg_attr(target_os = "linux", allow(unused_variables))] ipv6: bool,
+) -> Result<(), Error> {
+    #[cfg(target_os = "linux")]
+    socket
+        .bind_device(Some(interface_name.as_bytes()))
+        .map_err(Error::SocketOp)?;
+
+    #[cfg(target_os = "macos")]
+    {
+        let index = nix::net::if_::if_nametoindex(interface_name).map_err(Error::DeviceIdx)?;
+        // Asserting that `index` is non-zero since otherwise `if_nametoindex` would have return
+        // an error
+        let index = std::num::NonZeroU32::new(index);
+        if ipv6 {
+            socket.bind_device_by_index_v6(index)
+        } else {
+            socket.bind_device_by_index_v4(index)
+        }
+        .map_err(Error::BindSocketByDevice)?;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,90 @@
+//! Probes that open TCP connections through the tunnel, and optionally send an HTTP request, for
+//! networks where ICMP traffic is dropped or deprioritized.
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{io, net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpSocket,
+    task::JoinSet,
+};
+
+use super::Error;
+
+/// Time after which a single connection attempt is abandoned. Whether the tunnel works is decided
+/// by the connectivity monitor, so this only limits how long sockets are kept open.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Prober {
+    addr: SocketAddr,
+    /// Send an HTTP request once connected
+    http: bool,
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    interface_name: String,
+    probes: JoinSet<()>,
+}
+
+impl Prober {
+    /// Creates a new `Prober`. No connections are made until a probe is sent.
+    pub fn new(
+        addr: SocketAddr,
+        http: bool,
+        #[cfg(any(target_os = "linux", target_os = "macos"))] interface_name: String,
+    ) -> Self {
+        Self {
+            addr,
+            http,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            interface_name,
+            probes: JoinSet::new(),
+        }
+    }
+
+    fn open_socket(&self) -> Result<TcpSocket, Error> {
+        let sock = Socket::new(
+            Domain::for_address(self.addr),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )
+        .map_err(Error::OpenTcp)?;
+        sock.set_nonblocking(true).map_err(Error::OpenTcp)?;
+
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        super::bind_to_interface(&sock, &self.interface_name, self.addr.is_ipv6())?;
+
+        Ok(TcpSocket::from_std_stream(std::net::TcpStream::from(sock)))
+    }
+}
+
+#[async_trait::async_trait]
+impl super::Pinger for Prober {
+    async fn send_probe(&mut self) -> Result<(), Error> {
+        // Forget about probes that have already finished
+        while self.probes.try_join_next().is_some() {}
+
+        let socket = self.open_socket()?;
+        let addr = self.addr;
+        let request = self.http.then(|| {
+            format!("HEAD / HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n").into_bytes()
+        });
+
+        self.probes.spawn(async move {
+            let probe = async {
+                let mut stream = socket.connect(addr).await?;
+                if let Some(request) = request {
+                    stream.write_all(&request).await?;
+                    stream.read_u8().await?;
+                }
+                io::Result::Ok(())
+            };
+            if let Ok(Err(error)) = tokio::time::timeout(CONNECT_TIMEOUT, probe).await {
+                log::trace!("Connectivity probe to {addr} failed: {error}");
+            }
+        });
+        Ok(())
+    }
+
+    async fn reset(&mut self) {
+        self.probes.abort_all();
+    }
+}
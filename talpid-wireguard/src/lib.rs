@@ -52,6 +52,8 @@ mod mtu_detection;
 
 type TunnelType = Box<dyn Tunnel>;
 
+pub use stats::TunnelStatsHandle;
+
 type Result<T> = std::result::Result<T, Error>;
 
 /// Errors that can happen in the Wireguard tunnel monitor.
@@ -138,6 +140,7 @@ pub struct WireguardMonitor {
     close_msg_receiver: sync_mpsc::Receiver<CloseMsg>,
     pinger_stop_sender: connectivity::CancelToken,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
+    stats_handle: TunnelStatsHandle,
 }
 
 #[cfg(not(target_os = "android"))]
@@ -213,6 +216,7 @@ impl WireguardMonitor {
         let (cancel_token, cancel_receiver) = connectivity::CancelToken::new();
         let mut connectivity_monitor = connectivity::Check::new(
             gateway,
+            &config.connectivity_check,
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             iface_name.clone(),
             args.retry_attempt,
@@ -220,13 +224,20 @@ impl WireguardMonitor {
         )
         .map_err(Error::ConnectivityMonitorError)?;
 
+        let tunnel = Arc::new(AsyncMutex::new(Some(tunnel)));
+        let stats_handle = TunnelStatsHandle::new(
+            Arc::downgrade(&tunnel),
+            &params.connection,
+            connectivity_monitor.status(),
+        );
         let monitor = WireguardMonitor {
             runtime: args.runtime.clone(),
-            tunnel: Arc::new(AsyncMutex::new(Some(tunnel))),
+            tunnel,
             event_hook: args.event_hook.clone(),
             close_msg_receiver: close_obfs_listener,
             pinger_stop_sender: cancel_token,
             obfuscator,
+            stats_handle,
         };
 
         let mut event_hook = args.event_hook.clone();
@@ -432,6 +443,7 @@ impl WireguardMonitor {
         #[allow(unused_mut)]
         let mut connectivity_monitor = connectivity::Check::new(
             config.ipv4_gateway,
+            &config.connectivity_check,
             args.retry_attempt,
             cancel_receiver.clone(),
         )
@@ -465,6 +477,11 @@ impl WireguardMonitor {
 
         let iface_name = tunnel.get_interface_name();
         let tunnel = Arc::new(AsyncMutex::new(Some(tunnel)));
+        let stats_handle = TunnelStatsHandle::new(
+            Arc::downgrade(&tunnel),
+            &params.connection,
+            connectivity_monitor.status(),
+        );
         let mut event_hook = args.event_hook;
         let monitor = WireguardMonitor {
             runtime: args.runtime.clone(),
@@ -473,6 +490,7 @@ impl WireguardMonitor {
             close_msg_receiver: close_obfs_listener,
             pinger_stop_sender: cancel_token,
             obfuscator: Arc::new(AsyncMutex::new(obfuscator)),
+            stats_handle,
         };
 
         let moved_close_obfs_sender = close_obfs_sender.clone();
@@ -790,6 +808,11 @@ impl WireguardMonitor {
         }
     }
 
+    /// Returns a handle for reading the traffic statistics of the tunnel.
+    pub fn stats_handle(&self) -> TunnelStatsHandle {
+        self.stats_handle.clone()
+    }
+
     /// Blocks the current thread until tunnel disconnects
     pub fn wait(mut self) -> Result<()> {
         let wait_result = match self.close_msg_receiver.recv() {
@@ -17,6 +17,9 @@ use tunnel_obfuscation::{
     Settings as ObfuscationSettings, create_obfuscator, quic, shadowsocks, udp2tcp,
 };
 
+/// Port that Mullvad relays accept WireGuard traffic on from their obfuscation servers
+const RELAY_WIREGUARD_PORT: u16 = 51820;
+
 /// Begin running obfuscation machine, if configured. This function will patch `config`'s endpoint
 /// to point to an endpoint on localhost
 pub async fn apply_obfuscation_config(
@@ -98,13 +101,19 @@ fn settings_from_config(
             #[cfg(target_os = "linux")]
             fwmark,
         }),
-        ObfuscatorConfig::Shadowsocks { endpoint } => {
+        ObfuscatorConfig::Shadowsocks {
+            endpoint,
+            password,
+            wireguard_port,
+        } => {
+            let wireguard_port = wireguard_port.unwrap_or(RELAY_WIREGUARD_PORT);
             ObfuscationSettings::Shadowsocks(shadowsocks::Settings {
                 shadowsocks_endpoint: *endpoint,
+                password: password.clone(),
                 wireguard_endpoint: if endpoint.is_ipv4() {
-                    SocketAddr::from((Ipv4Addr::LOCALHOST, 51820))
+                    SocketAddr::from((Ipv4Addr::LOCALHOST, wireguard_port))
                 } else {
-                    SocketAddr::from((Ipv6Addr::LOCALHOST, 51820))
+                    SocketAddr::from((Ipv6Addr::LOCALHOST, wireguard_port))
                 },
                 #[cfg(target_os = "linux")]
                 fwmark,
@@ -114,8 +123,12 @@ fn settings_from_config(
             hostname,
             endpoint,
             auth_token,
+            wireguard_port,
         } => {
-            let wireguard_endpoint = SocketAddr::from((Ipv4Addr::LOCALHOST, 51820));
+            let wireguard_endpoint = SocketAddr::from((
+                Ipv4Addr::LOCALHOST,
+                wireguard_port.unwrap_or(RELAY_WIREGUARD_PORT),
+            ));
             let settings = quic::Settings::new(
                 *endpoint,
                 hostname.to_owned(),
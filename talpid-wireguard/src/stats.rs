@@ -1,9 +1,89 @@
+use crate::TunnelType;
+use std::{
+    sync::{Arc, Mutex, Weak},
+    time::SystemTime,
+};
+use talpid_types::net::wireguard::{
+    ConnectionConfig, ConnectivityStatus, PeerStats, PublicKey, TunnelStats,
+};
+use tokio::sync::Mutex as AsyncMutex;
+
 /// Contains bytes sent and received through a tunnel
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Stats {
     pub tx_bytes: u64,
     pub rx_bytes: u64,
+    /// Time of the most recent handshake, or `None` if no handshake has been completed.
+    pub last_handshake: Option<SystemTime>,
 }
 
 /// A map from peer pubkeys to peer stats.
 pub type StatsMap = std::collections::HashMap<[u8; 32], Stats>;
+
+/// Handle for reading the traffic statistics of a running tunnel.
+///
+/// The handle does not keep the tunnel alive.
+#[derive(Clone)]
+pub struct TunnelStatsHandle {
+    tunnel: Weak<AsyncMutex<Option<TunnelType>>>,
+    entry_peer: PublicKey,
+    exit_peer: Option<PublicKey>,
+    connectivity: Arc<Mutex<ConnectivityStatus>>,
+}
+
+impl TunnelStatsHandle {
+    pub(crate) fn new(
+        tunnel: Weak<AsyncMutex<Option<TunnelType>>>,
+        connection: &ConnectionConfig,
+        connectivity: Arc<Mutex<ConnectivityStatus>>,
+    ) -> Self {
+        Self {
+            tunnel,
+            entry_peer: connection.peer.public_key.clone(),
+            exit_peer: connection
+                .exit_peer
+                .as_ref()
+                .map(|peer| peer.public_key.clone()),
+            connectivity,
+        }
+    }
+
+    /// Returns the current statistics of each hop, or `None` if the tunnel is down or the stats
+    /// could not be obtained.
+    pub async fn get(&self) -> Option<TunnelStats> {
+        let tunnel = self.tunnel.upgrade()?;
+        let stats = {
+            let tunnel = tunnel.lock().await;
+            tunnel.as_ref()?.get_tunnel_stats().await.ok()?
+        };
+        let sampled_at = SystemTime::now();
+        let connectivity = Some(self.connectivity.lock().unwrap().clone());
+
+        let peer_stats = |public_key: &PublicKey| {
+            let stats = stats.get(public_key.as_bytes())?;
+            Some(PeerStats {
+                public_key: public_key.clone(),
+                tx_bytes: stats.tx_bytes,
+                rx_bytes: stats.rx_bytes,
+                last_handshake: stats.last_handshake,
+                tx_rate: None,
+                rx_rate: None,
+            })
+        };
+
+        match &self.exit_peer {
+            Some(exit_peer) => Some(TunnelStats {
+                entry: peer_stats(&self.entry_peer),
+                exit: peer_stats(exit_peer)?,
+                sampled_at,
+                connectivity,
+            }),
+            None => Some(TunnelStats {
+                entry: None,
+                exit: peer_stats(&self.entry_peer)?,
+                sampled_at,
+                connectivity,
+            }),
+        }
+    }
+}
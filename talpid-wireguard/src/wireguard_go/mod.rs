@@ -679,10 +679,15 @@ impl WgGoTunnel {
         Ok(())
     }
     async fn ensure_tunnel_is_running(&self) -> Result<()> {
-        let addr = self.handle().config.ipv4_gateway;
+        let config = &self.handle().config;
         let cancel_receiver = self.handle().cancel_receiver.clone();
-        let mut check = connectivity::Check::new(addr, 0, cancel_receiver)
-            .map_err(|err| TunnelError::RecoverableStartWireguardError(Box::new(err)))?;
+        let mut check = connectivity::Check::new(
+            config.ipv4_gateway,
+            &config.connectivity_check,
+            0,
+            cancel_receiver,
+        )
+        .map_err(|err| TunnelError::RecoverableStartWireguardError(Box::new(err)))?;
 
         // TODO: retry attempt?
 
@@ -758,6 +763,7 @@ impl Tunnel for WgGoTunnel {
 
 mod stats {
     use super::{Stats, StatsMap};
+    use std::time::{Duration, SystemTime};
 
     #[derive(thiserror::Error, Debug, PartialEq)]
     pub enum Error {
@@ -775,6 +781,8 @@ mod stats {
             let mut peer = None;
             let mut tx_bytes = None;
             let mut rx_bytes = None;
+            let mut handshake_sec = 0;
+            let mut handshake_nsec = 0;
 
             // parts iterates over keys and values
             let parts = config.split('\n').filter_map(|line| {
@@ -793,6 +801,20 @@ mod stats {
                         peer = Some(buffer);
                         tx_bytes = None;
                         rx_bytes = None;
+                        handshake_sec = 0;
+                        handshake_nsec = 0;
+                    }
+                    "last_handshake_time_sec" => {
+                        handshake_sec = value
+                            .trim()
+                            .parse()
+                            .map_err(|err| Error::IntParse(value.to_string(), err))?;
+                    }
+                    "last_handshake_time_nsec" => {
+                        handshake_nsec = value
+                            .trim()
+                            .parse()
+                            .map_err(|err| Error::IntParse(value.to_string(), err))?;
                     }
                     "rx_bytes" => {
                         rx_bytes = Some(
@@ -817,11 +839,19 @@ mod stats {
                 if let (Some(peer_val), Some(tx_bytes_val), Some(rx_bytes_val)) =
                     (peer, tx_bytes, rx_bytes)
                 {
+                    // A zero timestamp means that no handshake has been completed
+                    let last_handshake = (handshake_sec != 0 || handshake_nsec != 0)
+                        .then(|| {
+                            SystemTime::UNIX_EPOCH
+                                .checked_add(Duration::new(handshake_sec, handshake_nsec))
+                        })
+                        .flatten();
                     map.insert(
                         peer_val,
                         Self {
                             tx_bytes: tx_bytes_val,
                             rx_bytes: rx_bytes_val,
+                            last_handshake,
                         },
                     );
                     peer = None;
@@ -836,6 +866,7 @@ mod stats {
     #[cfg(test)]
     mod test {
         use super::super::stats::{Error, Stats};
+        use std::time::{Duration, UNIX_EPOCH};
 
         #[test]
         fn test_parsing() {
@@ -848,6 +879,10 @@ mod stats {
             assert_eq!(actual_keys, [pubkey]);
             assert_eq!(stats[&pubkey].rx_bytes, 2396);
             assert_eq!(stats[&pubkey].tx_bytes, 2740);
+            assert_eq!(
+                stats[&pubkey].last_handshake,
+                Some(UNIX_EPOCH + Duration::new(1578420649, 369416131))
+            );
         }
 
         #[test]
@@ -1,5 +1,7 @@
 use super::wg_message::{DeviceMessage, DeviceNla, PeerNla};
 use crate::stats::{Stats, StatsMap};
+use nix::sys::time::TimeValLike;
+use std::time::{Duration, SystemTime};
 
 impl Stats {
     pub fn parse_device_message(message: &DeviceMessage) -> StatsMap {
@@ -10,6 +12,7 @@ impl Stats {
                 for msg in peers {
                     let mut tx_bytes = 0;
                     let mut rx_bytes = 0;
+                    let mut last_handshake = None;
                     let mut pub_key = None;
 
                     for nla in &msg.0 {
@@ -17,11 +20,24 @@ impl Stats {
                             PeerNla::TxBytes(bytes) => tx_bytes = *bytes,
                             PeerNla::RxBytes(bytes) => rx_bytes = *bytes,
                             PeerNla::PublicKey(key) => pub_key = Some(*key),
+                            // A zero timestamp means that no handshake has been completed
+                            PeerNla::LastHandshakeTime(time) if time.num_nanoseconds() > 0 => {
+                                last_handshake = SystemTime::UNIX_EPOCH.checked_add(
+                                    Duration::from_nanos(time.num_nanoseconds() as u64),
+                                );
+                            }
                             _ => continue,
                         }
                     }
                     if let Some(key) = pub_key {
-                        map.insert(key, Stats { tx_bytes, rx_bytes });
+                        map.insert(
+                            key,
+                            Stats {
+                                tx_bytes,
+                                rx_bytes,
+                                last_handshake,
+                            },
+                        );
                     }
                 }
             }
@@ -1049,6 +1049,18 @@ unsafe fn deserialize_config(
     Ok((interface, peers))
 }
 
+/// Convert a Windows `FILETIME` (100-nanosecond intervals since 1601-01-01) to a `SystemTime`.
+/// Returns `None` for zero, which WireGuardNT uses to signal that no handshake has been completed.
+fn filetime_to_system_time(filetime: u64) -> Option<std::time::SystemTime> {
+    /// Number of 100-nanosecond intervals between 1601-01-01 and 1970-01-01.
+    const UNIX_EPOCH_AS_FILETIME: u64 = 116_444_736_000_000_000;
+
+    let since_unix_epoch = filetime.checked_sub(UNIX_EPOCH_AS_FILETIME)?;
+    std::time::SystemTime::UNIX_EPOCH.checked_add(std::time::Duration::from_nanos(
+        since_unix_epoch.saturating_mul(100),
+    ))
+}
+
 #[async_trait::async_trait]
 impl Tunnel for WgNtTunnel {
     fn get_interface_name(&self) -> String {
@@ -1077,6 +1089,7 @@ impl Tunnel for WgNtTunnel {
                     Stats {
                         tx_bytes: peer.tx_bytes,
                         rx_bytes: peer.rx_bytes,
+                        last_handshake: filetime_to_system_time(peer.last_handshake),
                     },
                 );
             }
@@ -1171,6 +1184,7 @@ mod tests {
         #[cfg(daita)]
         daita: false,
         quantum_resistant: false,
+        connectivity_check: Default::default(),
     });
 
     static WG_STRUCT_CONFIG: LazyLock<Interface> = LazyLock::new(|| Interface {
@@ -192,6 +192,8 @@ pub async fn test_connecting_state(
         CustomTunnelEndpoint {
             host: "1.3.3.7".to_owned(),
             config: mullvad_types::ConnectionConfig::Wireguard(unreachable_wireguard_tunnel()),
+            obfuscation: None,
+            preshared_key: None,
         },
     )
     .await
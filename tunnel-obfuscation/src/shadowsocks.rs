@@ -74,6 +74,9 @@ pub struct Settings {
     pub shadowsocks_endpoint: SocketAddr,
     /// Remote WireGuard endpoint
     pub wireguard_endpoint: SocketAddr,
+    /// Password of the Shadowsocks server. The password of the Mullvad relays is used if this is
+    /// `None`. The cipher is always AES-256-GCM
+    pub password: Option<String>,
     #[cfg(target_os = "linux")]
     pub fwmark: Option<u32>,
 }
@@ -95,8 +98,14 @@ impl Shadowsocks {
         #[cfg(target_os = "android")]
         let outbound_fd = remote_socket.as_raw_fd();
 
+        let password = settings
+            .password
+            .clone()
+            .unwrap_or_else(|| SHADOWSOCKS_PASSWORD.to_owned());
+
         let server = tokio::spawn(run_forwarding(
             settings.shadowsocks_endpoint,
+            password,
             remote_socket,
             local_udp_socket,
             settings.wireguard_endpoint,
@@ -116,6 +125,7 @@ impl Shadowsocks {
 
 async fn run_forwarding(
     shadowsocks_endpoint: SocketAddr,
+    password: String,
     remote_socket: UdpSocket,
     local_udp_socket: UdpSocket,
     wireguard_endpoint: SocketAddr,
@@ -125,7 +135,7 @@ async fn run_forwarding(
         .await
         .map_err(Error::WaitForUdpClient)?;
 
-    let shadowsocks = connect_shadowsocks(remote_socket, shadowsocks_endpoint);
+    let shadowsocks = connect_shadowsocks(remote_socket, shadowsocks_endpoint, &password);
     let shadowsocks = Arc::new(shadowsocks);
 
     let local_udp = Arc::new(local_udp_socket);
@@ -159,13 +169,14 @@ async fn run_forwarding(
     Ok(())
 }
 
-fn connect_shadowsocks(remote_socket: UdpSocket, shadowsocks_endpoint: SocketAddr) -> ProxySocket {
+fn connect_shadowsocks(
+    remote_socket: UdpSocket,
+    shadowsocks_endpoint: SocketAddr,
+    password: &str,
+) -> ProxySocket {
     let ss_context = Context::new_shared(ServerType::Local);
-    let ss_config: ServerConfig = ServerConfig::new(
-        shadowsocks_endpoint,
-        SHADOWSOCKS_PASSWORD,
-        SHADOWSOCKS_CIPHER,
-    );
+    let ss_config: ServerConfig =
+        ServerConfig::new(shadowsocks_endpoint, password, SHADOWSOCKS_CIPHER);
     ProxySocket::from_socket(UdpSocketType::Client, ss_context, &ss_config, remote_socket)
 }
 